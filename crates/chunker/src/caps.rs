@@ -0,0 +1,97 @@
+//! `[caps]` in mentat.toml: how many chunks one file, and one directory
+//! subtree over a whole index run, may keep. Spans past a cap are dropped
+//! before embedding, so an 80MB SQL dump costs at most `per_file` chunks;
+//! `select` decides which of its spans survive.
+
+use crate::Span;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Select {
+    /// the first spans
+    #[default]
+    Head,
+    /// the first half and the last half of the allowance
+    Headtail,
+    /// spans spread evenly over the file
+    Sample,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Caps {
+    /// chunks kept per file (0 = no cap)
+    pub per_file: usize,
+    /// directory (relative to the index root) -> chunks kept under it per
+    /// run; re-indexing saved files also counts those already in the index
+    pub dirs: BTreeMap<String, usize>,
+    pub select: Select,
+}
+
+impl Caps {
+    pub fn is_off(&self) -> bool {
+        self.per_file == 0 && self.dirs.is_empty()
+    }
+
+    /// Cut the spans of the file at `rel` (relative to the root) to what
+    /// its caps allow, charging the kept ones to `used`, the chunks kept so
+    /// far under each `dirs` entry. Returns the kept spans and how many
+    /// were dropped.
+    pub fn apply(&self, rel: &str, spans: Vec<Span>, used: &mut HashMap<String, usize>) -> (Vec<Span>, usize) {
+        let dirs: Vec<(&String, &usize)> = self.dirs.iter().filter(|(d, _)| under(rel, d)).collect();
+        let mut allow = if self.per_file > 0 { self.per_file } else { usize::MAX };
+        for (dir, cap) in &dirs {
+            allow = allow.min(cap.saturating_sub(used.get(*dir).copied().unwrap_or(0)));
+        }
+        let dropped = spans.len().saturating_sub(allow);
+        let spans = select(spans, allow, self.select);
+        self.charge(rel, spans.len(), used);
+        (spans, dropped)
+    }
+
+    /// Charge `n` chunks kept for the file at `rel` to `used`, as `apply`
+    /// does; for chunks already in the index when a run starts.
+    pub fn charge(&self, rel: &str, n: usize, used: &mut HashMap<String, usize>) {
+        for dir in self.dirs.keys().filter(|d| under(rel, d)) {
+            *used.entry(dir.clone()).or_default() += n;
+        }
+    }
+
+    /// Take back `n` chunks charged for the file at `rel`: its old version
+    /// is being replaced.
+    pub fn refund(&self, rel: &str, n: usize, used: &mut HashMap<String, usize>) {
+        for dir in self.dirs.keys().filter(|d| under(rel, d)) {
+            if let Some(u) = used.get_mut(dir) {
+                *u = u.saturating_sub(n);
+            }
+        }
+    }
+}
+
+fn under(rel: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    dir.is_empty() || dir == "." || rel.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Keep `n` of `spans`, in file order.
+pub fn select(mut spans: Vec<Span>, n: usize, how: Select) -> Vec<Span> {
+    let len = spans.len();
+    if len <= n {
+        return spans;
+    }
+    match how {
+        Select::Head => {
+            spans.truncate(n);
+            spans
+        }
+        Select::Headtail => {
+            let tail = spans.split_off(len - n / 2);
+            spans.truncate(n - n / 2);
+            spans.extend(tail);
+            spans
+        }
+        Select::Sample => spans.into_iter().enumerate().filter(|(i, _)| (i * n) % len < n).map(|(_, s)| s).collect(),
+    }
+}
@@ -1,10 +1,17 @@
 //! Deterministic, lightweight chunker.
-//! Strategy: split text files into ~6000 byte spans with 10% overlap.
+//! Default strategy: split text files into ~6000 byte spans with 10% overlap.
+//! Optional FastCDC strategy cuts at content-defined boundaries (no overlap),
+//! so an insertion only invalidates the spans around it.
+//! The adaptive strategy sizes each span from the text it starts at: dense
+//! code (short lines, many symbols) costs more tokens per byte than prose,
+//! so its spans are shorter and both fill about `target_tokens`.
 //! Skips binary-ish data (NUL present) and tiny files emitted as single chunk.
 
+pub mod caps;
+
 use anyhow::Result;
 use memchr::memchr;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{fs, path::Path};
 
 #[derive(Serialize, Clone)]
@@ -18,33 +25,161 @@ pub struct Span {
 const TARGET_BYTES: usize = 6000;
 const OVERLAP_BYTES: usize = TARGET_BYTES / 10;
 
+/// Bumped whenever either strategy would cut the same bytes differently.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    #[default]
+    Fixed,
+    Fastcdc,
+    Adaptive,
+}
+
+/// `[chunker]` in mentat.toml. Sizes apply to `fastcdc`; `adaptive` keeps
+/// its spans between `min_bytes` and `max_bytes`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkerConfig {
+    pub strategy: Strategy,
+    pub min_bytes: u32,
+    pub avg_bytes: u32,
+    pub max_bytes: u32,
+    /// tokens an adaptive span aims for
+    pub target_tokens: u32,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self { strategy: Strategy::Fixed, min_bytes: 2000, avg_bytes: 6000, max_bytes: 12000, target_tokens: 480 }
+    }
+}
+
+impl ChunkerConfig {
+    /// Nonzero tag for this version and the settings that take effect (the
+    /// fixed strategy ignores the sizes). Chunk ids and `ChunkMeta` carry
+    /// it, so spans from different settings never share an id.
+    pub fn id(&self) -> u64 {
+        let desc = match self.strategy {
+            Strategy::Fixed => format!("v{VERSION} fixed {TARGET_BYTES} {OVERLAP_BYTES}"),
+            Strategy::Fastcdc => format!("v{VERSION} fastcdc {} {} {}", self.min_bytes, self.avg_bytes, self.max_bytes),
+            Strategy::Adaptive => format!("v{VERSION} adaptive {} {} {}", self.min_bytes, self.max_bytes, self.target_tokens),
+        };
+        let h = blake3::hash(desc.as_bytes());
+        u64::from_le_bytes(h.as_bytes()[..8].try_into().expect("8 bytes")).max(1)
+    }
+
+    /// FastCDC panics on out-of-range sizes; check them up front.
+    pub fn validate(&self) -> Result<()> {
+        use fastcdc::v2020::*;
+        match self.strategy {
+            Strategy::Fixed => return Ok(()),
+            Strategy::Adaptive if self.min_bytes == 0 || self.min_bytes > self.max_bytes || self.target_tokens == 0 => {
+                anyhow::bail!(
+                    "chunker: adaptive needs 0 < min_bytes <= max_bytes and target_tokens > 0 (got {}/{}/{})",
+                    self.min_bytes, self.max_bytes, self.target_tokens
+                )
+            }
+            Strategy::Adaptive => return Ok(()),
+            Strategy::Fastcdc => {}
+        }
+        let ok = (MINIMUM_MIN..=MINIMUM_MAX).contains(&self.min_bytes)
+            && (AVERAGE_MIN..=AVERAGE_MAX).contains(&self.avg_bytes)
+            && (MAXIMUM_MIN..=MAXIMUM_MAX).contains(&self.max_bytes)
+            && self.min_bytes <= self.avg_bytes
+            && self.avg_bytes <= self.max_bytes;
+        if !ok {
+            anyhow::bail!(
+                "chunker: need {MINIMUM_MIN} <= min_bytes <= avg_bytes <= max_bytes, \
+                 avg_bytes >= {AVERAGE_MIN}, max_bytes >= {MAXIMUM_MIN} (got {}/{}/{})",
+                self.min_bytes, self.avg_bytes, self.max_bytes
+            );
+        }
+        Ok(())
+    }
+}
+
 pub fn chunk_file<P: AsRef<Path>>(path: P) -> Result<Vec<Span>> {
     let path_ref = path.as_ref();
     let data = fs::read(path_ref)?;
+    Ok(chunk_bytes(path_ref, &data))
+}
+
+/// Chunk already-loaded file contents; `path` only labels the spans.
+pub fn chunk_bytes<P: AsRef<Path>>(path: P, data: &[u8]) -> Vec<Span> {
+    chunk_bytes_with(path, data, &ChunkerConfig::default())
+}
+
+/// Like `chunk_bytes` under an explicit strategy. `cfg` must have passed `validate`.
+pub fn chunk_bytes_with<P: AsRef<Path>>(path: P, data: &[u8], cfg: &ChunkerConfig) -> Vec<Span> {
+    let path_ref = path.as_ref();
     // crude binary gate: NUL byte present -> skip
-    if memchr(0, &data).is_some() {
-        return Ok(vec![]);
+    if memchr(0, data).is_some() {
+        return vec![];
     }
     if data.is_empty() {
-        return Ok(vec![]);
+        return vec![];
+    }
+    if cfg.strategy == Strategy::Fastcdc {
+        let cdc = fastcdc::v2020::FastCDC::new(data, cfg.min_bytes, cfg.avg_bytes, cfg.max_bytes);
+        return cdc.map(|c| span(path_ref, data, c.offset, c.offset + c.length)).collect();
+    }
+    if cfg.strategy == Strategy::Adaptive {
+        return adaptive(path_ref, data, cfg);
     }
     let mut out = Vec::new();
     let mut off = 0usize;
     while off < data.len() {
         let end = (off + TARGET_BYTES).min(data.len());
-        let slice = &data[off..end];
-        let hash = blake3::hash(slice).to_hex().to_string();
-        out.push(Span {
-            path: display(path_ref),
-            start: off,
-            end,
-            hash,
-        });
+        out.push(span(path_ref, data, off, end));
         if end == data.len() { break; }
         let step = TARGET_BYTES - OVERLAP_BYTES;
         off = off.saturating_add(step);
     }
-    Ok(out)
+    out
+}
+
+/// Estimated tokens per byte: a base rate for prose, plus a share for each
+/// symbol and each line break, which tokenizers mostly split off alone.
+const BASE_TOKENS_PER_BYTE: f32 = 0.2;
+const SYMBOL_TOKENS: f32 = 0.5;
+const NEWLINE_TOKENS: f32 = 0.5;
+
+/// Spans of `target_tokens / estimated tokens per byte`, the estimate taken
+/// over the `max_bytes` that follow each span's start, with 10% overlap.
+/// A span ends after a line break in its last quarter when there is one.
+fn adaptive(path: &Path, data: &[u8], cfg: &ChunkerConfig) -> Vec<Span> {
+    let (min, max) = (cfg.min_bytes as usize, cfg.max_bytes as usize);
+    let mut out = Vec::new();
+    let mut off = 0usize;
+    while off < data.len() {
+        let ahead = &data[off..(off + max).min(data.len())];
+        let size = ((cfg.target_tokens as f32 / tokens_per_byte(ahead)) as usize).clamp(min, max);
+        let mut end = (off + size).min(data.len());
+        if end < data.len() {
+            let from = off + size * 3 / 4;
+            if let Some(nl) = memchr::memrchr(b'\n', &data[from..end]) {
+                end = from + nl + 1;
+            }
+        }
+        out.push(span(path, data, off, end));
+        if end == data.len() { break; }
+        off = (end - (end - off) / 10).max(off + 1);
+    }
+    out
+}
+
+fn tokens_per_byte(text: &[u8]) -> f32 {
+    let n = text.len().max(1) as f32;
+    let symbols = text.iter().filter(|b| b.is_ascii_punctuation()).count() as f32;
+    let newlines = memchr::memchr_iter(b'\n', text).count() as f32;
+    BASE_TOKENS_PER_BYTE + SYMBOL_TOKENS * symbols / n + NEWLINE_TOKENS * newlines / n
+}
+
+fn span(path: &Path, data: &[u8], start: usize, end: usize) -> Span {
+    let hash = blake3::hash(&data[start..end]).to_hex().to_string();
+    Span { path: display(path), start, end, hash }
 }
 
 pub fn chunk_many<P: AsRef<Path>>(roots: &[P]) -> Result<Vec<Span>> {
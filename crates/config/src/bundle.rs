@@ -0,0 +1,42 @@
+//! `mentat config export` / `import`: one TOML file that carries a setup to
+//! another machine or CI: mentat.toml as written (profiles and comments
+//! included), the root's `.ingestignore`, and the aliases saved in the index.
+
+use crate::Config;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Bundle format version written by this build; newer bundles are refused.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Bundle {
+    pub version: u32,
+    /// mentat.toml verbatim
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingestignore: Option<String>,
+    /// `mentat alias add` entries; mentat.toml `[aliases]` travel inside `config`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Parse a bundle and check that the mentat.toml inside it loads.
+    pub fn parse(text: &str) -> Result<Self> {
+        let b: Self = toml::from_str(text).context("parsing bundle")?;
+        anyhow::ensure!(b.version <= VERSION, "bundle version {} is newer than this mentat understands ({VERSION})", b.version);
+        if let Some(c) = &b.config {
+            let cfg: Config = toml::from_str(c).context("parsing the bundled mentat.toml")?;
+            cfg.chunker.validate().context("in the bundled mentat.toml")?;
+        }
+        Ok(b)
+    }
+}
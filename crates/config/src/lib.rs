@@ -0,0 +1,237 @@
+//! mentat.toml, read from the working directory (or $MENTAT_CONFIG).
+//! Every section is optional; a missing file means all defaults.
+//!
+//! ```toml
+//! profile = "code"     # use [profiles.code]; `mentat index --profile NAME` overrides
+//!
+//! [text]
+//! nfc = true
+//! strip_control = true
+//! collapse_whitespace = false
+//! lowercase = false
+//!
+//! [chunker]
+//! strategy = "fixed"   # or "fastcdc", or "adaptive" (shorter spans for dense code)
+//! min_bytes = 2000     # fastcdc and adaptive
+//! avg_bytes = 6000     # fastcdc only
+//! max_bytes = 12000
+//! target_tokens = 480  # adaptive: tokens per span, estimated from symbol and line density
+//!
+//! [embedder]
+//! batch_size = 0       # 0 = probe the device once and remember the result
+//! max_len = 0          # tokens per chunk; 0 = model maximum (or what the probe allows)
+//! precision = "f32"    # "f16" / "bf16" on CUDA; CPU always runs f32
+//! mode = "full"        # or "static" (needs `mentat models distill`), or "hashed" (no model; tests); unset = keep the index's mode
+//! tiered = false      # full mode: static vectors first, upgraded in the background by `mentat upgrade`
+//! devices = []         # e.g. ["cuda:0", "cuda:1"]: one indexing worker per device
+//! pins = { "model.safetensors" = "3f1c..." }  # blake3 checked before loading; `mentat models list` prints them
+//!
+//! [caps]               # spans past a cap are dropped before embedding
+//! per_file = 2000      # chunks kept per file; 0 = no cap
+//! dirs = { "data/dumps" = 5000 }  # chunks kept under a directory per run
+//! select = "sample"    # which spans survive: "head", "headtail" or "sample" (spread evenly)
+//!
+//! [generated]          # files with "@generated" / "DO NOT EDIT" in a comment near the top, minified JS/CSS, huge JSON
+//! action = "skip"      # or "downrank" (indexed, hits scaled by [ranking] generated) or "index"
+//! markers = ["Generated by protoc"]  # more markers, matched as written in a comment
+//! minified_line_bytes = 500  # JS/CSS averaging longer lines is minified; 0 = off
+//! max_json_bytes = 1048576   # bigger JSON is a data dump; 0 = off
+//!
+//! [quota]              # per collection; a run past one fails with an error
+//! max_files = 200000   # 0 = no limit
+//! max_chunks = 2000000
+//! max_embed_bytes = 4000000000  # 1536 bytes per chunk
+//! warn_at = 0.8        # runs and `mentat doctor` warn past this share of a quota
+//!
+//! [owners]             # CODEOWNERS is always read; these add commit authors
+//! authors = 3          # top authors kept per file, by commits; 0 = CODEOWNERS only
+//! history = 1000       # recent commits counted
+//!
+//! [search]
+//! exclude = ["tests", "vendor"]  # added to every query as -tests -vendor; `--all` skips them
+//! snippet_lines = 0    # chunk lines printed under each hit; `--snippet N`
+//! context_lines = 0    # file lines shown before/after each chunk (JSON: context_before/after); `--context N`
+//! full_text = true     # JSON nodes carry the whole chunk; false cuts them to snippet_lines; `--no-full-text`
+//! relevance = "similarity"  # 0-1 relevance beside each distance; "percentile" ranks it against the index's own neighbors
+//! budget_ms = 0        # past this, skip the rest of reranking/filtering and mark results truncated; `--budget-ms N`
+//! postprocess = ["./tools/ticket-links.sh"]  # commands that filter, rewrite or annotate hits; see mentat-bin's postprocess.rs
+//! postprocess_timeout_ms = 2000  # a command still running after this is killed and skipped
+//! federate = { team = "/mnt/shared/docs/index" }  # other index dirs searched with this one, hits labeled; `--local` skips them
+//!
+//! [ranking]            # see mentat_retriever::rank
+//! recency = 0.1
+//! path = { "README*" = 1.2, "generated/**" = 0.5 }
+//! score = "0.8*dense + 0.2*bm25 + 0.1*recency(30d)"   # optional; see mentat_retriever::score
+//!
+//! [history]
+//! keep_days = 0        # keep replaced file versions this long for `search --as-of`; 0 = drop them
+//!
+//! [usage]
+//! enabled = false      # log searches and index runs to index/usage.jsonl for `mentat usage`; stays local
+//!
+//! [hooks]              # shell commands; the event is JSON on stdin and $MENTAT_EVENT
+//! on_index = "curl -s -d @- https://ci.example/hooks/mentat"
+//! on_error = "notify-send 'mentat index failed'"
+//!
+//! [aliases]            # expanded as @name in queries; `mentat alias add` overrides per index
+//! api-auth = "path:services/auth/** authentication flow"
+//!
+//! [profiles.code]      # any of text / chunker / embedder, each replacing its whole section
+//! chunker = { strategy = "fastcdc", avg_bytes = 4096 }
+//!
+//! [profiles.prose]
+//! text = { collapse_whitespace = true }
+//! chunker = { strategy = "fixed" }
+//!
+//! [profiles.logs]      # `mentat index logs/ --into logs-index --profile logs`
+//! embedder = { mode = "static" }
+//! ```
+
+pub mod bundle;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub text: mentat_text::Normalize,
+    pub chunker: mentat_chunker::ChunkerConfig,
+    pub caps: mentat_chunker::caps::Caps,
+    pub embedder: mentat_embedder::EmbedConfig,
+    pub generated: mentat_ingest::generated::GeneratedConfig,
+    pub quota: mentat_indexer::quota::Quota,
+    pub owners: mentat_indexer::owners::OwnersConfig,
+    pub aliases: std::collections::BTreeMap<String, String>,
+    pub search: Search,
+    pub ranking: mentat_retriever::rank::Boosts,
+    pub history: History,
+    pub usage: Usage,
+    pub hooks: Hooks,
+    /// name of the `[profiles.*]` entry applied on load
+    pub profile: Option<String>,
+    pub profiles: std::collections::BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Search {
+    /// words excluded from every query, as if written `-word`
+    pub exclude: Vec<String>,
+    pub snippet_lines: usize,
+    pub context_lines: usize,
+    pub full_text: bool,
+    /// milliseconds a search may take before returning best-effort results (0 = no limit)
+    pub budget_ms: u64,
+    pub relevance: mentat_retriever::relevance::Scale,
+    /// commands run over each search's result nodes, in order
+    pub postprocess: Vec<String>,
+    pub postprocess_timeout_ms: u64,
+    /// other index directories `mentat search` queries too, by label
+    pub federate: std::collections::BTreeMap<String, String>,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            exclude: Vec::new(),
+            snippet_lines: 0,
+            context_lines: 0,
+            full_text: true,
+            budget_ms: 0,
+            relevance: Default::default(),
+            postprocess: Vec::new(),
+            postprocess_timeout_ms: 2000,
+            federate: Default::default(),
+        }
+    }
+}
+
+impl Search {
+    /// The configured verbosity; commands override fields from their flags.
+    pub fn verbosity(&self) -> mentat_retriever::snippet::Verbosity {
+        mentat_retriever::snippet::Verbosity { lines: self.snippet_lines, context: self.context_lines, full_text: self.full_text }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct History {
+    pub keep_days: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Usage {
+    pub enabled: bool,
+}
+
+/// Commands run after index events; unset means nothing runs.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// after `mentat index` finishes, with the report
+    pub on_index: Option<String>,
+    /// after a run that failed or recorded per-file errors
+    pub on_error: Option<String>,
+}
+
+/// Named bundle of index settings; sections it sets replace the top-level ones.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub text: Option<mentat_text::Normalize>,
+    pub chunker: Option<mentat_chunker::ChunkerConfig>,
+    pub embedder: Option<mentat_embedder::EmbedConfig>,
+}
+
+pub fn config_path() -> PathBuf {
+    std::env::var_os("MENTAT_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("mentat.toml"))
+}
+
+impl Config {
+    /// Load with the profile named in the file, if any.
+    pub fn load() -> Result<Self> {
+        Self::load_profile(None)
+    }
+
+    /// Load and apply `profile`, falling back to the one named in the file.
+    pub fn load_profile(profile: Option<&str>) -> Result<Self> {
+        let path = config_path();
+        let mut cfg = if path.exists() {
+            let txt = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            toml::from_str(&txt).with_context(|| format!("parsing {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        if let Some(name) = profile.map(str::to_string).or_else(|| cfg.profile.clone()) {
+            cfg.apply_profile(&name).with_context(|| format!("in {}", path.display()))?;
+        }
+        cfg.chunker.validate().with_context(|| format!("in {}", path.display()))?;
+        if let Some(expr) = &cfg.ranking.score {
+            mentat_retriever::score::Expr::parse(expr).with_context(|| format!("in {}", path.display()))?;
+        }
+        Ok(cfg)
+    }
+
+    fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(p) = self.profiles.get(name).cloned() else {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::bail!("no profile `{name}` (defined: {})", if known.is_empty() { "none".into() } else { known.join(", ") });
+        };
+        if let Some(text) = p.text {
+            self.text = text;
+        }
+        if let Some(chunker) = p.chunker {
+            self.chunker = chunker;
+        }
+        if let Some(embedder) = p.embedder {
+            self.embedder = embedder;
+        }
+        self.profile = Some(name.to_string());
+        Ok(())
+    }
+}
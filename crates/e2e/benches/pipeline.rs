@@ -0,0 +1,112 @@
+//! Pipeline benchmarks. Save a baseline with
+//!   cargo bench -p mentat-e2e -- --save-baseline <label>
+//! and compare a later run with `-- --baseline <label>`.
+//! Embedding and end-to-end benches are skipped when model files are absent.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use mentat_e2e::{fixture_dir, pseudo_vectors, use_repo_models};
+use mentat_store::{chunk_id, ChunkMeta, FileMeta, Store};
+use std::fs;
+
+const HNSW_VECTORS: usize = 2000;
+
+fn chunking(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big.txt");
+    let line = "fn stage(input: &[u8]) -> Result<Vec<u8>> { Ok(input.to_vec()) }\n";
+    fs::write(&path, line.repeat(1 << 20 >> 6)).unwrap();
+    let len = fs::metadata(&path).unwrap().len();
+
+    let mut g = c.benchmark_group("chunk");
+    g.throughput(Throughput::Bytes(len));
+    g.bench_function("chunk_file_1mb", |b| b.iter(|| mentat_chunker::chunk_file(&path).unwrap()));
+    g.finish();
+}
+
+fn embedding(c: &mut Criterion) {
+    use_repo_models();
+    if !mentat_embedder::model_available() {
+        eprintln!("[bench] model files missing, skipping embed benches");
+        return;
+    }
+    let text = fs::read_to_string(fixture_dir().join("notes.md")).unwrap();
+    let mut g = c.benchmark_group("embed");
+    g.sample_size(10);
+    g.bench_function("single", |b| b.iter(|| mentat_embedder::embed_text(&text).unwrap()));
+    g.finish();
+}
+
+fn store_writes(c: &mut Criterion) {
+    let vecs = pseudo_vectors(100, 7);
+    let mut g = c.benchmark_group("store");
+    g.throughput(Throughput::Elements(vecs.len() as u64));
+    g.bench_function("put_chunk_and_embed_x100", |b| {
+        b.iter_batched(
+            || {
+                let dir = tempfile::tempdir().unwrap();
+                let store = Store::open(dir.path()).unwrap();
+                (dir, store)
+            },
+            |(_dir, store)| {
+                let fhash = mentat_store::blake32(b"bench");
+                store.put_file(fhash, &FileMeta { path: "bench.txt".into(), size: 0 }).unwrap();
+                for (i, v) in vecs.iter().enumerate() {
+                    let id = chunk_id(fhash, i * 100, i * 100 + 100, 1);
+                    let meta = ChunkMeta { file_hash: fhash, start: i * 100, end: i * 100 + 100, span_hash: id, chunker: 1 };
+                    store.put_chunk(id, &meta).unwrap();
+                    store.put_embed(id, v).unwrap();
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    g.finish();
+}
+
+fn hnsw(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let store = Store::open(dir.path()).unwrap();
+        let fhash = mentat_store::blake32(b"bench");
+        store.put_file(fhash, &FileMeta { path: "bench.txt".into(), size: 0 }).unwrap();
+        for (i, v) in pseudo_vectors(HNSW_VECTORS, 11).iter().enumerate() {
+            let id = chunk_id(fhash, i, i + 1, 1);
+            store.put_chunk(id, &ChunkMeta { file_hash: fhash, start: i, end: i + 1, span_hash: id, chunker: 1 }).unwrap();
+            store.put_embed(id, v).unwrap();
+        }
+    }
+    let mut retr = mentat_retriever::Retriever::open(dir.path()).unwrap();
+    let query = pseudo_vectors(1, 99)[0];
+
+    let mut g = c.benchmark_group("hnsw");
+    g.sample_size(10);
+    g.bench_function("build_2000", |b| b.iter(|| retr.build_hnsw_in_memory().unwrap()));
+    retr.build_hnsw_in_memory().unwrap();
+    g.bench_function("query_top5", |b| b.iter(|| retr.search_vec(&query, 5).unwrap()));
+    g.finish();
+}
+
+fn end_to_end(c: &mut Criterion) {
+    use_repo_models();
+    if !mentat_embedder::model_available() {
+        eprintln!("[bench] model files missing, skipping end-to-end bench");
+        return;
+    }
+    let root = fixture_dir().display().to_string();
+    let mut g = c.benchmark_group("e2e");
+    g.sample_size(10);
+    g.bench_function("index_fixture_corpus", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| {
+                let store = Store::open(dir.path()).unwrap();
+                mentat_indexer::run_index(&root, &store, &Default::default()).unwrap();
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    g.finish();
+}
+
+criterion_group!(benches, chunking, embedding, store_writes, hnsw, end_to_end);
+criterion_main!(benches);
@@ -0,0 +1,518 @@
+//! Fixture: a long Rust-like source file spanning several chunks.
+
+/// Stage 0: ingest step of the pipeline.
+pub fn ingest_0(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 1: hash step of the pipeline.
+pub fn hash_1(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 2: chunk step of the pipeline.
+pub fn chunk_2(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 3: embed step of the pipeline.
+pub fn embed_3(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 4: store step of the pipeline.
+pub fn store_4(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 5: index step of the pipeline.
+pub fn index_5(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 6: search step of the pipeline.
+pub fn search_6(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 7: rank step of the pipeline.
+pub fn rank_7(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 8: report step of the pipeline.
+pub fn report_8(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 9: verify step of the pipeline.
+pub fn verify_9(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 10: ingest step of the pipeline.
+pub fn ingest_10(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 11: hash step of the pipeline.
+pub fn hash_11(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 12: chunk step of the pipeline.
+pub fn chunk_12(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 13: embed step of the pipeline.
+pub fn embed_13(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 14: store step of the pipeline.
+pub fn store_14(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 15: index step of the pipeline.
+pub fn index_15(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 16: search step of the pipeline.
+pub fn search_16(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 17: rank step of the pipeline.
+pub fn rank_17(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 18: report step of the pipeline.
+pub fn report_18(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 19: verify step of the pipeline.
+pub fn verify_19(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 20: ingest step of the pipeline.
+pub fn ingest_20(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 21: hash step of the pipeline.
+pub fn hash_21(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 22: chunk step of the pipeline.
+pub fn chunk_22(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 23: embed step of the pipeline.
+pub fn embed_23(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 24: store step of the pipeline.
+pub fn store_24(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 25: index step of the pipeline.
+pub fn index_25(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 26: search step of the pipeline.
+pub fn search_26(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 27: rank step of the pipeline.
+pub fn rank_27(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 28: report step of the pipeline.
+pub fn report_28(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 29: verify step of the pipeline.
+pub fn verify_29(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 30: ingest step of the pipeline.
+pub fn ingest_30(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 31: hash step of the pipeline.
+pub fn hash_31(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 32: chunk step of the pipeline.
+pub fn chunk_32(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 33: embed step of the pipeline.
+pub fn embed_33(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 34: store step of the pipeline.
+pub fn store_34(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 35: index step of the pipeline.
+pub fn index_35(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
+/// Stage 36: search step of the pipeline.
+pub fn search_36(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(1));
+    }
+    Ok(out)
+}
+
+/// Stage 37: rank step of the pipeline.
+pub fn rank_37(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(2));
+    }
+    Ok(out)
+}
+
+/// Stage 38: report step of the pipeline.
+pub fn report_38(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(3));
+    }
+    Ok(out)
+}
+
+/// Stage 39: verify step of the pipeline.
+pub fn verify_39(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(4));
+    }
+    Ok(out)
+}
+
+/// Stage 40: ingest step of the pipeline.
+pub fn ingest_40(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(5));
+    }
+    Ok(out)
+}
+
+/// Stage 41: hash step of the pipeline.
+pub fn hash_41(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(6));
+    }
+    Ok(out)
+}
+
+/// Stage 42: chunk step of the pipeline.
+pub fn chunk_42(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len().min(limit));
+    for (n, b) in input.iter().enumerate() {
+        if n >= limit {
+            break;
+        }
+        out.push(b.wrapping_add(0));
+    }
+    Ok(out)
+}
+
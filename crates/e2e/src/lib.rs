@@ -0,0 +1,95 @@
+//! Golden-test harness for index semantics.
+//! Fixture corpus lives in fixtures/corpus, expected outputs in golden/.
+//! Run `MENTAT_BLESS=1 cargo test -p mentat-e2e` to rewrite the goldens
+//! after an intentional change to chunking or pooling.
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/corpus")
+}
+
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+/// Fixture files sorted by name, as (name, absolute path).
+pub fn fixtures() -> Result<Vec<(String, PathBuf)>> {
+    let mut out = Vec::new();
+    for entry in fs::read_dir(fixture_dir())? {
+        let path = entry?.path();
+        if path.is_file() {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            out.push((name, path));
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+pub fn blessing() -> bool {
+    std::env::var_os("MENTAT_BLESS").is_some()
+}
+
+/// Compare `actual` with golden/<name>. When blessing, rewrite it instead;
+/// otherwise a missing golden is an error, so a snapshot never blesses
+/// itself. Returns the stored golden so callers can apply their own
+/// tolerances.
+pub fn check_golden<T>(name: &str, actual: &T) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let path = golden_dir().join(name);
+    anyhow::ensure!(
+        blessing() || path.exists(),
+        "{} is missing; run with MENTAT_BLESS=1 to write it, review it and commit it",
+        path.display()
+    );
+    if blessing() {
+        fs::create_dir_all(golden_dir())?;
+        fs::write(&path, serde_json::to_string_pretty(actual)? + "\n")?;
+        eprintln!("[e2e] wrote {}", path.display());
+    }
+    let txt = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&txt)?)
+}
+
+/// Point the embedder at the in-repo model directory unless overridden.
+pub fn use_repo_models() {
+    if std::env::var_os("MENTAT_MODEL_DIR").is_none() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../embedder/models");
+        std::env::set_var("MENTAT_MODEL_DIR", dir);
+    }
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let na = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let nb = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    dot / (na * nb).max(1e-12)
+}
+
+/// Deterministic unit vectors (xorshift64*) for benches that must not need a model.
+pub fn pseudo_vectors(n: usize, seed: u64) -> Vec<[f32; 384]> {
+    let mut state = seed.max(1);
+    let mut next = move || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    };
+    (0..n)
+        .map(|_| {
+            let mut v = [0f32; 384];
+            v.iter_mut().for_each(|x| *x = next());
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-6);
+            v.iter_mut().for_each(|x| *x /= norm);
+            v
+        })
+        .collect()
+}
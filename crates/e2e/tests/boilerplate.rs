@@ -0,0 +1,55 @@
+//! Boilerplate scores updated from the stored line counts match a rebuild
+//! that reads every file.
+
+use mentat_embedder::{EmbedConfig, Mode};
+use mentat_indexer::boilerplate;
+use std::{collections::BTreeMap, fs, path::Path};
+
+const HEADER: &str = "// Copyright (c) the veyrsson authors.\n// Licensed under the Apache License, Version 2.0.\n";
+const SHARED: &str = "    let config = load_settings_from_disk();\n";
+
+fn write(root: &Path, i: usize, header: bool, shared: bool) {
+    let mut text = if header { HEADER.to_string() } else { String::new() };
+    text += &format!("pub fn handler_{i}(input: &str) -> usize {{\n");
+    if shared {
+        text += SHARED;
+    }
+    text += &format!("    input.len() * {i} + {}\n}}\n", i * 7);
+    fs::write(root.join(format!("f{i}.rs")), text).unwrap();
+}
+
+fn scores(store: &mentat_store::Store) -> BTreeMap<[u8; 32], Option<f32>> {
+    store.chunks().unwrap().into_iter().map(|(id, _)| (id, store.get_boilerplate(id).unwrap())).collect()
+}
+
+#[test]
+fn updates_match_a_rebuild() {
+    let repo = tempfile::tempdir().unwrap();
+    for i in 0..8 {
+        write(repo.path(), i, i % 4 != 0, i < 2);
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let store = mentat_store::Store::open(dir.path()).unwrap();
+    let opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    let index = || {
+        let rep = mentat_indexer::run_index(repo.path().to_str().unwrap(), &store, &opts).unwrap();
+        assert!(rep.errors.is_empty(), "{:?}", rep.errors);
+    };
+    index();
+    // a third file takes the shared line over the threshold; one file
+    // drops the header, another goes away
+    write(repo.path(), 8, true, true);
+    write(repo.path(), 9, false, false);
+    write(repo.path(), 3, false, false);
+    fs::remove_file(repo.path().join("f5.rs")).unwrap();
+    index();
+
+    let updated = scores(&store);
+    assert!(updated.values().all(Option::is_some), "every chunk scored");
+    boilerplate::rebuild(&store).unwrap();
+    assert_eq!(updated, scores(&store));
+    assert!(updated.values().any(|s| s.unwrap() > 0.0));
+}
@@ -0,0 +1,87 @@
+//! Chunk boundaries must match exactly; embeddings within tolerance.
+
+use mentat_e2e::{blessing, check_golden, cosine, fixtures, golden_dir, use_repo_models};
+use mentat_embedder::{embed_text, model_available};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct GoldenSpan {
+    start: usize,
+    end: usize,
+    hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GoldenEmbed {
+    file: String,
+    start: usize,
+    end: usize,
+    vector: Vec<f32>,
+}
+
+// Cosine floor and per-component slack for embedding snapshots.
+const MIN_COSINE: f32 = 0.9995;
+const MAX_ABS_DIFF: f32 = 1e-3;
+
+fn chunk_fixtures() -> BTreeMap<String, Vec<GoldenSpan>> {
+    let mut out = BTreeMap::new();
+    for (name, path) in fixtures().unwrap() {
+        let spans = mentat_chunker::chunk_file(&path).unwrap();
+        let spans = spans
+            .into_iter()
+            .map(|s| GoldenSpan { start: s.start, end: s.end, hash: s.hash })
+            .collect();
+        out.insert(name, spans);
+    }
+    out
+}
+
+#[test]
+fn chunk_boundaries_match_golden() {
+    let actual = chunk_fixtures();
+    let golden = check_golden("chunks.json", &actual).unwrap();
+    assert_eq!(golden, actual, "chunk boundaries drifted; re-bless if intentional");
+}
+
+#[test]
+fn chunker_is_deterministic() {
+    assert_eq!(chunk_fixtures(), chunk_fixtures());
+}
+
+#[test]
+fn embeddings_match_snapshot() {
+    use_repo_models();
+    if !model_available() || mentat_embedder::FALLBACK {
+        eprintln!("[e2e] model files missing or fallback embedder, skipping embedding snapshot");
+        return;
+    }
+    // no snapshot is committed yet: bless one on a machine with the model
+    if !blessing() && !golden_dir().join("embeddings.json").exists() {
+        eprintln!("[e2e] golden/embeddings.json not blessed yet, skipping embedding snapshot");
+        return;
+    }
+    let mut actual = Vec::new();
+    for (name, path) in fixtures().unwrap() {
+        let data = fs::read(&path).unwrap();
+        for s in mentat_chunker::chunk_file(&path).unwrap() {
+            let text = String::from_utf8_lossy(&data[s.start..s.end]);
+            let vector = embed_text(&text).unwrap().to_vec();
+            actual.push(GoldenEmbed { file: name.clone(), start: s.start, end: s.end, vector });
+        }
+    }
+    let golden: Vec<GoldenEmbed> = check_golden("embeddings.json", &actual).unwrap();
+    assert_eq!(golden.len(), actual.len(), "embedded span count drifted");
+    for (g, a) in golden.iter().zip(&actual) {
+        assert_eq!((&g.file, g.start, g.end), (&a.file, a.start, a.end));
+        let cos = cosine(&g.vector, &a.vector);
+        assert!(cos >= MIN_COSINE, "{}@{}: cosine {cos} below {MIN_COSINE}", g.file, g.start);
+        let worst = g
+            .vector
+            .iter()
+            .zip(&a.vector)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0f32, f32::max);
+        assert!(worst <= MAX_ABS_DIFF, "{}@{}: component diff {worst}", g.file, g.start);
+    }
+}
@@ -0,0 +1,63 @@
+//! The whole index -> search path in hashed mode: no model files, any build.
+
+use mentat_e2e::fixture_dir;
+use mentat_embedder::{EmbedConfig, Mode};
+
+#[test]
+fn hashed_mode_indexes_and_searches_without_a_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    {
+        let store = mentat_store::Store::open(dir.path()).unwrap();
+        let rep = mentat_indexer::run_index(fixture_dir().to_str().unwrap(), &store, &opts).unwrap();
+        assert!(rep.errors.is_empty(), "{:?}", rep.errors);
+        assert_eq!(rep.embed.as_ref().map(|t| t.device.as_str()), Some("hashed"));
+        assert_eq!(store.get_meta(mentat_store::META_MODEL).unwrap().as_deref(), Some(mentat_embedder::HASHED.as_bytes()));
+    }
+    let retr = mentat_retriever::Retriever::open(dir.path()).unwrap();
+    assert_eq!(retr.embed_mode().unwrap(), Mode::Hashed);
+    let hits = retr.search_exact("binary files are detected by the presence of a NUL byte", 1).unwrap();
+    assert_eq!(hits[0].path, "notes.md");
+}
+
+#[test]
+fn each_file_is_counted_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = mentat_store::Store::open(dir.path()).unwrap();
+    let opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    let root = fixture_dir();
+    let first = mentat_indexer::run_index(root.to_str().unwrap(), &store, &opts).unwrap();
+    assert!(first.files_indexed > 0);
+    let again = mentat_indexer::run_index(root.to_str().unwrap(), &store, &opts).unwrap();
+    assert_eq!(again.files_indexed, 0);
+    assert_eq!(again.files_unchanged, first.files_indexed + first.files_unchanged);
+    assert_eq!(again.files_unchanged + again.files_skipped.len(), again.files_seen);
+}
+
+#[test]
+fn a_chunk_quota_stop_leaves_stored_files_complete() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = mentat_store::Store::open(dir.path()).unwrap();
+    let mut opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    opts.quota.max_chunks = 2;
+    let err = mentat_indexer::run_index(fixture_dir().to_str().unwrap(), &store, &opts).unwrap_err();
+    assert!(format!("{err:#}").contains("max_chunks"), "{err:#}");
+    let files = store.files().unwrap();
+    assert!(!files.is_empty());
+    for (fhash, meta) in files {
+        let chunks = store.file_chunks(fhash).unwrap();
+        assert!(!chunks.is_empty(), "{} stored without chunks", meta.path);
+        for (id, _) in chunks {
+            assert!(store.get_embed(id).unwrap().is_some(), "{} has a chunk without an embedding", meta.path);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! The related-files graph updated file by file matches one rebuilt whole.
+
+use mentat_e2e::pseudo_vectors;
+use mentat_indexer::related;
+use mentat_store::{ChunkMeta, FileMeta, Store};
+
+/// File `i`: two chunks near one of four cluster centers, so files in a
+/// cluster link and others do not.
+fn add_file(store: &Store, i: u8, centers: &[[f32; 384]], noise: &[[f32; 384]]) {
+    let fh = [i; 32];
+    store.put_file(fh, &FileMeta { path: format!("f{i}.rs"), size: 20 }).unwrap();
+    for c in 0..2u8 {
+        let id = mentat_store::chunk_id(fh, c as usize * 10, c as usize * 10 + 10, 1);
+        store.put_chunk(id, &ChunkMeta { file_hash: fh, start: c as usize * 10, end: c as usize * 10 + 10, span_hash: id, chunker: 1 }).unwrap();
+        let mut v = centers[i as usize % centers.len()];
+        v.iter_mut().zip(&noise[i as usize * 2 + c as usize]).for_each(|(x, n)| *x += 0.4 * n);
+        store.put_embed(id, &v).unwrap();
+    }
+}
+
+#[test]
+fn incremental_updates_match_a_rebuild() {
+    let (centers, noise) = (pseudo_vectors(4, 7), pseudo_vectors(120, 11));
+    let dir = tempfile::tempdir().unwrap();
+    let store = Store::open(dir.path()).unwrap();
+    for i in 1..=30 {
+        add_file(&store, i, &centers, &noise);
+    }
+    related::rebuild(&store).unwrap();
+    for i in 31..=45 {
+        add_file(&store, i, &centers, &noise);
+    }
+    for i in [2, 9, 33] {
+        store.delete_file([i; 32]).unwrap();
+    }
+    let edges = related::update(&store).unwrap();
+
+    let fresh_dir = tempfile::tempdir().unwrap();
+    let fresh = Store::open(fresh_dir.path()).unwrap();
+    for i in (1..=45).filter(|i| ![2, 9, 33].contains(i)) {
+        add_file(&fresh, i, &centers, &noise);
+    }
+    assert_eq!(related::rebuild(&fresh).unwrap(), edges);
+    assert!(edges > 0);
+    let mut got = store.related_rows().unwrap();
+    let mut want = fresh.related_rows().unwrap();
+    got.sort_by_key(|r| r.0);
+    want.sort_by_key(|r| r.0);
+    assert_eq!(got.len(), want.len());
+    for ((h, g), (_, w)) in got.iter().zip(&want) {
+        let ids = |n: &mentat_store::Neighbors| n.iter().map(|(o, _)| *o).collect::<Vec<_>>();
+        assert_eq!(ids(g), ids(w), "row of file {}", h[0]);
+    }
+}
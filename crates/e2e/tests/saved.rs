@@ -0,0 +1,44 @@
+//! Re-indexing saved files counts the chunks already in the index against
+//! the quota and the directory caps.
+
+use mentat_embedder::{EmbedConfig, Mode};
+use std::{collections::BTreeMap, fs, path::Path};
+
+fn write(path: &Path, fns: usize) {
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let text: String = (0..fns).map(|i| format!("pub fn {name}_{i}(x: u64) -> u64 {{\n    x.wrapping_mul({i}).rotate_left(7) ^ 0x5bd1e995\n}}\n\n")).collect();
+    fs::write(path, text).unwrap();
+}
+
+#[test]
+fn saves_count_what_the_index_holds() {
+    let repo = tempfile::tempdir().unwrap();
+    fs::create_dir(repo.path().join("src")).unwrap();
+    write(&repo.path().join("src/a.rs"), 400);
+    write(&repo.path().join("notes.rs"), 400);
+    let dir = tempfile::tempdir().unwrap();
+    let store = mentat_store::Store::open(dir.path()).unwrap();
+    let mut opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    opts.caps.dirs = BTreeMap::from([("src".to_string(), 3)]);
+    let rep = mentat_indexer::run_index(repo.path().to_str().unwrap(), &store, &opts).unwrap();
+    assert!(rep.errors.is_empty(), "{:?}", rep.errors);
+    assert!(rep.chunks_capped > 0, "src/a.rs fills the src cap");
+    let total = store.chunks().unwrap().len();
+
+    // the src cap is used up by a.rs, so a new file under it keeps nothing
+    write(&repo.path().join("src/b.rs"), 50);
+    let saved = |rel: &str, opts: &mentat_indexer::IndexOptions| {
+        let path = repo.path().join(rel).display().to_string();
+        mentat_indexer::saved::index_saved(&store, &[path], opts)
+    };
+    let rep = saved("src/b.rs", &opts).unwrap();
+    assert_eq!(rep.files_skipped.iter().map(|s| s.reason.as_str()).collect::<Vec<_>>(), ["capped"]);
+
+    // re-saving a file replaces its chunks rather than adding to them
+    opts.quota.max_chunks = total;
+    write(&repo.path().join("notes.rs"), 399);
+    saved("notes.rs", &opts).unwrap();
+}
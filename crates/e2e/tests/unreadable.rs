@@ -0,0 +1,34 @@
+//! A file that indexed fine and then fails to read keeps its rows.
+
+#![cfg(unix)]
+
+use mentat_embedder::{EmbedConfig, Mode};
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+#[test]
+fn a_read_error_keeps_the_last_run_rows() {
+    let repo = tempfile::tempdir().unwrap();
+    fs::write(repo.path().join("kept.rs"), "pub fn kept() -> u32 {\n    7\n}\n").unwrap();
+    // the walk reports paths lossily, so a name that is not UTF-8 reads
+    // back as this one and then fails to open: a read error even for root
+    let shown = repo.path().join("flaky_\u{fffd}.rs");
+    fs::write(&shown, "pub fn flaky(x: u32) -> u32 {\n    x * 3\n}\n").unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let store = mentat_store::Store::open(dir.path()).unwrap();
+    let opts = mentat_indexer::IndexOptions {
+        embed: EmbedConfig { mode: Some(Mode::Hashed), ..Default::default() },
+        ..Default::default()
+    };
+    let root = repo.path().to_str().unwrap();
+    let rep = mentat_indexer::run_index(root, &store, &opts).unwrap();
+    assert!(rep.errors.is_empty(), "{:?}", rep.errors);
+    let chunks = store.chunks().unwrap().len();
+    let files = store.files().unwrap().len();
+
+    fs::rename(&shown, repo.path().join(OsStr::from_bytes(b"flaky_\xff.rs"))).unwrap();
+    let rep = mentat_indexer::run_index(root, &store, &opts).unwrap();
+    assert_eq!(rep.errors.len(), 1, "{:?}", rep.errors);
+    assert!(rep.files_deleted.is_empty(), "{:?}", rep.files_deleted);
+    assert_eq!(store.files().unwrap().len(), files);
+    assert_eq!(store.chunks().unwrap().len(), chunks);
+}
@@ -0,0 +1,360 @@
+//! The BGE model on Candle, built with the `model` feature (on by default;
+//! `cuda` adds GPU support). Everything here sits behind [`Embedder`] and
+//! the device helpers the crate root re-exports.
+
+use crate::{check_files, model_dir, take_truncation, Precision, Truncation, Tuning, D, MAX_LEN, PINS, STATIC_FILE, TRUNCATION};
+use anyhow::{Context, Result};
+pub use candle_core::Device;
+use candle_core::{DType, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use once_cell::sync::Lazy;
+use std::{collections::BTreeMap, sync::Mutex, time::Instant};
+use tokenizers::Tokenizer;
+
+/// A loaded model on one device. The free functions in the crate root share
+/// a lazily loaded default instance; the indexer loads one per configured device.
+pub struct Embedder {
+    tokenizer: Tokenizer,
+    model: BertModel,
+    device: Device,
+    /// min(MAX_LEN, max_position_embeddings)
+    max_len: usize,
+    pad_id: u32,
+    requested: Precision,
+    dtype: DType,
+}
+
+/// Whether `cuda:0` can be opened (false on builds without CUDA support).
+pub fn cuda_available() -> bool {
+    Device::new_cuda(0).is_ok()
+}
+
+/// CUDA device 0 when this build has CUDA support and it opens, else the CPU.
+pub fn default_device() -> Result<Device> {
+    Device::cuda_if_available(0).context("initializing device")
+}
+
+/// `cpu` or `cuda:N`.
+pub fn parse_device(spec: &str) -> Result<Device> {
+    match spec.split_once(':') {
+        None if spec == "cpu" => Ok(Device::Cpu),
+        Some(("cuda", n)) => {
+            let n: usize = n.parse().with_context(|| format!("bad device `{spec}`"))?;
+            Device::new_cuda(n).with_context(|| format!("opening {spec}"))
+        }
+        _ => anyhow::bail!("unknown device `{spec}` (expected cpu or cuda:N)"),
+    }
+}
+
+impl Embedder {
+    pub fn load(device: Device, requested: Precision) -> Result<Self> {
+        verify_pins()?;
+        eprintln!("[embedder] Using device: {:?}", device);
+
+        // Load tokenizer
+        let dir = model_dir();
+        eprintln!("[embedder] Loading tokenizer...");
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| anyhow::anyhow!("loading tokenizer: {}", e))?;
+
+        // Load config
+        eprintln!("[embedder] Loading config...");
+        let config_path = dir.join("config.json");
+        let config_json = std::fs::read_to_string(config_path)
+            .context("reading config.json")?;
+        let config: Config = serde_json::from_str(&config_json)
+            .context("parsing config")?;
+
+        // Load model weights
+        let dtype = if device.is_cuda() { dtype_of(requested) } else { DType::F32 };
+        if dtype != dtype_of(requested) {
+            eprintln!("[embedder] {requested:?} needs CUDA, running f32 on {device:?}");
+        }
+        eprintln!("[embedder] Loading model weights as {dtype:?} (this may take a moment)...");
+        let weights_path = dir.join("model.safetensors");
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[weights_path], dtype, &device)
+                .context("loading safetensors")?
+        };
+
+        eprintln!("[embedder] Building BERT model...");
+        let model = BertModel::load(vb, &config)
+            .context("creating BERT model")?;
+
+        eprintln!("[embedder] Model ready!");
+        let max_len = config.max_position_embeddings.min(MAX_LEN);
+        Ok(Self { tokenizer, model, device, max_len, pad_id: config.pad_token_id as u32, requested, dtype })
+    }
+
+    pub fn requested(&self) -> Precision {
+        self.requested
+    }
+
+    /// Device and dtype, e.g. `Cpu/F32`.
+    pub fn device_name(&self) -> String {
+        format!("{:?}/{:?}", self.device, self.dtype)
+    }
+
+    /// Embed several texts in one forward pass, each truncated to `max_len`
+    /// tokens (further capped by the model) and padded to the longest.
+    pub fn embed_batch(&self, texts: &[&str], max_len: usize) -> Result<Vec<[f32; D]>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let max_len = max_len.clamp(1, self.max_len);
+
+        // Tokenize; over-long texts keep [CLS], the tokens up to a sentence
+        // (or at least word) boundary, and [SEP]
+        let mut rows = Vec::with_capacity(texts.len());
+        let mut cut = Truncation::default();
+        for text in texts {
+            let encoding = self
+                .tokenizer
+                .encode(*text, true)
+                .map_err(|e| anyhow::anyhow!("tokenization failed: {}", e))?;
+            let n = encoding.get_ids().len();
+            let keep: Vec<usize> = if n <= max_len || max_len < 3 {
+                (0..n.min(max_len)).collect()
+            } else {
+                let last = truncation_point(text, encoding.get_offsets(), max_len);
+                cut.texts += 1;
+                cut.tokens_dropped += n - 2 - last;
+                cut.bytes_dropped += text.len() - encoding.get_offsets()[last].1;
+                (0..=last).chain([n - 1]).collect()
+            };
+            let pick = |v: &[u32]| keep.iter().map(|&i| v[i]).collect::<Vec<u32>>();
+            rows.push((pick(encoding.get_ids()), pick(encoding.get_type_ids()), pick(encoding.get_attention_mask())));
+        }
+        if cut.texts > 0 {
+            TRUNCATION.lock().unwrap().add(cut);
+        }
+        self.forward(rows)
+    }
+
+    /// [CLS] vectors of already tokenized (ids, type ids, mask) rows.
+    fn forward(&self, rows: Vec<(Vec<u32>, Vec<u32>, Vec<u32>)>) -> Result<Vec<[f32; D]>> {
+        // Pad to the longest row; padded positions are masked out
+        let n = rows.len();
+        let seq_len = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        let (mut ids, mut types, mut mask) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, t, a) in rows {
+            let pad = seq_len - i.len();
+            ids.extend(i.into_iter().chain(std::iter::repeat_n(self.pad_id, pad)));
+            types.extend(t.into_iter().chain(std::iter::repeat_n(0, pad)));
+            mask.extend(a.into_iter().chain(std::iter::repeat_n(0, pad)));
+        }
+        let shape = (n, seq_len);
+        let token_ids = Tensor::from_vec(ids, shape, &self.device)?;
+        let token_type_ids = Tensor::from_vec(types, shape, &self.device)?;
+        let attention_mask = Tensor::from_vec(mask, shape, &self.device)?;
+
+        let embeddings = self.model.forward(&token_ids, &token_type_ids, Some(&attention_mask))?;
+
+        // [CLS] token of every row: [batch, hidden]
+        let cls = embeddings.narrow(1, 0, 1)?.squeeze(1)?.to_dtype(DType::F32)?.to_vec2::<f32>()?;
+
+        Ok(cls.iter().map(|row| normalize(row)).collect())
+    }
+
+    /// The static-mode token table: every vocabulary token run through the
+    /// model on its own, as `[CLS] token [SEP]`, so averaged token vectors
+    /// land near the model's vectors for the same text.
+    pub fn distill(&self) -> Result<Vec<[f32; D]>> {
+        let special = |t: &str| self.tokenizer.token_to_id(t).ok_or_else(|| anyhow::anyhow!("tokenizer has no {t} token"));
+        let (cls, sep) = (special("[CLS]")?, special("[SEP]")?);
+        let vocab = self.tokenizer.get_vocab_size(true) as u32;
+        let mut table = Vec::with_capacity(vocab as usize);
+        for start in (0..vocab).step_by(DISTILL_BATCH) {
+            let end = (start + DISTILL_BATCH as u32).min(vocab);
+            table.extend(self.forward((start..end).map(|id| (vec![cls, id, sep], vec![0; 3], vec![1; 3])).collect())?);
+            eprint!("\r[embedder] distilled {end}/{vocab} tokens");
+        }
+        eprintln!();
+        Ok(table)
+    }
+
+    /// Probe doubling batch sizes with full-length input and keep the one with the
+    /// best items/second, stopping once doubling gains under 5% or a batch fails
+    /// (out of device memory). If even one full-length item fails, halve max_len.
+    pub fn autotune(&self) -> Result<Tuning> {
+        // the probes are cut on purpose; keep them out of the counts
+        let counted = take_truncation();
+        let tuning = self.probe();
+        *TRUNCATION.lock().unwrap() = counted;
+        tuning
+    }
+
+    fn probe(&self) -> Result<Tuning> {
+        let word_count = MAX_LEN * 2; // comfortably over MAX_LEN tokens
+        let probe: String = (0..word_count).map(|i| ["alpha ", "beta ", "gamma ", "delta "][i % 4]).collect();
+        let mut max_len = self.max_len;
+        loop {
+            match self.embed_batch(&[probe.as_str()], max_len) {
+                Ok(_) => break, // also serves as warm-up
+                Err(e) if max_len > 64 => {
+                    eprintln!("[embedder] probe failed at max_len {max_len}: {e:#}");
+                    max_len /= 2;
+                }
+                Err(e) => return Err(e.context("embedding probe failed at every sequence length")),
+            }
+        }
+        let mut best = (1usize, 0f32);
+        for &bs in PROBE_SIZES {
+            let batch = vec![probe.as_str(); bs];
+            let t = Instant::now();
+            if let Err(e) = self.embed_batch(&batch, max_len) {
+                eprintln!("[embedder] batch {bs} failed, keeping {}: {e:#}", best.0);
+                break;
+            }
+            let secs = t.elapsed().as_secs_f32();
+            let rate = bs as f32 / secs.max(1e-6);
+            eprintln!("[embedder] probe batch {bs}: {rate:.1} items/s");
+            // a doubling has to pay for its memory
+            if rate <= best.1 * 1.05 {
+                break;
+            }
+            best = (bs, rate);
+            if secs > PROBE_BUDGET_SECS {
+                break;
+            }
+        }
+        Ok(Tuning { device: self.device_name(), batch_size: best.0, max_len, throughput: Some(best.1) })
+    }
+}
+
+/// tokens per forward pass while distilling
+const DISTILL_BATCH: usize = 256;
+
+const PROBE_SIZES: &[usize] = &[1, 2, 4, 8, 16, 32, 64];
+/// stop growing once a single probe batch takes this long
+const PROBE_BUDGET_SECS: f32 = 10.0;
+
+/// Last content token to keep when a text of more than `max_len` tokens is
+/// cut: the latest one ending a sentence or line, unless that keeps under
+/// half the window, then the latest one ending a word. `offsets` are byte
+/// spans with [CLS] first and [SEP] last.
+fn truncation_point(text: &str, offsets: &[(usize, usize)], max_len: usize) -> usize {
+    let limit = max_len - 2; // room for [CLS] and [SEP]
+    let ends = |i: usize, at: fn(&str, &str) -> bool| {
+        let end = offsets[i].1;
+        text.get(..end).zip(text.get(end..)).is_some_and(|(before, after)| at(before, after))
+    };
+    let sentence = ((limit / 2).max(1)..=limit).rev().find(|&i| ends(i, ends_sentence));
+    let word = || (1..=limit).rev().find(|&i| ends(i, |_, after| after.starts_with(char::is_whitespace)));
+    sentence.or_else(word).unwrap_or(limit)
+}
+
+/// Full stops of scripts written without spaces end a sentence on their own;
+/// `.`, `!` and `?` need whitespace after them.
+fn ends_sentence(before: &str, after: &str) -> bool {
+    let Some(last) = before.chars().next_back() else { return false };
+    match last {
+        '。' | '！' | '？' | '｡' | '।' | '۔' => true,
+        '.' | '!' | '?' | '…' => after.is_empty() || after.starts_with(char::is_whitespace),
+        _ => after.starts_with('\n'),
+    }
+}
+
+fn dtype_of(p: Precision) -> DType {
+    match p {
+        Precision::F32 => DType::F32,
+        Precision::F16 => DType::F16,
+        Precision::Bf16 => DType::BF16,
+    }
+}
+
+/// pins the files were last checked against, so each load doesn't rehash them
+static VERIFIED: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+
+fn verify_pins() -> Result<()> {
+    let pins = PINS.lock().unwrap().clone();
+    if pins.is_empty() || VERIFIED.lock().unwrap().as_ref() == Some(&pins) {
+        return Ok(());
+    }
+    let dir = model_dir();
+    for c in check_files(&pins)? {
+        let Some(pin) = &c.pin else { continue };
+        match &c.blake3 {
+            None => anyhow::bail!("{} is missing from {}; run `mentat models pull`", c.file, dir.display()),
+            Some(h) if h != pin => anyhow::bail!(
+                "{} in {} does not match its pin in [embedder] pins\n  expected {pin}\n  found    {h}\n\
+                 Run `mentat models pull --force` to fetch it again, or update the pin if the model was replaced on purpose.",
+                c.file,
+                dir.display()
+            ),
+            Some(_) => {}
+        }
+    }
+    *VERIFIED.lock().unwrap() = Some(pins);
+    Ok(())
+}
+
+fn normalize(v: &[f32]) -> [f32; D] {
+    let norm = (v.iter().map(|x| x * x).sum::<f32>())
+        .sqrt()
+        .max(1e-6);
+    let mut out = [0f32; D];
+    for (i, &x) in v.iter().enumerate().take(D) {
+        out[i] = x / norm;
+    }
+    out
+}
+
+/// Averaged token vectors from `STATIC_FILE`, sharing the model's tokenizer.
+struct StaticModel {
+    tokenizer: Tokenizer,
+    table: Vec<[f32; D]>,
+}
+
+static STATIC: Lazy<Mutex<Option<StaticModel>>> = Lazy::new(|| Mutex::new(None));
+
+/// Static-mode vectors: the mean of each text's token vectors. No sequence
+/// cap applies, so nothing is truncated.
+pub fn embed_static(texts: &[&str]) -> Result<Vec<[f32; D]>> {
+    let mut guard = STATIC.lock().unwrap();
+    if guard.is_none() {
+        *guard = Some(load_static()?);
+    }
+    let m = guard.as_ref().expect("loaded above");
+    let mut out = Vec::with_capacity(texts.len());
+    for text in texts {
+        let encoding = m.tokenizer.encode(*text, false).map_err(|e| anyhow::anyhow!("tokenization failed: {}", e))?;
+        let mut sum = [0f32; D];
+        for &id in encoding.get_ids() {
+            let Some(v) = m.table.get(id as usize) else { continue };
+            sum.iter_mut().zip(v).for_each(|(s, x)| *s += x);
+        }
+        out.push(normalize(&sum));
+    }
+    Ok(out)
+}
+
+fn load_static() -> Result<StaticModel> {
+    verify_pins()?;
+    let dir = model_dir();
+    let path = dir.join(STATIC_FILE);
+    let bytes = std::fs::read(&path).with_context(|| format!("reading {}; run `mentat models distill`", path.display()))?;
+    anyhow::ensure!(bytes.len() % (D * 4) == 0, "{} is not a table of {D}-dim vectors", path.display());
+    let table: Vec<[f32; D]> = bytes
+        .chunks_exact(D * 4)
+        .map(|row| std::array::from_fn(|i| f32::from_le_bytes(row[i * 4..i * 4 + 4].try_into().expect("4 bytes"))))
+        .collect();
+    let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).map_err(|e| anyhow::anyhow!("loading tokenizer: {}", e))?;
+    let vocab = tokenizer.get_vocab_size(true);
+    anyhow::ensure!(table.len() == vocab, "{} has {} rows for a {vocab}-token vocabulary; run `mentat models distill` again", path.display(), table.len());
+    eprintln!("[embedder] static mode: {vocab} token vectors loaded");
+    Ok(StaticModel { tokenizer, table })
+}
+
+/// Distill the default model into `STATIC_FILE`; returns the row count.
+pub fn distill() -> Result<usize> {
+    let table = crate::with_default(|m| m.distill())?;
+    let path = model_dir().join(STATIC_FILE);
+    let part = path.with_extension("part");
+    let bytes: Vec<u8> = table.iter().flatten().flat_map(|x| x.to_le_bytes()).collect();
+    std::fs::write(&part, bytes)?;
+    std::fs::rename(&part, &path)?;
+    *STATIC.lock().unwrap() = None;
+    Ok(table.len())
+}
@@ -0,0 +1,38 @@
+//! Pure-Rust fallback embedder, built with the `hashed` feature when `model`
+//! is off: the default model is the hash projection of `projection`. It
+//! exists so the pipeline runs where Candle or tokenizers will not build.
+
+pub use crate::no_model::{cuda_available, default_device, distill, embed_static, parse_device, Device};
+use crate::{Precision, Tuning, D};
+use anyhow::Result;
+
+/// Batches do no shared work, so any size is as fast as another.
+const BATCH_SIZE: usize = 64;
+
+pub struct Embedder {
+    requested: Precision,
+}
+
+impl Embedder {
+    pub fn load(_device: Device, requested: Precision) -> Result<Self> {
+        eprintln!("[embedder] using the hashed fallback embedder: low-quality vectors, for testing and unsupported platforms");
+        Ok(Self { requested })
+    }
+
+    pub fn requested(&self) -> Precision {
+        self.requested
+    }
+
+    pub fn device_name(&self) -> String {
+        "Cpu/hashed".into()
+    }
+
+    /// Texts of more than `max_len` words keep their first `max_len`.
+    pub fn embed_batch(&self, texts: &[&str], max_len: usize) -> Result<Vec<[f32; D]>> {
+        Ok(crate::projection::embed_batch(texts, max_len))
+    }
+
+    pub fn autotune(&self) -> Result<Tuning> {
+        Ok(Tuning { device: self.device_name(), batch_size: BATCH_SIZE, max_len: crate::MAX_LEN, throughput: None })
+    }
+}
@@ -1,105 +1,352 @@
 //! Real embedding via Candle + BGE-small-en-v1.5.
 //! Keeps the same API signature: text -> [f32; 384]
+//! The model needs the `model` feature (default); without it every embed
+//! call fails, which suits builds that only read or serve an index, unless
+//! the `hashed` feature supplies the low-quality pure-Rust fallback.
+//! `embed_batch` pads a batch into one forward pass; `autotune` picks the
+//! batch size and sequence length for the current device.
+//! Texts longer than the sequence length are cut at the last sentence (or
+//! word) boundary that fits; `take_truncation` reports how much was lost.
+//! Static mode (`[embedder] mode = "static"`) averages per-token vectors
+//! distilled from the model instead of running it: far faster, less
+//! accurate, for large low-stakes collections. Hashed mode (`mode =
+//! "hashed"`) runs no model at all: a deterministic hash projection for
+//! tests and for working on everything but the model.
+//! Model files pinned in `[embedder] pins` are checked against their blake3
+//! before the model loads.
+
+#[cfg(feature = "model")]
+mod bert;
+#[cfg(feature = "model")]
+use bert as backend;
+mod projection;
+#[cfg(not(feature = "model"))]
+mod no_model;
+#[cfg(all(not(feature = "model"), feature = "hashed"))]
+mod hashed;
+#[cfg(all(not(feature = "model"), feature = "hashed"))]
+use hashed as backend;
+#[cfg(not(any(feature = "model", feature = "hashed")))]
+use no_model as backend;
+
+pub use backend::{cuda_available, distill, parse_device, Device, Embedder};
 
 use anyhow::{Context, Result};
-use candle_core::{DType, Device, Tensor};
-use candle_nn::VarBuilder;
-use candle_transformers::models::bert::{BertModel, Config};
 use once_cell::sync::Lazy;
-use std::sync::Mutex;
-use tokenizers::Tokenizer;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
 
 pub const D: usize = 384;
+/// Model the vectors come from; vectors of any other model are not comparable.
+#[cfg(not(all(feature = "hashed", not(feature = "model"))))]
+pub const MODEL: &str = BGE;
+#[cfg(all(feature = "hashed", not(feature = "model")))]
+pub const MODEL: &str = HASHED;
+/// BGE's name; indexes that predate `META_MODEL` were all embedded with it.
+pub const BGE: &str = "bge-small-en-v1.5";
+/// Name recorded for indexes embedded by the hash projection.
+pub const HASHED: &str = "hashed-projection-v1";
+/// Embedding with the hashed fallback: vectors only match shared words, so
+/// search quality is far below the model's. For platforms Candle does not
+/// build on, and for running the pipeline without model files.
+pub const FALLBACK: bool = cfg!(all(feature = "hashed", not(feature = "model")));
+
+/// Directory holding tokenizer.json, config.json and model.safetensors.
+/// Overridable via MENTAT_MODEL_DIR; defaults to the in-repo location.
+pub fn model_dir() -> PathBuf {
+    std::env::var_os("MENTAT_MODEL_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("crates/embedder/models"))
+}
+
+/// Files making up the model, all in `model_dir()`.
+pub const MODEL_FILES: &[&str] = &["tokenizer.json", "config.json", "model.safetensors"];
+/// Where `mentat models pull` fetches `MODEL_FILES` from.
+pub const MODEL_URL: &str = "https://huggingface.co/BAAI/bge-small-en-v1.5/resolve/main";
+
+/// True when all model files are present, or none are needed (lets tests
+/// skip cleanly).
+pub fn model_available() -> bool {
+    let dir = model_dir();
+    FALLBACK || MODEL_FILES.iter().all(|f| dir.join(f).is_file())
+}
+
+/// A model file's state on disk next to its pin.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileCheck {
+    pub file: String,
+    /// None when the file is missing
+    pub size: Option<u64>,
+    pub blake3: Option<String>,
+    pub pin: Option<String>,
+}
+
+impl FileCheck {
+    /// Present, and matching its pin if it has one.
+    pub fn ok(&self) -> bool {
+        self.blake3.is_some() && self.pin.as_ref().is_none_or(|p| Some(p) == self.blake3.as_ref())
+    }
+}
+
+/// Hash each model file (plus any other pinned file) and pair it with its pin.
+pub fn check_files(pins: &BTreeMap<String, String>) -> Result<Vec<FileCheck>> {
+    let dir = model_dir();
+    let mut names: Vec<&str> = MODEL_FILES.to_vec();
+    names.extend(pins.keys().map(String::as_str).filter(|k| !MODEL_FILES.contains(k)));
+    let mut out = Vec::new();
+    for name in names {
+        let path = dir.join(name);
+        let (size, blake3) = if path.is_file() {
+            let mut h = blake3::Hasher::new();
+            let size = std::io::copy(&mut std::fs::File::open(&path)?, &mut h).with_context(|| format!("reading {}", path.display()))?;
+            (Some(size), Some(h.finalize().to_hex().to_string()))
+        } else {
+            (None, None)
+        };
+        let pin = pins.get(name).map(|p| p.to_ascii_lowercase());
+        out.push(FileCheck { file: name.to_string(), size, blake3, pin });
+    }
+    Ok(out)
+}
+
+static PINS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Expected blake3 (hex) per model file name, from `[embedder] pins`.
+pub fn set_pins(pins: BTreeMap<String, String>) {
+    *PINS.lock().unwrap() = pins;
+}
+
+/// Name recorded for indexes embedded in static mode.
+pub const STATIC_MODEL: &str = "bge-small-en-v1.5-static";
+/// Static-mode token table in `model_dir()`, written by `mentat models
+/// distill`: one `[f32; D]` (little-endian) per vocabulary id.
+pub const STATIC_FILE: &str = "static.f32";
+
+/// How chunks are embedded.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// run the model on every chunk
+    #[default]
+    Full,
+    /// average the distilled token vectors of `STATIC_FILE`
+    Static,
+    /// hash words to vectors; no model, for tests and development
+    Hashed,
+}
+
+impl Mode {
+    /// Name recorded in an index embedded in this mode.
+    pub fn model(self) -> &'static str {
+        match self {
+            Mode::Full => MODEL,
+            Mode::Static => STATIC_MODEL,
+            Mode::Hashed => HASHED,
+        }
+    }
+
+    /// The mode that embeds like an index recorded as `model`, if this build
+    /// has one. A build without the model embeds `Full` by hash projection
+    /// too (`MODEL == HASHED`); the index is then `Hashed`.
+    pub fn of(model: &str) -> Option<Mode> {
+        [Mode::Hashed, Mode::Static, Mode::Full].into_iter().find(|m| m.model() == model)
+    }
+}
+
+/// Distance stored vectors are compared by.
+pub const METRIC: &str = "cosine";
+
+/// What an index's vectors are: recorded with the index and in each HNSW
+/// header, and checked before distances are computed, so vectors from
+/// another build error out instead of ranking garbage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VectorInfo {
+    pub model: String,
+    pub dim: usize,
+    pub metric: String,
+    /// version of the embedder that wrote them
+    pub version: String,
+}
+
+impl VectorInfo {
+    /// Vectors this build embeds in `mode`.
+    pub fn of(mode: Mode) -> Self {
+        Self::named(mode.model(), env!("CARGO_PKG_VERSION"))
+    }
+
+    /// This build's dimension and metric under `model`, for indexes that
+    /// predate the record.
+    pub fn named(model: &str, version: &str) -> Self {
+        Self { model: model.into(), dim: D, metric: METRIC.into(), version: version.into() }
+    }
+
+    /// Err unless this build can compare with these vectors. The model is
+    /// left to `Mode::of`: vectors of another model still load for listing
+    /// and export, only embedding queries against them fails.
+    pub fn check(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.dim == D && self.metric == METRIC,
+            "the index holds {}-dim {} vectors (mentat-embedder {}) but this mentat compares {D}-dim {METRIC} ones; re-run `mentat index` with this build",
+            self.dim,
+            self.metric,
+            self.version
+        );
+        Ok(())
+    }
+
+    /// Same model, dimension and metric, whatever wrote them.
+    pub fn same_space(&self, other: &Self) -> bool {
+        self.model == other.model && self.dim == other.dim && self.metric == other.metric
+    }
+}
+
+/// `embed_batch` in `mode`; static mode ignores `max_len`, hashed mode
+/// counts it in words.
+pub fn embed_batch_as(mode: Mode, texts: &[&str], max_len: usize) -> Result<Vec<[f32; D]>> {
+    match mode {
+        Mode::Full => embed_batch(texts, max_len),
+        Mode::Static => backend::embed_static(texts),
+        Mode::Hashed => Ok(projection::embed_batch(texts, max_len)),
+    }
+}
+
+/// Sequence length cap when the model config allows more.
+pub const MAX_LEN: usize = 512;
 
-static INIT: Lazy<Mutex<Option<(Tokenizer, BertModel, Device)>>> = Lazy::new(|| Mutex::new(None));
+/// Weight/activation precision. Half precision only applies on CUDA; the CPU
+/// backend always runs f32.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    F32,
+    F16,
+    Bf16,
+}
+
+static PRECISION: Mutex<Precision> = Mutex::new(Precision::F32);
 
-fn get_model_and_tokenizer() -> Result<&'static Mutex<Option<(Tokenizer, BertModel, Device)>>> {
+/// Select the precision for the default model; drops an already-loaded one
+/// of a different precision so the next call reloads it.
+pub fn set_precision(p: Precision) {
+    *PRECISION.lock().unwrap() = p;
+    let mut guard = INIT.lock().unwrap();
+    if guard.as_ref().is_some_and(|m| m.requested() != p) {
+        *guard = None;
+    }
+}
+
+/// Texts cut to fit the sequence length, summed over embed calls.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Truncation {
+    pub texts: usize,
+    pub tokens_dropped: usize,
+    /// bytes of text after the cut
+    pub bytes_dropped: usize,
+}
+
+impl Truncation {
+    fn add(&mut self, o: Truncation) {
+        self.texts += o.texts;
+        self.tokens_dropped += o.tokens_dropped;
+        self.bytes_dropped += o.bytes_dropped;
+    }
+}
+
+static TRUNCATION: Mutex<Truncation> = Mutex::new(Truncation { texts: 0, tokens_dropped: 0, bytes_dropped: 0 });
+
+/// Truncation since the last call, across every model in the process.
+pub fn take_truncation() -> Truncation {
+    std::mem::take(&mut *TRUNCATION.lock().unwrap())
+}
+
+type ModelState = Option<Embedder>;
+
+static INIT: Lazy<Mutex<ModelState>> = Lazy::new(|| Mutex::new(None));
+
+/// Run `f` on the default model, loading it on first use.
+fn with_default<R>(f: impl FnOnce(&Embedder) -> Result<R>) -> Result<R> {
     let mut guard = INIT.lock().unwrap();
     if guard.is_none() {
         eprintln!("[embedder] Initializing model (first time only)...");
 
         // Initialize device
         eprintln!("[embedder] Setting up device...");
-        let device = Device::cuda_if_available(0)
-            .context("initializing device")?;
-        eprintln!("[embedder] Using device: {:?}", device);
-
-        // Load tokenizer
-        eprintln!("[embedder] Loading tokenizer...");
-        let tokenizer = Tokenizer::from_file("crates/embedder/models/tokenizer.json")
-            .map_err(|e| anyhow::anyhow!("loading tokenizer: {}", e))?;
-
-        // Load config
-        eprintln!("[embedder] Loading config...");
-        let config_path = "crates/embedder/models/config.json";
-        let config_json = std::fs::read_to_string(config_path)
-            .context("reading config.json")?;
-        let config: Config = serde_json::from_str(&config_json)
-            .context("parsing config")?;
-
-        // Load model weights
-        eprintln!("[embedder] Loading model weights (this may take a moment)...");
-        let weights_path = "crates/embedder/models/model.safetensors";
-        let vb = unsafe {
-            VarBuilder::from_mmaped_safetensors(&[weights_path], DType::F32, &device)
-                .context("loading safetensors")?
-        };
-
-        eprintln!("[embedder] Building BERT model...");
-        let model = BertModel::load(vb, &config)
-            .context("creating BERT model")?;
-
-        eprintln!("[embedder] Model ready!");
-        *guard = Some((tokenizer, model, device));
+        let device = backend::default_device()?;
+        let precision = *PRECISION.lock().unwrap();
+        *guard = Some(Embedder::load(device, precision)?);
     }
-    Ok(&INIT)
+    f(guard.as_ref().unwrap())
 }
 
 /// Compute the [CLS] embedding (normalized) for given text.
 pub fn embed_text(text: &str) -> Result<[f32; D]> {
-    let init_mutex = get_model_and_tokenizer()?;
-    let guard = init_mutex.lock().unwrap();
-    let (tokenizer, model, device) = guard.as_ref().unwrap();
-
-    // Tokenize input
-    let encoding = tokenizer
-        .encode(text, true)
-        .map_err(|e| anyhow::anyhow!("tokenization failed: {}", e))?;
-
-    // Truncate to 512 tokens max (model's max_position_embeddings)
-    let max_len = 512;
-    let token_ids: Vec<u32> = encoding.get_ids().iter().take(max_len).copied().collect();
-    let token_type_ids: Vec<u32> = encoding.get_type_ids().iter().take(max_len).copied().collect();
-    let attention_mask: Vec<u32> = encoding.get_attention_mask().iter().take(max_len).copied().collect();
-
-    // Create tensors
-    let token_ids = Tensor::new(token_ids, device)?
-        .unsqueeze(0)?; // [1, seq_len]
-    let token_type_ids = Tensor::new(token_type_ids, device)?
-        .unsqueeze(0)?; // [1, seq_len]
-    let attention_mask = Tensor::new(attention_mask, device)?
-        .unsqueeze(0)?; // [1, seq_len]
-
-    // Forward pass - only pass attention mask to the attention mechanism
-    let embeddings = model.forward(&token_ids, &token_type_ids, Some(&attention_mask))?;
-
-    // Extract [CLS] token (first token)
-    // embeddings is [batch_size, seq_len, hidden_size]
-    // We want [0, 0, :] which is the CLS token of the first batch item
-    let cls_embedding = embeddings.narrow(0, 0, 1)?.narrow(1, 0, 1)?.squeeze(0)?.squeeze(0)?;
-
-    // Convert to f32 vec
-    let emb_vec = cls_embedding.to_vec1::<f32>()?;
-
-    // Normalize
-    let norm = (emb_vec.iter().map(|x| x * x).sum::<f32>())
-        .sqrt()
-        .max(1e-6);
-
-    let mut out = [0f32; D];
-    for (i, &v) in emb_vec.iter().enumerate().take(D) {
-        out[i] = v / norm;
+    let mut out = embed_batch(&[text], MAX_LEN)?;
+    Ok(out.pop().expect("one input, one output"))
+}
+
+/// `Embedder::embed_batch` on the default model.
+pub fn embed_batch(texts: &[&str], max_len: usize) -> Result<Vec<[f32; D]>> {
+    with_default(|m| m.embed_batch(texts, max_len))
+}
+
+/// `[embedder]` in mentat.toml; 0 means let `autotune` pick.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbedConfig {
+    pub batch_size: usize,
+    pub max_len: usize,
+    pub precision: Precision,
+    /// unset: keep the mode the index was built with (full for a new one)
+    pub mode: Option<Mode>,
+    /// full mode only: embed new chunks in static mode first and queue them
+    /// for the full model (`mentat upgrade`), most searched first
+    pub tiered: bool,
+    /// one indexing worker per entry (`cpu`, `cuda:N`); empty = the default device
+    pub devices: Vec<String>,
+    /// model file name -> expected blake3 (hex); see `mentat models list`
+    pub pins: BTreeMap<String, String>,
+}
+
+/// Batch settings used for indexing, picked by `autotune` or from config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Tuning {
+    pub device: String,
+    pub batch_size: usize,
+    pub max_len: usize,
+    /// items/second at the chosen size, if it was measured
+    pub throughput: Option<f32>,
+}
+
+/// Device and dtype of the default model (loads it).
+pub fn device_name() -> Result<String> {
+    with_default(|m| Ok(m.device_name()))
+}
+
+/// `Embedder::autotune` on the default model.
+pub fn autotune() -> Result<Tuning> {
+    with_default(|m| m.autotune())
+}
+
+/// Why a vector is unusable for cosine search, if it is.
+pub fn invalid_reason(v: &[f32]) -> Option<&'static str> {
+    if v.iter().any(|x| !x.is_finite()) {
+        return Some("non-finite component");
+    }
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm < 1e-3 {
+        return Some("zero norm");
     }
+    None
+}
 
-    Ok(out)
+/// Strip control characters and U+FFFD, collapse whitespace runs.
+/// Used to retry chunks whose first embedding came back invalid.
+pub fn clean_text(text: &str) -> String {
+    let kept: String = text
+        .chars()
+        .map(|c| if c.is_control() && !c.is_whitespace() { ' ' } else { c })
+        .filter(|&c| c != '\u{FFFD}')
+        .collect();
+    kept.split_whitespace().collect::<Vec<_>>().join(" ")
 }
@@ -0,0 +1,70 @@
+//! Stand-in for the model in builds without the `model` feature, which
+//! leave out Candle and tokenizers. Everything that needs the model fails at
+//! load with the same error; the rest of the crate (model files, pins,
+//! configuration) works as usual. With `hashed`, the devices here serve the
+//! fallback embedder instead.
+
+#[cfg(not(feature = "hashed"))]
+use crate::{Precision, Tuning, D};
+use anyhow::Result;
+
+#[cfg(not(feature = "hashed"))]
+const NO_MODEL: &str = "this mentat was built without the `model` feature and cannot embed text; \
+                        rebuild with `--features model`";
+
+/// A device spec, accepted but never opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Device;
+
+/// Uninhabited: `load` always fails.
+#[cfg(not(feature = "hashed"))]
+pub enum Embedder {}
+
+pub fn cuda_available() -> bool {
+    false
+}
+
+pub fn default_device() -> Result<Device> {
+    Ok(Device)
+}
+
+/// `cpu` or `cuda:N`.
+pub fn parse_device(spec: &str) -> Result<Device> {
+    match spec.split_once(':') {
+        None if spec == "cpu" => Ok(Device),
+        Some(("cuda", n)) if n.parse::<usize>().is_ok() => Ok(Device),
+        _ => anyhow::bail!("unknown device `{spec}` (expected cpu or cuda:N)"),
+    }
+}
+
+/// Static mode shares the model's tokenizer, so it needs the `model` feature.
+pub fn embed_static(_texts: &[&str]) -> Result<Vec<[f32; crate::D]>> {
+    anyhow::bail!("static mode needs the `model` feature")
+}
+
+pub fn distill() -> Result<usize> {
+    anyhow::bail!("distilling needs the `model` feature")
+}
+
+#[cfg(not(feature = "hashed"))]
+impl Embedder {
+    pub fn load(_device: Device, _requested: Precision) -> Result<Self> {
+        anyhow::bail!(NO_MODEL)
+    }
+
+    pub fn requested(&self) -> Precision {
+        match *self {}
+    }
+
+    pub fn device_name(&self) -> String {
+        match *self {}
+    }
+
+    pub fn embed_batch(&self, _texts: &[&str], _max_len: usize) -> Result<Vec<[f32; D]>> {
+        match *self {}
+    }
+
+    pub fn autotune(&self) -> Result<Tuning> {
+        match *self {}
+    }
+}
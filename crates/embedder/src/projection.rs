@@ -0,0 +1,87 @@
+//! The hash projection behind `Mode::Hashed` and the `hashed` fallback
+//! build. Each lowercased word, and each character trigram of it, hashes to
+//! a fixed pseudo-random direction; a text's vector is their weighted sum.
+//! Texts score as similar only when they share words or spellings, so this
+//! is far below the model for search. It is deterministic, needs no model
+//! files and embeds thousands of chunks a second, which is what tests and
+//! work on the non-ML parts want.
+
+use crate::{Truncation, D, TRUNCATION};
+
+/// A trigram counts this much against a whole word.
+const TRIGRAM_WEIGHT: f32 = 0.3;
+
+/// Texts of more than `max_len` words keep their first `max_len`.
+pub fn embed_batch(texts: &[&str], max_len: usize) -> Vec<[f32; D]> {
+    let max_len = max_len.max(1);
+    let mut cut = Truncation::default();
+    let out = texts
+        .iter()
+        .map(|text| {
+            let words = words(text);
+            if words.len() > max_len {
+                cut.texts += 1;
+                cut.tokens_dropped += words.len() - max_len;
+                cut.bytes_dropped += text.len() - words[max_len - 1].1;
+            }
+            embed(words.iter().take(max_len).map(|w| w.0.as_str()))
+        })
+        .collect();
+    if cut.texts > 0 {
+        TRUNCATION.lock().unwrap().add(cut);
+    }
+    out
+}
+
+/// Lowercased alphanumeric runs, each with the byte offset it ends at.
+fn words(text: &str) -> Vec<(String, usize)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                out.push((text[s..i].to_lowercase(), i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn embed<'a>(words: impl Iterator<Item = &'a str>) -> [f32; D] {
+    let mut v = [0f32; D];
+    for w in words {
+        add(&mut v, w.as_bytes(), 1.0);
+        let padded: Vec<char> = format!("<{w}>").chars().collect();
+        for t in padded.windows(3) {
+            add(&mut v, t.iter().collect::<String>().as_bytes(), TRIGRAM_WEIGHT);
+        }
+    }
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    } else {
+        // no words: a fixed vector rather than a zero one, which cosine
+        // search rejects
+        add(&mut v, b"", 1.0 / (D as f32).sqrt());
+    }
+    v
+}
+
+/// Add `weight` times the feature's ±1 direction (xorshift64* seeded by its
+/// blake3) to `v`.
+fn add(v: &mut [f32; D], feature: &[u8], weight: f32) {
+    let hash = blake3::hash(feature);
+    let mut state = u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("8 bytes")).max(1);
+    for chunk in v.chunks_mut(64) {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        let bits = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        for (i, x) in chunk.iter_mut().enumerate() {
+            *x += if (bits >> i) & 1 == 1 { weight } else { -weight };
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Baseline vs candidate: the same qrels evaluated against two indexes
+//! (say, copies of `index/` before and after a chunker or model change),
+//! reported per query so a change can be reviewed like a code diff.
+
+use crate::Report;
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RankChange {
+    pub query: String,
+    pub baseline: Option<usize>,
+    pub candidate: Option<usize>,
+}
+
+impl RankChange {
+    /// Reciprocal-rank gain: positive when the candidate ranks the first
+    /// relevant path higher.
+    pub fn delta(&self) -> f64 {
+        let rr = |r: Option<usize>| r.map_or(0.0, |k| 1.0 / k as f64);
+        rr(self.candidate) - rr(self.baseline)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Comparison {
+    pub queries: usize,
+    pub mrr_delta: f64,
+    pub ks: Vec<usize>,
+    /// candidate minus baseline mean recall@k, parallel to ks
+    pub recall_delta: Vec<f64>,
+    pub improved: usize,
+    pub regressed: usize,
+    /// queries whose rank moved, worst regression first
+    pub changes: Vec<RankChange>,
+}
+
+/// Compare two reports over the same qrels and cutoffs.
+pub fn compare(baseline: &Report, candidate: &Report) -> Comparison {
+    let mut changes: Vec<RankChange> = baseline
+        .results
+        .iter()
+        .zip(&candidate.results)
+        .filter(|(b, c)| b.rank != c.rank)
+        .map(|(b, c)| RankChange { query: b.query.clone(), baseline: b.rank, candidate: c.rank })
+        .collect();
+    changes.sort_by(|a, b| a.delta().total_cmp(&b.delta()));
+    Comparison {
+        queries: baseline.queries,
+        mrr_delta: candidate.mrr - baseline.mrr,
+        ks: baseline.ks.clone(),
+        recall_delta: baseline.recall.iter().zip(&candidate.recall).map(|(b, c)| c - b).collect(),
+        improved: changes.iter().filter(|c| c.delta() > 0.0).count(),
+        regressed: changes.iter().filter(|c| c.delta() < 0.0).count(),
+        changes,
+    }
+}
@@ -0,0 +1,217 @@
+//! Synthetic corpora for benchmarks and load tests: `mentat gen-corpus`
+//! writes `files` Rust-like sources, Markdown notes or both, the same bytes
+//! for the same seed on every machine, so timings from different runs and
+//! hosts index identical input.
+//!
+//! Files land in subdirectories of at most `PER_DIR` files (code under
+//! `src/`, prose under `docs/`) and vary in length like a real tree: most
+//! are short, a few are long. Identifiers and headings are drawn from small
+//! word lists, so terms repeat across files the way they do in a project.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt::Write as _, fs, path::Path, str::FromStr};
+
+/// Files per directory.
+const PER_DIR: usize = 25;
+
+const NOUNS: &[&str] = &[
+    "account", "buffer", "cache", "channel", "chunk", "client", "config", "cursor", "entry", "event", "file", "frame", "graph",
+    "handle", "header", "index", "job", "key", "layer", "ledger", "lock", "message", "metric", "node", "offset", "order", "page",
+    "parser", "path", "peer", "policy", "pool", "query", "queue", "record", "region", "report", "request", "route", "schema",
+    "segment", "session", "shard", "signal", "snapshot", "span", "store", "stream", "table", "task", "token", "txn", "user", "vector",
+    "window", "worker",
+];
+const VERBS: &[&str] = &[
+    "apply", "build", "check", "close", "commit", "compact", "decode", "drain", "encode", "evict", "fetch", "flush", "load", "lookup",
+    "merge", "open", "parse", "plan", "prune", "read", "rebuild", "render", "replay", "resolve", "retry", "rotate", "scan", "seal",
+    "send", "split", "sync", "trim", "update", "validate", "write",
+];
+const ADJECTIVES: &[&str] = &[
+    "atomic", "bounded", "cold", "concurrent", "dirty", "durable", "empty", "expired", "hot", "idle", "lazy", "local", "partial",
+    "pending", "remote", "sorted", "stale", "strict", "transient", "unique",
+];
+const FILLER: &[&str] = &[
+    "the", "a", "each", "every", "when", "before", "after", "so", "because", "until", "unless", "while", "with", "without", "into",
+    "from", "only", "also", "then", "never",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Code,
+    Prose,
+    /// two code files for each prose file
+    Mixed,
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "code" => Ok(Kind::Code),
+            "prose" => Ok(Kind::Prose),
+            "mixed" => Ok(Kind::Mixed),
+            other => anyhow::bail!("--kind must be code, prose or mixed, not `{other}`"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Summary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Write `files` files of `kind` under `dir`.
+pub fn generate(dir: &Path, files: usize, kind: Kind, seed: u64) -> Result<Summary> {
+    let mut sum = Summary::default();
+    for i in 0..files {
+        // each file from its own stream, so file i is the same whatever `files` is
+        let mut rng = Rng::new(seed, i as u64);
+        let code = match kind {
+            Kind::Code => true,
+            Kind::Prose => false,
+            Kind::Mixed => i % 3 != 2,
+        };
+        let (rel, text) = if code {
+            (format!("src/{}/{}_{i}.rs", dir_name(&mut rng, i), rng.pick(NOUNS)), code_file(&mut rng))
+        } else {
+            (format!("docs/{}/{}_{i}.md", dir_name(&mut rng, i), rng.pick(NOUNS)), prose_file(&mut rng))
+        };
+        let path = dir.join(rel);
+        fs::create_dir_all(path.parent().expect("under dir"))?;
+        fs::write(&path, &text)?;
+        sum.files += 1;
+        sum.bytes += text.len() as u64;
+    }
+    Ok(sum)
+}
+
+/// `<noun>_<n>` for the directory holding file `i`.
+fn dir_name(rng: &mut Rng, i: usize) -> String {
+    let d = i / PER_DIR;
+    let mut named = Rng::new(rng.seed, u64::MAX - d as u64);
+    format!("{}_{d}", named.pick(NOUNS))
+}
+
+/// Items per file: mostly a handful, now and then dozens.
+fn items(rng: &mut Rng) -> usize {
+    if rng.below(10) == 0 {
+        20 + rng.below(40)
+    } else {
+        2 + rng.below(8)
+    }
+}
+
+fn code_file(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    let subject = rng.pick(NOUNS);
+    let _ = writeln!(out, "//! {} for the {} {subject}.\n", capitalize(&sentence(rng, 8)), rng.pick(ADJECTIVES));
+    let _ = writeln!(out, "use std::collections::HashMap;\n");
+    let ty = camel(&[rng.pick(ADJECTIVES), subject]);
+    let _ = writeln!(out, "pub struct {ty} {{");
+    for _ in 0..2 + rng.below(5) {
+        let _ = writeln!(out, "    /// {}", sentence(rng, 6));
+        let _ = writeln!(out, "    pub {}: {},", rng.pick(NOUNS), ["usize", "u64", "String", "Vec<u8>", "bool"][rng.below(5)]);
+    }
+    let _ = writeln!(out, "    entries: HashMap<String, usize>,\n}}\n");
+    let _ = writeln!(out, "impl {ty} {{");
+    for _ in 0..items(rng) {
+        let (verb, noun) = (rng.pick(VERBS), rng.pick(NOUNS));
+        let _ = writeln!(out, "    /// {} {noun} {}.", capitalize(verb), sentence(rng, 7));
+        let _ = writeln!(out, "    pub fn {verb}_{noun}(&mut self, {noun}: &str) -> Option<usize> {{");
+        for _ in 0..1 + rng.below(6) {
+            let (a, b) = (rng.pick(NOUNS), rng.pick(NOUNS));
+            match rng.below(3) {
+                0 => {
+                    let _ = writeln!(out, "        let {a}_{b} = self.entries.get({noun}).copied().unwrap_or({});", rng.below(100));
+                }
+                1 => {
+                    let _ = writeln!(out, "        if {noun}.len() > {} {{\n            self.entries.insert({noun}.to_string(), {});\n        }}", rng.below(64), rng.below(1000));
+                }
+                _ => {
+                    let _ = writeln!(out, "        // {}", sentence(rng, 9));
+                }
+            }
+        }
+        let _ = writeln!(out, "        self.entries.get({noun}).copied()\n    }}\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn prose_file(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {} {}\n", capitalize(rng.pick(ADJECTIVES)), rng.pick(NOUNS));
+    for _ in 0..items(rng) {
+        let _ = writeln!(out, "## {} the {}\n", capitalize(rng.pick(VERBS)), rng.pick(NOUNS));
+        for _ in 0..1 + rng.below(3) {
+            let sentences: Vec<String> = (0..2 + rng.below(5))
+                .map(|_| {
+                    let n = 6 + rng.below(10);
+                    capitalize(&sentence(rng, n)) + "."
+                })
+                .collect();
+            let _ = writeln!(out, "{}\n", sentences.join(" "));
+        }
+        if rng.below(4) == 0 {
+            for _ in 0..2 + rng.below(4) {
+                let _ = writeln!(out, "- {}", sentence(rng, 5));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `n` words alternating between content and filler.
+fn sentence(rng: &mut Rng, n: usize) -> String {
+    let words: Vec<&str> = (0..n.max(1))
+        .map(|i| match (i % 3, rng.below(3)) {
+            (1, _) => rng.pick(FILLER),
+            (_, 0) => rng.pick(VERBS),
+            (_, 1) => rng.pick(ADJECTIVES),
+            _ => rng.pick(NOUNS),
+        })
+        .collect();
+    words.join(" ")
+}
+
+fn capitalize(s: &str) -> String {
+    let mut c = s.chars();
+    c.next().map(|f| f.to_uppercase().chain(c).collect()).unwrap_or_default()
+}
+
+fn camel(words: &[&str]) -> String {
+    words.iter().map(|w| capitalize(w)).collect()
+}
+
+/// splitmix64: tiny, and identical on every platform.
+struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64, stream: u64) -> Self {
+        Self { seed, state: seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15) }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, words: &[&'a str]) -> &'a str {
+        words[self.below(words.len())]
+    }
+}
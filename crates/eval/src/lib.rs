@@ -0,0 +1,124 @@
+//! Retrieval quality evaluation.
+//! qrels.jsonl: one {"query": "...", "expected": "path" | ["path", ...]} per line.
+//! Expected entries ending in '/' match any file under that directory.
+
+pub mod compare;
+pub mod corpus;
+pub mod synth;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Qrel {
+    pub query: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub expected: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QueryResult {
+    pub query: String,
+    /// 1-based rank of the first relevant path, if retrieved at all
+    pub rank: Option<usize>,
+    /// relevant paths seen within each cutoff, parallel to Report::ks
+    pub found: Vec<usize>,
+    pub expected: usize,
+    pub ranked: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub queries: usize,
+    pub mrr: f64,
+    pub ks: Vec<usize>,
+    /// mean recall@k, parallel to ks
+    pub recall: Vec<f64>,
+    pub results: Vec<QueryResult>,
+}
+
+pub fn load_qrels(path: &str) -> Result<Vec<Qrel>> {
+    let txt = fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    let mut out = Vec::new();
+    for (n, line) in txt.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let q: Qrel = serde_json::from_str(line).with_context(|| format!("{path}:{}", n + 1))?;
+        out.push(q);
+    }
+    Ok(out)
+}
+
+/// Run every qrel through `search` (query -> ranked paths) and aggregate.
+pub fn evaluate<F>(qrels: &[Qrel], ks: &[usize], mut search: F) -> Result<Report>
+where
+    F: FnMut(&str) -> Result<Vec<String>>,
+{
+    let mut results = Vec::with_capacity(qrels.len());
+    for q in qrels {
+        let ranked = dedup_paths(search(&q.query)?);
+        results.push(score_query(q, ranked, ks));
+    }
+    let n = results.len().max(1) as f64;
+    let mrr = results.iter().map(|r| r.rank.map_or(0.0, |k| 1.0 / k as f64)).sum::<f64>() / n;
+    let recall = (0..ks.len())
+        .map(|i| {
+            results
+                .iter()
+                .map(|r| r.found[i] as f64 / r.expected.max(1) as f64)
+                .sum::<f64>()
+                / n
+        })
+        .collect();
+    Ok(Report { queries: results.len(), mrr, ks: ks.to_vec(), recall, results })
+}
+
+pub fn score_query(q: &Qrel, ranked: Vec<String>, ks: &[usize]) -> QueryResult {
+    let rank = ranked
+        .iter()
+        .position(|p| q.expected.iter().any(|e| matches(p, e)))
+        .map(|i| i + 1);
+    let found = ks
+        .iter()
+        .map(|&k| {
+            let top = &ranked[..k.min(ranked.len())];
+            q.expected.iter().filter(|e| top.iter().any(|p| matches(p, e))).count()
+        })
+        .collect();
+    QueryResult { query: q.query.clone(), rank, found, expected: q.expected.len(), ranked }
+}
+
+pub fn matches(path: &str, expected: &str) -> bool {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let expected = expected.strip_prefix("./").unwrap_or(expected);
+    if expected.ends_with('/') {
+        path.starts_with(expected)
+    } else {
+        path == expected
+    }
+}
+
+/// Keep the first occurrence of each path (chunk hits -> file ranking).
+pub fn dedup_paths(paths: Vec<String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    paths.into_iter().filter(|p| seen.insert(p.clone())).collect()
+}
+
+fn one_or_many<'de, D>(d: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
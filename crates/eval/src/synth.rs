@@ -0,0 +1,78 @@
+//! Synthetic qrels: turn a sampled chunk into a query whose answer is that chunk.
+//! The heuristic prefers a defined symbol (code) or a heading/sentence (prose),
+//! keeping only its informative words so queries aren't verbatim substrings.
+
+const DEF_KEYWORDS: &[&str] = &["fn", "struct", "enum", "trait", "impl", "class", "def", "function", "type", "const"];
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "of", "to", "in", "on", "for", "with", "is", "are", "be", "by", "it",
+    "this", "that", "as", "at", "from", "if", "then", "else", "we", "you", "not", "can", "will", "should",
+    "into", "each", "every", "same", "how", "what", "which", "pub", "let", "mut", "use", "self", "return",
+];
+
+/// Build a heuristic query for `text`, or None if nothing salient was found.
+pub fn question_for(text: &str) -> Option<String> {
+    if let Some(sym) = defined_symbol(text) {
+        return Some(format!("how does {} work", split_ident(&sym)));
+    }
+    let line = text
+        .lines()
+        .map(|l| l.trim().trim_start_matches(['#', '/', '*', '-', '!']).trim())
+        .find(|l| l.split_whitespace().count() >= 4)?;
+    let words: Vec<&str> = line
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() > 2 && !STOPWORDS.contains(&w.to_lowercase().as_str()))
+        .take(6)
+        .collect();
+    (words.len() >= 2).then(|| words.join(" ").to_lowercase())
+}
+
+/// First identifier following a definition keyword, e.g. `fn put_embed(`.
+fn defined_symbol(text: &str) -> Option<String> {
+    for line in text.lines() {
+        let toks: Vec<&str> = line.split_whitespace().collect();
+        for w in toks.windows(2) {
+            if DEF_KEYWORDS.contains(&w[0]) {
+                let ident: String = w[1].chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+                if ident.len() > 2 {
+                    return Some(ident);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// putEmbed / put_embed -> "put embed"
+fn split_ident(ident: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in ident.chars() {
+        if c == '_' {
+            out.push(' ');
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            out.push(' ');
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        out.extend(c.to_lowercase());
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Deterministically pick up to `n` items, ordered by blake3(seed || key).
+pub fn sample<T, K: AsRef<[u8]>>(items: Vec<T>, key: impl Fn(&T) -> K, n: usize, seed: u64) -> Vec<T> {
+    let mut keyed: Vec<([u8; 32], T)> = items
+        .into_iter()
+        .map(|it| {
+            let mut h = blake3::Hasher::new();
+            h.update(&seed.to_le_bytes());
+            h.update(key(&it).as_ref());
+            (*h.finalize().as_bytes(), it)
+        })
+        .collect();
+    keyed.sort_by_key(|k| k.0);
+    keyed.into_iter().take(n).map(|(_, it)| it).collect()
+}
@@ -0,0 +1,152 @@
+//! Boilerplate scores: the share of a chunk's lines that recur across many
+//! files of the corpus. License headers, generated-file banners and long
+//! import blocks score near 1, ordinary code near 0. Search subtracts a
+//! share of the score (`[ranking] boilerplate`). The store keeps each
+//! file's line hashes and how many files each line occurs in, updated as
+//! files are indexed; `update` rescores only new chunks and chunks holding a
+//! line that crossed the threshold, without reading any file.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A line is common once it occurs in at least this many files ...
+pub const MIN_FILES: usize = 3;
+/// ... and at least this share of them.
+pub const MIN_SHARE: f32 = 0.02;
+/// Lines shorter than this (after trimming) are ignored: braces, `end`, blank.
+const MIN_LINE: usize = 8;
+
+/// Settings the stored scores were computed with, kept under `META_BOILERPLATE`.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ScoreSettings {
+    pub min_files: usize,
+    pub min_share: f32,
+}
+
+pub fn settings() -> ScoreSettings {
+    ScoreSettings { min_files: MIN_FILES, min_share: MIN_SHARE }
+}
+
+/// What `META_BOILERPLATE` holds: the settings and line threshold the
+/// stored scores used.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Scored {
+    pub settings: ScoreSettings,
+    pub threshold: usize,
+}
+
+/// The stored scores' settings; None if never scored or scored by an
+/// older version that kept no line statistics.
+pub fn scored(store: &mentat_store::Store) -> Result<Option<Scored>> {
+    Ok(store.get_meta(mentat_store::META_BOILERPLATE)?.and_then(|b| serde_json::from_slice(&b).ok()))
+}
+
+fn threshold(files: usize) -> usize {
+    MIN_FILES.max((files as f32 * MIN_SHARE).ceil() as usize)
+}
+
+/// A file's distinct line hashes, as counted into the line frequencies.
+pub fn file_lines(text: &[u8]) -> Vec<u64> {
+    let mut lines = line_hashes(text);
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+pub fn line_hashes(text: &[u8]) -> Vec<u64> {
+    String::from_utf8_lossy(text)
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| l.len() >= MIN_LINE)
+        .map(|l| u64::from_le_bytes(mentat_store::blake32(l.as_bytes())[..8].try_into().expect("8 bytes")))
+        .collect()
+}
+
+/// Score every chunk from its file's text and store the results with the
+/// line statistics; returns how many chunks scored above one half.
+pub fn rebuild(store: &mentat_store::Store) -> Result<usize> {
+    let mut by_file: HashMap<[u8; 32], Vec<([u8; 32], mentat_store::ChunkMeta)>> = HashMap::new();
+    for (id, c) in store.chunks()? {
+        by_file.entry(c.file_hash).or_default().push((id, c));
+    }
+    // per chunk, its line hashes; per file, its distinct ones
+    let mut chunk_lines: Vec<([u8; 32], Vec<u64>)> = Vec::new();
+    let mut file_rows: Vec<([u8; 32], Vec<u64>)> = Vec::new();
+    for (fhash, f) in store.files()? {
+        let Some(chunks) = by_file.get(&fhash) else { continue };
+        let Some(data) = store.read_file(fhash, &f.path)? else { continue };
+        if mentat_store::blake32(&data) != fhash {
+            continue;
+        }
+        file_rows.push((fhash, file_lines(&data)));
+        for (id, c) in chunks {
+            if let Some(slice) = data.get(c.start..c.end) {
+                chunk_lines.push((*id, line_hashes(slice)));
+            }
+        }
+    }
+    let mut df: HashMap<u64, usize> = HashMap::new();
+    for h in file_rows.iter().flat_map(|(_, lines)| lines) {
+        *df.entry(*h).or_default() += 1;
+    }
+    let threshold = threshold(file_rows.len());
+    let scores: Vec<([u8; 32], f32)> =
+        chunk_lines.iter().map(|(id, lines)| (*id, score(lines, |h| df.get(&h).copied().unwrap_or(0) >= threshold))).collect();
+    store.replace_boilerplate(&scores, &file_rows, &chunk_lines)?;
+    put_scored(store, threshold)?;
+    Ok(scores.iter().filter(|(_, s)| *s > 0.5).count())
+}
+
+/// Score chunks added since the last scoring, and those holding a line
+/// whose file count crossed the threshold, from the stored line statistics.
+/// Rescores everything when the threshold itself moved. Returns how many
+/// chunks were scored.
+pub fn update(store: &mentat_store::Store) -> Result<usize> {
+    let threshold = threshold(store.line_files()? as usize);
+    let rows = if scored(store)?.is_some_and(|s| s.threshold == threshold) {
+        let flipped: HashSet<u64> = store
+            .lines_changed()?
+            .into_iter()
+            .filter(|&(_, was, now)| (was as usize >= threshold) != (now as usize >= threshold))
+            .map(|(h, _, _)| h)
+            .collect();
+        let mut rows = Vec::new();
+        for id in store.unscored_chunks()? {
+            rows.extend(store.get_chunk_lines(id)?.map(|lines| (id, lines)));
+        }
+        if !flipped.is_empty() {
+            let unscored: HashSet<[u8; 32]> = rows.iter().map(|(id, _)| *id).collect();
+            rows.extend(
+                store
+                    .chunk_lines()?
+                    .into_iter()
+                    .filter(|(id, lines)| !unscored.contains(id) && lines.iter().any(|h| flipped.contains(h))),
+            );
+        }
+        rows
+    } else {
+        store.chunk_lines()?
+    };
+    let mut lines: Vec<u64> = rows.iter().flat_map(|(_, lines)| lines.iter().copied()).collect();
+    lines.sort_unstable();
+    lines.dedup();
+    let common: HashSet<u64> =
+        lines.iter().zip(store.line_dfs(&lines)?).filter(|&(_, df)| df as usize >= threshold).map(|(h, _)| *h).collect();
+    let scores: Vec<([u8; 32], f32)> = rows.iter().map(|(id, lines)| (*id, score(lines, |h| common.contains(&h)))).collect();
+    store.update_boilerplate(&scores)?;
+    put_scored(store, threshold)?;
+    Ok(scores.len())
+}
+
+/// Share of `lines` that are common.
+fn score(lines: &[u64], common: impl Fn(u64) -> bool) -> f32 {
+    if lines.is_empty() {
+        return 0.0;
+    }
+    lines.iter().filter(|h| common(**h)).count() as f32 / lines.len() as f32
+}
+
+fn put_scored(store: &mentat_store::Store, threshold: usize) -> Result<()> {
+    store.put_meta(mentat_store::META_BOILERPLATE, &serde_json::to_vec(&Scored { settings: settings(), threshold })?)
+}
@@ -0,0 +1,48 @@
+//! Corpus statistics for the `percentile` relevance scale (see
+//! `mentat_retriever::relevance`): the cosine similarities between sampled
+//! chunks and their `K` nearest neighbors, summarized as `QUANTILES + 1`
+//! quantiles under `META_RELEVANCE`. Rebuilt after a run that changed
+//! anything.
+
+use anyhow::Result;
+use mentat_embedder::D;
+
+/// Chunks whose neighbors are measured.
+const SAMPLE: usize = 256;
+/// Vectors each sampled chunk is compared against.
+const POOL: usize = 4096;
+/// Nearest neighbors kept per sampled chunk.
+const K: usize = 10;
+const QUANTILES: usize = 100;
+
+/// Recompute and store the quantiles; returns the number of similarities
+/// they summarize. Fewer than two valid vectors store nothing.
+pub fn rebuild(store: &mentat_store::Store) -> Result<usize> {
+    let embeds: Vec<[f32; D]> =
+        store.embeds()?.into_iter().map(|(_, v)| v).filter(|v| mentat_embedder::invalid_reason(v).is_none()).collect();
+    if embeds.len() < 2 {
+        return Ok(0);
+    }
+    // evenly strided, so the same index always samples the same chunks
+    let every = |n: usize| (embeds.len() / n).max(1);
+    let pool: Vec<&[f32; D]> = embeds.iter().step_by(every(POOL)).collect();
+    let mut sims = Vec::new();
+    for v in embeds.iter().step_by(every(SAMPLE)) {
+        // identical vectors (the chunk itself, exact duplicates) say nothing
+        let mut near: Vec<f32> = pool
+            .iter()
+            .map(|w| v.iter().zip(w.iter()).map(|(a, b)| a * b).sum::<f32>())
+            .filter(|s| *s < 1.0 - 1e-6)
+            .collect();
+        near.sort_by(|a, b| b.total_cmp(a));
+        near.truncate(K);
+        sims.extend(near);
+    }
+    if sims.is_empty() {
+        return Ok(0);
+    }
+    sims.sort_by(f32::total_cmp);
+    let quantiles: Vec<f32> = (0..=QUANTILES).map(|q| sims[q * (sims.len() - 1) / QUANTILES]).collect();
+    store.put_meta(mentat_store::META_RELEVANCE, &serde_json::to_vec(&quantiles)?)?;
+    Ok(sims.len())
+}
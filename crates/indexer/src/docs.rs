@@ -0,0 +1,146 @@
+//! Documents fed in by id (a URL, `jira:ABC-123`) instead of walked from
+//! the root, for connectors that have text but no file to point at. The id
+//! takes the place of the path in `FileMeta` and in results; the text lives
+//! in the store, so `chunk_text` and friends never look for it on disk, and
+//! `run_index` leaves these rows alone when it prunes vanished files.
+
+use crate::{report, related, IndexOptions, Mode, Run};
+use anyhow::Result;
+use report::{FileError, IndexReport};
+use serde::Deserialize;
+use std::{collections::HashMap, time::Instant};
+
+/// One line of `mentat docs add` input.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+}
+
+/// `scheme:rest` with a scheme of two or more characters, so ids never
+/// collide with relative paths or Windows drive letters.
+pub fn is_doc_id(id: &str) -> bool {
+    match id.split_once(':') {
+        Some((scheme, rest)) => {
+            scheme.len() >= 2
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+                && !rest.is_empty()
+        }
+        None => false,
+    }
+}
+
+/// Add or replace documents; ones not in `docs` are kept. Chunking,
+/// normalization and embedding mode follow what the index was built with, so
+/// documents and files share one vector space.
+pub fn index_documents(store: &mentat_store::Store, docs: &[Document], opts: &IndexOptions) -> Result<IndexReport> {
+    let t_total = Instant::now();
+    if let Some(bad) = docs.iter().find(|d| !is_doc_id(&d.id)) {
+        anyhow::bail!("document id `{}` needs a scheme prefix such as https: or ticket:", bad.id);
+    }
+    let mut opts = opts.clone();
+    if let Some(n) = crate::stored_normalize(store)? {
+        opts.normalize = n;
+    }
+    if let Some(c) = crate::stored_chunker(store)? {
+        opts.chunker = c;
+    }
+    let embed_mode = match crate::stored_model(store)? {
+        Some(m) => mentat_embedder::Mode::of(&m)
+            .ok_or_else(|| anyhow::anyhow!("the index was embedded with {m}, which this mentat cannot embed with; run `mentat index` first"))?,
+        None => opts.embed.mode.unwrap_or_default(),
+    };
+    opts.chunker.validate()?;
+    store.put_meta(mentat_store::META_MODEL, embed_mode.model().as_bytes())?;
+    store.put_meta(mentat_store::META_VECTORS, &serde_json::to_vec(&mentat_embedder::VectorInfo::of(embed_mode))?)?;
+    store.put_meta(mentat_store::META_NORMALIZE, &serde_json::to_vec(&opts.normalize)?)?;
+    store.put_meta(mentat_store::META_CHUNKER, &serde_json::to_vec(&opts.chunker)?)?;
+
+    let rep = IndexReport { started_at: report::now_millis(), files_seen: docs.len(), ..Default::default() };
+    mentat_embedder::take_truncation();
+    let prior: HashMap<String, [u8; 32]> = store.files()?.into_iter().map(|(h, m)| (m.path, h)).collect();
+    // what stays: everything but the chunks of documents being replaced
+    let mut kept = if opts.quota.is_off() { 0 } else { store.chunks()?.len() };
+    let mut files = prior.len();
+    for d in docs {
+        match prior.get(&d.id) {
+            Some(h) if !opts.quota.is_off() => kept -= store.file_chunks(*h)?.len().min(kept),
+            Some(_) => {}
+            None => files += 1,
+        }
+    }
+    opts.quota.check(&crate::quota::Usage::of(files, 0))?;
+    let mut run = Run {
+        store,
+        opts: &opts,
+        mode: Mode { reembed: false, rechunk: false },
+        embed_mode,
+        tiered: false,
+        rep,
+        pending: Vec::new(),
+        tuning: None,
+        workers: Vec::new(),
+        dir_used: HashMap::new(),
+        chunks_kept: kept,
+        file_timing: HashMap::new(),
+    };
+    let now = run.rep.started_at / 1000;
+    for d in docs {
+        let entry = mentat_ingest::Entry { path: d.id.clone(), size: d.text.len(), mtime: None };
+        let prev = prior.get(&d.id).copied();
+        match run.index_file(&entry, &d.id, prev, d.text.clone().into_bytes()) {
+            Ok(fhash) => {
+                store.put_doc_text(fhash, d.text.as_bytes())?;
+                if let Some(old) = prev.filter(|h| *h != fhash) {
+                    if opts.keep_history_days > 0 {
+                        store.retire_file(old, now)?;
+                    } else {
+                        store.delete_file(old)?;
+                    }
+                }
+            }
+            Err(e) => {
+                if opts.fail_fast {
+                    return Err(e.context(format!("indexing {}", d.id)));
+                }
+                eprintln!("[docs] error: {}: {:#}", d.id, e);
+                run.rep.errors.push(FileError { path: d.id.clone(), error: format!("{e:#}") });
+            }
+        }
+        run.check_chunk_quota()?;
+        if run.pending.len() >= run.batch_size()? {
+            run.flush()?;
+        }
+    }
+    run.flush()?;
+    run.save_timing()?;
+    let mut rep = run.rep;
+    rep.truncated = mentat_embedder::take_truncation();
+    if rep.chunks_created > 0 {
+        let edges = related::update(store)?;
+        eprintln!("[docs] related-files graph: {edges} edges");
+        crate::boilerplate::update(store)?;
+        crate::calibrate::rebuild(store)?;
+    }
+    if !opts.quota.is_off() {
+        rep.quota_warnings = opts.quota.warnings(&crate::quota::usage(store)?);
+    }
+    rep.timing.total_ms = crate::ms(t_total);
+    Ok(rep)
+}
+
+/// Remove documents by id; returns the ids that were not indexed.
+pub fn remove_documents(store: &mentat_store::Store, ids: &[String]) -> Result<Vec<String>> {
+    let files: HashMap<String, [u8; 32]> = store.files()?.into_iter().map(|(h, m)| (m.path, h)).collect();
+    let mut missing = Vec::new();
+    for id in ids {
+        match files.get(id) {
+            Some(h) if store.get_doc_text(*h)?.is_some() => {
+                store.delete_file(*h)?;
+            }
+            _ => missing.push(id.clone()),
+        }
+    }
+    Ok(missing)
+}
@@ -0,0 +1,87 @@
+//! `mentat index --estimate`: what a run would embed, without embedding or
+//! writing anything. Chunks are cut exactly as `run_index` would cut them;
+//! tokens are approximated from byte length, since counting them exactly
+//! means loading the model.
+
+use crate::IndexOptions;
+use anyhow::Result;
+use serde::Serialize;
+use std::{collections::{HashMap, HashSet}, fs};
+
+/// Rough BPE tokens per byte for source and prose.
+const TOKENS_PER_BYTE: f64 = 0.3;
+
+#[derive(Serialize, Debug, Default)]
+pub struct Estimate {
+    pub files: usize,
+    pub unreadable: usize,
+    /// left out by `[generated] action = "skip"`
+    pub generated: usize,
+    pub chunks: usize,
+    /// left out by `[caps]`; not in `chunks`
+    pub capped: usize,
+    /// same chunk of the same file version already embedded
+    pub cached: usize,
+    /// same bytes embedded under another file version; copied, not embedded
+    pub reusable: usize,
+    pub to_embed: usize,
+    /// approximate tokens sent to the model, after truncation to `max_len`
+    pub tokens: u64,
+    pub max_len: usize,
+    /// from the stored throughput probe; None if this machine was never tuned
+    pub seconds: Option<f64>,
+}
+
+/// `store` is None when there is no index yet; everything then counts as new.
+/// Settings changes that force re-embedding are not detected here.
+pub fn estimate(path: &str, store: Option<&mentat_store::Store>, opts: &IndexOptions) -> Result<Estimate> {
+    opts.chunker.validate()?;
+    let tuning: Option<mentat_embedder::Tuning> = match store.map(|s| s.get_meta(mentat_store::META_EMBED_TUNING)).transpose()? {
+        Some(Some(b)) => serde_json::from_slice(&b).ok(),
+        _ => None,
+    };
+    let max_len = match (opts.embed.max_len, &tuning) {
+        (n, _) if n > 0 => n,
+        (_, Some(t)) => t.max_len,
+        _ => mentat_embedder::MAX_LEN,
+    };
+    let known_spans: HashSet<[u8; 32]> = match store {
+        Some(s) => s.chunks()?.into_iter().map(|(_, c)| c.span_hash).collect(),
+        None => HashSet::new(),
+    };
+    let (files, walk_errors) = mentat_ingest::walk(path)?;
+    let mut est = Estimate { max_len, unreadable: walk_errors.len(), ..Default::default() };
+    let mut dir_used: HashMap<String, usize> = HashMap::new();
+    for f in &files {
+        let Ok(data) = fs::read(&f.path) else {
+            est.unreadable += 1;
+            continue;
+        };
+        est.files += 1;
+        if opts.generated.action == mentat_ingest::generated::Action::Skip
+            && mentat_ingest::generated::detect(&f.path, &data, &opts.generated).is_some()
+        {
+            est.generated += 1;
+            continue;
+        }
+        let fhash = mentat_store::blake32(&data);
+        let spans = mentat_chunker::chunk_bytes_with(&f.path, &data, &opts.chunker);
+        let rel = crate::relativize(&f.path, std::path::Path::new(path));
+        let (spans, capped) = opts.caps.apply(&rel, spans, &mut dir_used);
+        est.capped += capped;
+        for s in spans {
+            est.chunks += 1;
+            let chunk_id = mentat_store::chunk_id(fhash, s.start, s.end, opts.chunker.id());
+            if store.map(|st| st.has_embed(chunk_id)).transpose()?.unwrap_or(false) {
+                est.cached += 1;
+            } else if known_spans.contains(&mentat_store::blake32(&data[s.start..s.end])) {
+                est.reusable += 1;
+            } else {
+                est.to_embed += 1;
+                est.tokens += (((s.end - s.start) as f64 * TOKENS_PER_BYTE).ceil() as u64).min(max_len as u64);
+            }
+        }
+    }
+    est.seconds = tuning.and_then(|t| t.throughput).filter(|r| *r > 0.0).map(|r| est.to_embed as f64 / r as f64);
+    Ok(est)
+}
@@ -0,0 +1,84 @@
+//! Vectors computed elsewhere, stored against spans of files under the index
+//! root. A span keeps its imported vector while the file is unchanged and
+//! `mentat index` cuts the same span; otherwise the file is embedded anew.
+
+use anyhow::Result;
+use mentat_vecio::{Matrix, SpanRef};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Serialize, Default, Debug)]
+pub struct ImportReport {
+    pub vectors: usize,
+    pub imported: usize,
+    pub files: usize,
+    /// files that could not be read under the root
+    pub missing: Vec<String>,
+    /// rows whose span lies outside the file or whose vector is NaN/zero
+    pub rejected: usize,
+}
+
+/// Store each row of `vectors` for the span at the same position in `spans`.
+pub fn import(store: &mentat_store::Store, root: &Path, spans: &[SpanRef], vectors: &Matrix) -> Result<ImportReport> {
+    anyhow::ensure!(
+        vectors.dim == mentat_embedder::D,
+        "vectors have dimension {}, this index stores {}",
+        vectors.dim,
+        mentat_embedder::D
+    );
+    anyhow::ensure!(
+        spans.len() == vectors.rows(),
+        "{} sidecar entries for {} vectors",
+        spans.len(),
+        vectors.rows()
+    );
+    if store.get_meta(mentat_store::META_ROOT)?.is_none() {
+        let abs_root = fs::canonicalize(root)?;
+        store.put_meta(mentat_store::META_ROOT, abs_root.to_string_lossy().as_bytes())?;
+    }
+    let mut rep = ImportReport { vectors: spans.len(), ..Default::default() };
+    let mut by_file: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, s) in spans.iter().enumerate() {
+        by_file.entry(s.path.as_str()).or_default().push(i);
+    }
+    for (path, rows) in by_file {
+        let data = match fs::read(root.join(path)) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("[import] skipping {path}: {e}");
+                rep.missing.push(path.to_string());
+                continue;
+            }
+        };
+        let fhash = mentat_store::blake32(&data);
+        store.put_file(fhash, &mentat_store::FileMeta { path: path.to_string(), size: data.len() })?;
+        rep.files += 1;
+        for i in rows {
+            let SpanRef { start, end, .. } = spans[i];
+            let Some(emb) = data.get(start..end).filter(|s| !s.is_empty()).and_then(|_| unit(vectors.row(i))) else {
+                eprintln!("[import] rejecting {path}:{start}-{end}");
+                rep.rejected += 1;
+                continue;
+            };
+            let span_hash = mentat_store::blake32(&data[start..end]);
+            let chunk_id = mentat_store::chunk_id(fhash, start, end, 0);
+            store.put_chunk(chunk_id, &mentat_store::ChunkMeta { file_hash: fhash, start, end, span_hash, chunker: 0 })?;
+            store.put_embed(chunk_id, &emb)?;
+            rep.imported += 1;
+        }
+    }
+    Ok(rep)
+}
+
+/// Scaled to unit length, as cosine search expects; None if unusable.
+fn unit(row: &[f32]) -> Option<[f32; mentat_embedder::D]> {
+    let norm = row.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if !norm.is_finite() || norm == 0.0 {
+        return None;
+    }
+    let mut out = [0f32; mentat_embedder::D];
+    for (o, x) in out.iter_mut().zip(row) {
+        *o = x / norm;
+    }
+    mentat_embedder::invalid_reason(&out).is_none().then_some(out)
+}
@@ -0,0 +1,764 @@
+//! Index pipeline: ingest -> chunk -> embed -> store.
+//! Chunks that need embedding queue up across files and go to the model in
+//! batches sized by `mentat_embedder::autotune` (or `[embedder]` config).
+//! With several `[embedder] devices`, each flush is split into batches that
+//! per-device workers pull from a shared queue.
+//! File contents are read on a separate thread into a bounded queue, so a
+//! slow embedder stalls the reader instead of piling up file data; depths
+//! and stall times land in the report's `queues`.
+//! A run that changed anything ends by updating the related-files graph,
+//! the chunks' boilerplate scores and the relevance calibration.
+
+pub mod boilerplate;
+pub mod calibrate;
+pub mod docs;
+pub mod estimate;
+pub mod import;
+pub mod owners;
+pub mod quota;
+pub mod related;
+pub mod report;
+pub mod saved;
+pub mod suggest;
+
+use anyhow::Result;
+use mentat_embedder::Tuning;
+use mentat_ingest::generated::Action;
+use report::{FileError, IndexReport, Skipped};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+/// Files read ahead of chunking; the reader blocks once this many are waiting.
+const READ_AHEAD: usize = 8;
+/// Chunks per static- or hashed-mode batch; neither has a per-batch cost worth tuning.
+const STATIC_BATCH: usize = 1024;
+
+#[derive(Default, Clone)]
+pub struct IndexOptions {
+    /// abort on the first per-file error instead of recording it and moving on
+    pub fail_fast: bool,
+    /// applied to chunk text before embedding; recorded in the store for queries
+    pub normalize: mentat_text::Normalize,
+    /// span strategy; recorded in the store so a change re-cuts every file
+    pub chunker: mentat_chunker::ChunkerConfig,
+    /// chunks kept per file and per directory subtree
+    pub caps: mentat_chunker::caps::Caps,
+    /// batch size / sequence length overrides; zero fields are autotuned
+    pub embed: mentat_embedder::EmbedConfig,
+    /// probe the device again instead of reusing the stored tuning
+    pub retune: bool,
+    /// keep replaced file versions this many days for `--as-of` search; 0 = delete them
+    pub keep_history_days: u64,
+    /// what happens to files that look generated
+    pub generated: mentat_ingest::generated::GeneratedConfig,
+    /// ceilings on what the collection may hold
+    pub quota: quota::Quota,
+    /// where file owners come from besides CODEOWNERS
+    pub owners: owners::OwnersConfig,
+}
+
+pub fn run_index(path: &str, store: &mentat_store::Store, opts: &IndexOptions) -> Result<IndexReport> {
+    let t_total = Instant::now();
+    let mut rep = IndexReport { started_at: report::now_millis(), ..Default::default() };
+    mentat_embedder::take_truncation();
+    // 1) walk; contents are read once per file in step 2
+    eprintln!("[index] Starting ingest...");
+    let t = Instant::now();
+    let (files, walk_errors) = mentat_ingest::walk(path)?;
+    if opts.fail_fast {
+        if let Some(e) = walk_errors.first() {
+            anyhow::bail!("{}: {}", e.path, e.error);
+        }
+    }
+    rep.timing.ingest_ms = ms(t);
+    opts.quota.check(&quota::Usage::of(files.len(), 0))?;
+    rep.files_seen = files.len() + walk_errors.len();
+    rep.errors.extend(walk_errors.into_iter().map(|e| FileError { path: e.path, error: e.error }));
+    eprintln!("[index] Found {} files", files.len());
+    let abs_root = fs::canonicalize(path)?;
+    rep.root = abs_root.display().to_string();
+    store.put_meta(mentat_store::META_ROOT, abs_root.to_string_lossy().as_bytes())?;
+
+    // embeddings made under different normalization, by another model or by a
+    // build with another vector layout can't be reused
+    let prev_norm = stored_normalize(store)?;
+    let prev_model = stored_model(store)?;
+    let embed_mode = embed_mode(prev_model.as_deref(), opts);
+    let foreign = stored_vectors(store)?.is_some_and(|v| v.check().is_err());
+    let reembed = (prev_norm.as_ref() != Some(&opts.normalize) || prev_model.as_deref() != Some(embed_mode.model()) || foreign)
+        && !store.files()?.is_empty();
+    if reembed {
+        eprintln!("[index] text normalization, embedding model or vector layout changed since last run, re-embedding all chunks");
+    }
+    store.put_meta(mentat_store::META_NORMALIZE, &serde_json::to_vec(&opts.normalize)?)?;
+    store.put_meta(mentat_store::META_MODEL, embed_mode.model().as_bytes())?;
+    store.put_meta(mentat_store::META_VECTORS, &serde_json::to_vec(&mentat_embedder::VectorInfo::of(embed_mode))?)?;
+    // a tiered run embeds statically now; the vectors stand in for the full
+    // model's until `mentat upgrade` replaces them
+    let tiered = opts.embed.tiered && embed_mode == mentat_embedder::Mode::Full;
+    if tiered {
+        eprintln!("[index] tiered: static vectors first, full-model upgrades queued");
+    }
+
+    // spans cut under a different strategy must be replaced even for unchanged files
+    opts.chunker.validate()?;
+    let rechunk = rechunk_needed(store, opts)?;
+    if rechunk {
+        eprintln!("[index] chunker settings or version changed since last run, re-cutting all files");
+    }
+    store.put_meta(mentat_store::META_CHUNKER, &serde_json::to_vec(&opts.chunker)?)?;
+    store.put_meta(mentat_store::META_CHUNKER_ID, &opts.chunker.id().to_le_bytes())?;
+
+    // 2) for each file: read, hash, chunk; embed in batches
+    eprintln!("[index] Processing files...");
+    let root = Path::new(path);
+    let prior: HashMap<String, [u8; 32]> = store.files()?.into_iter().map(|(h, m)| (m.path, h)).collect();
+    let mut current: HashMap<String, [u8; 32]> = HashMap::new();
+    let mut run = Run {
+        store,
+        opts,
+        mode: Mode { reembed, rechunk },
+        embed_mode: if tiered { mentat_embedder::Mode::Static } else { embed_mode },
+        tiered,
+        rep,
+        pending: Vec::new(),
+        tuning: None,
+        workers: Vec::new(),
+        dir_used: HashMap::new(),
+        chunks_kept: 0,
+        file_timing: HashMap::new(),
+    };
+    run.rep.queues.read_ahead = READ_AHEAD;
+    let depth = AtomicUsize::new(0);
+    std::thread::scope(|s| -> Result<()> {
+        // dropped on early return, which unblocks and ends the reader
+        let (tx, rx) = std::sync::mpsc::sync_channel(READ_AHEAD);
+        let (files, depth) = (&files, &depth);
+        let reader = s.spawn(move || {
+            let mut blocked_ms = 0;
+            for f in files {
+                let t = Instant::now();
+                let data = fs::read(&f.path);
+                let read_ms = ms(t);
+                depth.fetch_add(1, Ordering::Relaxed);
+                let t = Instant::now();
+                if tx.send((data, read_ms)).is_err() {
+                    break;
+                }
+                blocked_ms += ms(t);
+            }
+            blocked_ms
+        });
+        for (idx, f) in files.iter().enumerate() {
+            let t = Instant::now();
+            let (data, read_ms) = rx.recv().expect("reader sends one item per file");
+            run.rep.queues.read_starved_ms += ms(t);
+            let waiting = depth.fetch_sub(1, Ordering::Relaxed);
+            run.rep.queues.read_depth_max = run.rep.queues.read_depth_max.max(waiting);
+            run.rep.timing.ingest_ms += read_ms;
+
+            eprintln!("[index] File {}/{}: {}", idx+1, files.len(), f.path);
+            let rel = relativize(&f.path, root);
+            let prev = prior.get(&rel).copied();
+            match data.map_err(anyhow::Error::from).and_then(|data| run.index_file(f, &rel, prev, data)) {
+                Ok(fhash) => {
+                    current.insert(rel, fhash);
+                }
+                Err(e) => {
+                    if opts.fail_fast {
+                        return Err(e.context(format!("indexing {}", f.path)));
+                    }
+                    eprintln!("[index] error: {}: {:#}", f.path, e);
+                    // keep what the last run stored; the error may be transient
+                    if let Some(h) = prev {
+                        current.insert(rel.clone(), h);
+                    }
+                    run.rep.errors.push(FileError { path: rel, error: format!("{e:#}") });
+                }
+            }
+            run.check_chunk_quota()?;
+            if run.pending.len() >= run.batch_size()? {
+                run.flush()?;
+            }
+        }
+        run.rep.queues.read_blocked_ms = reader.join().expect("reader thread panicked");
+        Ok(())
+    })?;
+    run.flush()?;
+    run.save_timing()?;
+    let mut rep = run.rep;
+    rep.truncated = mentat_embedder::take_truncation();
+
+    // 3) drop rows for files that vanished or changed
+    let now = rep.started_at / 1000;
+    for (fhash, meta) in store.files()? {
+        // documents added by id are not under the root
+        if store.get_doc_text(fhash)?.is_some() {
+            continue;
+        }
+        if current.get(&meta.path) != Some(&fhash) {
+            if opts.keep_history_days > 0 {
+                store.retire_file(fhash, now)?;
+            } else {
+                store.delete_file(fhash)?;
+            }
+            rep.files_deleted.push(meta.path);
+        }
+    }
+    if opts.keep_history_days > 0 {
+        let pruned = store.prune_history(now.saturating_sub(opts.keep_history_days * 86400))?;
+        if pruned > 0 {
+            eprintln!("[index] pruned {pruned} file version(s) past the history window");
+        }
+    }
+    let owned = owners::rebuild(store, root, &opts.owners)?;
+    eprintln!("[index] owners: {owned} files with a CODEOWNERS entry or known authors");
+    let changed = rep.chunks_created > 0 || !rep.files_deleted.is_empty();
+    let graph_settings: Option<related::GraphSettings> = store.get_meta(mentat_store::META_RELATED)?.map(|b| serde_json::from_slice(&b)).transpose()?;
+    if graph_settings != Some(related::settings()) || reembed || rechunk {
+        let edges = related::rebuild(store)?;
+        eprintln!("[index] related-files graph: {edges} edges");
+    } else if changed {
+        let edges = related::update(store)?;
+        eprintln!("[index] related-files graph: {edges} edges");
+    }
+    if !boilerplate::scored(store)?.is_some_and(|s| s.settings == boilerplate::settings()) {
+        let high = boilerplate::rebuild(store)?;
+        eprintln!("[index] boilerplate: {high} chunks mostly boilerplate");
+    } else if changed {
+        let scored = boilerplate::update(store)?;
+        eprintln!("[index] boilerplate: rescored {scored} chunks");
+    }
+    if changed || store.get_meta(mentat_store::META_RELEVANCE)?.is_none() {
+        calibrate::rebuild(store)?;
+    }
+    if !opts.quota.is_off() {
+        rep.quota_warnings = opts.quota.warnings(&quota::usage(store)?);
+    }
+    rep.timing.total_ms = ms(t_total);
+    if !rep.errors.is_empty() {
+        eprintln!("[index] {} file(s) failed:", rep.errors.len());
+        for e in &rep.errors {
+            eprintln!("[index]   {}: {}", e.path, e.error);
+        }
+    }
+    Ok(rep)
+}
+
+/// What changed in the settings since the last run.
+#[derive(Clone, Copy)]
+struct Mode {
+    /// normalization differs: no stored embedding can be reused
+    reembed: bool,
+    /// chunker differs: an unchanged file's old spans must be replaced
+    rechunk: bool,
+}
+
+/// A chunk waiting for its embedding.
+struct Pending {
+    rel: String,
+    chunk_id: [u8; 32],
+    meta: mentat_store::ChunkMeta,
+    text: String,
+}
+
+/// State of one indexing run.
+struct Run<'a> {
+    store: &'a mentat_store::Store,
+    opts: &'a IndexOptions,
+    mode: Mode,
+    /// how this run embeds; static for a tiered first pass
+    embed_mode: mentat_embedder::Mode,
+    /// queue what this run embeds for a full-model upgrade
+    tiered: bool,
+    rep: IndexReport,
+    pending: Vec<Pending>,
+    /// resolved at the first batch, so fully cached runs never load the model
+    tuning: Option<Tuning>,
+    /// one model per `[embedder] devices` entry; empty = the default model
+    workers: Vec<mentat_embedder::Embedder>,
+    /// chunks kept so far under each `[caps] dirs` entry
+    dir_used: HashMap<String, usize>,
+    /// chunks the collection holds for the files seen so far, for `[quota]`
+    chunks_kept: usize,
+    /// per file version chunked this run: its path and stage timings
+    file_timing: HashMap<[u8; 32], (String, mentat_store::FileTiming)>,
+}
+
+impl Run<'_> {
+    /// Index one file from its contents; returns its content hash.
+    /// `prev` is the hash this path was last indexed under, if any.
+    fn index_file(
+        &mut self,
+        f: &mentat_ingest::Entry,
+        rel: &str,
+        prev: Option<[u8; 32]>,
+        data: Vec<u8>,
+    ) -> Result<[u8; 32]> {
+        let (store, opts, rep) = (self.store, self.opts, &mut self.rep);
+        let Mode { reembed, rechunk } = self.mode;
+        let path = f.path.as_str();
+        let t = Instant::now();
+        let fhash = mentat_store::blake32(&data);
+        rep.timing.ingest_ms += ms(t);
+        let known = store.get_file(fhash)?.is_some();
+        // write file meta
+        let t = Instant::now();
+        store.put_file(fhash, &mentat_store::FileMeta { path: rel.to_string(), size: data.len() })?;
+        store.mark_indexed(fhash, rep.started_at / 1000)?;
+        if let Some(spdx) = mentat_ingest::license::detect(&data) {
+            store.put_license(fhash, &spdx)?;
+        }
+        if let Some(mtime) = f.mtime {
+            store.put_mtime(fhash, mtime)?;
+        }
+        rep.timing.store_ms += ms(t);
+        let meta_us = us(t);
+        let generated = match opts.generated.action {
+            Action::Index => None,
+            _ => mentat_ingest::generated::detect(rel, &data, &opts.generated),
+        };
+        if let (Some(why), Action::Skip) = (&generated, opts.generated.action) {
+            let ids: Vec<[u8; 32]> = store.file_chunks(fhash)?.into_iter().map(|(id, _)| id).collect();
+            store.delete_chunks(&ids)?;
+            rep.files_skipped.push(Skipped { path: rel.to_string(), reason: format!("generated: {why}") });
+            return Ok(fhash);
+        }
+        // here only down-ranked files are still `generated`
+        if generated.is_some() || store.get_generated(fhash)?.is_some() {
+            store.put_generated(fhash, generated.as_deref())?;
+        }
+        // chunk
+        let t = Instant::now();
+        let spans = mentat_chunker::chunk_bytes_with(path, &data, &opts.chunker);
+        rep.timing.chunk_ms += ms(t);
+        let timing = &mut self.file_timing.entry(fhash).or_insert_with(|| (rel.to_string(), Default::default())).1;
+        timing.chunk_us += us(t);
+        timing.store_us += meta_us;
+        // re-cut, or spans a cap may now leave out: the old spans of this
+        // very file version go away after the loop
+        let stale = if rechunk || !opts.caps.is_off() { store.file_chunks(fhash)? } else { Vec::new() };
+        let found = spans.len();
+        let (spans, capped) = opts.caps.apply(rel, spans, &mut self.dir_used);
+        if capped > 0 {
+            rep.chunks_capped += capped;
+            eprintln!("[index] {rel}: keeping {} of {found} chunks ([caps])", spans.len());
+        }
+        if spans.is_empty() {
+            let ids: Vec<[u8; 32]> = stale.iter().map(|(id, _)| *id).collect();
+            store.delete_chunks(&ids)?;
+            let reason = match found {
+                0 if data.is_empty() => "empty",
+                0 => "binary",
+                _ => "capped",
+            };
+            rep.files_skipped.push(Skipped { path: rel.to_string(), reason: reason.into() });
+            return Ok(fhash);
+        }
+        // each file lands in one bucket: skipped above, else unchanged or indexed
+        if known {
+            rep.files_unchanged += 1;
+        } else {
+            rep.files_indexed += 1;
+        }
+        store.put_terms(fhash, &mentat_text::terms(&String::from_utf8_lossy(&data)))?;
+        store.put_lines(fhash, &boilerplate::file_lines(&data))?;
+        // content changed or re-cut: spans whose bytes survived keep their old embedding
+        let mut reuse: HashMap<[u8; 32], [u8; 32]> = HashMap::new();
+        if let Some(old) = prev.filter(|h| *h != fhash && !reembed) {
+            for (id, c) in store.file_chunks(old)? {
+                reuse.insert(c.span_hash, id);
+            }
+        }
+        if !reembed {
+            reuse.extend(stale.iter().map(|(id, c)| (c.span_hash, *id)));
+        }
+        let mut kept = std::collections::HashSet::new();
+        let mut lines = Vec::new();
+        let chunker = opts.chunker.id();
+        for s in spans {
+            let chunk_id = mentat_store::chunk_id(fhash, s.start, s.end, chunker);
+            kept.insert(chunk_id);
+            if !store.has_chunk_lines(chunk_id)? {
+                lines.push((chunk_id, boilerplate::line_hashes(&data[s.start..s.end])));
+            }
+            if !reembed && store.has_embed(chunk_id)? {
+                rep.embeddings_cached += 1;
+                continue;
+            }
+            let span_hash = hex_to32(&s.hash)?;
+            let meta = mentat_store::ChunkMeta { file_hash: fhash, start: s.start, end: s.end, span_hash, chunker };
+            if let Some(old_id) = reuse.get(&span_hash) {
+                if let Some(emb) = store.get_embed(*old_id)? {
+                    let t = Instant::now();
+                    store.put_chunk(chunk_id, &meta)?;
+                    store.put_embed(chunk_id, &emb)?;
+                    if store.upgrade_queued(*old_id)? {
+                        store.queue_upgrades(&[chunk_id])?;
+                    }
+                    rep.timing.store_ms += ms(t);
+                    timing.store_us += us(t);
+                    rep.embeddings_reused += 1;
+                    rep.chunks_created += 1;
+                    continue;
+                }
+            }
+            // text from the raw slice; embedded at the next flush
+            let text = opts.normalize.apply(&String::from_utf8_lossy(&data[s.start..s.end]));
+            self.pending.push(Pending { rel: rel.to_string(), chunk_id, meta, text });
+        }
+        store.put_chunk_lines(&lines)?;
+        self.chunks_kept += kept.len();
+        let gone: Vec<[u8; 32]> = stale.iter().map(|(id, _)| *id).filter(|id| !kept.contains(id)).collect();
+        store.delete_chunks(&gone)?;
+        Ok(fhash)
+    }
+
+    /// Store this run's per-file timings and list the costliest in the report.
+    fn save_timing(&mut self) -> Result<()> {
+        let at = self.rep.started_at / 1000;
+        let mut rows: Vec<([u8; 32], mentat_store::FileTiming)> = Vec::with_capacity(self.file_timing.len());
+        let mut costs = Vec::with_capacity(self.file_timing.len());
+        for (fhash, (path, mut timing)) in self.file_timing.drain() {
+            timing.at = at;
+            rows.push((fhash, timing));
+            costs.push(report::FileCost { path, timing });
+        }
+        self.store.put_timings(&rows)?;
+        costs.sort_by_key(|c| std::cmp::Reverse(c.timing.total_us()));
+        costs.truncate(report::SLOWEST);
+        self.rep.slowest = costs;
+        Ok(())
+    }
+
+    /// Err once the chunks kept so far pass `[quota]`, after embedding and
+    /// storing the pending batch so files already written are complete.
+    /// The file count is checked once, before the files are read.
+    fn check_chunk_quota(&mut self) -> Result<()> {
+        if let Err(e) = self.opts.quota.check(&quota::Usage::of(0, self.chunks_kept)) {
+            self.flush()?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn batch_size(&mut self) -> Result<usize> {
+        // don't load the model just to learn the batch size of an empty queue
+        if self.pending.is_empty() {
+            return Ok(usize::MAX);
+        }
+        let per_worker = self.tuning()?.batch_size;
+        Ok(per_worker * self.workers.len().max(1))
+    }
+
+    fn tuning(&mut self) -> Result<&Tuning> {
+        if self.tuning.is_none() && self.embed_mode != mentat_embedder::Mode::Full {
+            let device = if self.embed_mode == mentat_embedder::Mode::Static { "static" } else { "hashed" };
+            let t = Tuning { device: device.into(), batch_size: STATIC_BATCH, max_len: mentat_embedder::MAX_LEN, throughput: None };
+            eprintln!("[index] embedding in {device} mode, batches of {}", t.batch_size);
+            self.rep.embed = Some(t.clone());
+            self.tuning = Some(t);
+        }
+        if self.tuning.is_none() {
+            mentat_embedder::set_precision(self.opts.embed.precision);
+            mentat_embedder::set_pins(self.opts.embed.pins.clone());
+            for spec in &self.opts.embed.devices {
+                let device = mentat_embedder::parse_device(spec)?;
+                self.workers.push(mentat_embedder::Embedder::load(device, self.opts.embed.precision)?);
+            }
+            // workers are assumed alike; tune on the first
+            let t = match self.workers.first() {
+                Some(w) => resolve_tuning(self.store, self.opts, w.device_name(), || w.autotune())?,
+                None => resolve_tuning(self.store, self.opts, mentat_embedder::device_name()?, mentat_embedder::autotune)?,
+            };
+            eprintln!(
+                "[index] embedding in batches of {} (max_len {}) on {}{}",
+                t.batch_size,
+                t.max_len,
+                t.device,
+                if self.workers.len() > 1 { format!(" x{} workers", self.workers.len()) } else { String::new() },
+            );
+            self.rep.embed = Some(t.clone());
+            self.tuning = Some(t);
+        }
+        Ok(self.tuning.as_ref().expect("set above"))
+    }
+
+    /// Embed and store everything queued; failures are recorded per chunk.
+    fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let Tuning { max_len, batch_size, .. } = *self.tuning()?;
+        self.rep.queues.pending_max = self.rep.queues.pending_max.max(self.pending.len());
+        let batch = std::mem::take(&mut self.pending);
+        let t = Instant::now();
+        let texts: Vec<&str> = batch.iter().map(|p| p.text.as_str()).collect();
+        let results = if self.workers.len() <= 1 {
+            embed_all(&texts, max_len, &|t, n| embed_first(self.embed_mode, &self.workers, t, n))
+        } else {
+            embed_sharded(&self.workers, &texts, batch_size, max_len)
+        };
+        self.rep.timing.embed_ms += ms(t);
+        // each file's share of the batch, by text length
+        let (embed_us, bytes) = (us(t), batch.iter().map(|p| p.text.len()).sum::<usize>().max(1));
+        let mut queued = Vec::new();
+        for (p, res) in batch.into_iter().zip(results) {
+            let (start, end) = (p.meta.start, p.meta.end);
+            let share = (embed_us as u128 * p.text.len() as u128 / bytes as u128) as u64;
+            let timing = &mut self.file_timing.entry(p.meta.file_hash).or_insert_with(|| (p.rel.clone(), Default::default())).1;
+            timing.embed_us += share;
+            let retry = |t: &[&str], n| embed_first(self.embed_mode, &self.workers, t, n);
+            let emb = match res.and_then(|emb| check_embedding(emb, &p, max_len, &retry, &mut self.rep)) {
+                Ok(Some(emb)) => emb,
+                Ok(None) => continue,
+                Err(e) => {
+                    if self.opts.fail_fast {
+                        return Err(e.context(format!("embedding {}:{start}-{end}", p.rel)));
+                    }
+                    eprintln!("[index] error: {}:{start}-{end}: {e:#}", p.rel);
+                    self.rep.errors.push(FileError { path: p.rel, error: format!("chunk {start}-{end}: {e:#}") });
+                    continue;
+                }
+            };
+            self.rep.embeddings_computed += 1;
+            let t = Instant::now();
+            self.store.put_chunk(p.chunk_id, &p.meta)?;
+            self.store.put_embed(p.chunk_id, &emb)?;
+            self.rep.timing.store_ms += ms(t);
+            if let Some((_, timing)) = self.file_timing.get_mut(&p.meta.file_hash) {
+                timing.store_us += us(t);
+                timing.embedded += 1;
+            }
+            self.rep.chunks_created += 1;
+            if self.tiered {
+                queued.push(p.chunk_id);
+            }
+        }
+        self.store.queue_upgrades(&queued)?;
+        self.rep.upgrades_queued += queued.len();
+        Ok(())
+    }
+}
+
+type EmbedResult = Result<[f32; mentat_embedder::D]>;
+type EmbedFn<'a> = &'a dyn Fn(&[&str], usize) -> Result<Vec<[f32; mentat_embedder::D]>>;
+
+/// The only worker, or the default model (or static mode) when no devices
+/// are configured.
+fn embed_first(
+    mode: mentat_embedder::Mode,
+    workers: &[mentat_embedder::Embedder],
+    texts: &[&str],
+    max_len: usize,
+) -> Result<Vec<[f32; mentat_embedder::D]>> {
+    match workers.first() {
+        Some(w) => w.embed_batch(texts, max_len),
+        None => mentat_embedder::embed_batch_as(mode, texts, max_len),
+    }
+}
+
+/// One batch; if it fails, each item alone so one bad chunk only fails itself.
+fn embed_all(texts: &[&str], max_len: usize, embed: EmbedFn) -> Vec<EmbedResult> {
+    match embed(texts, max_len) {
+        Ok(embs) => embs.into_iter().map(Ok).collect(),
+        Err(e) => {
+            eprintln!("[index] batch of {} failed ({e:#}), embedding one by one", texts.len());
+            texts.iter().map(|txt| embed(&[*txt], max_len).map(|mut v| v.remove(0))).collect()
+        }
+    }
+}
+
+/// Split into `batch_size` slices that the workers pull until none are left,
+/// so a faster device simply takes more of them.
+fn embed_sharded(
+    workers: &[mentat_embedder::Embedder],
+    texts: &[&str],
+    batch_size: usize,
+    max_len: usize,
+) -> Vec<EmbedResult> {
+    let slices: Vec<&[&str]> = texts.chunks(batch_size.max(1)).collect();
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let done: std::sync::Mutex<Vec<(usize, Vec<EmbedResult>)>> = std::sync::Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for w in workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let Some(slice) = slices.get(i) else { break };
+                let res = embed_all(slice, max_len, &|t, n| w.embed_batch(t, n));
+                done.lock().unwrap().push((i, res));
+            });
+        }
+    });
+    let mut done = done.into_inner().unwrap();
+    done.sort_by_key(|(i, _)| *i);
+    done.into_iter().flat_map(|(_, r)| r).collect()
+}
+
+/// Config overrides on top of the stored tuning for this device, probing
+/// (and storing the result) when there is none or a retune was asked for.
+fn resolve_tuning(
+    store: &mentat_store::Store,
+    opts: &IndexOptions,
+    device: String,
+    probe: impl FnOnce() -> Result<Tuning>,
+) -> Result<Tuning> {
+    let cfg = &opts.embed;
+    let mut t = if cfg.batch_size > 0 && cfg.max_len > 0 {
+        Tuning { device, batch_size: cfg.batch_size, max_len: cfg.max_len, throughput: None }
+    } else {
+        let stored: Option<Tuning> = match store.get_meta(mentat_store::META_EMBED_TUNING)? {
+            Some(b) => serde_json::from_slice(&b).ok(),
+            None => None,
+        };
+        match stored.filter(|s| s.device == device && !opts.retune) {
+            Some(s) => s,
+            None => {
+                eprintln!("[index] probing embedding throughput on {device}...");
+                let probed = probe()?;
+                store.put_meta(mentat_store::META_EMBED_TUNING, &serde_json::to_vec(&probed)?)?;
+                probed
+            }
+        }
+    };
+    if cfg.batch_size > 0 {
+        t.batch_size = cfg.batch_size;
+    }
+    if cfg.max_len > 0 {
+        t.max_len = cfg.max_len;
+    }
+    Ok(t)
+}
+
+/// Normalization recorded by the last run. Indexes that predate the record
+/// were embedded from raw text.
+pub fn stored_normalize(store: &mentat_store::Store) -> Result<Option<mentat_text::Normalize>> {
+    match store.get_meta(mentat_store::META_NORMALIZE)? {
+        Some(b) => Ok(Some(serde_json::from_slice(&b)?)),
+        None if store.files()?.is_empty() => Ok(None),
+        None => Ok(Some(mentat_text::Normalize::none())),
+    }
+}
+
+/// Model the index was embedded with. Indexes that predate the record were
+/// embedded with BGE.
+pub fn stored_model(store: &mentat_store::Store) -> Result<Option<String>> {
+    match store.get_meta(mentat_store::META_MODEL)? {
+        Some(b) => Ok(Some(String::from_utf8(b)?)),
+        None if store.files()?.is_empty() => Ok(None),
+        None => Ok(Some(mentat_embedder::BGE.to_string())),
+    }
+}
+
+/// What the stored vectors are, for indexes that recorded it.
+pub fn stored_vectors(store: &mentat_store::Store) -> Result<Option<mentat_embedder::VectorInfo>> {
+    match store.get_meta(mentat_store::META_VECTORS)? {
+        Some(b) => Ok(Some(serde_json::from_slice(&b)?)),
+        None => Ok(None),
+    }
+}
+
+/// Chunker settings recorded by the last run. Indexes that predate the
+/// record were cut with the fixed-size default.
+pub fn stored_chunker(store: &mentat_store::Store) -> Result<Option<mentat_chunker::ChunkerConfig>> {
+    match store.get_meta(mentat_store::META_CHUNKER)? {
+        Some(b) => Ok(Some(serde_json::from_slice(&b)?)),
+        None if store.files()?.is_empty() => Ok(None),
+        None => Ok(Some(mentat_chunker::ChunkerConfig::default())),
+    }
+}
+
+/// A collection keeps its embedding mode unless one is configured.
+fn embed_mode(prev_model: Option<&str>, opts: &IndexOptions) -> mentat_embedder::Mode {
+    opts.embed.mode.or_else(|| prev_model.and_then(mentat_embedder::Mode::of)).unwrap_or_default()
+}
+
+/// Spans cut under other chunker settings, or before chunk ids carried the
+/// chunker version, must all be re-cut.
+fn rechunk_needed(store: &mentat_store::Store, opts: &IndexOptions) -> Result<bool> {
+    let prev_id = store.get_meta(mentat_store::META_CHUNKER_ID)?.and_then(|b| Some(u64::from_le_bytes(b.as_slice().try_into().ok()?)));
+    Ok(stored_chunker(store)?.is_some_and(|c| c != opts.chunker || prev_id != Some(opts.chunker.id())))
+}
+
+/// Why indexing `store` with `opts` would re-embed or re-cut every chunk
+/// (an older schema, another model, changed normalization or chunking), if
+/// it would. `mentat index` then builds a standby copy instead of updating
+/// the live one in place.
+pub fn full_rebuild_reason(store: &mentat_store::Store, opts: &IndexOptions) -> Result<Option<String>> {
+    if store.files()?.is_empty() {
+        return Ok(None);
+    }
+    if let Some(v) = store.schema_version()?.filter(|v| *v < mentat_store::SCHEMA_VERSION) {
+        return Ok(Some(format!("schema v{v} -> v{}", mentat_store::SCHEMA_VERSION)));
+    }
+    if let Some(Err(e)) = stored_vectors(store)?.map(|v| v.check()) {
+        return Ok(Some(format!("{e:#}")));
+    }
+    let prev_model = stored_model(store)?;
+    let model = embed_mode(prev_model.as_deref(), opts).model();
+    if prev_model.as_deref() != Some(model) {
+        return Ok(Some(format!("embedding model {} -> {model}", prev_model.as_deref().unwrap_or("unknown"))));
+    }
+    if stored_normalize(store)?.as_ref() != Some(&opts.normalize) {
+        return Ok(Some("text normalization changed".into()));
+    }
+    if rechunk_needed(store, opts)? {
+        return Ok(Some("chunker settings or version changed".into()));
+    }
+    Ok(None)
+}
+
+/// Validate; on NaN/zero-norm retry once with cleaned text.
+/// A chunk that is still invalid is logged, counted and left out of the store.
+fn check_embedding(
+    emb: [f32; mentat_embedder::D],
+    p: &Pending,
+    max_len: usize,
+    embed: EmbedFn,
+    rep: &mut IndexReport,
+) -> Result<Option<[f32; mentat_embedder::D]>> {
+    let (rel, start, end) = (&p.rel, p.meta.start, p.meta.end);
+    let Some(reason) = mentat_embedder::invalid_reason(&emb) else { return Ok(Some(emb)) };
+    eprintln!("[index] invalid embedding ({reason}) for {rel}:{start}-{end}, retrying with cleaned text");
+    let cleaned = mentat_embedder::clean_text(&p.text);
+    let emb = embed(&[cleaned.as_str()], max_len)?.remove(0);
+    match mentat_embedder::invalid_reason(&emb) {
+        None => {
+            rep.embeddings_retried += 1;
+            Ok(Some(emb))
+        }
+        Some(reason) => {
+            eprintln!("[index] dropping chunk {rel}:{start}-{end}: {reason}");
+            rep.embeddings_rejected += 1;
+            rep.errors.push(FileError { path: rel.to_string(), error: format!("chunk {start}-{end}: {reason}") });
+            Ok(None)
+        }
+    }
+}
+
+fn ms(t: Instant) -> u64 {
+    t.elapsed().as_millis() as u64
+}
+
+fn us(t: Instant) -> u64 {
+    t.elapsed().as_micros() as u64
+}
+
+pub fn hex_to32(h: &str) -> Result<[u8;32]> {
+    let bytes = hex::decode(h)?;
+    let arr: [u8;32] = bytes.as_slice().try_into().map_err(|_| anyhow::anyhow!("bad len"))?;
+    Ok(arr)
+}
+
+fn relativize(p: &str, root: &Path) -> String {
+    let pp = Path::new(p);
+    match pp.strip_prefix(root) {
+        Ok(r) => r.display().to_string(),
+        Err(_) => p.to_string(),
+    }
+}
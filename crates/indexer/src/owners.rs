@@ -0,0 +1,86 @@
+//! Who owns each file, for `owner:` filters and the owners shown on hits:
+//! its CODEOWNERS entry (see `mentat_ingest::codeowners`) and, unless
+//! `[owners] authors = 0`, its most frequent authors over the last
+//! `history` commits of `git log`. Rebuilt whole every run, since
+//! ownership changes without the files changing.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OwnersConfig {
+    /// authors kept per file (0 = CODEOWNERS only)
+    pub authors: usize,
+    /// commits of history counted
+    pub history: usize,
+}
+
+impl Default for OwnersConfig {
+    fn default() -> Self {
+        Self { authors: 3, history: 1000 }
+    }
+}
+
+/// Recompute and store every file's owners; returns how many have any.
+pub fn rebuild(store: &mentat_store::Store, root: &Path, cfg: &OwnersConfig) -> Result<usize> {
+    let codeowners = mentat_ingest::codeowners::CodeOwners::find(root)?;
+    let authors = if cfg.authors == 0 {
+        HashMap::new()
+    } else {
+        // outside a git checkout there is simply no history to count
+        git_authors(root, cfg).unwrap_or_else(|e| {
+            eprintln!("[index] owners: no commit authors ({e:#})");
+            HashMap::new()
+        })
+    };
+    let mut rows = Vec::new();
+    for (h, f) in store.files()? {
+        // documents added by id are not under the root
+        if store.get_doc_text(h)?.is_some() {
+            continue;
+        }
+        let owners = mentat_store::Owners {
+            codeowners: codeowners.as_ref().map(|c| c.owners(&f.path).to_vec()).unwrap_or_default(),
+            authors: authors.get(&f.path).cloned().unwrap_or_default(),
+        };
+        if owners != mentat_store::Owners::default() {
+            rows.push((h, owners));
+        }
+    }
+    store.replace_owners(&rows)?;
+    Ok(rows.len())
+}
+
+/// Each path's top `cfg.authors` commit authors, most commits first.
+fn git_authors(root: &Path, cfg: &OwnersConfig) -> Result<HashMap<String, Vec<String>>> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["log", "--no-merges", "--relative", "--name-only", "--format=%x00%ae"])
+        .arg(format!("-n{}", cfg.history))
+        .args(["--", "."])
+        .output()
+        .context("running git log")?;
+    anyhow::ensure!(out.status.success(), "git log: {}", String::from_utf8_lossy(&out.stderr).trim());
+    // "\0<email>\n\n<path>\n<path>\n" per commit
+    let mut counts: HashMap<String, HashMap<String, usize>> = HashMap::new();
+    for commit in String::from_utf8_lossy(&out.stdout).split('\0').filter(|c| !c.is_empty()) {
+        let mut lines = commit.lines();
+        let Some(email) = lines.next().map(str::trim).filter(|e| !e.is_empty()) else { continue };
+        for path in lines.map(str::trim).filter(|p| !p.is_empty()) {
+            *counts.entry(path.to_string()).or_default().entry(email.to_string()).or_default() += 1;
+        }
+    }
+    Ok(counts
+        .into_iter()
+        .map(|(path, by)| {
+            let mut by: Vec<(String, usize)> = by.into_iter().collect();
+            by.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            (path, by.into_iter().take(cfg.authors).map(|(email, _)| email).collect())
+        })
+        .collect())
+}
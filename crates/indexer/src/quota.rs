@@ -0,0 +1,80 @@
+//! `[quota]` in mentat.toml: ceilings on one collection (index directory),
+//! so a root pointed at `$HOME` by mistake stops with an error instead of
+//! filling the disk. A run past a quota fails as soon as the count is
+//! known: files right after the walk, chunks and embedding bytes as files
+//! are chunked, leaving the files already indexed in place. Past
+//! `warn_at` of a quota, runs and `mentat doctor` warn.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// indexed files and documents (0 = no limit)
+    pub max_files: usize,
+    /// stored chunks (0 = no limit)
+    pub max_chunks: usize,
+    /// bytes of stored embeddings, 1536 per chunk (0 = no limit)
+    pub max_embed_bytes: u64,
+    /// share of a quota that draws a warning
+    pub warn_at: f32,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self { max_files: 0, max_chunks: 0, max_embed_bytes: 0, warn_at: 0.8 }
+    }
+}
+
+/// What a collection holds, in the units `Quota` limits.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    pub files: usize,
+    pub chunks: usize,
+    pub embed_bytes: u64,
+}
+
+impl Usage {
+    pub fn of(files: usize, chunks: usize) -> Self {
+        Self { files, chunks, embed_bytes: (chunks * mentat_embedder::D * 4) as u64 }
+    }
+}
+
+/// Current usage of the collection in `store`.
+pub fn usage(store: &mentat_store::Store) -> Result<Usage> {
+    Ok(Usage::of(store.files()?.len(), store.chunks()?.len()))
+}
+
+impl Quota {
+    pub fn is_off(&self) -> bool {
+        self.max_files == 0 && self.max_chunks == 0 && self.max_embed_bytes == 0
+    }
+
+    /// `(name, used, limit)` for each quota that is set.
+    fn limits(&self, u: &Usage) -> Vec<(&'static str, u64, u64)> {
+        [("max_files", u.files as u64, self.max_files as u64), ("max_chunks", u.chunks as u64, self.max_chunks as u64), ("max_embed_bytes", u.embed_bytes, self.max_embed_bytes)]
+            .into_iter()
+            .filter(|(_, _, limit)| *limit > 0)
+            .collect()
+    }
+
+    /// Err naming the first quota `u` exceeds.
+    pub fn check(&self, u: &Usage) -> Result<()> {
+        if let Some((name, used, limit)) = self.limits(u).into_iter().find(|(_, used, limit)| used > limit) {
+            anyhow::bail!(
+                "collection quota exceeded: {used} over [quota] {name} = {limit}; narrow the root, add .ingestignore entries (`mentat suggest-ignores`) or raise the quota"
+            );
+        }
+        Ok(())
+    }
+
+    /// One line per quota `u` has reached `warn_at` of.
+    pub fn warnings(&self, u: &Usage) -> Vec<String> {
+        self.limits(u)
+            .into_iter()
+            .filter(|(_, used, limit)| *used as f64 >= *limit as f64 * self.warn_at as f64)
+            .map(|(name, used, limit)| format!("{name}: {used} of {limit} ({:.0}%)", used as f64 * 100.0 / limit as f64))
+            .collect()
+    }
+}
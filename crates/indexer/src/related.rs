@@ -0,0 +1,146 @@
+//! Related-files graph: each file's vector is the mean of its chunk vectors,
+//! and its neighbors are the `K` most similar other files above
+//! `MIN_SIMILARITY`. File vectors are stored beside the graph, so a run
+//! that added or removed files only `update`s the rows those files touch;
+//! `rebuild` recomputes everything, for new settings or new vectors.
+
+use anyhow::Result;
+use mentat_embedder::D;
+use mentat_store::Neighbors;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Neighbors kept per file.
+pub const K: usize = 10;
+/// Cosine similarity below which files are not linked.
+pub const MIN_SIMILARITY: f32 = 0.75;
+
+/// Settings the stored graph was built with, kept under `META_RELATED`.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GraphSettings {
+    pub k: usize,
+    pub min_similarity: f32,
+}
+
+pub fn settings() -> GraphSettings {
+    GraphSettings { k: K, min_similarity: MIN_SIMILARITY }
+}
+
+/// Unit-length mean of each file's valid chunk vectors.
+pub fn file_vectors(store: &mentat_store::Store) -> Result<Vec<([u8; 32], [f32; D])>> {
+    let embeds: HashMap<[u8; 32], [f32; D]> = store.embeds()?.into_iter().collect();
+    let mut by_file: HashMap<[u8; 32], Vec<&[f32; D]>> = HashMap::new();
+    for (id, c) in store.chunks()? {
+        if let Some(v) = embeds.get(&id) {
+            by_file.entry(c.file_hash).or_default().push(v);
+        }
+    }
+    let mut out: Vec<([u8; 32], [f32; D])> = by_file.into_iter().filter_map(|(h, vs)| Some((h, unit_mean(vs)?))).collect();
+    out.sort_by_key(|e| e.0);
+    Ok(out)
+}
+
+/// Recompute and store the graph and file vectors; returns the number of
+/// edges kept.
+pub fn rebuild(store: &mentat_store::Store) -> Result<usize> {
+    let files = file_vectors(store)?;
+    let vectors: HashMap<[u8; 32], [f32; D]> = files.iter().copied().collect();
+    let graph: Vec<([u8; 32], Neighbors)> = files.iter().map(|(h, v)| (*h, neighbors(*h, v, &vectors))).collect();
+    store.replace_related(&graph, &files)?;
+    store.put_meta(mentat_store::META_RELATED, &serde_json::to_vec(&settings())?)?;
+    Ok(graph.iter().map(|(_, n)| n.len()).sum())
+}
+
+/// Bring the stored graph up to date with the files now indexed: files
+/// without a stored vector (new, or with new vectors) get one and a full
+/// row, rows that named a removed or recomputed file are recomputed, and
+/// every other row takes in a new file where it ranks. The result equals `rebuild`'s at O(changed × files) instead
+/// of O(files²); returns the number of edges in the graph.
+pub fn update(store: &mentat_store::Store) -> Result<usize> {
+    let files: HashSet<[u8; 32]> = store.files()?.into_iter().map(|(h, _)| h).collect();
+    let mut vectors: HashMap<[u8; 32], [f32; D]> = store.file_vectors()?.into_iter().filter(|(h, _)| files.contains(h)).collect();
+    let mut added = Vec::new();
+    for h in &files {
+        if vectors.contains_key(h) {
+            continue;
+        }
+        let mut embeds = Vec::new();
+        for (id, _) in store.file_chunks(*h)? {
+            embeds.extend(store.get_embed(id)?);
+        }
+        if let Some(v) = unit_mean(embeds.iter()) {
+            vectors.insert(*h, v);
+            added.push((*h, v));
+        }
+    }
+    let mut rows: HashMap<[u8; 32], Neighbors> = store.related_rows()?.into_iter().filter(|(h, _)| vectors.contains_key(h)).collect();
+    // a row naming a removed file, or one whose vector was just recomputed,
+    // may have ranked it differently
+    let recomputed: HashSet<[u8; 32]> = added.iter().map(|(h, _)| *h).collect();
+    let lost: Vec<[u8; 32]> =
+        rows.iter().filter(|(_, n)| n.iter().any(|(o, _)| !vectors.contains_key(o) || recomputed.contains(o))).map(|(h, _)| *h).collect();
+    let fresh: HashSet<[u8; 32]> = lost.into_iter().chain(recomputed).collect();
+    for h in &fresh {
+        rows.insert(*h, neighbors(*h, &vectors[h], &vectors));
+    }
+    let mut dirty = fresh.clone();
+    for (a, av) in &added {
+        for (o, ov) in &vectors {
+            if o == a || fresh.contains(o) {
+                continue;
+            }
+            let sim = dot(av, ov);
+            if sim < MIN_SIMILARITY {
+                continue;
+            }
+            let row = rows.entry(*o).or_default();
+            if row.len() < K || row.last().is_some_and(|w| rank(&(*a, sim), w).is_lt()) {
+                row.push((*a, sim));
+                row.sort_by(rank);
+                row.truncate(K);
+                dirty.insert(*o);
+            }
+        }
+    }
+    let changed: Vec<([u8; 32], Neighbors)> = dirty.iter().map(|h| (*h, rows[h].clone())).collect();
+    store.update_related(&changed, &added)?;
+    Ok(rows.values().map(Vec::len).sum())
+}
+
+/// The `K` files nearest `v` among `vectors`, other than `h` itself.
+fn neighbors(h: [u8; 32], v: &[f32; D], vectors: &HashMap<[u8; 32], [f32; D]>) -> Neighbors {
+    let mut near: Neighbors = vectors
+        .iter()
+        .filter(|(o, _)| **o != h)
+        .map(|(o, w)| (*o, dot(v, w)))
+        .filter(|(_, s)| *s >= MIN_SIMILARITY)
+        .collect();
+    near.sort_by(rank);
+    near.truncate(K);
+    near
+}
+
+/// Most similar first; ties by hash, so rows do not depend on map order.
+fn rank(a: &([u8; 32], f32), b: &([u8; 32], f32)) -> std::cmp::Ordering {
+    b.1.total_cmp(&a.1).then(a.0.cmp(&b.0))
+}
+
+fn dot(a: &[f32; D], b: &[f32; D]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Unit-length mean of the valid vectors in `vs`, if any.
+fn unit_mean<'a>(vs: impl IntoIterator<Item = &'a [f32; D]>) -> Option<[f32; D]> {
+    let mut sum = [0.0; D];
+    for v in vs.into_iter().filter(|v| mentat_embedder::invalid_reason(*v).is_none()) {
+        for (s, x) in sum.iter_mut().zip(v) {
+            *s += x;
+        }
+    }
+    let norm = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 || !norm.is_finite() {
+        return None;
+    }
+    sum.iter_mut().for_each(|x| *x /= norm);
+    Some(sum)
+}
@@ -0,0 +1,148 @@
+//! Structured per-run index report, persisted under <index>/reports/<unix millis>.json.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexReport {
+    /// unix millis; also the report file name
+    pub started_at: u64,
+    pub root: String,
+    pub files_seen: usize,
+    /// new content, chunked and stored
+    pub files_indexed: usize,
+    /// unchanged since the last run (same content hash already stored);
+    /// not counted in `files_indexed`
+    pub files_unchanged: usize,
+    pub files_skipped: Vec<Skipped>,
+    /// stale rows removed: files gone from disk or replaced by new content
+    pub files_deleted: Vec<String>,
+    /// files that failed to read, chunk, embed or store
+    #[serde(default)]
+    pub errors: Vec<FileError>,
+    pub chunks_created: usize,
+    pub embeddings_computed: usize,
+    pub embeddings_cached: usize,
+    /// chunks whose embedding was NaN/zero even after cleaning; not stored
+    #[serde(default)]
+    pub embeddings_rejected: usize,
+    /// chunks that needed the cleaned-text retry to embed validly
+    #[serde(default)]
+    pub embeddings_retried: usize,
+    /// spans of a changed file whose bytes match the previous version; embedding copied over
+    #[serde(default)]
+    pub embeddings_reused: usize,
+    /// batch settings chunks were embedded with; None if nothing needed embedding
+    #[serde(default)]
+    pub embed: Option<mentat_embedder::Tuning>,
+    /// chunks cut to fit the model's sequence length, and how much was lost
+    #[serde(default)]
+    pub truncated: mentat_embedder::Truncation,
+    /// chunks given static vectors by a tiered run, waiting for `mentat upgrade`
+    #[serde(default)]
+    pub upgrades_queued: usize,
+    /// spans dropped by `[caps]` before embedding
+    #[serde(default)]
+    pub chunks_capped: usize,
+    /// `[quota]` limits this collection is near or at, one line each
+    #[serde(default)]
+    pub quota_warnings: Vec<String>,
+    /// the `SLOWEST` files by chunk + embed + store time this run
+    #[serde(default)]
+    pub slowest: Vec<FileCost>,
+    pub timing: StageTiming,
+    #[serde(default)]
+    pub queues: QueueStats,
+}
+
+/// Files listed in `IndexReport::slowest`; `mentat slowest` reads the
+/// store's timings of every file.
+pub const SLOWEST: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileCost {
+    pub path: String,
+    #[serde(flatten)]
+    pub timing: mentat_store::FileTiming,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Skipped {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileError {
+    pub path: String,
+    pub error: String,
+}
+
+/// Wall time per stage in milliseconds.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct StageTiming {
+    pub ingest_ms: u64,
+    pub chunk_ms: u64,
+    pub embed_ms: u64,
+    pub store_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Depth and stall time of the bounded hand-offs between stages.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct QueueStats {
+    /// capacity of the read-ahead queue, in files
+    pub read_ahead: usize,
+    /// most read files waiting for chunking at once
+    pub read_depth_max: usize,
+    /// reader blocked on a full queue: chunk/embed/store is the bottleneck
+    pub read_blocked_ms: u64,
+    /// chunking waited on an empty queue: reading is the bottleneck
+    pub read_starved_ms: u64,
+    /// most chunks waiting for an embedding batch at once
+    pub pending_max: usize,
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+pub fn reports_dir(index_dir: &Path) -> PathBuf {
+    index_dir.join("reports")
+}
+
+/// Write the report and return its path.
+pub fn save(index_dir: &Path, report: &IndexReport) -> Result<PathBuf> {
+    let dir = reports_dir(index_dir);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", report.started_at));
+    fs::write(&path, serde_json::to_string_pretty(report)?)?;
+    Ok(path)
+}
+
+/// Report file names, oldest first.
+pub fn list(index_dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = reports_dir(index_dir);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut out: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter_map(|p| Some((p.file_stem()?.to_str()?.parse().ok()?, p)))
+        .collect();
+    out.sort();
+    Ok(out.into_iter().map(|(_, p)| p).collect())
+}
+
+pub fn load(path: &Path) -> Result<IndexReport> {
+    let txt = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(serde_json::from_str(&txt)?)
+}
+
+pub fn load_last(index_dir: &Path) -> Result<Option<IndexReport>> {
+    match list(index_dir)?.last() {
+        Some(p) => Ok(Some(load(p)?)),
+        None => Ok(None),
+    }
+}
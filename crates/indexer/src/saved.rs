@@ -0,0 +1,132 @@
+//! Re-index just the files an editor saved (`mentat reindex <file>...`
+//! from an on-save hook) instead of walking the whole root, for index
+//! freshness in seconds. Settings follow what the index was built with, as
+//! for documents, so a save never triggers re-embedding everything. The
+//! whole-index passes (related files, boilerplate scores, relevance
+//! calibration, owners) are left to the next full `mentat index`.
+
+use crate::{report, IndexOptions, Mode, Run};
+use anyhow::{Context, Result};
+use report::{FileError, IndexReport, Skipped};
+use std::{collections::HashMap, fs, path::Path, time::Instant};
+
+/// Index `paths` (absolute, or relative to the working directory) into the
+/// index whose root they lie under. A path that no longer exists has its
+/// rows removed; one outside the root or ignored is skipped.
+pub fn index_saved(store: &mentat_store::Store, paths: &[String], opts: &IndexOptions) -> Result<IndexReport> {
+    let t_total = Instant::now();
+    let root = store
+        .get_meta(mentat_store::META_ROOT)?
+        .map(|b| String::from_utf8_lossy(&b).into_owned())
+        .ok_or_else(|| anyhow::anyhow!("the index has no root yet; run `mentat index <dir>` first"))?;
+    let root = Path::new(&root);
+    if let Some(why) = crate::full_rebuild_reason(store, opts)? {
+        anyhow::bail!("settings changed since the last run ({why}); run `mentat index`");
+    }
+    let embed_mode = crate::embed_mode(crate::stored_model(store)?.as_deref(), opts);
+
+    let rep = IndexReport { started_at: report::now_millis(), files_seen: paths.len(), root: root.display().to_string(), ..Default::default() };
+    mentat_embedder::take_truncation();
+    let prior: HashMap<String, [u8; 32]> = store.files()?.into_iter().map(|(h, m)| (m.path, h)).collect();
+    opts.quota.check(&crate::quota::Usage::of(prior.len(), 0))?;
+    // what the index holds already counts against the quota and the
+    // directory caps; a saved file's old version is taken back below
+    let counting = !opts.quota.is_off() || !opts.caps.dirs.is_empty();
+    let mut kept = 0;
+    let mut dir_used = HashMap::new();
+    if counting {
+        for (path, fhash) in &prior {
+            let n = store.file_chunks(*fhash)?.len();
+            kept += n;
+            opts.caps.charge(path, n, &mut dir_used);
+        }
+    }
+    let mut run = Run {
+        store,
+        opts,
+        mode: Mode { reembed: false, rechunk: false },
+        embed_mode,
+        tiered: false,
+        rep,
+        pending: Vec::new(),
+        tuning: None,
+        workers: Vec::new(),
+        dir_used,
+        chunks_kept: if opts.quota.is_off() { 0 } else { kept },
+        file_timing: HashMap::new(),
+    };
+    let now = run.rep.started_at / 1000;
+    let retire = |old: [u8; 32]| -> Result<()> {
+        if opts.keep_history_days > 0 {
+            store.retire_file(old, now)?;
+        } else {
+            store.delete_file(old)?;
+        }
+        Ok(())
+    };
+    for p in paths {
+        let abs = match fs::canonicalize(p) {
+            Ok(a) => a,
+            // deleted since the save: resolve against the working directory
+            Err(_) => std::env::current_dir()?.join(p),
+        };
+        let Ok(rel) = abs.strip_prefix(root) else {
+            run.rep.files_skipped.push(Skipped { path: p.clone(), reason: format!("outside the index root {}", root.display()) });
+            continue;
+        };
+        if mentat_ingest::is_ignored(root, rel) {
+            run.rep.files_skipped.push(Skipped { path: p.clone(), reason: "ignored".into() });
+            continue;
+        }
+        let rel = rel.display().to_string();
+        let prev = prior.get(&rel).copied();
+        if let Some(old) = prev.filter(|_| counting) {
+            let n = store.file_chunks(old)?.len();
+            run.chunks_kept = run.chunks_kept.saturating_sub(n);
+            opts.caps.refund(&rel, n, &mut run.dir_used);
+        }
+        if !abs.is_file() {
+            if let Some(old) = prev {
+                retire(old)?;
+                run.rep.files_deleted.push(rel);
+            }
+            continue;
+        }
+        let result = fs::read(&abs).with_context(|| format!("reading {}", abs.display())).and_then(|data| {
+            let mtime = fs::metadata(&abs)
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            let entry = mentat_ingest::Entry { path: abs.display().to_string(), size: data.len(), mtime };
+            run.index_file(&entry, &rel, prev, data)
+        });
+        match result {
+            Ok(fhash) => {
+                if let Some(old) = prev.filter(|h| *h != fhash) {
+                    retire(old)?;
+                }
+            }
+            Err(e) => {
+                if opts.fail_fast {
+                    return Err(e.context(format!("indexing {rel}")));
+                }
+                eprintln!("[reindex] error: {rel}: {e:#}");
+                run.rep.errors.push(FileError { path: rel, error: format!("{e:#}") });
+            }
+        }
+        run.check_chunk_quota()?;
+        if run.pending.len() >= run.batch_size()? {
+            run.flush()?;
+        }
+    }
+    run.flush()?;
+    run.save_timing()?;
+    let mut rep = run.rep;
+    rep.truncated = mentat_embedder::take_truncation();
+    if !opts.quota.is_off() {
+        rep.quota_warnings = opts.quota.warnings(&crate::quota::usage(store)?);
+    }
+    rep.timing.total_ms = crate::ms(t_total);
+    Ok(rep)
+}
@@ -0,0 +1,134 @@
+//! `mentat suggest-ignores`: directories and files that hold a large share
+//! of the index but little worth retrieving, as `.ingestignore` entries.
+//!
+//! A chunk counts as low-value when its file was kept as generated, its
+//! file looks binary (control bytes, or long runs without whitespace as in
+//! base64 and hex dumps), its bytes repeat a span of another file, or it
+//! is boilerplate (see `crate::boilerplate`). A directory is suggested when
+//! it holds at least `MIN_SHARE` of the index's chunks and at least
+//! `MIN_LOW` of them are low-value; nothing under a suggested directory is
+//! suggested again.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Share of the index's chunks a suggestion must hold.
+const MIN_SHARE: f32 = 0.02;
+/// Share of a suggestion's chunks that must be low-value.
+const MIN_LOW: f32 = 0.6;
+/// Bytes of each file sampled for the binary check.
+const SNIFF: usize = 4096;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Suggestion {
+    /// `.ingestignore` pattern: `dir/` or a file path
+    pub pattern: String,
+    pub files: usize,
+    pub chunks: usize,
+    pub bytes: u64,
+    /// low-value chunks by reason: generated, binary, duplicate, boilerplate
+    pub reasons: BTreeMap<&'static str, usize>,
+}
+
+impl Suggestion {
+    pub fn low(&self) -> usize {
+        self.reasons.values().sum()
+    }
+}
+
+#[derive(Default)]
+struct FileStats {
+    path: String,
+    bytes: u64,
+    chunks: usize,
+    /// why every chunk of the file is low-value, if one applies
+    whole: Option<&'static str>,
+    reasons: BTreeMap<&'static str, usize>,
+}
+
+/// Suggestions, largest first, and the index's total chunk count. Patterns
+/// already in `ignored` (the current `.ingestignore` lines) are left out.
+pub fn suggest(store: &mentat_store::Store, ignored: &[String]) -> Result<(Vec<Suggestion>, usize)> {
+    let chunks = store.chunks()?;
+    let total = chunks.len();
+    let mut span_files: HashMap<[u8; 32], Vec<[u8; 32]>> = HashMap::new();
+    for (_, c) in &chunks {
+        let files = span_files.entry(c.span_hash).or_default();
+        if !files.contains(&c.file_hash) {
+            files.push(c.file_hash);
+        }
+    }
+    let mut stats: HashMap<[u8; 32], FileStats> = HashMap::new();
+    for (h, f) in store.files()? {
+        let binary = store.read_file(h, &f.path)?.is_some_and(|d| binaryish(&d[..d.len().min(SNIFF)]));
+        let generated = store.get_generated(h)?.is_some();
+        let whole = if generated { Some("generated") } else if binary { Some("binary") } else { None };
+        stats.insert(h, FileStats { path: f.path, bytes: f.size as u64, whole, ..Default::default() });
+    }
+    for (id, c) in &chunks {
+        let Some(s) = stats.get_mut(&c.file_hash) else { continue };
+        s.chunks += 1;
+        let reason = if s.whole.is_some() {
+            s.whole
+        } else if span_files.get(&c.span_hash).is_some_and(|f| f.len() > 1) {
+            Some("duplicate")
+        } else if store.get_boilerplate(*id)?.is_some_and(|b| b > 0.5) {
+            Some("boilerplate")
+        } else {
+            None
+        };
+        if let Some(r) = reason {
+            *s.reasons.entry(r).or_default() += 1;
+        }
+    }
+
+    // every file charged to itself and each directory above it
+    let mut groups: BTreeMap<String, Suggestion> = BTreeMap::new();
+    for s in stats.values() {
+        let mut keys = vec![s.path.clone()];
+        let mut dir = s.path.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            keys.push(format!("{parent}/"));
+            dir = parent;
+        }
+        for k in keys {
+            let g = groups.entry(k.clone()).or_insert_with(|| Suggestion { pattern: k, ..Default::default() });
+            g.files += 1;
+            g.chunks += s.chunks;
+            g.bytes += s.bytes;
+            for (r, n) in &s.reasons {
+                *g.reasons.entry(r).or_default() += n;
+            }
+        }
+    }
+    let min_chunks = ((total as f32 * MIN_SHARE).ceil() as usize).max(1);
+    let mut picked: Vec<Suggestion> = Vec::new();
+    let covered = |picked: &[Suggestion], pattern: &str| {
+        let under = |dir: &str| dir.ends_with('/') && pattern.starts_with(dir);
+        ignored.iter().any(|i| i == pattern || under(i)) || picked.iter().any(|p| under(&p.pattern))
+    };
+    // shallowest first, so a directory wins over what it contains
+    let mut candidates: Vec<Suggestion> = groups
+        .into_values()
+        .filter(|g| g.chunks >= min_chunks && g.low() as f32 >= MIN_LOW * g.chunks as f32)
+        .collect();
+    candidates.sort_by_key(|g| (g.pattern.trim_end_matches('/').matches('/').count(), g.pattern.clone()));
+    for g in candidates {
+        if !covered(&picked, &g.pattern) {
+            picked.push(g);
+        }
+    }
+    picked.sort_by_key(|p| std::cmp::Reverse(p.chunks));
+    Ok((picked, total))
+}
+
+/// Control bytes, or text that is mostly long unbroken runs.
+fn binaryish(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return false;
+    }
+    let control = data.iter().filter(|b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r')).count();
+    let space = data.iter().filter(|b| b.is_ascii_whitespace()).count();
+    control * 10 > data.len() || (data.len() >= 512 && space * 50 < data.len())
+}
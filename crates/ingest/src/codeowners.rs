@@ -0,0 +1,77 @@
+//! CODEOWNERS: lines of `pattern owner...`, the last matching line winning,
+//! with gitignore-style patterns. A pattern with a leading or inner `/` is
+//! anchored at the root, one without matches at any depth, a trailing `/`
+//! matches only directories, and a pattern naming a directory owns
+//! everything under it (`docs/*` only the files directly in docs/). A line without owners leaves the path unowned.
+
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+
+/// Where GitHub and GitLab look, in their order of precedence.
+pub const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS", ".gitlab/CODEOWNERS"];
+
+pub struct CodeOwners {
+    rules: Vec<(Vec<GlobMatcher>, Vec<String>)>,
+}
+
+impl CodeOwners {
+    /// The first CODEOWNERS file under `root`, if any.
+    pub fn find(root: &Path) -> Result<Option<Self>> {
+        for loc in LOCATIONS {
+            let p = root.join(loc);
+            if p.is_file() {
+                return Ok(Some(Self::parse(&std::fs::read_to_string(p)?)?));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.split_once(" #").map_or(line, |(l, _)| l).trim();
+            // GitLab `[Section]` headers carry no pattern
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else { continue };
+            let owners = fields.map(str::to_string).collect();
+            rules.push((matchers(pattern)?, owners));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Owners of `path` (relative to the root, `/`-separated).
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(globs, _)| globs.iter().any(|g| g.is_match(path)))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+}
+
+fn matchers(pattern: &str) -> Result<Vec<GlobMatcher>> {
+    let dir_only = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.contains('/');
+    let base = trimmed.trim_start_matches('/');
+    let base = if anchored { base.to_string() } else { format!("**/{base}") };
+    // paths are files, so a directory-only pattern matches what is under
+    // it; `docs/*` owns the files in docs/ but not deeper ones
+    let wild_tail = trimmed.rsplit('/').next().is_some_and(|seg| seg.contains('*'));
+    let globs = match (dir_only, wild_tail) {
+        (true, _) => vec![format!("{base}/**")],
+        (false, true) => vec![base],
+        (false, false) => vec![base.clone(), format!("{base}/**")],
+    };
+    globs
+        .iter()
+        .map(|g| {
+            let glob = GlobBuilder::new(g).literal_separator(true).build();
+            Ok(glob.map_err(|e| anyhow::anyhow!("bad CODEOWNERS pattern `{pattern}`: {e}"))?.compile_matcher())
+        })
+        .collect()
+}
@@ -0,0 +1,105 @@
+//! Generated files: a marker in a comment near the top (`@generated`,
+//! `DO NOT EDIT`, `// Code generated by ... DO NOT EDIT.`), minified JS/CSS,
+//! or JSON too big to be written by hand. A marker outside a comment, say in
+//! a string or in prose, does not count. They match most queries on vocabulary alone and crowd out the
+//! source they were made from, so `[generated]` in mentat.toml skips them
+//! by default, or keeps them ranked lower.
+
+use serde::{Deserialize, Serialize};
+
+/// Markers are looked for in the start of a file only.
+const HEAD_BYTES: usize = 2048;
+
+/// Line comment leaders.
+const LINE_COMMENTS: &[&str] = &["//", "#", "--", ";", "%", "rem ", "REM "];
+/// Block comment (and docstring) delimiters; the lines between count too.
+const BLOCK_COMMENTS: &[(&str, &str)] = &[("/*", "*/"), ("<!--", "-->"), ("\"\"\"", "\"\"\""), ("'''", "'''"), ("{-", "-}"), ("(*", "*)")];
+
+/// Matched as written.
+const MARKERS: &[&str] = &["@generated", "DO NOT EDIT", "Code generated by", "<auto-generated"];
+/// Matched against the lowercased head.
+const MARKERS_ANY_CASE: &[&str] = &["auto-generated", "autogenerated", "this file was generated", "this file is generated"];
+
+const MINIFIABLE: &[&str] = &["js", "mjs", "cjs", "css"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// not indexed; listed under the run's skipped files
+    #[default]
+    Skip,
+    /// indexed and marked; `[ranking] generated` scales their hits
+    Downrank,
+    /// indexed like any other file
+    Index,
+}
+
+/// `[generated]` in mentat.toml.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneratedConfig {
+    pub action: Action,
+    /// more markers, matched as written in a comment
+    pub markers: Vec<String>,
+    /// JS/CSS whose lines average more bytes than this are minified (0 = off)
+    pub minified_line_bytes: usize,
+    /// JSON files bigger than this are data dumps (0 = off)
+    pub max_json_bytes: usize,
+}
+
+impl Default for GeneratedConfig {
+    fn default() -> Self {
+        Self { action: Action::Skip, markers: Vec::new(), minified_line_bytes: 500, max_json_bytes: 1 << 20 }
+    }
+}
+
+/// Why `path` with contents `data` looks generated, if it does.
+pub fn detect(path: &str, data: &[u8], cfg: &GeneratedConfig) -> Option<String> {
+    let head = String::from_utf8_lossy(&data[..data.len().min(HEAD_BYTES)]);
+    let comments = comment_lines(&head);
+    let mut markers = MARKERS.iter().copied().chain(cfg.markers.iter().map(String::as_str));
+    if let Some(m) = markers.find(|m| comments.iter().any(|l| l.contains(m))) {
+        return Some(format!("marker `{m}`"));
+    }
+    let lower: Vec<String> = comments.iter().map(|l| l.to_lowercase()).collect();
+    if let Some(m) = MARKERS_ANY_CASE.iter().find(|m| lower.iter().any(|l| l.contains(*m))) {
+        return Some(format!("marker `{m}`"));
+    }
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path).to_lowercase();
+    let ext = name.rsplit_once('.').map(|(_, e)| e).unwrap_or("");
+    if MINIFIABLE.contains(&ext) {
+        if name.contains(".min.") {
+            return Some("minified".into());
+        }
+        let lines = data.split(|b| *b == b'\n').filter(|l| !l.is_empty()).count().max(1);
+        if cfg.minified_line_bytes > 0 && data.len() / lines > cfg.minified_line_bytes {
+            return Some(format!("minified ({} bytes per line)", data.len() / lines));
+        }
+    }
+    if ext == "json" && cfg.max_json_bytes > 0 && data.len() > cfg.max_json_bytes {
+        return Some(format!("JSON of {} bytes", data.len()));
+    }
+    None
+}
+
+/// Lines of `head` that are, or lie inside, a comment.
+fn comment_lines(head: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut open: Option<&str> = None;
+    for line in head.lines().map(str::trim_start) {
+        if let Some(end) = open {
+            out.push(line);
+            if line.contains(end) {
+                open = None;
+            }
+        } else if let Some((start, end)) = BLOCK_COMMENTS.iter().find(|(start, _)| line.starts_with(start)) {
+            out.push(line);
+            if !line[start.len()..].contains(end) {
+                open = Some(end);
+            }
+        } else if LINE_COMMENTS.iter().any(|c| line.starts_with(c)) {
+            out.push(line);
+        }
+    }
+    out
+}
@@ -1,3 +1,8 @@
+pub mod codeowners;
+pub mod generated;
+pub mod license;
+pub mod manifest;
+
 use anyhow::Result;
 use blake3::Hasher;
 use serde::Serialize;
@@ -12,35 +17,83 @@ pub struct Chunk {
     pub size: usize,
 }
 
+/// A file (or directory entry) that could not be read during the walk.
+#[derive(Serialize, Debug)]
+pub struct IngestError {
+    pub path: String,
+    pub error: String,
+}
+
+/// Strict walk: the first unreadable entry aborts.
 pub fn ingest<P: AsRef<Path>>(root: P) -> Result<Vec<Chunk>> {
+    let (out, errors) = ingest_tolerant(root)?;
+    if let Some(e) = errors.into_iter().next() {
+        anyhow::bail!("{}: {}", e.path, e.error);
+    }
+    Ok(out)
+}
+
+/// A file selected by the walk, not yet read.
+pub struct Entry {
+    pub path: String,
+    pub size: usize,
+    /// modification time, unix seconds, when the filesystem reports one
+    pub mtime: Option<u64>,
+}
+
+/// Walk and filter without reading file contents, collecting per-entry failures.
+pub fn walk<P: AsRef<Path>>(root: P) -> Result<(Vec<Entry>, Vec<IngestError>)> {
     let mut out = Vec::new();
+    let mut errors = Vec::new();
     let ignore = load_ignore(root.as_ref());
 
     for entry in WalkDir::new(root) {
-        let entry = entry?;
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                let path = e.path().map(|p| p.display().to_string()).unwrap_or_default();
+                errors.push(IngestError { path, error: e.to_string() });
+                continue;
+            }
+        };
         if entry.file_type().is_file() {
             let path = entry.path();
             if should_ignore(path, &ignore) {
                 continue;
             }
-            let data = fs::read(path)?;
-            let mut hasher = Hasher::new();
-            hasher.update(&data);
-            let hash = hasher.finalize().to_hex().to_string();
-            out.push(Chunk {
-                path: path.display().to_string(),
-                hash,
-                size: data.len(),
-            });
+            let md = entry.metadata().ok();
+            let size = md.as_ref().map(|m| m.len() as usize).unwrap_or(0);
+            let mtime = md
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            out.push(Entry { path: path.display().to_string(), size, mtime });
         }
     }
-    Ok(out)
+    Ok((out, errors))
+}
+
+/// Walk that reads and hashes every file, collecting per-entry failures.
+pub fn ingest_tolerant<P: AsRef<Path>>(root: P) -> Result<(Vec<Chunk>, Vec<IngestError>)> {
+    let (entries, mut errors) = walk(root)?;
+    let mut out = Vec::new();
+    for e in entries {
+        let data = match fs::read(&e.path) {
+            Ok(d) => d,
+            Err(err) => {
+                errors.push(IngestError { path: e.path, error: err.to_string() });
+                continue;
+            }
+        };
+        out.push(Chunk { path: e.path, hash: hash_hex(&data), size: data.len() });
+    }
+    Ok((out, errors))
 }
 
-pub fn dump_json(chunks: &[Chunk]) -> Result<()> {
-    let json = serde_json::to_string_pretty(chunks)?;
-    fs::write("ingest_manifest.json", json)?;
-    Ok(())
+pub fn hash_hex(data: &[u8]) -> String {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    hasher.finalize().to_hex().to_string()
 }
 
 fn load_ignore(root: &Path) -> GlobSet {
@@ -102,6 +155,11 @@ fn load_ignore(root: &Path) -> GlobSet {
         .unwrap_or_else(|_| GlobSetBuilder::new().build().unwrap())
 }
 
+/// Whether `walk(root)` leaves out `rel`, a path under `root`.
+pub fn is_ignored(root: &Path, rel: &Path) -> bool {
+    should_ignore(rel, &load_ignore(root))
+}
+
 fn should_ignore(path: &Path, ignore: &GlobSet) -> bool {
     let path_str = path.to_string_lossy();
     let cleaned = path_str.strip_prefix("./").unwrap_or(&path_str);
@@ -0,0 +1,55 @@
+//! License of a file from its head: an `SPDX-License-Identifier:` tag, else
+//! well-known phrases of common license headers. Full license texts
+//! (LICENSE, COPYING) are recognized by the same phrases.
+
+/// Only the start of a file is searched.
+const HEAD_BYTES: usize = 8192;
+
+/// SPDX id and phrases that must all appear, checked in order (lowercase,
+/// comment markers and line breaks removed).
+const HEADERS: &[(&str, &[&str])] = &[
+    ("Apache-2.0", &["apache license", "version 2.0"]),
+    ("AGPL-3.0", &["gnu affero general public license"]),
+    ("LGPL-2.1", &["gnu lesser general public license", "version 2.1"]),
+    ("LGPL-3.0", &["gnu lesser general public license", "version 3"]),
+    ("GPL-3.0", &["gnu general public license", "version 3"]),
+    ("GPL-2.0", &["gnu general public license", "version 2"]),
+    ("MPL-2.0", &["mozilla public license", "2.0"]),
+    ("MIT", &["permission is hereby granted, free of charge"]),
+    ("ISC", &["permission to use, copy, modify, and/or distribute this software for any purpose"]),
+    ("BSD-3-Clause", &["redistribution and use in source and binary forms", "neither the name"]),
+    ("BSD-2-Clause", &["redistribution and use in source and binary forms"]),
+    ("Unlicense", &["this is free and unencumbered software released into the public domain"]),
+];
+
+pub fn detect(data: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&data[..data.len().min(HEAD_BYTES)]);
+    if let Some(id) = spdx(&head) {
+        return Some(id);
+    }
+    let flat = flatten(&head);
+    HEADERS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|p| flat.contains(p)))
+        .map(|(id, _)| id.to_string())
+}
+
+fn spdx(head: &str) -> Option<String> {
+    let (_, rest) = head.split_once("SPDX-License-Identifier:")?;
+    let line = rest.lines().next()?;
+    let expr = line.trim().trim_end_matches("*/").trim_end_matches("-->").trim();
+    (!expr.is_empty()).then(|| expr.to_string())
+}
+
+/// Lowercase words of the text with comment leaders dropped, single-spaced.
+fn flatten(head: &str) -> String {
+    let mut out = String::with_capacity(head.len());
+    for line in head.lines() {
+        let line = line.trim_start().trim_start_matches(['/', '*', '#', ';', '-', '!', '%']);
+        for word in line.split_whitespace() {
+            out.push_str(&word.to_lowercase());
+            out.push(' ');
+        }
+    }
+    out
+}
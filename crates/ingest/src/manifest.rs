@@ -0,0 +1,171 @@
+//! Versioned ingest manifest (`ingest_manifest.json`) and tree diffs.
+//!
+//! v1 was a flat array of `{path, hash, size}`; v2 wraps the entries with a
+//! version and root and records per-file status.
+
+use crate::{hash_hex, walk};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+pub const VERSION: u32 = 2;
+pub const DEFAULT_PATH: &str = "ingest_manifest.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub version: u32,
+    /// root as given on the command line; entry paths are relative to it
+    pub root: String,
+    pub files: Vec<FileEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: usize,
+    #[serde(default)]
+    pub mtime: Option<u64>,
+    /// blake3 of the contents; empty when the file could not be read
+    pub hash: String,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+    pub kind: Kind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// will be chunked and embedded
+    Ok,
+    /// readable but not indexed (see `skip_reason`)
+    Skipped,
+    /// could not be read (see `skip_reason`)
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Text,
+    Binary,
+    Empty,
+    Unknown,
+}
+
+/// Same gate the chunker uses: a NUL byte means binary.
+pub fn detect_kind(data: &[u8]) -> Kind {
+    if data.is_empty() {
+        Kind::Empty
+    } else if data.contains(&0) {
+        Kind::Binary
+    } else {
+        Kind::Text
+    }
+}
+
+pub fn build<P: AsRef<Path>>(root: P) -> Result<Manifest> {
+    let root = root.as_ref();
+    let (entries, errors) = walk(root)?;
+    let mut files = Vec::with_capacity(entries.len() + errors.len());
+    for e in entries {
+        let path = relativize(&e.path, root);
+        let entry = match fs::read(&e.path) {
+            Ok(data) => {
+                let kind = detect_kind(&data);
+                let (status, skip_reason) = match kind {
+                    Kind::Text => (Status::Ok, None),
+                    Kind::Binary => (Status::Skipped, Some("binary".into())),
+                    _ => (Status::Skipped, Some("empty".into())),
+                };
+                FileEntry { path, size: data.len(), mtime: e.mtime, hash: hash_hex(&data), status, skip_reason, kind }
+            }
+            Err(err) => FileEntry {
+                path,
+                size: e.size,
+                mtime: e.mtime,
+                hash: String::new(),
+                status: Status::Error,
+                skip_reason: Some(err.to_string()),
+                kind: Kind::Unknown,
+            },
+        };
+        files.push(entry);
+    }
+    for e in errors {
+        files.push(FileEntry {
+            path: relativize(&e.path, root),
+            size: 0,
+            mtime: None,
+            hash: String::new(),
+            status: Status::Error,
+            skip_reason: Some(e.error),
+            kind: Kind::Unknown,
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Manifest { version: VERSION, root: root.display().to_string(), files })
+}
+
+impl Manifest {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let txt = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        if txt.trim_start().starts_with('[') {
+            anyhow::bail!(
+                "{} is a v1 manifest (flat list, no root); regenerate it with `mentat ingest`",
+                path.display()
+            );
+        }
+        let m: Self = serde_json::from_str(&txt).with_context(|| format!("parsing {}", path.display()))?;
+        if m.version > VERSION {
+            anyhow::bail!("{}: manifest version {} is newer than supported ({VERSION})", path.display(), m.version);
+        }
+        Ok(m)
+    }
+
+    pub fn errors(&self) -> usize {
+        self.files.iter().filter(|f| f.status == Status::Error).count()
+    }
+}
+
+/// Paths added, changed (by content hash) and removed between two manifests.
+#[derive(Serialize, Default, Debug)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+pub fn diff(old: &Manifest, new: &Manifest) -> Diff {
+    let before: BTreeMap<&str, &str> = old.files.iter().map(|f| (f.path.as_str(), f.hash.as_str())).collect();
+    let after: BTreeMap<&str, &str> = new.files.iter().map(|f| (f.path.as_str(), f.hash.as_str())).collect();
+    let mut d = Diff::default();
+    for (path, hash) in &after {
+        match before.get(path) {
+            None => d.added.push(path.to_string()),
+            Some(h) if h != hash => d.changed.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    d.removed = before.keys().filter(|p| !after.contains_key(*p)).map(|p| p.to_string()).collect();
+    d
+}
+
+fn relativize(p: &str, root: &Path) -> String {
+    match Path::new(p).strip_prefix(root) {
+        Ok(r) => r.display().to_string(),
+        Err(_) => p.to_string(),
+    }
+}
@@ -0,0 +1,217 @@
+//! Citations that outlive the chunk they were taken from:
+//!
+//! ```text
+//! src/auth/session.rs:1200-7200#9f3a0c51e2d47b08
+//! ```
+//!
+//! Path and byte range say where the text was; the fingerprint is a simhash
+//! of its word 3-shingles, so a lightly edited copy lands within a few bits.
+//! Resolving prefers the same path, then searches every indexed file.
+//!
+//! `extract` pulls the checkable claims out of an answer (citations, path
+//! references, quoted code) for `Retriever::check_claim`.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+/// Max differing fingerprint bits for a chunk to count as the cited text.
+pub const MAX_DISTANCE: u32 = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+    pub fingerprint: u64,
+}
+
+impl Citation {
+    pub fn new(path: &str, start: usize, end: usize, text: &str) -> Self {
+        Self { path: path.to_string(), start, end, fingerprint: fingerprint(text) }
+    }
+}
+
+impl fmt::Display for Citation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}-{}#{:016x}", self.path, self.start, self.end, self.fingerprint)
+    }
+}
+
+impl FromStr for Citation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bad = || anyhow::anyhow!("bad citation `{s}`, expected PATH:START-END#FINGERPRINT");
+        let (loc, fp) = s.rsplit_once('#').ok_or_else(bad)?;
+        let (path, range) = loc.rsplit_once(':').ok_or_else(bad)?;
+        let (start, end) = range.split_once('-').ok_or_else(bad)?;
+        Ok(Self {
+            path: path.to_string(),
+            start: start.parse().map_err(|_| bad())?,
+            end: end.parse().map_err(|_| bad())?,
+            fingerprint: u64::from_str_radix(fp, 16).map_err(|_| bad())?,
+        })
+    }
+}
+
+/// Where a citation's text is now.
+#[derive(Serialize, Debug, Clone)]
+pub struct Resolved {
+    pub chunk_id: String,
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+    /// differing fingerprint bits; 0 for unchanged text
+    pub distance: u32,
+    /// same path and range as cited
+    pub in_place: bool,
+}
+
+/// 64-bit simhash over word 3-shingles (shorter ones for texts under 3 words).
+pub fn fingerprint(text: &str) -> u64 {
+    let mut votes = [0i32; 64];
+    for h in shingles(text) {
+        for (bit, v) in votes.iter_mut().enumerate() {
+            *v += if (h >> bit) & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    votes.iter().enumerate().filter(|(_, v)| **v > 0).fold(0, |acc, (bit, _)| acc | (1 << bit))
+}
+
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hashes of the word 3-shingles of `text`.
+fn shingles(text: &str) -> Vec<u64> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let n = words.len().clamp(1, 3);
+    words
+        .windows(n)
+        .map(|w| {
+            let h = mentat_store::blake32(w.join(" ").as_bytes());
+            u64::from_le_bytes(h[..8].try_into().expect("8 bytes"))
+        })
+        .collect()
+}
+
+/// Share of `snippet`'s shingles that also occur in `text`; 1.0 when the
+/// snippet appears verbatim (whitespace aside).
+pub fn containment(snippet: &str, text: &str) -> f32 {
+    let (s, t) = (collapse(snippet), collapse(text));
+    if s.is_empty() || t.contains(&s) {
+        return if s.is_empty() { 0.0 } else { 1.0 };
+    }
+    let have: std::collections::HashSet<u64> = shingles(&t).into_iter().collect();
+    let want = shingles(&s);
+    want.iter().filter(|h| have.contains(h)).count() as f32 / want.len() as f32
+}
+
+fn collapse(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Something an answer says is in the corpus.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Claim {
+    /// a citation in this module's format
+    Citation { citation: String },
+    /// `path` or `path:line` in backticks
+    Path { path: String, line: Option<usize> },
+    /// fenced code, a blockquote, or inline code of three or more words
+    Snippet { text: String },
+}
+
+/// Score from which a claim counts as found.
+pub const MIN_SCORE: f32 = 0.6;
+
+/// Outcome of checking one claim.
+#[derive(Serialize, Debug, Clone)]
+pub struct Check {
+    #[serde(flatten)]
+    pub claim: Claim,
+    pub found: bool,
+    /// 1.0 for an exact match; fingerprint closeness for citations,
+    /// shingle containment for snippets
+    pub score: f32,
+    /// PATH:START-END of the best match, or the path for path claims
+    pub location: Option<String>,
+}
+
+/// Claims in a markdown answer, in order of appearance, without repeats.
+pub fn extract(answer: &str) -> Vec<Claim> {
+    let mut out: Vec<Claim> = Vec::new();
+    let mut push = |c: Claim| {
+        if !out.contains(&c) {
+            out.push(c);
+        }
+    };
+    let mut fence: Option<Vec<&str>> = None;
+    let mut quote: Vec<&str> = Vec::new();
+    for line in answer.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            match fence.take() {
+                Some(body) => push(Claim::Snippet { text: body.join("\n") }),
+                None => fence = Some(Vec::new()),
+            }
+            continue;
+        }
+        if let Some(body) = fence.as_mut() {
+            body.push(line);
+            continue;
+        }
+        match trimmed.strip_prefix('>') {
+            Some(q) => {
+                quote.push(q.strip_prefix(' ').unwrap_or(q));
+                continue;
+            }
+            None if !quote.is_empty() => push(Claim::Snippet { text: std::mem::take(&mut quote).join("\n") }),
+            None => {}
+        }
+        for (i, span) in line.split('`').enumerate() {
+            if i % 2 == 1 {
+                if let Some(c) = inline_claim(span) {
+                    push(c);
+                }
+                continue;
+            }
+            for word in span.split_whitespace() {
+                let word = word.trim_matches(|c: char| matches!(c, '(' | ')' | '[' | ']' | ',' | ';' | '"'));
+                if word.parse::<Citation>().is_ok() {
+                    push(Claim::Citation { citation: word.to_string() });
+                }
+            }
+        }
+    }
+    if !quote.is_empty() {
+        push(Claim::Snippet { text: quote.join("\n") });
+    }
+    out
+}
+
+fn inline_claim(span: &str) -> Option<Claim> {
+    let span = span.trim();
+    if span.parse::<Citation>().is_ok() {
+        return Some(Claim::Citation { citation: span.to_string() });
+    }
+    if span.split_whitespace().count() >= 3 {
+        return Some(Claim::Snippet { text: span.to_string() });
+    }
+    if !span.chars().all(|c| c.is_alphanumeric() || "/._-:".contains(c)) {
+        return None;
+    }
+    let (path, line) = match span.rsplit_once(':') {
+        Some((p, l)) => match l.split('-').next().and_then(|n| n.parse().ok()) {
+            Some(n) => (p, Some(n)),
+            None => (span, None),
+        },
+        None => (span, None),
+    };
+    // `self.store` is code, `src/lib.rs` and `main.rs` are files
+    let ext = std::path::Path::new(path).extension().and_then(|e| e.to_str());
+    (path.contains('/') || ext.is_some_and(crate::query::known_extension))
+        .then(|| Claim::Path { path: path.to_string(), line })
+}
@@ -0,0 +1,68 @@
+//! Tell code-like queries (`fn put_embed`, `HashMap::entry`, `self.store`)
+//! from prose ones. The model is queried the same way for both (it has no
+//! instruction prefix to skip); a code-like query additionally gets the
+//! `[ranking] lexical` boost for chunks that share its identifier terms.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryKind {
+    Code,
+    Prose,
+}
+
+/// Declaration keywords that make a short query code when they lead it.
+const KEYWORDS: &[&str] = &[
+    "fn", "def", "class", "struct", "impl", "func", "function", "let", "const", "var", "pub", "import", "use", "enum",
+    "trait", "interface", "type", "mod", "package",
+];
+
+/// Queries with more words than this need half of them to look like code.
+const SHORT_QUERY_WORDS: usize = 8;
+
+pub fn classify(text: &str) -> QueryKind {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let code = words.iter().filter(|w| is_code_token(w)).count();
+    let keyword_led = words.len() >= 2 && words.len() <= 4 && KEYWORDS.contains(&words[0]);
+    let short = words.len() <= SHORT_QUERY_WORDS;
+    if keyword_led || code > 0 && (short || code * 2 >= words.len()) {
+        QueryKind::Code
+    } else {
+        QueryKind::Prose
+    }
+}
+
+/// The identifiers of a code-like query: the names inside code-looking
+/// words, plus every word after a leading keyword (`fn foo` -> `foo`).
+pub fn identifiers(text: &str) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let keyword_led = words.first().is_some_and(|w| KEYWORDS.contains(w));
+    let mut out: Vec<String> = Vec::new();
+    for (i, w) in words.iter().enumerate() {
+        if !(is_code_token(w) || keyword_led && i > 0) {
+            continue;
+        }
+        for name in w.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            if name.chars().count() >= 2 && !KEYWORDS.contains(&name) && !out.iter().any(|o| o == name) {
+                out.push(name.to_string());
+            }
+        }
+    }
+    out
+}
+
+fn is_code_token(w: &str) -> bool {
+    let w = w.trim_end_matches(['?', ',', '!']);
+    if ["::", "->", "=>", "()", "{", "}", ";", "[]", "==", "!=", "&&", "||"].iter().any(|p| w.contains(p)) {
+        return true;
+    }
+    let chars: Vec<char> = w.chars().collect();
+    let inner = |c: char| chars.windows(3).any(|t| t[1] == c && t[0].is_alphanumeric() && t[2].is_alphanumeric());
+    // snake_case, and `obj.field` but not "e.g." or version numbers
+    if inner('_') {
+        return true;
+    }
+    if inner('.') && w.split('.').all(|p| p.chars().count() >= 2 && p.starts_with(|c: char| c.is_alphabetic() || c == '_')) {
+        return true;
+    }
+    // camelCase and PascalCase with an inner capital: putEmbed, HashMap (not "Rust" or "NASA")
+    chars.windows(2).any(|p| p[0].is_lowercase() && p[1].is_uppercase())
+}
@@ -0,0 +1,56 @@
+//! The hnsw_rs graph behind `Retriever`'s approximate search, built with the
+//! `hnsw` feature (on by default).
+
+use crate::HnswParams;
+use anyhow::Result;
+use hnsw_rs::{hnswio::HnswIo, prelude::*};
+use mentat_embedder::D;
+use std::path::Path;
+
+/// Points inserted between progress calls.
+const PROGRESS_EVERY: usize = 1000;
+
+/// Recorded in graph headers; a graph written by another backend is not loaded.
+pub const BACKEND: &str = "hnsw_rs-0.3";
+
+/// The graph and the ef it is searched with.
+pub struct Graph(Hnsw<'static, f32, DistCosine>, usize);
+
+impl Graph {
+    /// Deterministic single-threaded build; point `i` is `data[i]`.
+    /// `progress(done, total)` runs every `PROGRESS_EVERY` points and at the
+    /// end; returning false abandons the build with an error.
+    pub fn build(data: &[([u8; 32], [f32; D])], params: &HnswParams, progress: &mut dyn FnMut(usize, usize) -> bool) -> Result<Self> {
+        let dist = DistCosine {};
+        let mut hnsw = Hnsw::<f32, DistCosine>::new(params.m, data.len(), params.max_layers, params.ef_construction, dist);
+
+        for (i, (_, v)) in data.iter().enumerate() {
+            if i % PROGRESS_EVERY == 0 && !progress(i, data.len()) {
+                anyhow::bail!("HNSW build cancelled after {i} of {} points", data.len());
+            }
+            hnsw.insert((&v[..], i));
+        }
+        progress(data.len(), data.len());
+        hnsw.set_searching_mode(true);
+        Ok(Self(hnsw, params.ef_search))
+    }
+
+    /// Dump under `dir/<name>`; returns the basename hnsw_rs chose.
+    pub fn dump(&self, dir: &Path, name: &str) -> Result<String> {
+        self.0.file_dump(dir, name)
+    }
+
+    pub fn load(dir: &Path, basename: &str, params: &HnswParams) -> Result<Self> {
+        // the loaded graph borrows from its loader for as long as it lives;
+        // a process loads one graph, so the loader is leaked rather than stored
+        let io: &'static mut HnswIo = Box::leak(Box::new(HnswIo::new(dir, basename)));
+        let mut hnsw: Hnsw<'static, f32, DistCosine> = io.load_hnsw()?;
+        hnsw.set_searching_mode(true);
+        Ok(Self(hnsw, params.ef_search))
+    }
+
+    /// (point, cosine distance), closest first.
+    pub fn search(&self, q: &[f32], topk: usize) -> Vec<(usize, f32)> {
+        self.0.search(q, topk, self.1).iter().map(|ne| (ne.d_id, ne.distance)).collect()
+    }
+}
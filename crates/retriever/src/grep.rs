@@ -0,0 +1,115 @@
+//! `mentat grep`: exact regex matches over the indexed files, optionally
+//! ranked by meaning.
+//!
+//! The query's file clauses (`path:`, `lang:`, `license:`, `owner:`,
+//! `-word`, ...) pick the files to scan, from stored metadata, so only
+//! those are read. Each matching line is tied to the chunk that holds it;
+//! when the query has search words, matches are ordered by their chunk's
+//! distance to them, otherwise by path and line.
+
+use crate::{cosine_distance, query, Retriever};
+use anyhow::Result;
+use regex::RegexBuilder;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Characters of a matching line kept in the output.
+const MAX_LINE: usize = 200;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Match {
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+    /// chunk holding the line; None for lines no chunk covers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_id: Option<String>,
+    /// the chunk's cosine distance to the query's search words, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub matches: Vec<Match>,
+    /// matches found before `limit` cut the list
+    pub total: usize,
+    pub files_scanned: usize,
+    /// indexed files that changed on disk since indexing and were not scanned
+    pub stale: Vec<String>,
+}
+
+/// Lines matching `pattern` in the files `q` selects; at most `limit` (0 = all).
+pub fn grep(retr: &Retriever, pattern: &str, ignore_case: bool, q: &query::Query, limit: usize) -> Result<Report> {
+    let re = RegexBuilder::new(pattern).case_insensitive(ignore_case).build().map_err(|e| anyhow::anyhow!("bad regex: {e}"))?;
+    let store = retr.store();
+    let filter = query::FileFilter::new(q)?;
+    let mut by_file: HashMap<[u8; 32], Vec<([u8; 32], mentat_store::ChunkMeta)>> = HashMap::new();
+    for (id, c) in store.chunks()? {
+        by_file.entry(c.file_hash).or_default().push((id, c));
+    }
+    let mut rep = Report::default();
+    let mut hits: Vec<(Match, Option<[u8; 32]>)> = Vec::new();
+    for (fhash, f) in store.files()? {
+        let license = store.get_license(fhash)?;
+        let owners = store.get_owners(fhash)?.map(|o| [o.codeowners, o.authors].concat()).unwrap_or_default();
+        let facts = query::FileFacts { path: &f.path, mtime: store.get_mtime(fhash)?, license: license.as_deref(), owners: &owners };
+        if !filter.matches(&facts) {
+            continue;
+        }
+        let data = match store.read_file(fhash, &f.path)? {
+            Some(d) if mentat_store::blake32(&d) == fhash => d,
+            _ => {
+                rep.stale.push(f.path);
+                continue;
+            }
+        };
+        rep.files_scanned += 1;
+        let chunks = by_file.get(&fhash).map(Vec::as_slice).unwrap_or_default();
+        let text = String::from_utf8_lossy(&data);
+        let mut offset = 0;
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            let at = offset;
+            offset += line.len();
+            let line = line.trim_end_matches(['\n', '\r']);
+            if !re.is_match(line) {
+                continue;
+            }
+            let chunk = chunks.iter().find(|(_, c)| c.start <= at && at < c.end).map(|(id, _)| *id);
+            let m = Match {
+                path: f.path.clone(),
+                line: i + 1,
+                text: line.chars().take(MAX_LINE).collect(),
+                chunk_id: chunk.map(hex::encode),
+                distance: None,
+            };
+            hits.push((m, chunk));
+        }
+    }
+    let words = q.semantic_text();
+    if words.trim().is_empty() {
+        hits.sort_by(|a, b| (&a.0.path, a.0.line).cmp(&(&b.0.path, b.0.line)));
+    } else {
+        let qv = retr.embed_query(&words)?;
+        let mut distances: HashMap<[u8; 32], Option<f32>> = HashMap::new();
+        for (m, chunk) in &mut hits {
+            let Some(id) = chunk else { continue };
+            if !distances.contains_key(id) {
+                distances.insert(*id, store.get_embed(*id)?.map(|v| cosine_distance(&qv, &v)));
+            }
+            m.distance = distances[id];
+        }
+        // unranked lines last, then path and line among equals
+        hits.sort_by(|a, b| {
+            let key = |m: &Match| m.distance.unwrap_or(f32::INFINITY);
+            key(&a.0).total_cmp(&key(&b.0)).then_with(|| (&a.0.path, a.0.line).cmp(&(&b.0.path, b.0.line)))
+        });
+    }
+    rep.total = hits.len();
+    if limit > 0 {
+        hits.truncate(limit);
+    }
+    rep.matches = hits.into_iter().map(|(m, _)| m).collect();
+    Ok(rep)
+}
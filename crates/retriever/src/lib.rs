@@ -1,122 +1,757 @@
 //! Phase 3b – Offline HNSW build and search.
 //! Deterministic single-threaded index built from ReDB embeddings.
+//! A saved graph is only loaded if its header checksums match the files,
+//! its generation (the chunk ids it was built from) matches the store, and
+//! it was built by the same ANN backend over vectors of the same model,
+//! dimension and metric; otherwise `load_hnsw` errors and search stays
+//! exact. A store whose vectors this build cannot compare (another
+//! dimension or metric) does not open at all. Builds without the
+//! `hnsw` feature (default on) have no graph and always search exact.
+//! With a budget set (`set_budget`), the slow parts of a search (text
+//! clause checks, reranking) stop at the deadline and the search returns
+//! what it has, with `truncated()` true.
 
-use anyhow::Result;
-use redb::{Database, TableDefinition, ReadableTable};
-use mentat_embedder::{embed_text, D};
-use hnsw_rs::prelude::*;
+pub mod cite;
+pub mod classify;
+pub mod grep;
+pub mod provenance;
+pub mod pseudonym;
+pub mod query;
+pub mod rag;
+pub mod rank;
+pub mod relevance;
+pub mod rev;
+pub mod rewrite;
+pub mod score;
+pub mod secrets;
+pub mod snippet;
+
+#[cfg(feature = "hnsw")]
+mod graph;
+#[cfg(not(feature = "hnsw"))]
+mod no_graph;
+#[cfg(not(feature = "hnsw"))]
+use no_graph as graph;
+
+use anyhow::{Context, Result};
+use mentat_embedder::{VectorInfo, D};
+use mentat_store::Store;
+use graph::Graph;
 use serde::{Serialize, Deserialize};
-use std::{fs, path::Path};
+use std::{fs, path::Path, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
 
-const EMBEDS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("embeds");
+/// Whether this build can build and load HNSW graphs.
+pub const HNSW: bool = cfg!(feature = "hnsw");
 
+/// Written last by `build_hnsw`, as `<base>.hdr`, so an interrupted build
+/// leaves no header that vouches for half-written files.
 #[derive(Serialize, Deserialize)]
 pub struct HnswHeader {
     pub n: usize,
     pub d: usize,
+    /// the vectors the graph was built over
+    pub vectors: VectorInfo,
+    /// ANN library that wrote the graph files
+    pub backend: String,
+    pub params: HnswParams,
+    /// mentat-retriever version that built it
+    pub version: String,
+    /// basename hnsw_rs dumped the graph under (`<basename>.hnsw.graph` / `.hnsw.data`)
+    pub basename: String,
+    /// blake3 over the chunk ids in insertion order
+    pub generation: [u8; 32],
+    /// blake3 of the graph, data and ids files, in that order
+    pub checksums: Vec<[u8; 32]>,
+}
+
+/// Build and search parameters of a graph. A graph loads with the ones it
+/// was built with, whatever this build would pick.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct HnswParams {
+    /// links per node
+    pub m: usize,
+    pub max_layers: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+}
+
+/// What `build_hnsw` uses.
+pub const HNSW_PARAMS: HnswParams = HnswParams { m: 16, max_layers: 16, ef_construction: 200, ef_search: 16 };
+
+fn generation(ids: &[[u8; 32]]) -> [u8; 32] {
+    mentat_store::blake32(&ids.concat())
+}
+
+/// Graph, data and ids files of a dump under `dir`.
+fn hnsw_files(dir: &Path, basename: &str) -> [std::path::PathBuf; 3] {
+    [
+        dir.join(format!("{basename}.hnsw.graph")),
+        dir.join(format!("{basename}.hnsw.data")),
+        dir.join(format!("{basename}.ids")),
+    ]
+}
+
+/// A ranked chunk resolved back to its file.
+#[derive(Serialize, Clone, Debug)]
+pub struct Hit {
+    pub chunk_id: String,
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+    /// cosine distance (0 = identical)
+    pub distance: f32,
+    /// ids of overlapping hits folded into this one by `merge_overlaps`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<String>,
+}
+
+/// A chunk with its text and, optionally, the chunks around it in the same file.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChunkDetail {
+    pub chunk_id: String,
+    pub path: String,
+    pub file_size: usize,
+    pub start: usize,
+    pub end: usize,
+    /// None when the file on disk no longer matches the indexed span
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<ChunkDetail>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<ChunkDetail>,
+}
+
+/// One page of a listing; `next` is the offset of the following page, if any.
+#[derive(Serialize, Clone, Debug)]
+pub struct Page<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub next: Option<usize>,
+    pub items: Vec<T>,
+}
+
+impl<T> Page<T> {
+    fn slice(all: Vec<T>, offset: usize, limit: usize) -> Self {
+        let total = all.len();
+        let items: Vec<T> = all.into_iter().skip(offset).take(limit).collect();
+        let end = offset + items.len();
+        Page { total, offset, next: (end < total).then_some(end), items }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FileEntry {
+    pub path: String,
+    pub file_hash: String,
+    pub size: usize,
+    pub chunks: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChunkEntry {
+    pub chunk_id: String,
+    pub start: usize,
+    pub end: usize,
+    pub embedded: bool,
 }
 
 pub struct Retriever {
-    db: Database,
-    hnsw: Option<Hnsw<'static, f32, DistCosine>>,
+    store: Store,
+    hnsw: Option<Graph>,
+    /// chunk ids in graph insertion order
+    ids: Vec<[u8; 32]>,
+    /// normalization the index was embedded with, applied to queries
+    norm: mentat_text::Normalize,
+    /// model the index was embedded with; queries are embedded the same way
+    model: String,
+    /// what the stored vectors are
+    vectors: VectorInfo,
+    /// content hashes search is limited to (e.g. files at a git revision)
+    only: Option<std::collections::HashSet<[u8; 32]>>,
+    /// search the index as it stood at this unix time, history included
+    as_of: Option<u64>,
+    /// searches past this return best-effort results
+    deadline: Option<Instant>,
+    /// a search stopped early at `deadline`
+    truncated: AtomicBool,
+    /// how `relevance` maps distances to 0..1
+    scale: relevance::Scale,
+    /// the index's near-neighbor similarity quantiles; empty if never sampled
+    quantiles: Vec<f32>,
+    /// stand-ins for paths in `to_nodes` output, when set
+    pseudonyms: Option<pseudonym::Pseudonyms>,
 }
 
 impl Retriever {
     pub fn open_default() -> Result<Self> {
-        let db = Database::builder().open("index/kv.redb")?;
-        Ok(Self { db, hnsw: None })
+        Self::open("index")
+    }
+
+    /// Open the store at `<dir>/kv.redb` read-side.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let store = Store::open_existing(dir)?;
+        let norm = match store.get_meta(mentat_store::META_NORMALIZE)? {
+            Some(b) => serde_json::from_slice(&b)?,
+            None => mentat_text::Normalize::none(),
+        };
+        let model = match store.get_meta(mentat_store::META_MODEL)? {
+            Some(b) => String::from_utf8_lossy(&b).into_owned(),
+            None => mentat_embedder::BGE.to_string(),
+        };
+        // indexes from before the record hold this build's layout
+        let vectors: VectorInfo = match store.get_meta(mentat_store::META_VECTORS)? {
+            Some(b) => serde_json::from_slice(&b).context("the index's vector record is unreadable; re-run `mentat index`")?,
+            None => VectorInfo::named(&model, "unknown"),
+        };
+        vectors.check()?;
+        let quantiles = match store.get_meta(mentat_store::META_RELEVANCE)? {
+            Some(b) => serde_json::from_slice(&b)?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            store,
+            hnsw: None,
+            ids: Vec::new(),
+            norm,
+            model,
+            vectors,
+            only: None,
+            as_of: None,
+            deadline: None,
+            truncated: AtomicBool::new(false),
+            scale: relevance::Scale::default(),
+            quantiles,
+            pseudonyms: None,
+        })
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
     }
 
-    pub fn build_hnsw(&mut self, out_path: &str) -> Result<()> {
-        let tx = self.db.begin_read()?;
-        let table = tx.open_table(EMBEDS)?;
+    /// What the stored vectors are.
+    pub fn vectors(&self) -> &VectorInfo {
+        &self.vectors
+    }
 
-        let mut data: Vec<Vec<f32>> = Vec::new();
-        let mut ids: Vec<String> = Vec::new();
+    /// Limit `search_query` to chunks of these file versions.
+    pub fn restrict_files(&mut self, files: std::collections::HashSet<[u8; 32]>) {
+        self.only = Some(files);
+    }
 
-        for item in table.iter()? {
-            let (key, val) = item?;
-            let key_bytes: &[u8] = key.value();
-            let id_hex = hex::encode(key_bytes);
-            let val_bytes: &[u8] = val.value();
+    /// Make `search_query` see the file versions live at `t`, including
+    /// replaced ones still in the history table. Their text is gone from
+    /// disk, so they never satisfy phrase or negated-word clauses.
+    pub fn set_as_of(&mut self, t: u64) {
+        self.as_of = Some(t);
+    }
 
-            // Convert bytes to Vec<f32>
-            let float_slice = unsafe {
-                std::slice::from_raw_parts(val_bytes.as_ptr() as *const f32, D)
-            };
-            let v = float_slice.to_vec();
+    pub fn set_relevance(&mut self, scale: relevance::Scale) {
+        self.scale = scale;
+    }
 
-            data.push(v);
-            ids.push(id_hex);
+    /// Show pseudonyms instead of paths in nodes (see `pseudonym`).
+    pub fn set_pseudonymize(&mut self) -> Result<()> {
+        self.pseudonyms = Some(pseudonym::Pseudonyms::open(&self.store)?);
+        Ok(())
+    }
+
+    /// `path`, or its pseudonym when `set_pseudonymize` was called.
+    pub fn shown_path(&self, path: &str) -> Result<String> {
+        match &self.pseudonyms {
+            Some(p) => p.name(&self.store, path),
+            None => Ok(path.to_string()),
         }
+    }
 
-        println!("Building HNSW index for {} vectors...", data.len());
+    /// Owners of the file a hit is from; None when unknown, as for hits
+    /// from history.
+    pub fn hit_owners(&self, h: &Hit) -> Result<Option<mentat_store::Owners>> {
+        let Some(id) = hex::decode(&h.chunk_id).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) else { return Ok(None) };
+        match self.store.get_chunk(id)? {
+            Some(c) => self.store.get_owners(c.file_hash),
+            None => Ok(None),
+        }
+    }
 
-        let ef_c = 200;
-        let m = 16;
-        let dist = DistCosine {};
-        let mut hnsw = Hnsw::<f32, DistCosine>::new(m, data.len(), 16, ef_c, dist);
+    /// 0..1 relevance of a hit `distance` away, on the configured scale.
+    pub fn relevance(&self, distance: f32) -> f32 {
+        relevance::relevance(distance, self.scale, &self.quantiles)
+    }
 
-        for (i, v) in data.iter().enumerate() {
-            hnsw.insert((v, i));
+    /// Give searches until `budget` from now.
+    pub fn set_budget(&mut self, budget: Duration) {
+        self.deadline = Some(Instant::now() + budget);
+        self.truncated.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether a search cut its work short at the budget's deadline.
+    pub fn truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /// Past the deadline; marks the search truncated.
+    fn over_budget(&self) -> bool {
+        let over = self.deadline.is_some_and(|d| Instant::now() >= d);
+        if over {
+            self.truncated.store(true, Ordering::Relaxed);
         }
-        hnsw.set_searching_mode(true);
+        over
+    }
+
+    /// Build the graph and save it under `base` (e.g. `index/embeds`).
+    pub fn build_hnsw(&mut self, base: &str) -> Result<()> {
+        self.build_hnsw_with(base, &mut |_, _| true)
+    }
+
+    /// `build_hnsw`, calling `progress(points inserted, total)` as it goes;
+    /// when that returns false the build stops and the saved graph, if any,
+    /// is left as it was.
+    pub fn build_hnsw_with(&mut self, base: &str, progress: &mut dyn FnMut(usize, usize) -> bool) -> Result<()> {
+        let embeds = self.valid_embeds()?;
+
+        println!("Building HNSW index for {} vectors...", embeds.len());
+        let hnsw = Graph::build(&embeds, &HNSW_PARAMS, progress)?;
+        let ids: Vec<[u8; 32]> = embeds.iter().map(|(id, _)| *id).collect();
 
-        fs::create_dir_all(Path::new(out_path).parent().unwrap())?;
-        let dir_path = Path::new(out_path).parent().unwrap();
-        let file_name = Path::new(out_path).file_name().unwrap().to_str().unwrap();
-        hnsw.file_dump(dir_path, file_name)?;
-        let hdr = HnswHeader { n: data.len(), d: D };
-        fs::write(format!("{}.hdr", out_path), bincode::serialize(&hdr)?)?;
-        println!("Saved HNSW index to {}/{}.hnsw", dir_path.display(), file_name);
+        let dir_path = Path::new(base).parent().unwrap();
+        let file_name = Path::new(base).file_name().unwrap().to_str().unwrap();
+        fs::create_dir_all(dir_path)?;
+        let hdr_path = format!("{base}.hdr");
+        if Path::new(&hdr_path).exists() {
+            fs::remove_file(&hdr_path)?;
+        }
+        let basename = hnsw.dump(dir_path, file_name)?;
+        let files = hnsw_files(dir_path, &basename);
+        fs::write(&files[2], ids.concat())?;
+        let checksums = files.iter().map(|f| Ok(mentat_store::blake32(&fs::read(f)?))).collect::<Result<Vec<_>>>()?;
+        let hdr = HnswHeader {
+            n: ids.len(),
+            d: D,
+            vectors: self.vectors.clone(),
+            backend: graph::BACKEND.into(),
+            params: HNSW_PARAMS,
+            version: env!("CARGO_PKG_VERSION").into(),
+            basename,
+            generation: generation(&ids),
+            checksums,
+        };
+        fs::write(&hdr_path, bincode::serialize(&hdr)?)?;
+        println!("Saved HNSW index to {}", files[0].display());
         self.hnsw = Some(hnsw);
+        self.ids = ids;
+        Ok(())
+    }
+
+    /// Build the graph in memory only.
+    pub fn build_hnsw_in_memory(&mut self) -> Result<()> {
+        let embeds = self.valid_embeds()?;
+        self.hnsw = Some(Graph::build(&embeds, &HNSW_PARAMS, &mut |_, _| true)?);
+        self.ids = embeds.iter().map(|(id, _)| *id).collect();
         Ok(())
     }
 
-    pub fn load_hnsw(&mut self, _path: &str) -> Result<()> {
-        // For now, rebuild the index from ReDB data
-        // TODO: implement proper serialization when hnsw_rs supports it better
-        self.build_hnsw_internal()?;
+    /// Load the graph saved under `base`. Errors, leaving search exact, when
+    /// the files are missing, fail their checksums, were written by another
+    /// ANN backend, or were built from a different set or kind of vectors
+    /// than the store now holds.
+    pub fn load_hnsw(&mut self, base: &str) -> Result<()> {
+        let hdr_path = format!("{base}.hdr");
+        anyhow::ensure!(Path::new(&hdr_path).exists(), "no HNSW graph at {base} (run `mentat build-hnsw`)");
+        let hdr: HnswHeader = bincode::deserialize(&fs::read(&hdr_path)?)
+            .with_context(|| format!("{hdr_path} is unreadable or from an older mentat; run `mentat build-hnsw`"))?;
+        anyhow::ensure!(hdr.d == D, "{hdr_path} is for {}-dim vectors, not {D}", hdr.d);
+        anyhow::ensure!(
+            hdr.backend == graph::BACKEND,
+            "{hdr_path} was written by {} (mentat-retriever {}), this mentat searches with {}; run `mentat build-hnsw`",
+            hdr.backend,
+            hdr.version,
+            graph::BACKEND
+        );
+        anyhow::ensure!(
+            hdr.vectors.same_space(&self.vectors),
+            "{hdr_path} was built over {} {}-dim {} vectors, the index now holds {} {}-dim {} ones; run `mentat build-hnsw`",
+            hdr.vectors.model,
+            hdr.vectors.dim,
+            hdr.vectors.metric,
+            self.vectors.model,
+            self.vectors.dim,
+            self.vectors.metric
+        );
+        let dir = Path::new(base).parent().unwrap();
+        let files = hnsw_files(dir, &hdr.basename);
+        anyhow::ensure!(hdr.checksums.len() == files.len(), "{hdr_path} lists {} checksums", hdr.checksums.len());
+        for (f, sum) in files.iter().zip(&hdr.checksums) {
+            let data = fs::read(f).with_context(|| format!("reading {}", f.display()))?;
+            anyhow::ensure!(mentat_store::blake32(&data) == *sum, "{} is truncated or corrupt", f.display());
+        }
+        let ids: Vec<[u8; 32]> = fs::read(&files[2])?.chunks_exact(32).map(|c| c.try_into().expect("32 bytes")).collect();
+        let current: Vec<[u8; 32]> = self.valid_embeds()?.into_iter().map(|(id, _)| id).collect();
+        anyhow::ensure!(
+            ids.len() == hdr.n && generation(&ids) == hdr.generation && generation(&current) == hdr.generation,
+            "HNSW graph is out of date: the index changed since it was built"
+        );
+        self.hnsw = Some(Graph::load(dir, &hdr.basename, &hdr.params)?);
+        self.ids = ids;
         Ok(())
     }
 
-    fn build_hnsw_internal(&mut self) -> Result<()> {
-        let tx = self.db.begin_read()?;
-        let table = tx.open_table(EMBEDS)?;
+    pub fn has_hnsw(&self) -> bool {
+        self.hnsw.is_some()
+    }
 
-        let mut data: Vec<Vec<f32>> = Vec::new();
+    /// Stored vectors minus NaN/zero-norm ones, which would poison cosine distances.
+    fn valid_embeds(&self) -> Result<Vec<([u8; 32], [f32; D])>> {
+        let mut embeds = self.store.embeds()?;
+        let before = embeds.len();
+        embeds.retain(|(_, v)| mentat_embedder::invalid_reason(v).is_none());
+        if embeds.len() < before {
+            eprintln!("[retriever] skipping {} invalid vectors (run `mentat verify`)", before - embeds.len());
+        }
+        Ok(embeds)
+    }
 
-        for item in table.iter()? {
-            let (_, val) = item?;
-            let val_bytes: &[u8] = val.value();
+    /// Query via the HNSW graph (must be built or loaded first).
+    pub fn search(&self, query: &str, topk: usize) -> Result<Vec<Hit>> {
+        let q = self.embed_query(query)?;
+        self.search_vec(&q, topk)
+    }
 
-            let float_slice = unsafe {
-                std::slice::from_raw_parts(val_bytes.as_ptr() as *const f32, D)
+    /// Embed a query under the same normalization as the indexed chunks.
+    pub fn embed_query(&self, query: &str) -> Result<[f32; D]> {
+        let mut out = self.embed_queries(&[query])?;
+        Ok(out.pop().expect("one query, one vector"))
+    }
+
+    /// `embed_query` for many queries in one model batch.
+    pub fn embed_queries(&self, queries: &[&str]) -> Result<Vec<[f32; D]>> {
+        let texts: Vec<String> = queries.iter().map(|q| self.norm.apply(q)).collect();
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        mentat_embedder::embed_batch_as(self.embed_mode()?, &refs, mentat_embedder::MAX_LEN)
+    }
+
+    /// How queries must be embedded to compare with the index. Vectors from
+    /// a model this build lacks are meaningless against it.
+    pub fn embed_mode(&self) -> Result<mentat_embedder::Mode> {
+        mentat_embedder::Mode::of(&self.model).ok_or_else(|| {
+            anyhow::anyhow!("the index was embedded with {} but this mentat embeds with {}; re-run `mentat index`", self.model, mentat_embedder::MODEL)
+        })
+    }
+
+    /// Query the loaded graph with a precomputed embedding.
+    pub fn search_vec(&self, q: &[f32], topk: usize) -> Result<Vec<Hit>> {
+        let h = self.hnsw.as_ref().ok_or_else(|| anyhow::anyhow!("HNSW not loaded"))?;
+        h.search(q, topk).into_iter().map(|(i, d)| self.resolve(self.ids[i], d)).collect()
+    }
+
+    /// Brute-force cosine scan over every stored vector.
+    pub fn search_exact(&self, query: &str, topk: usize) -> Result<Vec<Hit>> {
+        let q = self.embed_query(query)?;
+        self.search_exact_vec(&q, topk)
+    }
+
+    pub fn search_exact_vec(&self, q: &[f32], topk: usize) -> Result<Vec<Hit>> {
+        self.nearest(q, topk)?.into_iter().map(|(id, d)| self.resolve(id, d)).collect()
+    }
+
+    /// The `topk` closest chunk ids by cosine distance, closest first.
+    fn nearest(&self, q: &[f32], topk: usize) -> Result<Vec<([u8; 32], f32)>> {
+        let mut scored: Vec<([u8; 32], f32)> = self
+            .valid_embeds()?
+            .iter()
+            .map(|(id, v)| (*id, cosine_distance(q, v)))
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        scored.truncate(topk);
+        Ok(scored)
+    }
+
+    /// Search with the query syntax in [`query`]. Plain word queries take the
+    /// usual path (HNSW if loaded, else exact); anything with filters (or a
+    /// `restrict_files` set) scans the matching files exactly and checks
+    /// phrases against chunk text.
+    pub fn search_query(&self, q: &query::Query, topk: usize) -> Result<Vec<Hit>> {
+        let text = q.semantic_text();
+        if text.trim().is_empty() {
+            anyhow::bail!("query has no search words or phrases");
+        }
+        self.search_query_vec(q, &self.embed_query(&text)?, topk)
+    }
+
+    /// `search_query` with `q.semantic_text()` already embedded.
+    pub fn search_query_vec(&self, q: &query::Query, qv: &[f32; D], topk: usize) -> Result<Vec<Hit>> {
+        if !q.has_filters() && self.only.is_none() && self.as_of.is_none() {
+            return match self.hnsw {
+                Some(_) => self.search_vec(qv, topk),
+                None => self.search_exact_vec(qv, topk),
+            };
+        }
+        let filter = query::FileFilter::new(q)?;
+        let mut allowed = std::collections::HashSet::new();
+        for (h, f) in self.store.files()? {
+            let license = self.store.get_license(h)?;
+            let owners = self.store.get_owners(h)?.map(|o| [o.codeowners, o.authors].concat()).unwrap_or_default();
+            let facts = query::FileFacts { path: &f.path, mtime: self.store.get_mtime(h)?, license: license.as_deref(), owners: &owners };
+            let live = match self.as_of {
+                Some(t) => self.store.get_indexed_at(h)?.is_none_or(|at| at <= t),
+                None => true,
             };
-            let v = float_slice.to_vec();
-            data.push(v);
+            if live && filter.matches(&facts) && self.only.as_ref().is_none_or(|o| o.contains(&h)) {
+                allowed.insert(h);
+            }
+        }
+        let chunks: std::collections::HashMap<[u8; 32], mentat_store::ChunkMeta> =
+            self.store.chunks()?.into_iter().filter(|(_, c)| allowed.contains(&c.file_hash)).collect();
+        let mut scored: Vec<([u8; 32], f32)> = self
+            .valid_embeds()?
+            .iter()
+            .filter(|(id, _)| chunks.contains_key(id))
+            .map(|(id, v)| (*id, cosine_distance(qv, v)))
+            .collect();
+        let mut retired = std::collections::HashMap::new();
+        if let Some(t) = self.as_of {
+            for r in self.store.history()? {
+                let alive = r.indexed_at.is_none_or(|at| at <= t) && t < r.retired_at;
+                let facts = query::FileFacts { path: &r.meta.path, mtime: r.mtime, license: None, owners: &[] };
+                if !alive || !filter.matches(&facts) || self.only.as_ref().is_some_and(|o| !o.contains(&r.file_hash)) {
+                    continue;
+                }
+                for c in r.chunks {
+                    let Some(v) = c.embed.filter(|v| mentat_embedder::invalid_reason(v).is_none()) else { continue };
+                    scored.push((c.id, cosine_distance(qv, &v)));
+                    retired.insert(c.id, (r.meta.path.clone(), c.meta));
+                }
+            }
         }
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let needs_text = q.text_clauses().next().is_some();
+        let mut out = Vec::new();
+        for (id, d) in scored {
+            if out.len() == topk {
+                break;
+            }
+            if let Some((path, c)) = retired.remove(&id) {
+                if !needs_text {
+                    out.push(Hit { chunk_id: hex::encode(id), path, start: c.start, end: c.end, distance: d, merged: Vec::new() });
+                }
+                continue;
+            }
+            if needs_text {
+                if self.over_budget() {
+                    break;
+                }
+                let Some(t) = self.store.chunk_text(&chunks[&id])? else { continue };
+                if !query::text_matches(q, &t) {
+                    continue;
+                }
+            }
+            out.push(self.resolve(id, d)?);
+        }
+        Ok(out)
+    }
 
-        let ef_c = 200;
-        let m = 16;
-        let dist = DistCosine {};
-        let mut hnsw = Hnsw::<f32, DistCosine>::new(m, data.len(), 16, ef_c, dist);
+    /// Fetch a chunk's text plus up to `expand` neighbors on each side, by offset.
+    pub fn chunk(&self, id: [u8; 32], expand: usize) -> Result<Option<ChunkDetail>> {
+        let Some(meta) = self.store.get_chunk(id)? else { return Ok(None) };
+        let file = self.store.get_file(meta.file_hash)?;
+        let (path, file_size) = match &file {
+            Some(f) => (f.path.clone(), f.size),
+            None => (format!("<missing file {}>", hex::encode(meta.file_hash)), 0),
+        };
+        let detail = |id: [u8; 32], m: &mentat_store::ChunkMeta| -> Result<ChunkDetail> {
+            let text = if file.is_some() { self.store.chunk_text(m)? } else { None };
+            Ok(ChunkDetail {
+                chunk_id: hex::encode(id),
+                path: path.clone(),
+                file_size,
+                start: m.start,
+                end: m.end,
+                text,
+                before: Vec::new(),
+                after: Vec::new(),
+            })
+        };
+        let mut out = detail(id, &meta)?;
+        if expand > 0 {
+            let mut siblings = self.store.file_chunks(meta.file_hash)?;
+            siblings.sort_by_key(|(_, c)| (c.start, c.end));
+            let pos = siblings.iter().position(|(sid, _)| *sid == id).unwrap_or(0);
+            for (sid, c) in &siblings[pos.saturating_sub(expand)..pos] {
+                out.before.push(detail(*sid, c)?);
+            }
+            for (sid, c) in siblings.iter().skip(pos + 1).take(expand) {
+                out.after.push(detail(*sid, c)?);
+            }
+        }
+        Ok(Some(out))
+    }
 
-        for (i, v) in data.iter().enumerate() {
-            hnsw.insert((v, i));
+    /// Citation for a chunk, or None if there is no such chunk or its text
+    /// is no longer on disk.
+    pub fn cite(&self, id: [u8; 32]) -> Result<Option<cite::Citation>> {
+        let Some(meta) = self.store.get_chunk(id)? else { return Ok(None) };
+        let Some(file) = self.store.get_file(meta.file_hash)? else { return Ok(None) };
+        let Some(text) = self.store.chunk_text(&meta)? else { return Ok(None) };
+        Ok(Some(cite::Citation::new(&file.path, meta.start, meta.end, &text)))
+    }
+
+    /// Best current chunk for a citation: the cited path first, then every
+    /// file, taking the closest fingerprint within `cite::MAX_DISTANCE`.
+    pub fn resolve_citation(&self, c: &cite::Citation) -> Result<Option<cite::Resolved>> {
+        let files = self.store.files()?;
+        let chunks = self.store.chunks()?;
+        let same_path: std::collections::HashSet<[u8; 32]> =
+            files.iter().filter(|(_, f)| f.path == c.path).map(|(h, _)| *h).collect();
+        let paths: std::collections::HashMap<[u8; 32], &str> = files.iter().map(|(h, f)| (*h, f.path.as_str())).collect();
+        for pass_same_path in [true, false] {
+            let mut best: Option<cite::Resolved> = None;
+            for (id, m) in &chunks {
+                if same_path.contains(&m.file_hash) != pass_same_path {
+                    continue;
+                }
+                let Some(path) = paths.get(&m.file_hash) else { continue };
+                let Some(text) = self.store.chunk_text(m)? else { continue };
+                let distance = cite::distance(c.fingerprint, cite::fingerprint(&text));
+                let in_place = pass_same_path && m.start == c.start && m.end == c.end;
+                // ties go to the cited range, then the nearest offset
+                let key = |r: &cite::Resolved| (r.distance, !r.in_place, r.start.abs_diff(c.start));
+                let cand = cite::Resolved { chunk_id: hex::encode(id), path: path.to_string(), start: m.start, end: m.end, distance, in_place };
+                if distance <= cite::MAX_DISTANCE && best.as_ref().is_none_or(|b| key(&cand) < key(b)) {
+                    best = Some(cand);
+                }
+            }
+            if best.is_some() {
+                return Ok(best);
+            }
         }
-        hnsw.set_searching_mode(true);
+        Ok(None)
+    }
 
-        self.hnsw = Some(hnsw);
-        Ok(())
+    /// Check one claim from an answer: citations must resolve, paths (and
+    /// lines) must exist, snippets must occur in one of the nearest chunks.
+    pub fn check_claim(&self, claim: &cite::Claim) -> Result<cite::Check> {
+        let check = |score: f32, location: Option<String>| cite::Check {
+            claim: claim.clone(),
+            found: score >= cite::MIN_SCORE,
+            score,
+            location,
+        };
+        match claim {
+            cite::Claim::Citation { citation } => Ok(match self.resolve_citation(&citation.parse()?)? {
+                Some(r) => check(1.0 - r.distance as f32 / 64.0, Some(format!("{}:{}-{}", r.path, r.start, r.end))),
+                None => check(0.0, None),
+            }),
+            cite::Claim::Path { path, line } => {
+                let Some((fhash, _)) = self.store.files()?.into_iter().find(|(_, f)| &f.path == path) else {
+                    return Ok(check(0.0, None));
+                };
+                let in_file = match line {
+                    Some(n) => {
+                        let data = self.store.read_file(fhash, path)?.unwrap_or_default();
+                        let lines = String::from_utf8_lossy(&data).lines().count();
+                        (1..=lines).contains(n)
+                    }
+                    None => true,
+                };
+                Ok(check(if in_file { 1.0 } else { 0.0 }, Some(path.clone())))
+            }
+            cite::Claim::Snippet { text } => {
+                let qv = self.embed_query(text)?;
+                let mut best = check(0.0, None);
+                for (id, _) in self.nearest(&qv, SNIPPET_CANDIDATES)? {
+                    let Some(meta) = self.store.get_chunk(id)? else { continue };
+                    let Some(chunk) = self.store.chunk_text(&meta)? else { continue };
+                    let score = cite::containment(text, &chunk);
+                    if score > best.score {
+                        let path = self.store.get_file(meta.file_hash)?.map(|f| f.path).unwrap_or_default();
+                        best = check(score, Some(format!("{path}:{}-{}", meta.start, meta.end)));
+                    }
+                }
+                Ok(best)
+            }
+        }
     }
 
-    pub fn search(&self, query: &str, topk: usize) -> Result<Vec<(usize, f32)>> {
-        let q = embed_text(query)?;
-        let q_vec: Vec<f32> = q.to_vec();
-        let h = self.hnsw.as_ref().ok_or_else(|| anyhow::anyhow!("HNSW not loaded"))?;
-        let res = h.search(&q_vec, topk, 16);
-        let hits: Vec<(usize, f32)> = res.iter().map(|ne| (ne.d_id, ne.distance)).collect();
-        Ok(hits)
+    /// Indexed files whose path starts with `prefix`, in path order.
+    pub fn list_files(&self, prefix: &str, offset: usize, limit: usize) -> Result<Page<FileEntry>> {
+        let mut counts: std::collections::HashMap<[u8; 32], usize> = std::collections::HashMap::new();
+        for (_, c) in self.store.chunks()? {
+            *counts.entry(c.file_hash).or_default() += 1;
+        }
+        let mut all: Vec<FileEntry> = self
+            .store
+            .files()?
+            .into_iter()
+            .filter(|(_, f)| f.path.starts_with(prefix))
+            .map(|(h, f)| FileEntry {
+                chunks: counts.get(&h).copied().unwrap_or(0),
+                path: f.path,
+                file_hash: hex::encode(h),
+                size: f.size,
+            })
+            .collect();
+        all.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Page::slice(all, offset, limit))
+    }
+
+    /// Chunks of the file indexed at `path`, in offset order. None if the path isn't indexed.
+    pub fn list_chunks(&self, path: &str, offset: usize, limit: usize) -> Result<Option<Page<ChunkEntry>>> {
+        let Some((fhash, _)) = self.store.files()?.into_iter().find(|(_, f)| f.path == path) else {
+            return Ok(None);
+        };
+        let mut chunks = self.store.file_chunks(fhash)?;
+        chunks.sort_by_key(|(_, c)| (c.start, c.end));
+        let all = chunks
+            .into_iter()
+            .map(|(id, c)| {
+                Ok(ChunkEntry { chunk_id: hex::encode(id), start: c.start, end: c.end, embedded: self.store.has_embed(id)? })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Page::slice(all, offset, limit)))
+    }
+
+    fn resolve(&self, id: [u8; 32], distance: f32) -> Result<Hit> {
+        let chunk = self
+            .store
+            .get_chunk(id)?
+            .ok_or_else(|| anyhow::anyhow!("embed {} has no chunk row", hex::encode(id)))?;
+        let path = match self.store.get_file(chunk.file_hash)? {
+            Some(f) => f.path,
+            None => format!("<missing file {}>", hex::encode(chunk.file_hash)),
+        };
+        Ok(Hit { chunk_id: hex::encode(id), path, start: chunk.start, end: chunk.end, distance, merged: Vec::new() })
+    }
+}
+
+/// Chunks a quoted snippet is compared against, nearest first.
+const SNIPPET_CANDIDATES: usize = 10;
+
+/// Fold hits whose byte ranges intersect in the same file into one, keeping
+/// the best-ranked hit's id and distance and the union of the ranges.
+/// Input is assumed ranked; output stays in rank order.
+pub fn merge_overlaps(hits: Vec<Hit>) -> Vec<Hit> {
+    let mut out: Vec<Hit> = Vec::with_capacity(hits.len());
+    for mut cur in hits {
+        // the grown range can bridge hits that were disjoint so far, so repeat
+        while let Some(i) = out.iter().position(|o| o.path == cur.path && o.start < cur.end && cur.start < o.end) {
+            let o = out.remove(i);
+            let (mut win, lose) = if o.distance <= cur.distance { (o, cur) } else { (cur, o) };
+            win.start = win.start.min(lose.start);
+            win.end = win.end.max(lose.end);
+            win.merged.push(lose.chunk_id);
+            win.merged.extend(lose.merged);
+            cur = win;
+        }
+        let at = out.partition_point(|o| o.distance <= cur.distance);
+        out.insert(at, cur);
     }
+    out
+}
+
+/// 1 - cos(a, b), matching DistCosine.
+pub fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let na = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let nb = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    1.0 - dot / (na * nb).max(1e-12)
 }
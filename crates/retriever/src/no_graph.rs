@@ -0,0 +1,32 @@
+//! Stand-in for the HNSW graph in builds without the `hnsw` feature: a graph
+//! can be neither built nor loaded, so `Retriever` searches exact.
+
+use crate::HnswParams;
+use anyhow::Result;
+use mentat_embedder::D;
+use std::path::Path;
+
+const NO_HNSW: &str = "this mentat was built without the `hnsw` feature; search is exact";
+
+pub const BACKEND: &str = "none";
+
+/// Uninhabited: `build` and `load` always fail.
+pub enum Graph {}
+
+impl Graph {
+    pub fn build(_data: &[([u8; 32], [f32; D])], _params: &HnswParams, _progress: &mut dyn FnMut(usize, usize) -> bool) -> Result<Self> {
+        anyhow::bail!(NO_HNSW)
+    }
+
+    pub fn dump(&self, _dir: &Path, _name: &str) -> Result<String> {
+        match *self {}
+    }
+
+    pub fn load(_dir: &Path, _basename: &str, _params: &HnswParams) -> Result<Self> {
+        anyhow::bail!(NO_HNSW)
+    }
+
+    pub fn search(&self, _q: &[f32], _topk: usize) -> Vec<(usize, f32)> {
+        match *self {}
+    }
+}
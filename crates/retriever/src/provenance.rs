@@ -0,0 +1,102 @@
+//! Likely copies of a file's code in a reference index, e.g. third-party
+//! sources indexed with `mentat index <dir> --into reference`.
+//!
+//! The file is cut the way the reference was cut, each span is embedded and
+//! matched against its nearest reference chunk, and runs of adjacent spans
+//! matching the same reference file are joined into one region. `verbatim`
+//! is the shingle containment of the text in the match, which tells copied
+//! code from code that merely does the same thing.
+
+use crate::{cite, cosine_distance, Retriever};
+use anyhow::Result;
+use serde::Serialize;
+
+/// Default cosine similarity from which a span counts as a likely copy.
+pub const MIN_SIMILARITY: f32 = 0.92;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Region {
+    /// byte range in the checked file
+    pub start: usize,
+    pub end: usize,
+    /// 1-based lines of that range
+    pub first_line: usize,
+    pub last_line: usize,
+    pub ref_path: String,
+    pub ref_start: usize,
+    pub ref_end: usize,
+    /// best cosine similarity among the joined spans
+    pub similarity: f32,
+    /// share of the region's word 3-shingles found in the matched reference text
+    pub verbatim: f32,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub path: String,
+    pub spans: usize,
+    pub regions: Vec<Region>,
+}
+
+/// Match `data` (the contents of `path`) against every chunk of `reference`.
+pub fn check(reference: &Retriever, path: &str, data: &[u8], min_similarity: f32) -> Result<Report> {
+    let store = reference.store();
+    let chunker = match store.get_meta(mentat_store::META_CHUNKER)? {
+        Some(b) => serde_json::from_slice(&b)?,
+        None => mentat_chunker::ChunkerConfig::default(),
+    };
+    let spans = mentat_chunker::chunk_bytes_with(path, data, &chunker);
+    let embeds = reference.valid_embeds()?;
+    let mut rep = Report { path: path.to_string(), spans: spans.len(), regions: Vec::new() };
+    let mut matches: Vec<Region> = Vec::new();
+    for s in &spans {
+        let qv = reference.embed_query(&String::from_utf8_lossy(&data[s.start..s.end]))?;
+        let Some((id, distance)) =
+            embeds.iter().map(|(id, v)| (*id, cosine_distance(&qv, v))).min_by(|a, b| a.1.total_cmp(&b.1))
+        else {
+            break;
+        };
+        let similarity = 1.0 - distance;
+        if similarity < min_similarity {
+            continue;
+        }
+        let Some(meta) = store.get_chunk(id)? else { continue };
+        let Some(file) = store.get_file(meta.file_hash)? else { continue };
+        matches.push(Region {
+            start: s.start,
+            end: s.end,
+            first_line: 0,
+            last_line: 0,
+            ref_path: file.path,
+            ref_start: meta.start,
+            ref_end: meta.end,
+            similarity,
+            verbatim: 0.0,
+        });
+    }
+    // spans come in offset order; join overlapping or touching ones per reference file
+    for m in matches {
+        match rep.regions.last_mut() {
+            Some(r) if r.ref_path == m.ref_path && m.start <= r.end => {
+                r.end = r.end.max(m.end);
+                r.ref_start = r.ref_start.min(m.ref_start);
+                r.ref_end = r.ref_end.max(m.ref_end);
+                r.similarity = r.similarity.max(m.similarity);
+            }
+            _ => rep.regions.push(m),
+        }
+    }
+    let hashes: std::collections::HashMap<String, [u8; 32]> = store.files()?.into_iter().map(|(h, f)| (f.path, h)).collect();
+    let line = |off: usize| 1 + data[..off].iter().filter(|&&b| b == b'\n').count();
+    for r in &mut rep.regions {
+        r.first_line = line(r.start);
+        r.last_line = line(r.end.saturating_sub(1).max(r.start));
+        let Some(h) = hashes.get(&r.ref_path) else { continue };
+        let ref_data = store.read_file(*h, &r.ref_path)?.unwrap_or_default();
+        if let Some(ref_text) = ref_data.get(r.ref_start..r.ref_end) {
+            let text = String::from_utf8_lossy(&data[r.start..r.end]);
+            r.verbatim = cite::containment(&text, &String::from_utf8_lossy(ref_text));
+        }
+    }
+    Ok(rep)
+}
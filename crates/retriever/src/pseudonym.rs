@@ -0,0 +1,53 @@
+//! `--pseudonymize`: stable stand-ins for file paths in exported data and
+//! search output, so content can be shared without the project layout.
+//!
+//! A pseudonym is `p-` plus 12 hex digits of a keyed hash of the path,
+//! keeping the extension (`src/auth/token.rs` -> `p-3fa9c2e1b04d.rs`).
+//! The key is a salt generated once per index under `META_PSEUDONYM_SALT`,
+//! so the same path always maps to the same pseudonym but it cannot be
+//! guessed from outside. Every pseudonym handed out is recorded in the
+//! store; `mentat reveal <pseudonym>` maps it back.
+
+use anyhow::Result;
+use mentat_store::Store;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+pub struct Pseudonyms {
+    salt: [u8; 32],
+    /// pseudonyms already recorded this session
+    recorded: Mutex<HashSet<String>>,
+}
+
+impl Pseudonyms {
+    /// Load the index's salt, creating it on first use.
+    pub fn open(store: &Store) -> Result<Self> {
+        let salt = match store.get_meta(mentat_store::META_PSEUDONYM_SALT)? {
+            Some(b) if b.len() == 32 => b.try_into().expect("32 bytes"),
+            _ => {
+                let seed = format!("{:?} {} {:?}", std::time::SystemTime::now(), std::process::id(), store.get_meta(mentat_store::META_ROOT)?);
+                let salt = mentat_store::blake32(seed.as_bytes());
+                store.put_meta(mentat_store::META_PSEUDONYM_SALT, &salt)?;
+                salt
+            }
+        };
+        Ok(Self { salt, recorded: Mutex::new(HashSet::new()) })
+    }
+
+    /// The pseudonym for `path`, recorded so it can be revealed later.
+    pub fn name(&self, store: &Store, path: &str) -> Result<String> {
+        let mut keyed = self.salt.to_vec();
+        keyed.extend_from_slice(path.as_bytes());
+        let hash = hex::encode(&mentat_store::blake32(&keyed)[..6]);
+        let file = path.rsplit('/').next().unwrap_or(path);
+        let name = match file.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => format!("p-{hash}.{ext}"),
+            _ => format!("p-{hash}"),
+        };
+        let mut recorded = self.recorded.lock().expect("pseudonym cache poisoned");
+        if recorded.insert(name.clone()) {
+            store.put_pseudonym(&name, path)?;
+        }
+        Ok(name)
+    }
+}
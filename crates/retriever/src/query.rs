@@ -0,0 +1,469 @@
+//! Query syntax for `mentat search`:
+//!
+//! ```text
+//! path:src/** lang:rust after:2024-01-01 "exact phrase" -"todo" concurrency bug
+//! ```
+//!
+//! - bare words are embedded as the semantic query
+//! - `"..."` phrases are embedded too, and each must occur in the chunk (case-insensitive)
+//! - `path:` glob (or plain prefix), `lang:` language or extension,
+//!   `after:` / `before:` file mtime as YYYY-MM-DD, `license:` SPDX id detected
+//!   at index time (`license:none` for files without one), `owner:` a
+//!   CODEOWNERS owner (`owner:@alice`, `owner:@org/team`) or commit author
+//!   (`owner:alice@example.com`, or just `owner:alice`); `owner:none` for
+//!   files with neither
+//! - clauses AND together; `-` negates a clause; `a,b` or `key:a OR key:b`
+//!   matches either value
+//! - a negated bare word (`-tests`) drops chunks containing it and files with
+//!   it as a path segment or name part (`tests/`, `vendor/`, `foo_test.go`)
+//!
+//! A word with an unknown `key:` prefix (say `std::sync`) is just a word.
+//! `@name` expands a saved alias before parsing.
+
+use anyhow::Result;
+use globset::{Glob, GlobMatcher};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Path,
+    Lang,
+    After,
+    Before,
+    License,
+    Owner,
+    Phrase,
+    Word,
+}
+
+#[derive(Debug, Clone)]
+pub struct Clause {
+    pub field: Field,
+    /// alternatives; the clause holds if any matches
+    pub values: Vec<String>,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+impl Query {
+    /// Text to embed: positive words and phrases in their original order.
+    pub fn semantic_text(&self) -> String {
+        let parts: Vec<&str> = self
+            .clauses
+            .iter()
+            .filter(|c| !c.negated && matches!(c.field, Field::Word | Field::Phrase))
+            .flat_map(|c| c.values.iter().map(String::as_str))
+            .collect();
+        parts.join(" ")
+    }
+
+    /// Anything beyond plain words, i.e. needs the filtered search path.
+    pub fn has_filters(&self) -> bool {
+        self.clauses.iter().any(|c| c.negated || c.field != Field::Word)
+    }
+
+    pub fn file_clauses(&self) -> impl Iterator<Item = &Clause> {
+        self.clauses.iter().filter(|c| {
+            matches!(c.field, Field::Path | Field::Lang | Field::After | Field::Before | Field::License | Field::Owner)
+                || (c.negated && c.field == Field::Word)
+        })
+    }
+
+    pub fn text_clauses(&self) -> impl Iterator<Item = &Clause> {
+        self.clauses.iter().filter(|c| c.field == Field::Phrase || (c.negated && c.field == Field::Word))
+    }
+}
+
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Replace `@name` words (outside quotes) with their alias text, recursively.
+pub fn expand_aliases(input: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    expand_at(input, lookup, &mut Vec::new())
+}
+
+fn expand_at(input: &str, lookup: &dyn Fn(&str) -> Option<String>, stack: &mut Vec<String>) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut in_quote = false;
+    for (i, word) in input.split(' ').enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let name = word.strip_prefix('@').filter(|n| !n.is_empty() && !in_quote);
+        in_quote ^= word.matches('"').count() % 2 == 1;
+        let Some(name) = name else {
+            out.push_str(word);
+            continue;
+        };
+        if stack.iter().any(|s| s == name) {
+            anyhow::bail!("alias @{name} refers to itself");
+        }
+        if stack.len() >= MAX_ALIAS_DEPTH {
+            anyhow::bail!("aliases nest deeper than {MAX_ALIAS_DEPTH}");
+        }
+        let body = lookup(name).ok_or_else(|| anyhow::anyhow!("unknown alias @{name}"))?;
+        stack.push(name.to_string());
+        out.push_str(&expand_at(&body, lookup, stack)?);
+        stack.pop();
+    }
+    Ok(out)
+}
+
+pub fn parse(input: &str) -> Result<Query> {
+    let mut q = Query::default();
+    let mut pending_or = false;
+    for tok in tokenize(input)? {
+        if tok.text == "OR" && !tok.quoted {
+            if q.clauses.is_empty() {
+                anyhow::bail!("OR needs a clause on its left");
+            }
+            pending_or = true;
+            continue;
+        }
+        let clause = to_clause(&tok)?;
+        if pending_or {
+            pending_or = false;
+            let prev = q.clauses.last_mut().expect("checked above");
+            if prev.field != clause.field || prev.negated || clause.negated {
+                anyhow::bail!("OR only joins un-negated clauses of the same kind");
+            }
+            prev.values.extend(clause.values);
+            continue;
+        }
+        q.clauses.push(clause);
+    }
+    if pending_or {
+        anyhow::bail!("OR needs a clause on its right");
+    }
+    Ok(q)
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+    negated: bool,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut out = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(ch) => text.push(ch),
+                    None => anyhow::bail!("unterminated quote in query"),
+                }
+            }
+            out.push(Token { text, quoted: true, negated });
+            continue;
+        }
+        let mut text = String::new();
+        while let Some(&ch) = chars.peek() {
+            if ch.is_whitespace() {
+                break;
+            }
+            text.push(ch);
+            chars.next();
+        }
+        if text.is_empty() {
+            // lone "-"
+            continue;
+        }
+        out.push(Token { text, quoted: false, negated });
+    }
+    Ok(out)
+}
+
+fn to_clause(tok: &Token) -> Result<Clause> {
+    if tok.quoted {
+        return Ok(Clause { field: Field::Phrase, values: vec![tok.text.clone()], negated: tok.negated });
+    }
+    let (field, rest) = match tok.text.split_once(':') {
+        Some(("path", v)) => (Field::Path, v),
+        Some(("lang", v)) => (Field::Lang, v),
+        Some(("after", v)) => (Field::After, v),
+        Some(("before", v)) => (Field::Before, v),
+        Some(("license", v)) => (Field::License, v),
+        Some(("owner", v)) => (Field::Owner, v),
+        _ => return Ok(Clause { field: Field::Word, values: vec![tok.text.clone()], negated: tok.negated }),
+    };
+    let values: Vec<String> = rest.split(',').filter(|v| !v.is_empty()).map(str::to_string).collect();
+    if values.is_empty() {
+        anyhow::bail!("empty value in `{}`", tok.text);
+    }
+    for v in &values {
+        match field {
+            Field::After | Field::Before => {
+                parse_date(v)?;
+            }
+            Field::Path if is_glob(v) => {
+                Glob::new(v).map_err(|e| anyhow::anyhow!("bad path glob `{v}`: {e}"))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(Clause { field, values, negated: tok.negated })
+}
+
+/// File-level facts the filters look at.
+pub struct FileFacts<'a> {
+    pub path: &'a str,
+    pub mtime: Option<u64>,
+    pub license: Option<&'a str>,
+    /// CODEOWNERS owners, then commit authors
+    pub owners: &'a [String],
+}
+
+/// Compiled file-level clauses.
+pub struct FileFilter {
+    clauses: Vec<(Clause, Vec<Option<GlobMatcher>>)>,
+}
+
+impl FileFilter {
+    pub fn new(q: &Query) -> Result<Self> {
+        let mut clauses = Vec::new();
+        for c in q.file_clauses() {
+            let globs = c
+                .values
+                .iter()
+                .map(|v| (c.field == Field::Path && is_glob(v)).then(|| Glob::new(v).map(|g| g.compile_matcher())).transpose())
+                .collect::<Result<Vec<_>, _>>()?;
+            clauses.push((c.clone(), globs));
+        }
+        Ok(Self { clauses })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub fn matches(&self, f: &FileFacts) -> bool {
+        self.clauses.iter().all(|(c, globs)| {
+            let hit = c.values.iter().zip(globs).any(|(v, g)| match c.field {
+                Field::Path => match g {
+                    Some(g) => g.is_match(f.path),
+                    None => f.path.starts_with(v.as_str()),
+                },
+                Field::Lang => lang_matches(v, f.path),
+                // files indexed before mtimes were recorded never satisfy a date clause
+                Field::After => f.mtime.is_some_and(|m| m >= parse_date(v).unwrap_or(0)),
+                Field::Before => f.mtime.is_some_and(|m| m < parse_date(v).unwrap_or(0)),
+                Field::License => license_matches(v, f.license),
+                Field::Owner => owner_matches(v, f.owners),
+                Field::Word => path_mentions(v, f.path),
+                _ => true,
+            });
+            hit != c.negated
+        })
+    }
+}
+
+/// Phrase and negated-word clauses, checked against chunk text.
+pub fn text_matches(q: &Query, text: &str) -> bool {
+    let lower = text.to_lowercase();
+    q.text_clauses().all(|c| {
+        let hit = c.values.iter().any(|v| lower.contains(&v.to_lowercase()));
+        hit != c.negated
+    })
+}
+
+/// `word` is a directory, the file stem, or a `_`/`-`/`.`-separated part of
+/// one; never the extension, so `-rs` doesn't drop every Rust file.
+fn path_mentions(word: &str, path: &str) -> bool {
+    let (dirs, name) = path.rsplit_once(['/', '\\']).unwrap_or(("", path));
+    let stem = name.rsplit_once('.').map_or(name, |(s, _)| s);
+    dirs.split(['/', '\\'])
+        .chain(std::iter::once(stem))
+        .flat_map(|seg| std::iter::once(seg).chain(seg.split(['_', '-', '.'])))
+        .any(|part| part.eq_ignore_ascii_case(word))
+}
+
+/// `license:MIT` holds for any expression naming MIT, e.g. `MIT OR Apache-2.0`.
+fn license_matches(id: &str, license: Option<&str>) -> bool {
+    match license {
+        None => id.eq_ignore_ascii_case("none"),
+        Some(expr) => expr.split(|c: char| c.is_whitespace() || c == '(' || c == ')').any(|t| t.eq_ignore_ascii_case(id)),
+    }
+}
+
+/// `owner:alice` holds for `@alice`, `alice@example.com` and `@org/alice`;
+/// `owner:@org/team` only for that team.
+fn owner_matches(want: &str, owners: &[String]) -> bool {
+    if want.eq_ignore_ascii_case("none") {
+        return owners.is_empty();
+    }
+    let want = want.trim_start_matches('@');
+    owners.iter().any(|o| {
+        let o = o.trim_start_matches('@');
+        let user = o.split_once('@').map_or(o, |(local, _)| local);
+        let team = o.rsplit_once('/').map_or(o, |(_, t)| t);
+        [o, user, team].iter().any(|n| n.eq_ignore_ascii_case(want))
+    })
+}
+
+fn is_glob(v: &str) -> bool {
+    v.contains(['*', '?', '[', '{'])
+}
+
+const LANGS: &[(&str, &[&str])] = &[
+    ("rust", &["rs"]),
+    ("python", &["py", "pyi"]),
+    ("javascript", &["js", "mjs", "cjs", "jsx"]),
+    ("typescript", &["ts", "tsx"]),
+    ("go", &["go"]),
+    ("c", &["c", "h"]),
+    ("cpp", &["cc", "cpp", "cxx", "hpp", "hh"]),
+    ("java", &["java"]),
+    ("shell", &["sh", "bash", "zsh"]),
+    ("markdown", &["md", "markdown"]),
+    ("toml", &["toml"]),
+    ("yaml", &["yml", "yaml"]),
+    ("json", &["json"]),
+];
+
+/// An extension of one of the languages `lang:` knows.
+pub(crate) fn known_extension(ext: &str) -> bool {
+    let ext = ext.to_ascii_lowercase();
+    LANGS.iter().any(|(_, exts)| exts.contains(&ext.as_str()))
+}
+
+/// `lang:rust` or a bare extension such as `lang:rs`.
+pub(crate) fn lang_matches(lang: &str, path: &str) -> bool {
+    let Some(ext) = std::path::Path::new(path).extension().and_then(|e| e.to_str()) else { return false };
+    let lang = lang.to_ascii_lowercase();
+    let ext = ext.to_ascii_lowercase();
+    match LANGS.iter().find(|(name, _)| *name == lang) {
+        Some((_, exts)) => exts.contains(&ext.as_str()),
+        None => ext == lang,
+    }
+}
+
+/// YYYY-MM-DD (UTC midnight) to unix seconds.
+pub fn parse_date(s: &str) -> Result<u64> {
+    let bad = || anyhow::anyhow!("bad date `{s}`, expected YYYY-MM-DD");
+    let mut it = s.splitn(3, '-');
+    let (Some(y), Some(m), Some(d)) = (it.next(), it.next(), it.next()) else { return Err(bad()) };
+    let (y, m, d): (i64, i64, i64) = (y.parse().map_err(|_| bad())?, m.parse().map_err(|_| bad())?, d.parse().map_err(|_| bad())?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) || y < 1970 {
+        return Err(bad());
+    }
+    // days from civil (Howard Hinnant)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    Ok(days as u64 * 86400)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(input: &str) -> Vec<(String, bool, bool)> {
+        tokenize(input).unwrap().into_iter().map(|t| (t.text, t.quoted, t.negated)).collect()
+    }
+
+    #[test]
+    fn tokenize_words_phrases_and_negation() {
+        let t = |s: &str, quoted, negated| (s.to_string(), quoted, negated);
+        assert_eq!(
+            texts(r#"  lock -tests "exact  phrase" -"todo later" - path:src/**  "#),
+            [t("lock", false, false), t("tests", false, true), t("exact  phrase", true, false), t("todo later", true, true), t("path:src/**", false, false)]
+        );
+        assert!(tokenize(r#"a "open"#).is_err());
+    }
+
+    #[test]
+    fn negated_phrase() {
+        let q = parse(r#"cache -"not implemented""#).unwrap();
+        let c = &q.clauses[1];
+        assert_eq!((c.field, c.negated, c.values.as_slice()), (Field::Phrase, true, ["not implemented".to_string()].as_slice()));
+        assert_eq!(q.semantic_text(), "cache");
+        assert!(text_matches(&q, "the cache is warm"));
+        assert!(!text_matches(&q, "cache: NOT IMPLEMENTED yet"));
+    }
+
+    #[test]
+    fn or_joins_values_and_needs_both_sides() {
+        let q = parse("lang:rust OR lang:go bug").unwrap();
+        assert_eq!(q.clauses[0].values, ["rust", "go"]);
+        assert_eq!(q.clauses[1].values, ["bug"]);
+        assert!(parse("OR lang:go").is_err());
+        assert!(parse("lang:go OR").is_err());
+        assert!(parse("lang:go OR path:src").is_err());
+        assert!(parse("lang:go OR -lang:rust").is_err());
+        // quoted, OR is a phrase
+        assert_eq!(parse(r#""OR" x"#).unwrap().clauses[0].field, Field::Phrase);
+    }
+
+    #[test]
+    fn unknown_key_is_a_word() {
+        let q = parse("std::sync foo:bar").unwrap();
+        assert!(q.clauses.iter().all(|c| c.field == Field::Word));
+        assert_eq!(q.semantic_text(), "std::sync foo:bar");
+        assert!(!q.has_filters());
+        assert!(parse("path:").is_err());
+    }
+
+    #[test]
+    fn parse_date_bounds() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2000-03-01").unwrap(), 951_868_800);
+        assert_eq!(parse_date("2024-02-29").unwrap(), 1_709_164_800);
+        assert_eq!(parse_date("2024-12-31").unwrap(), 1_735_603_200);
+        for bad in ["1969-12-31", "2024-00-10", "2024-13-01", "2024-01-00", "2024-01-32", "2024-01", "2024/01/01", "yyyy-01-01"] {
+            assert!(parse_date(bad).is_err(), "{bad}");
+        }
+        assert!(parse("after:2024-13-01").is_err());
+    }
+
+    #[test]
+    fn path_mentions_segments_and_name_parts() {
+        assert!(path_mentions("tests", "crates/store/tests/props.rs"));
+        assert!(path_mentions("test", "pkg/foo_test.go"));
+        assert!(path_mentions("vendor", "Vendor/lib.c"));
+        assert!(path_mentions("lock", "src/lock-free.rs"));
+        assert!(path_mentions("props", "crates\\store\\tests\\props.rs"));
+        assert!(!path_mentions("rs", "src/main.rs"));
+        assert!(!path_mentions("test", "src/testing.rs"));
+    }
+
+    #[test]
+    fn owner_matches_users_authors_and_teams() {
+        let owners = ["@alice".to_string(), "@org/platform".to_string(), "bob@example.com".to_string()];
+        for want in ["alice", "@alice", "ALICE", "platform", "@org/platform", "bob", "bob@example.com"] {
+            assert!(owner_matches(want, &owners), "{want}");
+        }
+        assert!(!owner_matches("org", &owners));
+        assert!(!owner_matches("carol", &owners));
+        assert!(!owner_matches("none", &owners));
+        assert!(owner_matches("none", &[]));
+    }
+
+    #[test]
+    fn license_matches_expressions() {
+        assert!(license_matches("MIT", Some("MIT OR Apache-2.0")));
+        assert!(license_matches("apache-2.0", Some("(MIT OR Apache-2.0)")));
+        assert!(!license_matches("Apache", Some("Apache-2.0")));
+        assert!(license_matches("none", None));
+        assert!(!license_matches("MIT", None));
+        assert!(!license_matches("none", Some("MIT")));
+    }
+}
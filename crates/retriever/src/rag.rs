@@ -0,0 +1,95 @@
+//! Hits as RAG-framework nodes: `{id, text, metadata, score}`.
+//!
+//! The shape maps field for field onto the common node types:
+//!
+//! ```text
+//! llama-index  TextNode(id_=id, text=text, metadata=metadata)
+//!              NodeWithScore(node=.., score=score)
+//! langchain    Document(id=id, page_content=text, metadata=metadata)
+//!              similarity_search_with_score -> (Document, score)
+//! ```
+//!
+//! `score` is cosine similarity (higher is better), not the distance `Hit`
+//! carries; the `relevance` metadata puts it on the 0-1 scale `[search]
+//! relevance` picks (see `relevance`). Metadata values are flat strings and numbers, so they survive
+//! vector stores that reject nested metadata. Nodes of a search cut short
+//! by its budget carry `truncated: true`. `owners` (CODEOWNERS) and
+//! `authors` (frequent committers) are space-separated lists, present when
+//! known. With `set_pseudonymize`, `path`
+//! and `citation` name the file by its pseudonym.
+
+use crate::{snippet::{self, Verbosity}, Hit, Retriever};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Node {
+    /// the chunk id
+    pub id: String,
+    /// empty when the file changed on disk since indexing
+    pub text: String,
+    pub metadata: Map<String, Value>,
+    pub score: f32,
+}
+
+impl Retriever {
+    /// Resolve hits to nodes carrying their text and file metadata.
+    pub fn to_nodes(&self, hits: &[Hit]) -> Result<Vec<Node>> {
+        self.to_nodes_with(hits, &Verbosity::default())
+    }
+
+    /// `to_nodes` with the text cut down, or context lines added as the
+    /// `context_before` / `context_after` metadata, as `v` asks.
+    pub fn to_nodes_with(&self, hits: &[Hit], v: &Verbosity) -> Result<Vec<Node>> {
+        let mut out = Vec::with_capacity(hits.len());
+        for h in hits {
+            let id: Option<[u8; 32]> = hex::decode(&h.chunk_id).ok().and_then(|b| b.try_into().ok());
+            let meta = match id {
+                Some(id) => self.store.get_chunk(id)?,
+                None => None,
+            };
+            let text = match &meta {
+                Some(m) => self.store.chunk_text(m)?.unwrap_or_default(),
+                None => String::new(),
+            };
+            let path = self.shown_path(&h.path)?;
+            let mut metadata = Map::new();
+            metadata.insert("path".into(), path.clone().into());
+            metadata.insert("start".into(), h.start.into());
+            metadata.insert("end".into(), h.end.into());
+            metadata.insert("relevance".into(), self.relevance(h.distance).into());
+            if let Some(m) = &meta {
+                metadata.insert("file_hash".into(), hex::encode(m.file_hash).into());
+                if let Some(l) = self.store.get_license(m.file_hash)? {
+                    metadata.insert("license".into(), l.into());
+                }
+                if let Some(o) = self.store.get_owners(m.file_hash)? {
+                    if !o.codeowners.is_empty() {
+                        metadata.insert("owners".into(), o.codeowners.join(" ").into());
+                    }
+                    if !o.authors.is_empty() {
+                        metadata.insert("authors".into(), o.authors.join(" ").into());
+                    }
+                }
+                if !text.is_empty() {
+                    let c = crate::cite::Citation::new(&path, h.start, h.end, &text);
+                    metadata.insert("citation".into(), c.to_string().into());
+                }
+                if v.context > 0 && !text.is_empty() {
+                    if let Some(data) = self.store.read_file(m.file_hash, &h.path)? {
+                        let (before, after) = snippet::around(&data, m.start, m.end, v.context);
+                        metadata.insert("context_before".into(), before.into());
+                        metadata.insert("context_after".into(), after.into());
+                    }
+                }
+            }
+            if self.truncated() {
+                metadata.insert("truncated".into(), true.into());
+            }
+            let text = if v.full_text { text } else { snippet::head(&text, v.lines) };
+            out.push(Node { id: h.chunk_id.clone(), text, metadata, score: 1.0 - h.distance });
+        }
+        Ok(out)
+    }
+}
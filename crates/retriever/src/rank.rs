@@ -0,0 +1,174 @@
+//! Final scoring pass from `[ranking]` in mentat.toml:
+//!
+//! ```toml
+//! [ranking]
+//! recency = 0.1            # up to +10% similarity for a file modified just now
+//! half_life_days = 90      # ... halving every 90 days of age
+//! path = { "README*" = 1.2, "docs/**" = 1.1, "generated/**" = 0.5 }
+//! lang = { markdown = 1.1 }
+//! boilerplate = 0.3        # scale similarity by 1 - 0.3 x the chunk's boilerplate score
+//! generated = 0.5          # files kept by `[generated] action = "downrank"`
+//! lexical = 0.2            # code-like queries: up to +20% for chunks containing their identifiers
+//! score = "0.8*dense + 0.2*bm25"   # optional; see `crate::score`
+//! ```
+//!
+//! Every matching factor multiplies the hit's similarity (or, with `score`
+//! set, the expression's value), and the hits are re-sorted; `distance` is then `1 - adjusted similarity`. A search budget
+//! that runs out midway returns the hits in their unadjusted order.
+
+use crate::{classify, query, score, Hit, Retriever};
+use anyhow::Result;
+use globset::Glob;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Boosts {
+    /// path glob (or plain prefix) -> similarity multiplier
+    pub path: BTreeMap<String, f32>,
+    /// language name or extension, as in `lang:` -> similarity multiplier
+    pub lang: BTreeMap<String, f32>,
+    /// extra similarity share for a file modified just now
+    pub recency: f32,
+    pub half_life_days: f32,
+    /// penalty per unit of the chunk's boilerplate score (0 = off)
+    pub boilerplate: f32,
+    /// similarity multiplier for files indexed as generated (1 = off)
+    pub generated: f32,
+    /// for code-like queries, bonus share times the fraction of the query's
+    /// identifier terms (see `mentat_text::analyze`) found in the chunk (0 = off)
+    pub lexical: f32,
+    /// expression replacing the similarity the factors above multiply
+    pub score: Option<String>,
+}
+
+impl Default for Boosts {
+    fn default() -> Self {
+        Self { path: BTreeMap::new(), lang: BTreeMap::new(), recency: 0.0, half_life_days: 90.0, boilerplate: 0.3, generated: 0.5, lexical: 0.2, score: None }
+    }
+}
+
+impl Boosts {
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty() && self.lang.is_empty() && self.recency == 0.0 && self.boilerplate == 0.0 && self.generated == 1.0 && self.lexical == 0.0 && self.score.is_none()
+    }
+}
+
+impl Retriever {
+    /// Apply `b` to the ranked hits of `q` and re-sort them.
+    pub fn rerank(&self, mut hits: Vec<Hit>, b: &Boosts, q: &query::Query) -> Result<Vec<Hit>> {
+        if b.is_empty() {
+            return Ok(hits);
+        }
+        let expr = b.score.as_deref().map(score::Expr::parse).transpose()?;
+        let uses = |v: score::Var| expr.as_ref().is_some_and(|e| e.uses(v));
+        let text = q.semantic_text();
+        // identifiers and their parts, so `putEmbed` also finds `put_embed`
+        let idents = match classify::classify(&text) {
+            classify::QueryKind::Code if b.lexical != 0.0 || uses(score::Var::Lexical) => {
+                mentat_text::terms(&classify::identifiers(&text).join(" "))
+            }
+            _ => Vec::new(),
+        };
+        let idf: Vec<(String, f32)> = if uses(score::Var::Bm25) {
+            mentat_text::terms(&text).into_iter().map(|t| {
+                let w = self.store.idf(&t)?;
+                Ok((t, w))
+            })
+            .collect::<Result<_>>()?
+        } else {
+            Vec::new()
+        };
+        let idf_total: f32 = idf.iter().map(|(_, w)| w).sum();
+        let globs = b
+            .path
+            .iter()
+            .map(|(p, f)| {
+                let g = p.contains(['*', '?', '[', '{']).then(|| Glob::new(p).map(|g| g.compile_matcher())).transpose()?;
+                Ok((p.as_str(), g, *f))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mtimes: std::collections::HashMap<String, u64> = if b.recency != 0.0 || expr.as_ref().is_some_and(|e| e.uses_recency()) {
+            let mut m = std::collections::HashMap::new();
+            for (h, f) in self.store.files()? {
+                if let Some(t) = self.store.get_mtime(h)? {
+                    m.insert(f.path, t);
+                }
+            }
+            m
+        } else {
+            Default::default()
+        };
+        let unranked = self.deadline.is_some().then(|| hits.clone());
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        for h in &mut hits {
+            if self.over_budget() {
+                return Ok(unranked.expect("kept while a deadline is set"));
+            }
+            let mut factor = 1.0f32;
+            for (prefix, g, f) in &globs {
+                let hit = match g {
+                    Some(g) => g.is_match(&h.path),
+                    None => h.path.starts_with(prefix),
+                };
+                if hit {
+                    factor *= f;
+                }
+            }
+            for (lang, f) in &b.lang {
+                if query::lang_matches(lang, &h.path) {
+                    factor *= f;
+                }
+            }
+            let age_days = mtimes.get(&h.path).map(|&t| now.saturating_sub(t) as f32 / 86400.0);
+            if let Some(age_days) = age_days.filter(|_| b.recency != 0.0) {
+                factor *= 1.0 + b.recency * 0.5f32.powf(age_days / b.half_life_days.max(f32::EPSILON));
+            }
+            let id: Option<[u8; 32]> = hex::decode(&h.chunk_id).ok().and_then(|v| v.try_into().ok());
+            let meta = match id {
+                Some(id) if b.generated != 1.0 || !idents.is_empty() || !idf.is_empty() || uses(score::Var::Generated) => self.store.get_chunk(id)?,
+                _ => None,
+            };
+            let boilerplate = match id {
+                Some(id) if b.boilerplate != 0.0 || uses(score::Var::Boilerplate) => self.store.get_boilerplate(id)?,
+                _ => None,
+            };
+            if let Some(s) = boilerplate.filter(|_| b.boilerplate != 0.0) {
+                factor *= 1.0 - b.boilerplate * s;
+            }
+            let generated = match &meta {
+                Some(c) if b.generated != 1.0 || uses(score::Var::Generated) => self.store.get_generated(c.file_hash)?.is_some(),
+                _ => false,
+            };
+            if generated && b.generated != 1.0 {
+                factor *= b.generated;
+            }
+            let terms: std::collections::HashSet<String> = match &meta {
+                Some(m) if !idents.is_empty() || !idf.is_empty() => {
+                    self.store.chunk_text(m)?.map(|t| mentat_text::terms(&t).into_iter().collect()).unwrap_or_default()
+                }
+                _ => Default::default(),
+            };
+            let lexical = if idents.is_empty() { 0.0 } else { idents.iter().filter(|i| terms.contains(*i)).count() as f32 / idents.len() as f32 };
+            if !idents.is_empty() && b.lexical != 0.0 && !terms.is_empty() {
+                factor *= 1.0 + b.lexical * lexical;
+            }
+            let sim = match &expr {
+                Some(e) => e.eval(&score::Vars {
+                    dense: 1.0 - h.distance,
+                    // binary term frequency, so each found term adds its idf
+                    bm25: if idf_total > 0.0 { idf.iter().filter(|(t, _)| terms.contains(t)).map(|(_, w)| w).sum::<f32>() / idf_total } else { 0.0 },
+                    lexical,
+                    boilerplate: boilerplate.unwrap_or(0.0),
+                    generated: if generated { 1.0 } else { 0.0 },
+                    age_days,
+                }),
+                None => 1.0 - h.distance,
+            };
+            h.distance = 1.0 - sim * factor;
+        }
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        Ok(hits)
+    }
+}
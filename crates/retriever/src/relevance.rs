@@ -0,0 +1,37 @@
+//! A 0-1 relevance shown beside each hit's raw cosine distance, set by
+//! `[search] relevance` in mentat.toml:
+//!
+//! - `similarity`: `1 - distance`, clamped to 0..1.
+//! - `percentile`: where the similarity falls among the index's own
+//!   near-neighbor similarities, sampled at index time and stored under
+//!   `META_RELEVANCE`. 0.9 means closer than 90% of the sampled chunk
+//!   pairs; an index without the sample falls back to `similarity`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scale {
+    #[default]
+    Similarity,
+    Percentile,
+}
+
+/// Relevance of `distance` on `scale`. `quantiles` are evenly spaced
+/// quantiles of the sampled similarities, ascending.
+pub fn relevance(distance: f32, scale: Scale, quantiles: &[f32]) -> f32 {
+    let sim = (1.0 - distance).clamp(0.0, 1.0);
+    if scale == Scale::Similarity || quantiles.len() < 2 {
+        return sim;
+    }
+    let i = quantiles.partition_point(|q| *q < sim);
+    if i == 0 {
+        return 0.0;
+    }
+    if i == quantiles.len() {
+        return 1.0;
+    }
+    let (lo, hi) = (quantiles[i - 1], quantiles[i]);
+    let frac = if hi > lo { (sim - lo) / (hi - lo) } else { 0.0 };
+    ((i - 1) as f32 + frac) / (quantiles.len() - 1) as f32
+}
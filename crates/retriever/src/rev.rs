@@ -0,0 +1,91 @@
+//! Files as they were at a git revision. An indexed file version belongs to
+//! a revision when the blob git has at that path hashes to the same content
+//! hash, so versions shared by several revisions are stored once. Only
+//! versions still in the index are found: `mentat index` drops a file's
+//! previous version when its content changes.
+
+use anyhow::{Context, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Content hashes of indexed files whose bytes match `rev` in the git
+/// repository containing `root` (paths relative to `root`).
+pub fn files_at(store: &mentat_store::Store, root: &Path, rev: &str) -> Result<HashSet<[u8; 32]>> {
+    let indexed: HashMap<String, Vec<[u8; 32]>> = store.files()?.into_iter().fold(HashMap::new(), |mut m, (h, f)| {
+        m.entry(f.path).or_default().push(h);
+        m
+    });
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-tree", "-r", "-z", rev, "--", "."])
+        .output()
+        .context("running git ls-tree")?;
+    anyhow::ensure!(out.status.success(), "git ls-tree {rev}: {}", String::from_utf8_lossy(&out.stderr).trim());
+    // "<mode> blob <sha>\t<path>\0"
+    let mut wanted: Vec<(String, &Vec<[u8; 32]>)> = Vec::new();
+    for entry in out.stdout.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((info, path)) = entry.split_once('\t') else { continue };
+        let mut info = info.split(' ');
+        let (Some(_), Some("blob"), Some(sha)) = (info.next(), info.next(), info.next()) else { continue };
+        if let Some(hashes) = indexed.get(path) {
+            wanted.push((sha.to_string(), hashes));
+        }
+    }
+    let blobs = cat_blobs(root, wanted.iter().map(|(sha, _)| sha.clone()).collect())?;
+    let mut found = HashSet::new();
+    for ((_, hashes), data) in wanted.iter().zip(blobs) {
+        let h = mentat_store::blake32(&data);
+        if hashes.contains(&h) {
+            found.insert(h);
+        }
+    }
+    Ok(found)
+}
+
+/// Contents of each blob, in order, from one `git cat-file --batch`.
+fn cat_blobs(root: &Path, shas: Vec<String>) -> Result<Vec<Vec<u8>>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("running git cat-file")?;
+    let n = shas.len();
+    let mut stdin = child.stdin.take().expect("piped");
+    // feed from a thread so a full stdout pipe can't deadlock us
+    let feeder = std::thread::spawn(move || -> std::io::Result<()> {
+        for sha in shas {
+            writeln!(stdin, "{sha}")?;
+        }
+        Ok(())
+    });
+    let mut out = BufReader::new(child.stdout.take().expect("piped"));
+    let mut blobs = Vec::with_capacity(n);
+    let mut header = String::new();
+    for _ in 0..n {
+        header.clear();
+        out.read_line(&mut header)?;
+        // "<sha> blob <size>"
+        let size: usize = header
+            .trim_end()
+            .rsplit(' ')
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("unexpected git cat-file output: {header:?}"))?;
+        let mut data = vec![0; size + 1]; // content plus trailing newline
+        out.read_exact(&mut data)?;
+        data.pop();
+        blobs.push(data);
+    }
+    feeder.join().expect("feeder thread panicked")?;
+    child.wait()?;
+    Ok(blobs)
+}
@@ -0,0 +1,55 @@
+//! Follow-up questions made standalone before embedding. "what about its
+//! error handling?" after "how does the session cache work" becomes
+//! "session cache work error handling": when the query leans on a pronoun,
+//! the pronoun and filler words go and the content words of the latest
+//! earlier turn that has any take their place.
+//!
+//! Query syntax (`path:src`, `-tests`, `"a phrase"`) is kept as written.
+
+/// Words that point back into the conversation.
+const REFERENTS: &[&str] = &["it", "its", "it's", "this", "that", "these", "those", "they", "them", "their", "there", "one", "ones"];
+
+/// Filler dropped from both the query and the borrowed topic.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "again", "all", "also", "an", "and", "any", "are", "as", "at", "be", "but", "by", "can", "could", "do",
+    "does", "for", "from", "how", "i", "if", "in", "into", "is", "me", "more", "my", "now", "of", "ok", "okay", "on", "or",
+    "same", "show", "so", "tell", "the", "then", "to", "us", "we", "what", "what's", "when", "where", "which", "who",
+    "why", "with", "would", "you",
+];
+
+/// Standalone version of `query`, or None if it needs no context.
+/// `history` holds the earlier turns, oldest first.
+pub fn rewrite(query: &str, history: &[String]) -> Option<String> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if !words.iter().any(|w| REFERENTS.contains(&bare(w).as_str())) {
+        return None;
+    }
+    let topic = history.iter().rev().map(|turn| content_words(turn)).find(|w| !w.is_empty())?;
+    let kept: Vec<&str> = words.iter().copied().filter(|w| is_syntax(w) || is_content(&bare(w))).collect();
+    let mut out: Vec<String> = Vec::new();
+    for w in topic {
+        if !kept.iter().any(|k| bare(k) == w) && !out.contains(&w) {
+            out.push(w);
+        }
+    }
+    out.extend(kept.into_iter().map(|w| if is_syntax(w) { w.to_string() } else { bare(w) }));
+    Some(out.join(" "))
+}
+
+fn content_words(turn: &str) -> Vec<String> {
+    turn.split_whitespace().filter(|w| !is_syntax(w)).map(bare).filter(|w| is_content(w)).collect()
+}
+
+fn is_content(w: &str) -> bool {
+    !w.is_empty() && !REFERENTS.contains(&w) && !STOPWORDS.contains(&w)
+}
+
+/// Filters, exclusions and phrases pass through untouched.
+fn is_syntax(w: &str) -> bool {
+    w.contains(':') || w.contains('"') || w.starts_with('-') || w.starts_with('@')
+}
+
+/// Lowercased, without surrounding punctuation (`handling?` -> `handling`).
+fn bare(w: &str) -> String {
+    w.trim_matches(|c: char| !c.is_alphanumeric() && c != '_' && c != '\'').to_lowercase()
+}
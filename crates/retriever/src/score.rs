@@ -0,0 +1,252 @@
+//! `[ranking] score`: an arithmetic expression that replaces a hit's
+//! similarity before the other `[ranking]` factors multiply it.
+//!
+//! ```toml
+//! [ranking]
+//! score = "0.7*dense + 0.3*bm25 + 0.1*recency(30d)"
+//! ```
+//!
+//! Numbers, `+ - * /`, parentheses and these terms, each 0..1 for a hit:
+//!
+//! - `dense`: cosine similarity to the query
+//! - `bm25`: the query terms found in the chunk, weighted by their idf
+//!   (BM25 with binary term frequency and no length normalization)
+//! - `lexical`: share of a code-like query's identifiers found in the chunk
+//! - `boilerplate`: the chunk's boilerplate score
+//! - `generated`: 1 for a file kept as generated, else 0
+//! - `recency(N)`: 1 for a file modified just now, halving every `N`
+//!   (`30d`, `12h`, or a plain number of days); 0 if its mtime is unknown
+
+use anyhow::{bail, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Var {
+    Dense,
+    Bm25,
+    Lexical,
+    Boilerplate,
+    Generated,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Num(f32),
+    Var(Var),
+    /// half-life in days
+    Recency(f32),
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+}
+
+/// A hit's terms; `age_days` is None when its file's mtime is unknown.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Vars {
+    pub dense: f32,
+    pub bm25: f32,
+    pub lexical: f32,
+    pub boilerplate: f32,
+    pub generated: f32,
+    pub age_days: Option<f32>,
+}
+
+impl Expr {
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut p = Parser { src, pos: 0 };
+        let e = p.sum()?;
+        p.skip_ws();
+        if p.pos < src.len() {
+            bail!("[ranking] score: unexpected `{}` at {}", &src[p.pos..], p.pos);
+        }
+        Ok(e)
+    }
+
+    pub fn eval(&self, v: &Vars) -> f32 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var(Var::Dense) => v.dense,
+            Expr::Var(Var::Bm25) => v.bm25,
+            Expr::Var(Var::Lexical) => v.lexical,
+            Expr::Var(Var::Boilerplate) => v.boilerplate,
+            Expr::Var(Var::Generated) => v.generated,
+            Expr::Recency(half_life) => v.age_days.map_or(0.0, |a| 0.5f32.powf(a / half_life.max(f32::EPSILON))),
+            Expr::Neg(e) => -e.eval(v),
+            Expr::Bin(op, a, b) => {
+                let (a, b) = (a.eval(v), b.eval(v));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ if b == 0.0 => 0.0,
+                    _ => a / b,
+                }
+            }
+        }
+    }
+
+    /// Whether evaluating reads `var`, so unused terms are never computed.
+    pub fn uses(&self, var: Var) -> bool {
+        match self {
+            Expr::Var(v) => *v == var,
+            Expr::Neg(e) => e.uses(var),
+            Expr::Bin(_, a, b) => a.uses(var) || b.uses(var),
+            Expr::Num(_) | Expr::Recency(_) => false,
+        }
+    }
+
+    pub fn uses_recency(&self) -> bool {
+        match self {
+            Expr::Recency(_) => true,
+            Expr::Neg(e) => e.uses_recency(),
+            Expr::Bin(_, a, b) => a.uses_recency() || b.uses_recency(),
+            Expr::Num(_) | Expr::Var(_) => false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.src[self.pos..].chars().next().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.src[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut e = self.product()?;
+        loop {
+            let op = if self.eat('+') { '+' } else if self.eat('-') { '-' } else { return Ok(e) };
+            e = Expr::Bin(op, Box::new(e), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut e = self.unary()?;
+        loop {
+            let op = if self.eat('*') { '*' } else if self.eat('/') { '/' } else { return Ok(e) };
+            e = Expr::Bin(op, Box::new(e), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('(') {
+            let e = self.sum()?;
+            if !self.eat(')') {
+                bail!("[ranking] score: missing `)` at {}", self.pos);
+            }
+            return Ok(e);
+        }
+        let word = self.word();
+        if let Ok(n) = word.parse::<f32>() {
+            return Ok(Expr::Num(n));
+        }
+        let var = match word {
+            "dense" => Var::Dense,
+            "bm25" => Var::Bm25,
+            "lexical" => Var::Lexical,
+            "boilerplate" => Var::Boilerplate,
+            "generated" => Var::Generated,
+            "recency" => {
+                if !self.eat('(') {
+                    bail!("[ranking] score: recency needs a half-life, as in recency(30d)");
+                }
+                let days = duration_days(self.word())?;
+                if !self.eat(')') {
+                    bail!("[ranking] score: missing `)` after recency's half-life");
+                }
+                return Ok(Expr::Recency(days));
+            }
+            "" => bail!("[ranking] score: expected a number or term at {}", self.pos),
+            other => bail!("[ranking] score: unknown term `{other}` (dense, bm25, lexical, boilerplate, generated, recency(N))"),
+        };
+        Ok(Expr::Var(var))
+    }
+
+    /// The next run of letters, digits, `_` and `.`.
+    fn word(&mut self) -> &str {
+        self.skip_ws();
+        let start = self.pos;
+        while self.src[self.pos..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            self.pos += 1;
+        }
+        &self.src[start..self.pos]
+    }
+}
+
+/// `30d`, `12h` or `7` (days).
+fn duration_days(s: &str) -> Result<f32> {
+    let (n, per_day) = match s.strip_suffix('h') {
+        Some(n) => (n, 24.0),
+        None => (s.strip_suffix('d').unwrap_or(s), 1.0),
+    };
+    match n.parse::<f32>() {
+        Ok(n) if n > 0.0 => Ok(n / per_day),
+        _ => bail!("[ranking] score: bad half-life `{s}` (e.g. 30d or 12h)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, v: &Vars) -> f32 {
+        Expr::parse(src).unwrap().eval(v)
+    }
+
+    #[test]
+    fn precedence_and_unary_minus() {
+        let v = Vars { dense: 0.5, bm25: 0.25, ..Default::default() };
+        assert_eq!(eval("1 + 2 * 3", &v), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", &v), 9.0);
+        assert_eq!(eval("8 - 2 - 1", &v), 5.0);
+        assert_eq!(eval("-dense + 1", &v), 0.5);
+        assert_eq!(eval("2 * -bm25", &v), -0.5);
+        assert_eq!(eval("- -1", &v), 1.0);
+        assert_eq!(eval("0.7*dense + 0.3*bm25", &v), 0.7 * 0.5 + 0.3 * 0.25);
+    }
+
+    #[test]
+    fn division_by_zero_is_zero() {
+        let v = Vars::default();
+        assert_eq!(eval("1 / 0", &v), 0.0);
+        assert_eq!(eval("dense / bm25", &v), 0.0);
+    }
+
+    #[test]
+    fn recency_half_lives() {
+        assert_eq!(Expr::parse("recency(12h)").unwrap(), Expr::Recency(0.5));
+        assert_eq!(Expr::parse("recency(30d)").unwrap(), Expr::Recency(30.0));
+        assert_eq!(Expr::parse("recency(7)").unwrap(), Expr::Recency(7.0));
+        let v = Vars { age_days: Some(0.5), ..Default::default() };
+        assert_eq!(eval("recency(12h)", &v), 0.5);
+        assert_eq!(eval("recency(12h)", &Vars::default()), 0.0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for src in ["", "dense +", "(dense", "dense)", "dense bm25", "speed", "recency", "recency(0d)", "recency(30d", "2 $ 3"] {
+            assert!(Expr::parse(src).is_err(), "{src:?} parsed");
+        }
+    }
+
+    #[test]
+    fn non_ascii_whitespace() {
+        assert_eq!(eval("dense\u{3000}*\u{a0}2", &Vars { dense: 0.5, ..Default::default() }), 1.0);
+        assert!(Expr::parse("dense\u{3000}é").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! Leaked-secret scan over the indexed files.
+//!
+//! Regex rules run over every file still matching its indexed version and
+//! give line-exact findings. Semantic queries ("database password", ...)
+//! then point at the nearest chunks the rules did not already flag, as
+//! leads to review rather than findings.
+
+use crate::Retriever;
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+
+const RULES: &[(&str, &str)] = &[
+    ("private-key", r"-----BEGIN ((RSA|EC|DSA|OPENSSH|PGP|ENCRYPTED) )?PRIVATE KEY( BLOCK)?-----"),
+    ("aws-access-key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("slack-token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+    ("google-api-key", r"\bAIza[0-9A-Za-z_-]{35}\b"),
+    ("stripe-key", r"\b[sr]k_live_[0-9A-Za-z]{20,}\b"),
+    ("jwt", r"\beyJ[A-Za-z0-9_-]{10,}\.eyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}"),
+    ("url-credentials", r"\b[a-z][a-z0-9+.-]*://[^/\s:@]+:[^/\s:@]{3,}@"),
+    (
+        "assigned-secret",
+        r#"(?i)\b(password|passwd|pwd|secret|api[_-]?key|access[_-]?token|auth[_-]?token)\b["']?\s*[:=]\s*["'][^"'\s]{8,}["']"#,
+    ),
+];
+
+const QUERIES: &[&str] = &[
+    "private key",
+    "database password",
+    "API key or access token",
+    "credentials in a connection string",
+];
+
+/// Nearest chunks reviewed per semantic query.
+const LEADS_PER_QUERY: usize = 5;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Finding {
+    /// regex rule name, or the semantic query for leads
+    pub rule: String,
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    /// the match with everything after its first four characters masked
+    pub excerpt: String,
+    /// set for semantic leads only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    pub leads: Vec<Finding>,
+    /// indexed files that changed on disk since indexing and were not scanned
+    pub stale: Vec<String>,
+}
+
+pub fn scan(retr: &Retriever, semantic: bool) -> Result<Report> {
+    let rules: Vec<(&str, Regex)> = RULES.iter().map(|(n, p)| Ok((*n, Regex::new(p)?))).collect::<Result<_>>()?;
+    let store = retr.store();
+    let mut rep = Report::default();
+    let mut flagged: HashSet<(String, usize)> = HashSet::new();
+    for (fhash, f) in store.files()? {
+        let data = match store.read_file(fhash, &f.path)? {
+            Some(d) if mentat_store::blake32(&d) == fhash => d,
+            _ => {
+                rep.stale.push(f.path);
+                continue;
+            }
+        };
+        let text = String::from_utf8_lossy(&data);
+        for (i, line) in text.lines().enumerate() {
+            for (name, re) in &rules {
+                if let Some(m) = re.find(line) {
+                    flagged.insert((f.path.clone(), i + 1));
+                    rep.findings.push(Finding {
+                        rule: name.to_string(),
+                        path: f.path.clone(),
+                        line: i + 1,
+                        excerpt: mask(m.as_str()),
+                        distance: None,
+                    });
+                }
+            }
+        }
+    }
+    if !semantic {
+        return Ok(rep);
+    }
+    let mut seen = HashSet::new();
+    for q in QUERIES {
+        for (id, distance) in retr.nearest(&retr.embed_query(q)?, LEADS_PER_QUERY)? {
+            let Some(meta) = store.get_chunk(id)? else { continue };
+            let Some(file) = store.get_file(meta.file_hash)? else { continue };
+            let Some(text) = store.chunk_text(&meta)? else { continue };
+            let first_line = line_of(&store.read_file(meta.file_hash, &file.path)?.unwrap_or_default(), meta.start);
+            let end_line = first_line + text.lines().count();
+            if (first_line..end_line).any(|l| flagged.contains(&(file.path.clone(), l))) || !seen.insert(id) {
+                continue;
+            }
+            let first = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+            rep.leads.push(Finding {
+                rule: q.to_string(),
+                path: file.path,
+                line: first_line,
+                excerpt: first.chars().take(80).collect(),
+                distance: Some(distance),
+            });
+        }
+    }
+    Ok(rep)
+}
+
+/// 1-based line holding byte `offset` of `data`.
+fn line_of(data: &[u8], offset: usize) -> usize {
+    1 + data.iter().take(offset).filter(|&&b| b == b'\n').count()
+}
+
+fn mask(s: &str) -> String {
+    let keep: String = s.chars().take(4).collect();
+    format!("{keep}{}", "*".repeat(s.chars().count().saturating_sub(4).min(16)))
+}
@@ -0,0 +1,43 @@
+//! How much text search results carry. A terminal wants a few lines per hit;
+//! an agent's context pack wants the whole chunk and the lines around it.
+//! Set by `[search]` in mentat.toml and per command (`--snippet`,
+//! `--context`, `--no-full-text`).
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Verbosity {
+    /// lines of chunk text shown under each hit, and kept in nodes when
+    /// `full_text` is off
+    pub lines: usize,
+    /// lines of the file before and after the chunk
+    pub context: usize,
+    /// nodes carry the whole chunk text
+    pub full_text: bool,
+}
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Self { lines: 0, context: 0, full_text: true }
+    }
+}
+
+/// The first `n` lines of `text`, with a note of how many were left out.
+pub fn head(text: &str, n: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = lines[..lines.len().min(n)].join("\n");
+    if lines.len() > n {
+        out.push_str(&format!("\n... ({} more lines)", lines.len() - n));
+    }
+    out
+}
+
+/// Up to `n` whole lines of `data` before `start` and after `end`. The
+/// partial lines the span starts and ends inside are not counted.
+pub fn around(data: &[u8], start: usize, end: usize, n: usize) -> (String, String) {
+    let before = String::from_utf8_lossy(&data[..start.min(data.len())]);
+    let mut lines: Vec<&str> = before.split('\n').collect();
+    lines.pop();
+    let before = lines[lines.len().saturating_sub(n)..].join("\n");
+    let after = String::from_utf8_lossy(&data[end.min(data.len())..]);
+    let after = after.split('\n').skip(1).take(n).collect::<Vec<_>>().join("\n");
+    (before, after)
+}
@@ -1,31 +1,235 @@
 //! ReDB-backed index at ./index/kv.redb
 //! Tables:
 //!   files: key=blake3(file bytes), val=bincode(FileMeta)
-//!   chunks: key=blake3(file bytes) + start..end, val=bincode(ChunkMeta)
+//!   chunks: key=blake3(file bytes) + start..end + chunker id, val=bincode(ChunkMeta)
 //!   embeds: key=chunk_id, val=[f32; D] as bytes
+//!   meta: key=name, val=raw bytes (index root, settings)
+//!   file_mtime: key=file hash, val=unix seconds last seen on disk
+//!   aliases: key=alias name, val=query text
+//!   path_pseudonym: key=pseudonym handed out in place of a path, val=the path
+//!   file_indexed_at: key=file hash, val=unix seconds the version was first indexed
+//!   file_license: key=file hash, val=SPDX expression detected in the file
+//!   file_timing: key=file hash, val=bincode(FileTiming) stage durations of the version's last index run
+//!   file_generated: key=file hash, val=why a file kept under `[generated] action = "downrank"` looks generated
+//!   doc_text: key=file hash, val=text of a document fed in by id rather than read from the root
+//!   chunk_boilerplate: key=chunk_id, val=share of the chunk's lines common across the corpus (0..1)
+//!   stats: key=term, val=number of indexed file versions containing it
+//!   file_terms: key=file hash, val=bincode(Vec<String>) the distinct terms counted into stats
+//!   file_related: key=file hash, val=bincode(Vec<(file hash, cosine similarity)>), best first
+//!   history: key=file hash + retired-at (u64 BE), val=bincode(Retired) with its chunks and vectors
+//!   file_chunks: key=file hash + chunk_id, val=() so a file's chunks are one range scan
+//!   file_vector: key=file hash, val=unit-length mean of its chunk vectors as bytes
+//!   file_owners: key=file hash, val=bincode(Owners) CODEOWNERS owners and commit authors
+//!   chunk_upgrade: key=chunk_id, val=search hits of a chunk still holding a static first-pass vector
+//!   file_lines: key=file hash, val=bincode(Vec<u64>) the distinct line hashes counted into line_df
+//!   line_df: key=line hash, val=number of indexed file versions containing the line
+//!   chunk_lines: key=chunk_id, val=bincode(Vec<u64>) the chunk's line hashes
+//!   line_df_scored: key=line hash, val=its line_df when boilerplate scores were last stored
+//!   chunk_unscored: key=chunk_id, val=() chunks with line hashes but no boilerplate score yet
 
 use anyhow::Result;
-use redb::{Database, TableDefinition};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition, TableHandle, WriteTransaction};
 use serde::{Serialize, Deserialize};
-use std::fs;
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
 use bytemuck::cast_slice;
 
+pub mod salvage;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+
 const FILES: TableDefinition<&[u8], &[u8]>  = TableDefinition::new("files");
 const CHUNKS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("chunks");
 const EMBEDS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("embeds");
+const META: TableDefinition<&str, &[u8]>   = TableDefinition::new("meta");
+const MTIMES: TableDefinition<&[u8], u64>  = TableDefinition::new("file_mtime");
+const ALIASES: TableDefinition<&str, &str> = TableDefinition::new("aliases");
+const PSEUDONYMS: TableDefinition<&str, &str> = TableDefinition::new("path_pseudonym");
+const INDEXED_AT: TableDefinition<&[u8], u64> = TableDefinition::new("file_indexed_at");
+const HISTORY: TableDefinition<&[u8], &[u8]> = TableDefinition::new("history");
+const LICENSES: TableDefinition<&[u8], &str> = TableDefinition::new("file_license");
+const GENERATED: TableDefinition<&[u8], &str> = TableDefinition::new("file_generated");
+const TIMING: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_timing");
+const RELATED: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_related");
+/// unit-length mean of each file's chunk vectors, kept for the related-files graph
+const FILE_VECS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_vector");
+const OWNERS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_owners");
+const DOC_TEXT: TableDefinition<&[u8], &[u8]> = TableDefinition::new("doc_text");
+const BOILERPLATE: TableDefinition<&[u8], f32> = TableDefinition::new("chunk_boilerplate");
+/// each file version's distinct line hashes, counted into `LINE_DF`
+const FILE_LINES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_lines");
+/// file versions each line hash occurs in
+const LINE_DF: TableDefinition<u64, u64> = TableDefinition::new("line_df");
+/// each chunk's line hashes, so scores are recomputed without reading files
+const CHUNK_LINES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("chunk_lines");
+/// lines whose `LINE_DF` changed since scores were stored, with the count they were scored at
+const LINES_CHANGED: TableDefinition<u64, u64> = TableDefinition::new("line_df_scored");
+/// chunks with line hashes but no score yet
+const UNSCORED: TableDefinition<&[u8], ()> = TableDefinition::new("chunk_unscored");
+const STATS: TableDefinition<&str, u64> = TableDefinition::new("stats");
+const FILE_TERMS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("file_terms");
+/// file hash ++ chunk id for every chunk row, so a file's chunks are a range
+const FILE_CHUNKS: TableDefinition<&[u8], ()> = TableDefinition::new("file_chunks");
+/// chunks holding a static first-pass vector, with the search hits each has had
+const UPGRADES: TableDefinition<&[u8], u64> = TableDefinition::new("chunk_upgrade");
+
+/// Layout version of the tables above, recorded under `META_SCHEMA` when an
+/// index is created. Bumped only by changes that old builds cannot read.
+pub const SCHEMA_VERSION: u32 = 1;
+/// meta key holding the schema version (u32 LE) the index was created with
+pub const META_SCHEMA: &str = "schema";
+/// meta key holding the absolute root the index was built from
+pub const META_ROOT: &str = "root";
+/// meta key holding the JSON text-normalization settings chunks were embedded with
+pub const META_NORMALIZE: &str = "normalize";
+/// meta key holding the name of the model chunks were embedded with
+pub const META_MODEL: &str = "model";
+/// meta key holding the JSON `mentat_embedder::VectorInfo` of the stored vectors
+pub const META_VECTORS: &str = "vectors";
+/// meta key holding the JSON chunker settings spans were cut with
+pub const META_CHUNKER: &str = "chunker";
+/// meta key holding the `ChunkerConfig::id` (u64 LE) the last run cut spans with
+pub const META_CHUNKER_ID: &str = "chunker_id";
+/// meta key holding the JSON embed batch tuning picked on this machine
+pub const META_EMBED_TUNING: &str = "embed_tuning";
+/// meta key holding the JSON settings the related-files graph was built with
+pub const META_RELATED: &str = "related";
+/// meta key holding the JSON settings boilerplate scores were computed with
+pub const META_BOILERPLATE: &str = "boilerplate";
+/// meta key holding the JSON similarity quantiles the `percentile` relevance scale uses
+pub const META_RELEVANCE: &str = "relevance";
+/// meta key holding the secret salt path pseudonyms are derived with
+pub const META_PSEUDONYM_SALT: &str = "pseudonym_salt";
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct FileMeta {
     pub path: String,
     pub size: usize,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ChunkMeta {
     pub file_hash: [u8; 32],
     pub start: usize,
     pub end: usize,
     pub span_hash: [u8; 32],
+    /// `ChunkerConfig::id` of the settings that cut this span; 0 for spans
+    /// stored before it was recorded, or imported from elsewhere
+    pub chunker: u64,
+}
+
+/// `ChunkMeta` as written before `chunker` was added. History rows keep
+/// this layout, so their `chunker` reads back as 0.
+#[derive(Serialize, Deserialize)]
+struct ChunkMetaV1 {
+    file_hash: [u8; 32],
+    start: usize,
+    end: usize,
+    span_hash: [u8; 32],
+}
+
+impl From<ChunkMetaV1> for ChunkMeta {
+    fn from(v1: ChunkMetaV1) -> Self {
+        ChunkMeta { file_hash: v1.file_hash, start: v1.start, end: v1.end, span_hash: v1.span_hash, chunker: 0 }
+    }
+}
+
+/// Encoded size of a `ChunkMetaV1` row.
+const CHUNK_META_V1_LEN: usize = 32 + 8 + 8 + 32;
+
+/// Decode a `chunks` row of either layout.
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkMeta> {
+    if bytes.len() == CHUNK_META_V1_LEN {
+        return Ok(bincode::deserialize::<ChunkMetaV1>(bytes)?.into());
+    }
+    Ok(bincode::deserialize(bytes)?)
+}
+
+mod chunk_meta_v1 {
+    use super::{ChunkMeta, ChunkMetaV1};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(m: &ChunkMeta, s: S) -> Result<S::Ok, S::Error> {
+        ChunkMetaV1 { file_hash: m.file_hash, start: m.start, end: m.end, span_hash: m.span_hash }.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<ChunkMeta, D::Error> {
+        ChunkMetaV1::deserialize(d).map(ChunkMeta::from)
+    }
+}
+
+/// What one file version cost the last index run that chunked it, in
+/// microseconds; embedding time is the file's share of its batches by text
+/// length.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct FileTiming {
+    pub chunk_us: u64,
+    pub embed_us: u64,
+    pub store_us: u64,
+    /// chunks embedded (not cached or reused) in that run
+    pub embedded: usize,
+    /// unix seconds of the run
+    pub at: u64,
+}
+
+impl FileTiming {
+    pub fn total_us(&self) -> u64 {
+        self.chunk_us + self.embed_us + self.store_us
+    }
+}
+
+/// A file's related files and their cosine similarity, best first.
+pub type Neighbors = Vec<([u8;32], f32)>;
+
+/// Who to ask about a file: its CODEOWNERS entry and its most frequent
+/// recent committers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Owners {
+    /// `@user`, `@org/team` or emails, as CODEOWNERS lists them
+    pub codeowners: Vec<String>,
+    /// commit author emails, most commits first
+    pub authors: Vec<String>,
+}
+
+/// A file version replaced or removed since indexing, kept for `--as-of` search.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Retired {
+    pub file_hash: [u8; 32],
+    pub meta: FileMeta,
+    pub mtime: Option<u64>,
+    /// None for versions indexed before this was recorded
+    pub indexed_at: Option<u64>,
+    pub retired_at: u64,
+    pub chunks: Vec<RetiredChunk>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RetiredChunk {
+    pub id: [u8; 32],
+    #[serde(with = "chunk_meta_v1")]
+    pub meta: ChunkMeta,
+    /// None if the chunk was never embedded
+    pub embed: Option<Vec<f32>>,
+}
+
+/// Row counts plus references that point at nothing.
+#[derive(Debug, Default, PartialEq)]
+pub struct Integrity {
+    pub files: usize,
+    pub chunks: usize,
+    pub embeds: usize,
+    /// chunk count per `ChunkMeta::chunker`; more than one key means some
+    /// spans were cut under other settings
+    pub chunkers: BTreeMap<u64, usize>,
+    /// chunks whose file_hash has no FileMeta
+    pub dangling_chunks: Vec<[u8; 32]>,
+    /// embeds whose chunk_id has no ChunkMeta
+    pub dangling_embeds: Vec<[u8; 32]>,
+}
+
+impl Integrity {
+    pub fn is_clean(&self) -> bool {
+        self.dangling_chunks.is_empty() && self.dangling_embeds.is_empty()
+    }
 }
 
 pub struct Store {
@@ -34,11 +238,32 @@ pub struct Store {
 
 impl Store {
     pub fn open_default() -> Result<Self> {
-        fs::create_dir_all("index")?;
-        let db = Database::builder().create("index/kv.redb")?;
+        Self::open("index")
+    }
+
+    /// Open an existing store without creating anything (read paths).
+    pub fn open_existing<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref().join("kv.redb");
+        anyhow::ensure!(path.exists(), "no index at {} (run `mentat index` first)", path.display());
+        let db = Database::builder().open(path)?;
+        Ok(Self { db })
+    }
+
+    /// Open (or create) the store at `<dir>/kv.redb`.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let db = Database::builder().create(dir.join("kv.redb"))?;
         // create tables if not exist
         let tx = db.begin_write()?;
-        { tx.open_table(FILES)?; tx.open_table(CHUNKS)?; tx.open_table(EMBEDS)?; }
+        ensure_file_chunks(&tx)?;
+        { tx.open_table(FILES)?; tx.open_table(CHUNKS)?; tx.open_table(EMBEDS)?; tx.open_table(META)?; tx.open_table(MTIMES)?; tx.open_table(ALIASES)?; tx.open_table(PSEUDONYMS)?; tx.open_table(INDEXED_AT)?; tx.open_table(HISTORY)?; tx.open_table(LICENSES)?; tx.open_table(GENERATED)?; tx.open_table(TIMING)?; tx.open_table(RELATED)?; tx.open_table(OWNERS)?; tx.open_table(DOC_TEXT)?; tx.open_table(BOILERPLATE)?; tx.open_table(STATS)?; tx.open_table(FILE_TERMS)?; tx.open_table(UPGRADES)?; tx.open_table(FILE_CHUNKS)?; tx.open_table(FILE_VECS)?; tx.open_table(FILE_LINES)?; tx.open_table(LINE_DF)?; tx.open_table(CHUNK_LINES)?; tx.open_table(LINES_CHANGED)?; tx.open_table(UNSCORED)?; }
+        {
+            let mut meta = tx.open_table(META)?;
+            if meta.get(META_SCHEMA)?.is_none() {
+                meta.insert(META_SCHEMA, SCHEMA_VERSION.to_le_bytes().as_slice())?;
+            }
+        }
         tx.commit()?;
         Ok(Self { db })
     }
@@ -56,10 +281,12 @@ impl Store {
 
     pub fn put_chunk(&self, chunk_id: [u8;32], meta: &ChunkMeta) -> Result<()> {
         let tx = self.db.begin_write()?;
+        ensure_file_chunks(&tx)?;
         {
             let mut t = tx.open_table(CHUNKS)?;
             let val = bincode::serialize(meta)?;
             t.insert(chunk_id.as_slice(), val.as_slice())?;
+            tx.open_table(FILE_CHUNKS)?.insert(file_chunk_key(meta.file_hash, chunk_id).as_slice(), ())?;
         }
         tx.commit()?;
         Ok(())
@@ -75,9 +302,1116 @@ impl Store {
         tx.commit()?;
         Ok(())
     }
+
+    pub fn get_file(&self, file_hash: [u8;32]) -> Result<Option<FileMeta>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(FILES)?;
+        match t.get(file_hash.as_slice())? {
+            Some(v) => Ok(Some(bincode::deserialize(v.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_chunk(&self, chunk_id: [u8;32]) -> Result<Option<ChunkMeta>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(CHUNKS)?;
+        match t.get(chunk_id.as_slice())? {
+            Some(v) => Ok(Some(decode_chunk(v.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_embed(&self, chunk_id: [u8;32]) -> Result<Option<[f32;384]>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(EMBEDS)?;
+        match t.get(chunk_id.as_slice())? {
+            Some(v) => {
+                let mut out = [0f32; 384];
+                bytemuck::cast_slice_mut::<f32, u8>(&mut out).copy_from_slice(v.value());
+                Ok(Some(out))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn put_mtime(&self, file_hash: [u8;32], mtime: u64) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(MTIMES)?;
+            t.insert(file_hash.as_slice(), mtime)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// None for files indexed before mtimes were recorded.
+    pub fn get_mtime(&self, file_hash: [u8;32]) -> Result<Option<u64>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(MTIMES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| v.value()))
+    }
+
+    /// Record when a file version was first indexed; later calls keep the first time.
+    pub fn mark_indexed(&self, file_hash: [u8;32], at: u64) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(INDEXED_AT)?;
+            if t.get(file_hash.as_slice())?.is_none() {
+                t.insert(file_hash.as_slice(), at)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// None for versions indexed before this was recorded.
+    pub fn get_indexed_at(&self, file_hash: [u8;32]) -> Result<Option<u64>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(INDEXED_AT) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| v.value()))
+    }
+
+    pub fn put_license(&self, file_hash: [u8;32], spdx: &str) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(LICENSES)?;
+            t.insert(file_hash.as_slice(), spdx)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// None if no license was detected (or the file predates detection).
+    pub fn get_license(&self, file_hash: [u8;32]) -> Result<Option<String>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(LICENSES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| v.value().to_string()))
+    }
+
+    /// Mark a file as generated, or clear the mark with None.
+    pub fn put_generated(&self, file_hash: [u8;32], reason: Option<&str>) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(GENERATED)?;
+            match reason {
+                Some(r) => { t.insert(file_hash.as_slice(), r)?; }
+                None => { t.remove(file_hash.as_slice())?; }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_generated(&self, file_hash: [u8;32]) -> Result<Option<String>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(GENERATED) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| v.value().to_string()))
+    }
+
+    /// Keep the text of an external document, whose `FileMeta::path` is its
+    /// id (a URL, a ticket key) rather than a path under the root.
+    pub fn put_doc_text(&self, file_hash: [u8;32], text: &[u8]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(DOC_TEXT)?;
+            t.insert(file_hash.as_slice(), text)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Text of an external document; None for files read from the root.
+    pub fn get_doc_text(&self, file_hash: [u8;32]) -> Result<Option<Vec<u8>>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(DOC_TEXT) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| v.value().to_vec()))
+    }
+
+    /// Current contents of an indexed file: the stored text for external
+    /// documents, else the file under the recorded root. None if it is gone.
+    pub fn read_file(&self, file_hash: [u8;32], path: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(text) = self.get_doc_text(file_hash)? {
+            return Ok(Some(text));
+        }
+        let root = self
+            .get_meta(META_ROOT)?
+            .map(|r| PathBuf::from(String::from_utf8_lossy(&r).into_owned()))
+            .unwrap_or_default();
+        match fs::read(root.join(path)) {
+            Ok(d) => Ok(Some(d)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Count a file version's distinct terms into the document frequencies.
+    /// A version already counted is left alone, so re-indexing is idempotent.
+    pub fn put_terms(&self, file_hash: [u8;32], terms: &[String]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut files = tx.open_table(FILE_TERMS)?;
+            if files.get(file_hash.as_slice())?.is_some() {
+                return Ok(());
+            }
+            files.insert(file_hash.as_slice(), bincode::serialize(terms)?.as_slice())?;
+            let mut stats = tx.open_table(STATS)?;
+            for term in terms {
+                let n = stats.get(term.as_str())?.map(|v| v.value()).unwrap_or(0);
+                stats.insert(term.as_str(), n + 1)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Number of file versions containing `term`.
+    pub fn doc_freq(&self, term: &str) -> Result<u64> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(STATS) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(term)?.map(|v| v.value()).unwrap_or(0))
+    }
+
+    /// Number of file versions counted into the statistics.
+    pub fn stats_docs(&self) -> Result<u64> {
+        let tx = self.db.begin_read()?;
+        match tx.open_table(FILE_TERMS) {
+            Ok(t) => Ok(t.len()?),
+            Err(redb::TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// BM25 inverse document frequency: ln(1 + (N - df + 0.5) / (df + 0.5)).
+    pub fn idf(&self, term: &str) -> Result<f32> {
+        let n = self.stats_docs()? as f32;
+        let df = self.doc_freq(term)? as f32;
+        Ok((1.0 + (n - df + 0.5) / (df + 0.5)).ln())
+    }
+
+    /// Replace every chunk's boilerplate score and the line statistics
+    /// they came from, in one transaction.
+    pub fn replace_boilerplate(&self, scores: &[([u8;32], f32)], files: &[([u8;32], Vec<u64>)], chunks: &[([u8;32], Vec<u64>)]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        tx.delete_table(BOILERPLATE)?;
+        tx.delete_table(FILE_LINES)?;
+        tx.delete_table(CHUNK_LINES)?;
+        tx.delete_table(LINE_DF)?;
+        {
+            let mut t = tx.open_table(FILE_LINES)?;
+            for (file_hash, lines) in files {
+                t.insert(file_hash.as_slice(), bincode::serialize(lines)?.as_slice())?;
+                count_lines(&tx, lines, 1)?;
+            }
+            let mut t = tx.open_table(CHUNK_LINES)?;
+            for (id, lines) in chunks {
+                t.insert(id.as_slice(), bincode::serialize(lines)?.as_slice())?;
+            }
+        }
+        write_boilerplate(&tx, scores)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store some chunks' scores and mark the index scored: no line counts
+    /// changed since, no chunk waiting.
+    pub fn update_boilerplate(&self, scores: &[([u8;32], f32)]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        write_boilerplate(&tx, scores)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Count a file version's distinct line hashes into the line document
+    /// frequencies. A version already counted is left alone, as for terms.
+    pub fn put_lines(&self, file_hash: [u8;32], lines: &[u64]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut files = tx.open_table(FILE_LINES)?;
+            if files.get(file_hash.as_slice())?.is_some() {
+                return Ok(());
+            }
+            files.insert(file_hash.as_slice(), bincode::serialize(lines)?.as_slice())?;
+            count_lines(&tx, lines, 1)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record chunks' line hashes; a chunk not recorded before waits to be scored.
+    pub fn put_chunk_lines(&self, rows: &[([u8;32], Vec<u64>)]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(CHUNK_LINES)?;
+            let mut unscored = tx.open_table(UNSCORED)?;
+            for (id, lines) in rows {
+                if t.get(id.as_slice())?.is_none() {
+                    t.insert(id.as_slice(), bincode::serialize(lines)?.as_slice())?;
+                    unscored.insert(id.as_slice(), ())?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Whether a chunk's line hashes are recorded.
+    pub fn has_chunk_lines(&self, chunk_id: [u8;32]) -> Result<bool> {
+        let tx = self.db.begin_read()?;
+        match tx.open_table(CHUNK_LINES) {
+            Ok(t) => Ok(t.get(chunk_id.as_slice())?.is_some()),
+            Err(redb::TableError::TableDoesNotExist(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// File versions counted into the line frequencies.
+    pub fn line_files(&self) -> Result<u64> {
+        let tx = self.db.begin_read()?;
+        match tx.open_table(FILE_LINES) {
+            Ok(t) => Ok(t.len()?),
+            Err(redb::TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Number of file versions each of `lines` occurs in.
+    pub fn line_dfs(&self, lines: &[u64]) -> Result<Vec<u64>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(LINE_DF) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(vec![0; lines.len()]),
+            Err(e) => return Err(e.into()),
+        };
+        lines.iter().map(|h| Ok(t.get(*h)?.map(|v| v.value()).unwrap_or(0))).collect()
+    }
+
+    /// Lines whose frequency changed since scores were stored: (line, the
+    /// frequency it was scored at, the frequency now).
+    pub fn lines_changed(&self) -> Result<Vec<(u64, u64, u64)>> {
+        let tx = self.db.begin_read()?;
+        let (t, df) = match (tx.open_table(LINES_CHANGED), tx.open_table(LINE_DF)) {
+            (Ok(t), Ok(df)) => (t, df),
+            (Err(redb::TableError::TableDoesNotExist(_)), _) | (_, Err(redb::TableError::TableDoesNotExist(_))) => return Ok(Vec::new()),
+            (Err(e), _) | (_, Err(e)) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((k.value(), v.value(), df.get(k.value())?.map(|d| d.value()).unwrap_or(0)));
+        }
+        Ok(out)
+    }
+
+    /// Chunks waiting for a score.
+    pub fn unscored_chunks(&self) -> Result<Vec<[u8;32]>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(UNSCORED) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            out.push(to32(item?.0.value()));
+        }
+        Ok(out)
+    }
+
+    /// Every chunk's line hashes.
+    pub fn chunk_lines(&self) -> Result<Vec<([u8;32], Vec<u64>)>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(CHUNK_LINES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), bincode::deserialize(v.value())?));
+        }
+        Ok(out)
+    }
+
+    /// A chunk's line hashes.
+    pub fn get_chunk_lines(&self, chunk_id: [u8;32]) -> Result<Option<Vec<u64>>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(CHUNK_LINES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(chunk_id.as_slice())?.map(|v| bincode::deserialize(v.value())).transpose()?)
+    }
+
+    /// None for chunks scored before they existed (or never scored).
+    pub fn get_boilerplate(&self, chunk_id: [u8;32]) -> Result<Option<f32>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(BOILERPLATE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(chunk_id.as_slice())?.map(|v| v.value()))
+    }
+
+    /// Replace the whole related-files graph and the file vectors it was
+    /// computed from, in one transaction.
+    pub fn replace_related(&self, graph: &[([u8;32], Neighbors)], vectors: &[([u8;32], [f32;384])]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        tx.delete_table(RELATED)?;
+        tx.delete_table(FILE_VECS)?;
+        write_related(&tx, graph, vectors)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Overwrite some files' related rows and add file vectors, leaving the
+    /// rest of the graph as it is.
+    pub fn update_related(&self, rows: &[([u8;32], Neighbors)], vectors: &[([u8;32], [f32;384])]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        write_related(&tx, rows, vectors)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Every file's related row.
+    pub fn related_rows(&self) -> Result<Vec<([u8;32], Neighbors)>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(RELATED) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), bincode::deserialize(v.value())?));
+        }
+        Ok(out)
+    }
+
+    /// File vectors stored with the related-files graph.
+    pub fn file_vectors(&self) -> Result<Vec<([u8;32], [f32;384])>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(FILE_VECS) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            let mut vec = [0f32; 384];
+            bytemuck::cast_slice_mut::<f32, u8>(&mut vec).copy_from_slice(v.value());
+            out.push((to32(k.value()), vec));
+        }
+        Ok(out)
+    }
+
+    /// Replace every file's owners in one transaction.
+    pub fn replace_owners(&self, owners: &[([u8;32], Owners)]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        tx.delete_table(OWNERS)?;
+        {
+            let mut t = tx.open_table(OWNERS)?;
+            for (file_hash, o) in owners {
+                t.insert(file_hash.as_slice(), bincode::serialize(o)?.as_slice())?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_owners(&self, file_hash: [u8;32]) -> Result<Option<Owners>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(OWNERS) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(file_hash.as_slice())?.map(|v| bincode::deserialize(v.value())).transpose()?)
+    }
+
+    /// Record the timings of one run, in one transaction.
+    pub fn put_timings(&self, timings: &[([u8;32], FileTiming)]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(TIMING)?;
+            for (file_hash, timing) in timings {
+                t.insert(file_hash.as_slice(), bincode::serialize(timing)?.as_slice())?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Every file version's last recorded timing.
+    pub fn timings(&self) -> Result<Vec<([u8;32], FileTiming)>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(TIMING) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), bincode::deserialize(v.value())?));
+        }
+        Ok(out)
+    }
+
+    /// Files most similar to this one, best first; empty if none were recorded.
+    pub fn get_related(&self, file_hash: [u8;32]) -> Result<Neighbors> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(RELATED) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        match t.get(file_hash.as_slice())? {
+            Some(v) => Ok(bincode::deserialize(v.value())?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn put_alias(&self, name: &str, query: &str) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(ALIASES)?;
+            t.insert(name, query)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns whether the alias existed.
+    pub fn remove_alias(&self, name: &str) -> Result<bool> {
+        let tx = self.db.begin_write()?;
+        let existed = {
+            let mut t = tx.open_table(ALIASES)?;
+            let old = t.remove(name)?;
+            old.is_some()
+        };
+        tx.commit()?;
+        Ok(existed)
+    }
+
+    /// All saved aliases in name order.
+    pub fn aliases(&self) -> Result<Vec<(String, String)>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(ALIASES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((k.value().to_string(), v.value().to_string()));
+        }
+        Ok(out)
+    }
+
+    pub fn put_pseudonym(&self, pseudonym: &str, path: &str) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(PSEUDONYMS)?;
+            t.insert(pseudonym, path)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The path a pseudonym was handed out for.
+    pub fn get_pseudonym(&self, pseudonym: &str) -> Result<Option<String>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(PSEUDONYMS) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(pseudonym)?.map(|v| v.value().to_string()))
+    }
+
+    pub fn put_meta(&self, key: &str, val: &[u8]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(META)?;
+            t.insert(key, val)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_meta(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(META) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(t.get(key)?.map(|v| v.value().to_vec()))
+    }
+
+    /// None for indexes created before the version was recorded.
+    pub fn schema_version(&self) -> Result<Option<u32>> {
+        Ok(self.get_meta(META_SCHEMA)?.and_then(|b| Some(u32::from_le_bytes(b.as_slice().try_into().ok()?))))
+    }
+
+    /// All file rows in key order.
+    pub fn files(&self) -> Result<Vec<([u8;32], FileMeta)>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(FILES)?;
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), bincode::deserialize(v.value())?));
+        }
+        Ok(out)
+    }
+
+    pub fn has_embed(&self, chunk_id: [u8;32]) -> Result<bool> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(EMBEDS)?;
+        Ok(t.get(chunk_id.as_slice())?.is_some())
+    }
+
+    /// All chunk rows in key order.
+    pub fn chunks(&self) -> Result<Vec<([u8;32], ChunkMeta)>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(CHUNKS)?;
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), decode_chunk(v.value())?));
+        }
+        Ok(out)
+    }
+
+    /// Up to `n` chunk rows, picked by the lowest blake3(seed || chunk id):
+    /// uniform over the index, and the same rows for the same seed.
+    pub fn sample_chunks(&self, n: usize, seed: u64) -> Result<Vec<([u8;32], ChunkMeta)>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(CHUNKS)?;
+        // the n lowest keys seen so far, highest on top
+        let mut picked: std::collections::BinaryHeap<([u8;32], [u8;32])> = std::collections::BinaryHeap::new();
+        for item in t.iter()? {
+            let (k, _) = item?;
+            let id = to32(k.value());
+            let key = blake32(&[seed.to_le_bytes().as_slice(), &id].concat());
+            if picked.len() < n {
+                picked.push((key, id));
+            } else if picked.peek().is_some_and(|top| key < top.0) {
+                picked.pop();
+                picked.push((key, id));
+            }
+        }
+        let mut out = Vec::with_capacity(picked.len());
+        for (_, id) in picked.into_sorted_vec() {
+            if let Some(v) = t.get(id.as_slice())? {
+                out.push((id, decode_chunk(v.value())?));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Chunk rows belonging to one file version.
+    pub fn file_chunks(&self, file_hash: [u8;32]) -> Result<Vec<([u8;32], ChunkMeta)>> {
+        let tx = self.db.begin_read()?;
+        let chunks = tx.open_table(CHUNKS)?;
+        let mut out = Vec::new();
+        let by_file = match tx.open_table(FILE_CHUNKS) {
+            Ok(t) => t,
+            // written before the index existed and not yet opened for writing
+            Err(redb::TableError::TableDoesNotExist(_)) => {
+                for item in chunks.iter()? {
+                    let (k, v) = item?;
+                    let c = decode_chunk(v.value())?;
+                    if c.file_hash == file_hash {
+                        out.push((to32(k.value()), c));
+                    }
+                }
+                return Ok(out);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for id in chunk_ids_of(&by_file, file_hash)? {
+            if let Some(v) = chunks.get(id.as_slice())? {
+                out.push((id, decode_chunk(v.value())?));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Re-read a chunk's text from disk under the recorded root (or from
+    /// the stored document text). Returns None if the file changed since
+    /// indexing (span hash mismatch).
+    pub fn chunk_text(&self, chunk: &ChunkMeta) -> Result<Option<String>> {
+        let file = self
+            .get_file(chunk.file_hash)?
+            .ok_or_else(|| anyhow::anyhow!("chunk references missing file"))?;
+        let Some(data) = self.read_file(chunk.file_hash, &file.path)? else { return Ok(None) };
+        let Some(slice) = data.get(chunk.start..chunk.end) else { return Ok(None) };
+        if blake32(slice) != chunk.span_hash {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(slice).into_owned()))
+    }
+
+    /// All embeddings in key order.
+    pub fn embeds(&self) -> Result<Vec<([u8;32], [f32;384])>> {
+        let tx = self.db.begin_read()?;
+        let t = tx.open_table(EMBEDS)?;
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            let mut emb = [0f32; 384];
+            bytemuck::cast_slice_mut::<f32, u8>(&mut emb).copy_from_slice(v.value());
+            out.push((to32(k.value()), emb));
+        }
+        Ok(out)
+    }
+
+    /// Remove a file and every chunk/embed derived from it, in one transaction.
+    pub fn delete_file(&self, file_hash: [u8;32]) -> Result<usize> {
+        let tx = self.db.begin_write()?;
+        ensure_file_chunks(&tx)?;
+        let removed;
+        {
+            let mut files = tx.open_table(FILES)?;
+            let mut chunks = tx.open_table(CHUNKS)?;
+            let mut embeds = tx.open_table(EMBEDS)?;
+            let mut mtimes = tx.open_table(MTIMES)?;
+            let mut indexed = tx.open_table(INDEXED_AT)?;
+            let mut licenses = tx.open_table(LICENSES)?;
+            tx.open_table(DOC_TEXT)?.remove(file_hash.as_slice())?;
+            forget_terms(&tx, file_hash)?;
+            forget_lines(&tx, file_hash)?;
+            files.remove(file_hash.as_slice())?;
+            mtimes.remove(file_hash.as_slice())?;
+            indexed.remove(file_hash.as_slice())?;
+            licenses.remove(file_hash.as_slice())?;
+            tx.open_table(GENERATED)?.remove(file_hash.as_slice())?;
+            tx.open_table(TIMING)?.remove(file_hash.as_slice())?;
+            tx.open_table(RELATED)?.remove(file_hash.as_slice())?;
+            tx.open_table(FILE_VECS)?.remove(file_hash.as_slice())?;
+            let mut by_file = tx.open_table(FILE_CHUNKS)?;
+            let ids = chunk_ids_of(&by_file, file_hash)?;
+            let mut upgrades = tx.open_table(UPGRADES)?;
+            for id in &ids {
+                chunks.remove(id.as_slice())?;
+                embeds.remove(id.as_slice())?;
+                upgrades.remove(id.as_slice())?;
+                by_file.remove(file_chunk_key(file_hash, *id).as_slice())?;
+                forget_chunk_lines(&tx, *id)?;
+            }
+            removed = ids.len();
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Like `delete_file`, but first copy the version with its chunks and
+    /// vectors into the history table, in the same transaction.
+    pub fn retire_file(&self, file_hash: [u8;32], at: u64) -> Result<usize> {
+        let tx = self.db.begin_write()?;
+        ensure_file_chunks(&tx)?;
+        let removed;
+        {
+            let mut files = tx.open_table(FILES)?;
+            let mut chunks = tx.open_table(CHUNKS)?;
+            let mut embeds = tx.open_table(EMBEDS)?;
+            let mut mtimes = tx.open_table(MTIMES)?;
+            let mut indexed = tx.open_table(INDEXED_AT)?;
+            let mut history = tx.open_table(HISTORY)?;
+            tx.open_table(LICENSES)?.remove(file_hash.as_slice())?;
+            tx.open_table(GENERATED)?.remove(file_hash.as_slice())?;
+            tx.open_table(TIMING)?.remove(file_hash.as_slice())?;
+            tx.open_table(RELATED)?.remove(file_hash.as_slice())?;
+            tx.open_table(FILE_VECS)?.remove(file_hash.as_slice())?;
+            tx.open_table(DOC_TEXT)?.remove(file_hash.as_slice())?;
+            forget_terms(&tx, file_hash)?;
+            forget_lines(&tx, file_hash)?;
+            let Some(meta) = files.remove(file_hash.as_slice())? else { return Ok(0) };
+            let meta: FileMeta = bincode::deserialize(meta.value())?;
+            let mtime = mtimes.remove(file_hash.as_slice())?.map(|v| v.value());
+            let indexed_at = indexed.remove(file_hash.as_slice())?.map(|v| v.value());
+            let mut by_file = tx.open_table(FILE_CHUNKS)?;
+            let mut kept = Vec::new();
+            for id in chunk_ids_of(&by_file, file_hash)? {
+                if let Some(v) = chunks.get(id.as_slice())? {
+                    kept.push(RetiredChunk { id, meta: decode_chunk(v.value())?, embed: None });
+                }
+                by_file.remove(file_chunk_key(file_hash, id).as_slice())?;
+            }
+            let mut upgrades = tx.open_table(UPGRADES)?;
+            for c in &mut kept {
+                chunks.remove(c.id.as_slice())?;
+                upgrades.remove(c.id.as_slice())?;
+                forget_chunk_lines(&tx, c.id)?;
+                c.embed = embeds.remove(c.id.as_slice())?.map(|v| {
+                    v.value().chunks_exact(4).map(|b| f32::from_ne_bytes(b.try_into().expect("4 bytes"))).collect()
+                });
+            }
+            removed = kept.len();
+            let row = Retired { file_hash, meta, mtime, indexed_at, retired_at: at, chunks: kept };
+            history.insert(history_key(file_hash, at).as_slice(), bincode::serialize(&row)?.as_slice())?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// All retired versions, oldest retirement last within each file.
+    pub fn history(&self) -> Result<Vec<Retired>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(HISTORY) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (_, v) = item?;
+            out.push(bincode::deserialize(v.value())?);
+        }
+        Ok(out)
+    }
+
+    /// Drop history rows retired before `before`; returns how many went.
+    pub fn prune_history(&self, before: u64) -> Result<usize> {
+        let tx = self.db.begin_write()?;
+        let removed;
+        {
+            let mut t = tx.open_table(HISTORY)?;
+            let mut old = Vec::new();
+            for item in t.iter()? {
+                let (k, _) = item?;
+                let key = k.value();
+                let at = u64::from_be_bytes(key[32..].try_into().expect("40-byte key"));
+                if at < before {
+                    old.push(key.to_vec());
+                }
+            }
+            for k in &old {
+                t.remove(k.as_slice())?;
+            }
+            removed = old.len();
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Mark chunks as holding a first-pass vector to be replaced by the
+    /// full model's.
+    pub fn queue_upgrades(&self, ids: &[[u8;32]]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(UPGRADES)?;
+            for id in ids {
+                if t.get(id.as_slice())?.is_none() {
+                    t.insert(id.as_slice(), 0)?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn upgrade_queued(&self, chunk_id: [u8;32]) -> Result<bool> {
+        let tx = self.db.begin_read()?;
+        match tx.open_table(UPGRADES) {
+            Ok(t) => Ok(t.get(chunk_id.as_slice())?.is_some()),
+            Err(redb::TableError::TableDoesNotExist(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Count a search hit for each of `ids` still waiting for an upgrade.
+    /// Writes nothing when none are, so fully embedded indexes pay one read.
+    pub fn note_hits(&self, ids: &[[u8;32]]) -> Result<()> {
+        let mut queued = Vec::new();
+        for id in ids {
+            if self.upgrade_queued(*id)? {
+                queued.push(*id);
+            }
+        }
+        if queued.is_empty() {
+            return Ok(());
+        }
+        let tx = self.db.begin_write()?;
+        {
+            let mut t = tx.open_table(UPGRADES)?;
+            for id in &queued {
+                let hits = t.get(id.as_slice())?.map(|v| v.value()).unwrap_or(0);
+                t.insert(id.as_slice(), hits + 1)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Chunks waiting for an upgrade, most searched first.
+    pub fn upgrade_queue(&self) -> Result<Vec<([u8;32], u64)>> {
+        let tx = self.db.begin_read()?;
+        let t = match tx.open_table(UPGRADES) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for item in t.iter()? {
+            let (k, v) = item?;
+            out.push((to32(k.value()), v.value()));
+        }
+        out.sort_by_key(|e| std::cmp::Reverse(e.1));
+        Ok(out)
+    }
+
+    /// Store upgraded vectors and take their chunks (plus `dropped`, which
+    /// no longer need one) off the queue, in one transaction. A chunk that
+    /// left the queue meanwhile (replaced or re-embedded) keeps its vector,
+    /// and one deleted meanwhile gets none.
+    pub fn finish_upgrades(&self, done: &[([u8;32], [f32;384])], dropped: &[[u8;32]]) -> Result<usize> {
+        let tx = self.db.begin_write()?;
+        let mut stored = 0;
+        {
+            let mut queue = tx.open_table(UPGRADES)?;
+            let mut embeds = tx.open_table(EMBEDS)?;
+            let chunks = tx.open_table(CHUNKS)?;
+            let mut file_vecs = tx.open_table(FILE_VECS)?;
+            for (id, emb) in done {
+                if queue.remove(id.as_slice())?.is_some() {
+                    let Some(meta) = chunks.get(id.as_slice())?.map(|v| decode_chunk(v.value())).transpose()? else { continue };
+                    embeds.insert(id.as_slice(), cast_slice::<f32, u8>(emb))?;
+                    // the file's mean moved; the related-files graph recomputes it
+                    file_vecs.remove(meta.file_hash.as_slice())?;
+                    stored += 1;
+                }
+            }
+            for id in dropped {
+                queue.remove(id.as_slice())?;
+            }
+        }
+        tx.commit()?;
+        Ok(stored)
+    }
+
+    /// Remove chunk rows and their embeddings, in one transaction.
+    pub fn delete_chunks(&self, ids: &[[u8;32]]) -> Result<()> {
+        let tx = self.db.begin_write()?;
+        ensure_file_chunks(&tx)?;
+        {
+            let mut chunks = tx.open_table(CHUNKS)?;
+            let mut embeds = tx.open_table(EMBEDS)?;
+            let mut upgrades = tx.open_table(UPGRADES)?;
+            let mut by_file = tx.open_table(FILE_CHUNKS)?;
+            for id in ids {
+                if let Some(v) = chunks.remove(id.as_slice())? {
+                    by_file.remove(file_chunk_key(decode_chunk(v.value())?.file_hash, *id).as_slice())?;
+                }
+                embeds.remove(id.as_slice())?;
+                upgrades.remove(id.as_slice())?;
+                forget_chunk_lines(&tx, *id)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Scan all tables and report references that don't resolve.
+    pub fn integrity(&self) -> Result<Integrity> {
+        let tx = self.db.begin_read()?;
+        let files = tx.open_table(FILES)?;
+        let chunks = tx.open_table(CHUNKS)?;
+        let embeds = tx.open_table(EMBEDS)?;
+        let mut out = Integrity { files: files.len()? as usize, ..Default::default() };
+        for item in chunks.iter()? {
+            let (k, v) = item?;
+            out.chunks += 1;
+            let meta = decode_chunk(v.value())?;
+            *out.chunkers.entry(meta.chunker).or_default() += 1;
+            if files.get(meta.file_hash.as_slice())?.is_none() {
+                out.dangling_chunks.push(to32(k.value()));
+            }
+        }
+        for item in embeds.iter()? {
+            let (k, _) = item?;
+            out.embeds += 1;
+            if chunks.get(k.value())?.is_none() {
+                out.dangling_embeds.push(to32(k.value()));
+            }
+        }
+        Ok(out)
+    }
 }
 
 // helpers
+/// Take a file version's terms back out of the document frequencies.
+fn forget_terms(tx: &WriteTransaction, file_hash: [u8;32]) -> Result<()> {
+    let Some(row) = tx.open_table(FILE_TERMS)?.remove(file_hash.as_slice())?.map(|v| v.value().to_vec()) else {
+        return Ok(());
+    };
+    let terms: Vec<String> = bincode::deserialize(&row)?;
+    let mut stats = tx.open_table(STATS)?;
+    for term in &terms {
+        let n = stats.get(term.as_str())?.map(|v| v.value()).unwrap_or(0);
+        if n <= 1 {
+            stats.remove(term.as_str())?;
+        } else {
+            stats.insert(term.as_str(), n - 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add `delta` to each line's file count, remembering the count it was
+/// last scored at.
+fn count_lines(tx: &WriteTransaction, lines: &[u64], delta: i64) -> Result<()> {
+    let mut df = tx.open_table(LINE_DF)?;
+    let mut changed = tx.open_table(LINES_CHANGED)?;
+    for h in lines {
+        let n = df.get(*h)?.map(|v| v.value()).unwrap_or(0);
+        if changed.get(*h)?.is_none() {
+            changed.insert(*h, n)?;
+        }
+        match n.saturating_add_signed(delta) {
+            0 => df.remove(*h)?,
+            m => df.insert(*h, m)?,
+        };
+    }
+    Ok(())
+}
+
+fn forget_lines(tx: &WriteTransaction, file_hash: [u8;32]) -> Result<()> {
+    let Some(row) = tx.open_table(FILE_LINES)?.remove(file_hash.as_slice())?.map(|v| v.value().to_vec()) else {
+        return Ok(());
+    };
+    count_lines(tx, &bincode::deserialize::<Vec<u64>>(&row)?, -1)
+}
+
+/// Drop a removed chunk's score and line hashes.
+fn forget_chunk_lines(tx: &WriteTransaction, chunk_id: [u8;32]) -> Result<()> {
+    tx.open_table(BOILERPLATE)?.remove(chunk_id.as_slice())?;
+    tx.open_table(CHUNK_LINES)?.remove(chunk_id.as_slice())?;
+    tx.open_table(UNSCORED)?.remove(chunk_id.as_slice())?;
+    Ok(())
+}
+
+/// Store scores, and take every chunk and line as scored.
+fn write_boilerplate(tx: &WriteTransaction, scores: &[([u8;32], f32)]) -> Result<()> {
+    let mut t = tx.open_table(BOILERPLATE)?;
+    for (id, s) in scores {
+        t.insert(id.as_slice(), *s)?;
+    }
+    tx.delete_table(LINES_CHANGED)?;
+    tx.delete_table(UNSCORED)?;
+    tx.open_table(LINES_CHANGED)?;
+    tx.open_table(UNSCORED)?;
+    Ok(())
+}
+
 pub fn blake32(bytes: &[u8]) -> [u8;32] {
     blake3::hash(bytes).as_bytes().to_owned()
 }
+
+/// chunk id = blake3(file_hash || start || end || chunker), all as u64 LE.
+/// `chunker` 0 leaves it out, which keeps the ids of spans stored before
+/// it was recorded.
+pub fn chunk_id(file_hash: [u8;32], start: usize, end: usize, chunker: u64) -> [u8;32] {
+    let mut id_src = Vec::with_capacity(32 + 24);
+    id_src.extend_from_slice(&file_hash);
+    id_src.extend_from_slice(&(start as u64).to_le_bytes());
+    id_src.extend_from_slice(&(end as u64).to_le_bytes());
+    if chunker != 0 {
+        id_src.extend_from_slice(&chunker.to_le_bytes());
+    }
+    blake32(&id_src)
+}
+
+fn history_key(file_hash: [u8;32], retired_at: u64) -> Vec<u8> {
+    let mut k = file_hash.to_vec();
+    k.extend_from_slice(&retired_at.to_be_bytes());
+    k
+}
+
+fn write_related(tx: &WriteTransaction, rows: &[([u8;32], Neighbors)], vectors: &[([u8;32], [f32;384])]) -> Result<()> {
+    let mut t = tx.open_table(RELATED)?;
+    for (file_hash, neighbors) in rows {
+        t.insert(file_hash.as_slice(), bincode::serialize(neighbors)?.as_slice())?;
+    }
+    let mut t = tx.open_table(FILE_VECS)?;
+    for (file_hash, v) in vectors {
+        t.insert(file_hash.as_slice(), cast_slice::<f32, u8>(v))?;
+    }
+    Ok(())
+}
+
+fn file_chunk_key(file_hash: [u8;32], chunk_id: [u8;32]) -> [u8;64] {
+    let mut k = [0u8; 64];
+    k[..32].copy_from_slice(&file_hash);
+    k[32..].copy_from_slice(&chunk_id);
+    k
+}
+
+/// Ids of `file_hash`'s chunks, from the `FILE_CHUNKS` range under it.
+fn chunk_ids_of(t: &impl ReadableTable<&'static [u8], ()>, file_hash: [u8;32]) -> Result<Vec<[u8;32]>> {
+    let (lo, hi) = (file_chunk_key(file_hash, [0; 32]), file_chunk_key(file_hash, [0xff; 32]));
+    let mut out = Vec::new();
+    for item in t.range::<&[u8]>(lo.as_slice()..=hi.as_slice())? {
+        out.push(to32(&item?.0.value()[32..]));
+    }
+    Ok(out)
+}
+
+/// Rebuild `FILE_CHUNKS` from the chunk rows.
+fn reindex_file_chunks(tx: &WriteTransaction) -> Result<()> {
+    tx.delete_table(FILE_CHUNKS)?;
+    let chunks = tx.open_table(CHUNKS)?;
+    let mut by_file = tx.open_table(FILE_CHUNKS)?;
+    for item in chunks.iter()? {
+        let (k, v) = item?;
+        by_file.insert(file_chunk_key(decode_chunk(v.value())?.file_hash, to32(k.value())).as_slice(), ())?;
+    }
+    Ok(())
+}
+
+/// Fill `FILE_CHUNKS` for an index written before it existed. Called at
+/// the start of each write that touches it, so read-only opens never write.
+fn ensure_file_chunks(tx: &WriteTransaction) -> Result<()> {
+    if !tx.list_tables()?.any(|t| t.name() == FILE_CHUNKS.name()) {
+        reindex_file_chunks(tx)?;
+    }
+    Ok(())
+}
+
+fn to32(b: &[u8]) -> [u8;32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(b);
+    out
+}
@@ -0,0 +1,226 @@
+//! Copy whatever still reads out of a damaged store into a fresh one.
+//!
+//! The source file is copied first and only the copy is opened, since redb
+//! repairs an uncleanly closed file in place on open. Each row is checked
+//! (key length, value decodes) before it is written; a table whose scan hits
+//! an unreadable page keeps the rows read up to that point. Afterwards,
+//! chunks without a file and embeddings without a chunk are dropped, and
+//! the `stats`, `line_df` and `file_chunks` tables are rebuilt from the
+//! surviving `file_terms`, `file_lines` and chunk rows.
+
+use crate::{
+    decode_chunk, to32, FileMeta, FileTiming, Owners, Retired, Store, ALIASES, BOILERPLATE, CHUNKS, CHUNK_LINES, DOC_TEXT, EMBEDS, FILES, FILE_LINES, FILE_TERMS,
+    FILE_VECS, GENERATED, HISTORY, INDEXED_AT, LICENSES, LINE_DF, META, MTIMES, OWNERS, PSEUDONYMS, RELATED, STATS, TIMING, UNSCORED,
+};
+use anyhow::Result;
+use redb::{Database, Key, ReadTransaction, ReadableTable, TableDefinition, TableHandle, Value, WriteTransaction};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+#[derive(Serialize, Debug, Default)]
+pub struct TableSalvage {
+    pub table: String,
+    pub copied: usize,
+    /// rows read but rejected (bad key length, value does not decode)
+    pub invalid: usize,
+    /// the error that ended the scan early, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+    /// table absent from the source (an index from before it existed)
+    pub missing: bool,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Salvage {
+    pub tables: Vec<TableSalvage>,
+    pub dangling_chunks: usize,
+    pub dangling_embeds: usize,
+}
+
+impl Salvage {
+    /// Nothing was dropped or left unread.
+    pub fn is_lossless(&self) -> bool {
+        self.dangling_chunks == 0
+            && self.dangling_embeds == 0
+            && self.tables.iter().all(|t| t.invalid == 0 && t.stopped.is_none())
+    }
+}
+
+type Check = fn(&[u8], &[u8]) -> bool;
+
+fn hash_key(k: &[u8]) -> bool {
+    k.len() == 32
+}
+
+/// Salvage `<src>/kv.redb` into a new store at `<dst>/kv.redb`, which must not exist.
+pub fn salvage(src: &Path, dst: &Path) -> Result<Salvage> {
+    let src_file = src.join("kv.redb");
+    anyhow::ensure!(src_file.exists(), "no store at {}", src_file.display());
+    anyhow::ensure!(!dst.join("kv.redb").exists(), "{} already exists", dst.join("kv.redb").display());
+    fs::create_dir_all(dst)?;
+    let copy = dst.join("source.redb");
+    fs::copy(&src_file, &copy)?;
+    let result = (|| -> Result<Salvage> {
+        let db = Database::builder().open(&copy)?;
+        let out = Store::open(dst)?;
+        let rx = db.begin_read()?;
+        let tx = out.db.begin_write()?;
+        let mut rep = Salvage::default();
+        rep.tables.push(copy_table(&rx, &tx, FILES, |k, v| hash_key(k) && bincode::deserialize::<FileMeta>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, CHUNKS, |k, v| hash_key(k) && decode_chunk(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, EMBEDS, |k, v| hash_key(k) && v.len() == 384 * 4)?);
+        rep.tables.push(copy_table(&rx, &tx, META, |_, _| true)?);
+        rep.tables.push(copy_table(&rx, &tx, MTIMES, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, ALIASES, |_, _| true)?);
+        rep.tables.push(copy_table(&rx, &tx, PSEUDONYMS, |_, _| true)?);
+        rep.tables.push(copy_table(&rx, &tx, INDEXED_AT, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, HISTORY, |k, v| k.len() == 40 && bincode::deserialize::<Retired>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, LICENSES, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, GENERATED, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, TIMING, |k, v| hash_key(k) && bincode::deserialize::<FileTiming>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, RELATED, |k, v| hash_key(k) && bincode::deserialize::<crate::Neighbors>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, FILE_VECS, |k, v| hash_key(k) && v.len() == 384 * 4)?);
+        rep.tables.push(copy_table(&rx, &tx, OWNERS, |k, v| hash_key(k) && bincode::deserialize::<Owners>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, DOC_TEXT, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, BOILERPLATE, |k, _| hash_key(k))?);
+        rep.tables.push(copy_table(&rx, &tx, FILE_TERMS, |k, v| hash_key(k) && bincode::deserialize::<Vec<String>>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, FILE_LINES, |k, v| hash_key(k) && bincode::deserialize::<Vec<u64>>(v).is_ok())?);
+        rep.tables.push(copy_table(&rx, &tx, CHUNK_LINES, |k, v| hash_key(k) && bincode::deserialize::<Vec<u64>>(v).is_ok())?);
+        recount_stats(&tx)?;
+        recount_lines(&tx)?;
+        crate::reindex_file_chunks(&tx)?;
+        tx.commit()?;
+        drop(rx);
+        drop(db);
+        let integ = out.integrity()?;
+        rep.dangling_chunks = integ.dangling_chunks.len();
+        rep.dangling_embeds = integ.dangling_embeds.len();
+        let dangling: Vec<[u8; 32]> = integ.dangling_chunks.iter().chain(&integ.dangling_embeds).copied().collect();
+        out.delete_chunks(&dangling)?;
+        Ok(rep)
+    })();
+    let _ = fs::remove_file(&copy);
+    result
+}
+
+fn copy_table<K: Key + 'static, V: Value + 'static>(
+    rx: &ReadTransaction,
+    tx: &WriteTransaction,
+    def: TableDefinition<K, V>,
+    valid: Check,
+) -> Result<TableSalvage> {
+    let mut rep = TableSalvage { table: def.name().to_string(), ..Default::default() };
+    let src = match rx.open_table(def) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => {
+            rep.missing = true;
+            return Ok(rep);
+        }
+        Err(e) => {
+            rep.stopped = Some(e.to_string());
+            return Ok(rep);
+        }
+    };
+    let mut dst = tx.open_table(def)?;
+    let iter = match src.iter() {
+        Ok(it) => it,
+        Err(e) => {
+            rep.stopped = Some(e.to_string());
+            return Ok(rep);
+        }
+    };
+    for item in iter {
+        let (k, v) = match item {
+            Ok(kv) => kv,
+            Err(e) => {
+                rep.stopped = Some(e.to_string());
+                break;
+            }
+        };
+        let (k, v) = (k.value(), v.value());
+        if !valid(K::as_bytes(&k).as_ref(), V::as_bytes(&v).as_ref()) {
+            rep.invalid += 1;
+            continue;
+        }
+        dst.insert(k, v)?;
+        rep.copied += 1;
+    }
+    Ok(rep)
+}
+
+/// Rebuild the document frequencies from `file_terms`, dropping term rows
+/// of file versions that did not survive.
+fn recount_stats(tx: &WriteTransaction) -> Result<()> {
+    let files: HashSet<[u8; 32]> = tx.open_table(FILES)?.iter()?.filter_map(|r| r.ok()).map(|(k, _)| to32(k.value())).collect();
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut orphans = Vec::new();
+    {
+        let terms = tx.open_table(FILE_TERMS)?;
+        for item in terms.iter()? {
+            let (k, v) = item?;
+            if !files.contains(&to32(k.value())) {
+                orphans.push(k.value().to_vec());
+                continue;
+            }
+            for t in bincode::deserialize::<Vec<String>>(v.value())? {
+                *counts.entry(t).or_default() += 1;
+            }
+        }
+    }
+    let mut terms = tx.open_table(FILE_TERMS)?;
+    for k in &orphans {
+        terms.remove(k.as_slice())?;
+    }
+    let mut stats = tx.open_table(STATS)?;
+    for (t, n) in &counts {
+        stats.insert(t.as_str(), *n)?;
+    }
+    Ok(())
+}
+
+/// Rebuild the line frequencies from `file_lines`, dropping line rows of
+/// file versions and chunks that did not survive. Every surviving chunk is
+/// left to be rescored, since which lines changed since the last scoring is
+/// not copied.
+fn recount_lines(tx: &WriteTransaction) -> Result<()> {
+    let files: HashSet<[u8; 32]> = tx.open_table(FILES)?.iter()?.filter_map(|r| r.ok()).map(|(k, _)| to32(k.value())).collect();
+    let chunks: HashSet<[u8; 32]> = tx.open_table(CHUNKS)?.iter()?.filter_map(|r| r.ok()).map(|(k, _)| to32(k.value())).collect();
+    let mut counts: HashMap<u64, u64> = HashMap::new();
+    let mut orphans = Vec::new();
+    {
+        let lines = tx.open_table(FILE_LINES)?;
+        for item in lines.iter()? {
+            let (k, v) = item?;
+            if !files.contains(&to32(k.value())) {
+                orphans.push(k.value().to_vec());
+                continue;
+            }
+            for h in bincode::deserialize::<Vec<u64>>(v.value())? {
+                *counts.entry(h).or_default() += 1;
+            }
+        }
+    }
+    let mut lines = tx.open_table(FILE_LINES)?;
+    for k in &orphans {
+        lines.remove(k.as_slice())?;
+    }
+    let mut df = tx.open_table(LINE_DF)?;
+    for (h, n) in &counts {
+        df.insert(*h, *n)?;
+    }
+    let mut by_chunk = tx.open_table(CHUNK_LINES)?;
+    let ids: Vec<[u8; 32]> = by_chunk.iter()?.filter_map(|r| r.ok()).map(|(k, _)| to32(k.value())).collect();
+    let mut unscored = tx.open_table(UNSCORED)?;
+    for id in &ids {
+        if chunks.contains(id) {
+            unscored.insert(id.as_slice(), ())?;
+        } else {
+            by_chunk.remove(id.as_slice())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Reusable proptest generators and a reference model of the store.
+//! Enabled for this crate's tests and, via the `testkit` feature, for others.
+
+use crate::{chunk_id, ChunkMeta, FileMeta, Store};
+use anyhow::Result;
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+pub fn hash32() -> impl Strategy<Value = [u8; 32]> {
+    any::<[u8; 32]>()
+}
+
+pub fn file_meta() -> impl Strategy<Value = FileMeta> {
+    ("[a-z0-9_./-]{1,48}", 0usize..1 << 24).prop_map(|(path, size)| FileMeta { path, size })
+}
+
+pub fn chunk_meta() -> impl Strategy<Value = ChunkMeta> {
+    (hash32(), 0usize..1 << 24, 1usize..8192, hash32(), any::<u64>()).prop_map(|(file_hash, start, len, span_hash, chunker)| {
+        ChunkMeta { file_hash, start, end: start + len, span_hash, chunker }
+    })
+}
+
+pub fn embedding() -> impl Strategy<Value = [f32; 384]> {
+    prop::collection::vec(-1f32..1f32, 384).prop_map(|v| {
+        let mut out = [0f32; 384];
+        out.copy_from_slice(&v);
+        out
+    })
+}
+
+/// Store mutation addressed by small indices so sequences collide often.
+#[derive(Clone, Debug)]
+pub enum Op {
+    PutFile(u8),
+    PutChunk { file: u8, start: u16, len: u16 },
+    PutEmbed { file: u8, start: u16, len: u16 },
+    DeleteFile(u8),
+}
+
+pub fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (0u8..4).prop_map(Op::PutFile),
+        (0u8..4, any::<u16>(), 1u16..4096).prop_map(|(file, start, len)| Op::PutChunk { file, start, len }),
+        (0u8..4, any::<u16>(), 1u16..4096).prop_map(|(file, start, len)| Op::PutEmbed { file, start, len }),
+        (0u8..4).prop_map(Op::DeleteFile),
+    ]
+}
+
+/// `ChunkMeta::chunker` of the chunks the model writes.
+pub const CHUNKER: u64 = 1;
+
+pub fn file_hash_of(file: u8) -> [u8; 32] {
+    crate::blake32(&[file])
+}
+
+/// In-memory mirror of what the store should contain.
+#[derive(Default, Debug)]
+pub struct Model {
+    pub files: BTreeMap<[u8; 32], FileMeta>,
+    pub chunks: BTreeMap<[u8; 32], ChunkMeta>,
+    pub embeds: BTreeMap<[u8; 32], [f32; 384]>,
+}
+
+impl Model {
+    /// Apply `op` to both model and store, following indexer ordering rules
+    /// (chunks only for known files, embeds only for known chunks).
+    pub fn apply(&mut self, store: &Store, op: &Op) -> Result<()> {
+        match *op {
+            Op::PutFile(f) => {
+                let h = file_hash_of(f);
+                let meta = FileMeta { path: format!("file{f}.txt"), size: f as usize * 100 };
+                store.put_file(h, &meta)?;
+                self.files.insert(h, meta);
+            }
+            Op::PutChunk { file, start, len } => {
+                let h = file_hash_of(file);
+                if !self.files.contains_key(&h) {
+                    return Ok(());
+                }
+                let (start, end) = (start as usize, start as usize + len as usize);
+                let id = chunk_id(h, start, end, CHUNKER);
+                let meta = ChunkMeta { file_hash: h, start, end, span_hash: crate::blake32(&id), chunker: CHUNKER };
+                store.put_chunk(id, &meta)?;
+                self.chunks.insert(id, meta);
+            }
+            Op::PutEmbed { file, start, len } => {
+                let id = chunk_id(file_hash_of(file), start as usize, start as usize + len as usize, CHUNKER);
+                if !self.chunks.contains_key(&id) {
+                    return Ok(());
+                }
+                let mut emb = [0f32; 384];
+                emb[0] = start as f32;
+                emb[1] = len as f32;
+                store.put_embed(id, &emb)?;
+                self.embeds.insert(id, emb);
+            }
+            Op::DeleteFile(f) => {
+                let h = file_hash_of(f);
+                store.delete_file(h)?;
+                self.files.remove(&h);
+                let gone: Vec<_> = self.chunks.iter().filter(|(_, c)| c.file_hash == h).map(|(k, _)| *k).collect();
+                for id in gone {
+                    self.chunks.remove(&id);
+                    self.embeds.remove(&id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assert the store holds exactly the model's rows.
+    pub fn check(&self, store: &Store) -> Result<()> {
+        for (h, meta) in &self.files {
+            anyhow::ensure!(store.get_file(*h)?.as_ref() == Some(meta), "file row mismatch");
+        }
+        for (id, meta) in &self.chunks {
+            anyhow::ensure!(store.get_chunk(*id)?.as_ref() == Some(meta), "chunk row mismatch");
+        }
+        for (id, emb) in &self.embeds {
+            anyhow::ensure!(store.get_embed(*id)?.as_ref() == Some(emb), "embed row mismatch");
+        }
+        for h in self.files.keys() {
+            let mut want: Vec<[u8; 32]> = self.chunks.iter().filter(|(_, c)| c.file_hash == *h).map(|(id, _)| *id).collect();
+            let mut got: Vec<[u8; 32]> = store.file_chunks(*h)?.into_iter().map(|(id, _)| id).collect();
+            want.sort();
+            got.sort();
+            anyhow::ensure!(got == want, "file_chunks mismatch");
+        }
+        let integ = store.integrity()?;
+        anyhow::ensure!(integ.files == self.files.len(), "file count {} != {}", integ.files, self.files.len());
+        anyhow::ensure!(integ.chunks == self.chunks.len(), "chunk count mismatch");
+        anyhow::ensure!(integ.embeds == self.embeds.len(), "embed count mismatch");
+        anyhow::ensure!(integ.is_clean(), "dangling references: {integ:?}");
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Property tests: encoding stability, chunk-id derivation, op sequences.
+
+use mentat_store::testkit::{self, Model};
+use mentat_store::{blake32, chunk_id, decode_chunk, ChunkMeta, FileMeta, Store};
+use proptest::prelude::*;
+
+// Pinned bincode encoding. If this changes, existing indexes stop decoding.
+const FILE_META_V1: &[u8] = &[
+    9, 0, 0, 0, 0, 0, 0, 0, b's', b'r', b'c', b'/', b'a', b'.', b'r', b's', b'x', // path
+    0x39, 0x30, 0, 0, 0, 0, 0, 0, // size = 12345
+];
+
+fn chunk_meta_v1() -> (ChunkMeta, Vec<u8>) {
+    let meta = ChunkMeta { file_hash: [1; 32], start: 600, end: 6600, span_hash: [2; 32], chunker: 0 };
+    let mut bytes = vec![1u8; 32];
+    bytes.extend_from_slice(&600u64.to_le_bytes());
+    bytes.extend_from_slice(&6600u64.to_le_bytes());
+    bytes.extend_from_slice(&[2u8; 32]);
+    (meta, bytes)
+}
+
+fn chunk_meta_v2() -> (ChunkMeta, Vec<u8>) {
+    let (mut meta, mut bytes) = chunk_meta_v1();
+    meta.chunker = 0x0102;
+    bytes.extend_from_slice(&0x0102u64.to_le_bytes());
+    (meta, bytes)
+}
+
+#[test]
+fn file_meta_encoding_is_pinned() {
+    let meta = FileMeta { path: "src/a.rsx".into(), size: 12345 };
+    assert_eq!(bincode::serialize(&meta).unwrap(), FILE_META_V1);
+    assert_eq!(bincode::deserialize::<FileMeta>(FILE_META_V1).unwrap(), meta);
+}
+
+#[test]
+fn chunk_meta_encoding_is_pinned() {
+    let (meta, bytes) = chunk_meta_v2();
+    assert_eq!(bincode::serialize(&meta).unwrap(), bytes);
+    assert_eq!(decode_chunk(&bytes).unwrap(), meta);
+    // rows written before `chunker` existed still decode
+    let (meta, bytes) = chunk_meta_v1();
+    assert_eq!(decode_chunk(&bytes).unwrap(), meta);
+}
+
+#[test]
+fn chunk_id_derivation_is_pinned() {
+    let src = [[7u8; 32].as_slice(), &0u64.to_le_bytes(), &6000u64.to_le_bytes()].concat();
+    assert_eq!(chunk_id([7; 32], 0, 6000, 0), blake32(&src));
+    let src = [src.as_slice(), &9u64.to_le_bytes()].concat();
+    assert_eq!(chunk_id([7; 32], 0, 6000, 9), blake32(&src));
+}
+
+proptest! {
+    #[test]
+    fn file_meta_roundtrips(meta in testkit::file_meta()) {
+        let bytes = bincode::serialize(&meta).unwrap();
+        prop_assert_eq!(bincode::deserialize::<FileMeta>(&bytes).unwrap(), meta);
+    }
+
+    #[test]
+    fn chunk_meta_roundtrips(meta in testkit::chunk_meta()) {
+        let bytes = bincode::serialize(&meta).unwrap();
+        prop_assert_eq!(decode_chunk(&bytes).unwrap(), meta);
+    }
+
+    #[test]
+    fn chunk_id_is_deterministic_and_position_and_chunker_sensitive(
+        h in testkit::hash32(), start in 0usize..1 << 30, len in 1usize..1 << 20,
+        chunker in 1u64..,
+    ) {
+        let id = chunk_id(h, start, start + len, chunker);
+        prop_assert_eq!(id, chunk_id(h, start, start + len, chunker));
+        prop_assert_ne!(id, chunk_id(h, start + 1, start + len, chunker));
+        prop_assert_ne!(id, chunk_id(h, start, start + len + 1, chunker));
+        prop_assert_ne!(id, chunk_id(h, start, start + len, 0));
+    }
+}
+
+#[test]
+fn term_stats_follow_file_versions() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Store::open(dir.path()).unwrap();
+    let terms = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    store.put_terms([1; 32], &terms(&["cache", "session"])).unwrap();
+    store.put_terms([1; 32], &terms(&["cache", "session"])).unwrap();
+    store.put_terms([2; 32], &terms(&["cache"])).unwrap();
+    assert_eq!((store.stats_docs().unwrap(), store.doc_freq("cache").unwrap()), (2, 2));
+    assert!(store.idf("session").unwrap() > store.idf("cache").unwrap());
+    store.delete_file([1; 32]).unwrap();
+    assert_eq!((store.stats_docs().unwrap(), store.doc_freq("cache").unwrap()), (1, 1));
+    assert_eq!(store.doc_freq("session").unwrap(), 0);
+}
+
+#[test]
+fn salvage_copies_a_healthy_store_losslessly() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let store = Store::open(src.path()).unwrap();
+    let fh = [5; 32];
+    store.put_file(fh, &FileMeta { path: "a.rs".into(), size: 10 }).unwrap();
+    store.put_chunk([7; 32], &ChunkMeta { file_hash: fh, start: 0, end: 10, span_hash: [6; 32], chunker: 1 }).unwrap();
+    store.put_embed([7; 32], &[0.5; 384]).unwrap();
+    store.put_chunk([8; 32], &ChunkMeta { file_hash: [9; 32], start: 0, end: 10, span_hash: [6; 32], chunker: 1 }).unwrap();
+    store.put_terms(fh, &["cache".to_string()]).unwrap();
+    drop(store);
+    let out = dst.path().join("index");
+    let rep = mentat_store::salvage::salvage(src.path(), &out).unwrap();
+    assert_eq!(rep.dangling_chunks, 1);
+    let salvaged = Store::open_existing(&out).unwrap();
+    assert_eq!(salvaged.files().unwrap().len(), 1);
+    assert_eq!(salvaged.chunks().unwrap().len(), 1);
+    assert_eq!(salvaged.get_embed([7; 32]).unwrap(), Some([0.5; 384]));
+    assert_eq!(salvaged.doc_freq("cache").unwrap(), 1);
+    assert!(salvaged.integrity().unwrap().is_clean());
+}
+
+#[test]
+fn deleted_chunks_leave_the_upgrade_queue() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Store::open(dir.path()).unwrap();
+    let fh = [5; 32];
+    store.put_file(fh, &FileMeta { path: "a.rs".into(), size: 10 }).unwrap();
+    store.put_chunk([7; 32], &ChunkMeta { file_hash: fh, start: 0, end: 10, span_hash: [6; 32], chunker: 1 }).unwrap();
+    store.queue_upgrades(&[[7; 32]]).unwrap();
+    store.delete_file(fh).unwrap();
+    assert!(store.upgrade_queue().unwrap().is_empty());
+    // an upgrade embedded while the chunk was being deleted stores nothing
+    store.queue_upgrades(&[[7; 32]]).unwrap();
+    assert_eq!(store.finish_upgrades(&[([7; 32], [0.5; 384])], &[]).unwrap(), 0);
+    assert_eq!(store.get_embed([7; 32]).unwrap(), None);
+    assert!(store.integrity().unwrap().is_clean());
+}
+
+#[test]
+fn file_chunks_of_an_older_index_are_read_without_writing() {
+    use redb::TableHandle;
+    let dir = tempfile::tempdir().unwrap();
+    let fh = [5; 32];
+    let meta = ChunkMeta { file_hash: fh, start: 0, end: 10, span_hash: [6; 32], chunker: 1 };
+    {
+        let store = Store::open(dir.path()).unwrap();
+        store.put_file(fh, &FileMeta { path: "a.rs".into(), size: 10 }).unwrap();
+        store.put_chunk([7; 32], &meta).unwrap();
+    }
+    // an index written before `file_chunks` existed
+    let path = dir.path().join("kv.redb");
+    let db = redb::Database::open(&path).unwrap();
+    let tx = db.begin_write().unwrap();
+    tx.delete_table(redb::TableDefinition::<&[u8], ()>::new("file_chunks")).unwrap();
+    tx.commit().unwrap();
+    drop(db);
+    let has_index = || {
+        let db = redb::Database::open(&path).unwrap();
+        let names: Vec<String> = db.begin_read().unwrap().list_tables().unwrap().map(|t| t.name().to_string()).collect();
+        names.iter().any(|n| n == "file_chunks")
+    };
+
+    let store = Store::open_existing(dir.path()).unwrap();
+    assert_eq!(store.file_chunks(fh).unwrap(), [([7; 32], meta.clone())]);
+    drop(store);
+    assert!(!has_index(), "a read-only open wrote the index");
+    Store::open(dir.path()).unwrap();
+    assert!(has_index());
+    assert_eq!(Store::open_existing(dir.path()).unwrap().file_chunks(fh).unwrap(), [([7; 32], meta)]);
+}
+
+#[test]
+fn chunk_samples_are_distinct_and_repeatable() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Store::open(dir.path()).unwrap();
+    for i in 0..20u8 {
+        store.put_chunk([i; 32], &ChunkMeta { file_hash: [1; 32], start: i as usize, end: i as usize + 1, span_hash: [i; 32], chunker: 0 }).unwrap();
+    }
+    let ids = |n, seed| store.sample_chunks(n, seed).unwrap().into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    let five = ids(5, 1);
+    assert_eq!(five.len(), 5);
+    assert_eq!(five.iter().collect::<std::collections::HashSet<_>>().len(), 5);
+    assert_eq!(five, ids(5, 1));
+    assert_ne!(five, ids(5, 2));
+    assert_eq!(ids(50, 1).len(), 20);
+    // a larger sample extends a smaller one
+    assert_eq!(&ids(8, 1)[..5], five.as_slice());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn retire_moves_a_version_into_history(meta in testkit::file_meta(), emb in testkit::embedding()) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let fh = [5; 32];
+        let chunk = ChunkMeta { file_hash: fh, start: 0, end: 10, span_hash: [6; 32], chunker: 1 };
+        store.put_file(fh, &meta).unwrap();
+        store.mark_indexed(fh, 100).unwrap();
+        store.put_chunk([7; 32], &chunk).unwrap();
+        store.put_embed([7; 32], &emb).unwrap();
+        prop_assert_eq!(store.retire_file(fh, 200).unwrap(), 1);
+        prop_assert!(store.files().unwrap().is_empty() && store.chunks().unwrap().is_empty());
+        prop_assert!(store.integrity().unwrap().is_clean());
+        let hist = store.history().unwrap();
+        prop_assert_eq!(hist.len(), 1);
+        prop_assert_eq!(&hist[0].meta, &meta);
+        prop_assert_eq!((hist[0].indexed_at, hist[0].retired_at), (Some(100), 200));
+        prop_assert_eq!(hist[0].chunks[0].embed.as_deref(), Some(emb.as_slice()));
+        prop_assert_eq!(store.prune_history(200).unwrap(), 0);
+        prop_assert_eq!(store.prune_history(201).unwrap(), 1);
+    }
+
+    #[test]
+    fn embed_roundtrips(emb in testkit::embedding()) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        store.put_embed([3; 32], &emb).unwrap();
+        prop_assert_eq!(store.get_embed([3; 32]).unwrap(), Some(emb));
+    }
+
+    #[test]
+    fn op_sequences_keep_referential_integrity(ops in prop::collection::vec(testkit::op(), 1..40)) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let mut model = Model::default();
+        for op in &ops {
+            model.apply(&store, op).unwrap();
+        }
+        model.check(&store).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+//! The lexical analyzer: how text becomes the terms counted in the `stats`
+//! table at index time and matched against chunks at query time. Identifiers
+//! are indexed whole and as their parts, so `putEmbed`, `put_embed` and
+//! "put embed" share the terms `put` and `embed`.
+
+/// Distinct lowercased terms of `text`, sorted: runs of letters, digits and
+/// `_`, plus the `subtokens` of each run, all 2 to 64 bytes long.
+pub fn terms(text: &str) -> Vec<String> {
+    let mut set = std::collections::BTreeSet::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if !(2..=64).contains(&word.len()) {
+            continue;
+        }
+        set.insert(word.to_lowercase());
+        set.extend(subtokens(word).into_iter().filter(|t| t.len() >= 2));
+    }
+    set.into_iter().collect()
+}
+
+/// Lowercased parts of an identifier, split at `_` and at case changes:
+/// `putEmbed` -> put, embed; `HTTPServer` -> http, server; `utf8_decode` ->
+/// utf8, decode. A word with no inner boundary yields just itself.
+pub fn subtokens(word: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for part in word.split('_').filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, cur) = (chars[i - 1], chars[i]);
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            // fooBar | FOOBar (the last capital starts the next word)
+            let boundary = ((prev.is_lowercase() || prev.is_ascii_digit()) && cur.is_uppercase())
+                || (prev.is_uppercase() && cur.is_uppercase() && next_lower);
+            if boundary {
+                out.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        out.push(chars[start..].iter().collect::<String>().to_lowercase());
+    }
+    out
+}
@@ -0,0 +1,48 @@
+//! Text cleaning shared by indexing and querying.
+//! Whatever is applied to chunks before embedding must be applied to queries
+//! too, so the settings used for an index are recorded alongside it.
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+pub mod analyze;
+pub use analyze::{subtokens, terms};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Normalize {
+    /// Unicode NFC composition (e.g. "e\u{301}" -> "é")
+    pub nfc: bool,
+    /// drop control characters other than \n, \r, \t
+    pub strip_control: bool,
+    /// collapse whitespace runs (including newlines) to one space
+    pub collapse_whitespace: bool,
+    pub lowercase: bool,
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Self { nfc: true, strip_control: true, collapse_whitespace: false, lowercase: false }
+    }
+}
+
+impl Normalize {
+    /// Identity settings: what indexes built before normalization existed used.
+    pub fn none() -> Self {
+        Self { nfc: false, strip_control: false, collapse_whitespace: false, lowercase: false }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut s: String = if self.nfc { text.nfc().collect() } else { text.to_string() };
+        if self.strip_control {
+            s.retain(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'));
+        }
+        if self.collapse_whitespace {
+            s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.lowercase {
+            s = s.to_lowercase();
+        }
+        s
+    }
+}
@@ -0,0 +1,83 @@
+//! FAISS flat indexes (`IndexFlatIP` / `IndexFlatL2`) as written by
+//! `faiss.write_index`. Other index types store codes, not vectors, and are
+//! rejected on read; `write` produces an `IndexFlatIP`, which on unit vectors
+//! ranks by cosine similarity.
+
+use crate::Matrix;
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+const FLAT_IP: &[u8; 4] = b"IxFI";
+const FLAT_L2: &[u8; 4] = b"IxF2";
+const METRIC_INNER_PRODUCT: i32 = 0;
+/// value FAISS itself writes into the two unused header fields
+const UNUSED: i64 = 1 << 20;
+
+pub fn read(path: &Path) -> Result<Matrix> {
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    parse(&bytes).with_context(|| format!("in {}", path.display()))
+}
+
+pub fn write(path: &Path, m: &Matrix) -> Result<()> {
+    let mut out = Vec::with_capacity(45 + m.data.len() * 4);
+    out.extend_from_slice(FLAT_IP);
+    out.extend_from_slice(&i32::try_from(m.dim)?.to_le_bytes());
+    out.extend_from_slice(&(m.rows() as i64).to_le_bytes());
+    out.extend_from_slice(&UNUSED.to_le_bytes());
+    out.extend_from_slice(&UNUSED.to_le_bytes());
+    out.push(1); // is_trained
+    out.extend_from_slice(&METRIC_INNER_PRODUCT.to_le_bytes());
+    out.extend_from_slice(&(m.data.len() as u64).to_le_bytes());
+    for x in &m.data {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+    fs::write(path, out).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Layout: fourcc, d: i32, ntotal: i64, two unused i64, is_trained: u8,
+/// metric: i32 (+ f32 arg for metrics past L2), then a u64 count and the floats.
+pub fn parse(bytes: &[u8]) -> Result<Matrix> {
+    let mut r = Reader { bytes, pos: 0 };
+    let fourcc = r.take(4)?;
+    if fourcc != FLAT_IP && fourcc != FLAT_L2 {
+        anyhow::bail!(
+            "unsupported FAISS index type {:?}; only flat indexes carry raw vectors",
+            String::from_utf8_lossy(fourcc)
+        );
+    }
+    let dim = usize::try_from(i32::from_le_bytes(r.array()?)).context("negative dimension")?;
+    let ntotal = usize::try_from(i64::from_le_bytes(r.array()?)).context("negative row count")?;
+    r.take(16)?; // unused header fields
+    r.take(1)?; // is_trained
+    let metric = i32::from_le_bytes(r.array()?);
+    if metric > 1 {
+        r.take(4)?;
+    }
+    let count = u64::from_le_bytes(r.array()?) as usize;
+    anyhow::ensure!(count == dim * ntotal, "{count} floats for {ntotal} vectors of dimension {dim}");
+    let data = r
+        .take(count * 4)?
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().expect("chunks of 4")))
+        .collect();
+    Ok(Matrix { dim, data })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow::anyhow!("truncated at byte {}", self.pos))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+}
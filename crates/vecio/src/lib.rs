@@ -0,0 +1,69 @@
+//! Vector file formats shared with other tools.
+//! Vectors travel as a flat matrix plus a JSONL sidecar naming the span each
+//! row embeds (`{"path": .., "start": .., "end": ..}` per line, same order).
+//! `project` turns a matrix into 2D coordinates and cluster labels for plots.
+
+pub mod faiss;
+pub mod npy;
+pub mod project;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// Byte span of an indexed file that one vector row embeds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpanRef {
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+    /// hex chunk id in the exporting index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_id: Option<String>,
+}
+
+/// Row-major vectors of one dimension.
+#[derive(Debug, Default, PartialEq)]
+pub struct Matrix {
+    pub dim: usize,
+    pub data: Vec<f32>,
+}
+
+impl Matrix {
+    pub fn rows(&self) -> usize {
+        self.data.len().checked_div(self.dim).unwrap_or(0)
+    }
+
+    pub fn row(&self, i: usize) -> &[f32] {
+        &self.data[i * self.dim..(i + 1) * self.dim]
+    }
+}
+
+pub fn read_sidecar(path: &Path) -> Result<Vec<SpanRef>> {
+    let f = fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut out = Vec::new();
+    for (n, line) in BufReader::new(f).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let span = serde_json::from_str(&line).with_context(|| format!("{}:{}", path.display(), n + 1))?;
+        out.push(span);
+    }
+    Ok(out)
+}
+
+pub fn write_sidecar(path: &Path, spans: &[SpanRef]) -> Result<()> {
+    let f = fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut w = BufWriter::new(f);
+    for s in spans {
+        serde_json::to_writer(&mut w, s)?;
+        w.write_all(b"\n")?;
+    }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//! NumPy `.npy` (format 1.0): a little-endian f32 array of shape (rows, dim),
+//! loadable with `numpy.load`.
+
+use crate::Matrix;
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+pub fn write(path: &Path, m: &Matrix) -> Result<()> {
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", m.rows(), m.dim);
+    // magic + u16 length + header, padded with spaces to a multiple of 64, ends in \n
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+    let mut out = Vec::with_capacity(MAGIC.len() + 2 + header.len() + m.data.len() * 4);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&u16::try_from(header.len())?.to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    for x in &m.data {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+    fs::write(path, out).with_context(|| format!("writing {}", path.display()))
+}
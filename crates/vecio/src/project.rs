@@ -0,0 +1,118 @@
+//! 2D views of a vector matrix for plotting: PCA coordinates and k-means
+//! cluster labels. Both are deterministic, so re-running on the same index
+//! gives the same picture.
+
+use crate::Matrix;
+
+const PCA_ITERS: usize = 100;
+const KMEANS_ITERS: usize = 50;
+
+/// Each row projected onto the matrix's first two principal components.
+pub fn pca2(m: &Matrix) -> Vec<[f32; 2]> {
+    let n = m.rows();
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut mean = vec![0f32; m.dim];
+    for i in 0..n {
+        for (a, x) in mean.iter_mut().zip(m.row(i)) {
+            *a += x / n as f32;
+        }
+    }
+    let centered: Vec<Vec<f32>> = (0..n).map(|i| m.row(i).iter().zip(&mean).map(|(x, a)| x - a).collect()).collect();
+    let first = component(&centered, m.dim, None);
+    let second = component(&centered, m.dim, Some(&first));
+    centered.iter().map(|r| [dot(r, &first), dot(r, &second)]).collect()
+}
+
+/// Leading eigenvector of the covariance by power iteration, kept orthogonal
+/// to `not` when given.
+fn component(rows: &[Vec<f32>], dim: usize, not: Option<&[f32]>) -> Vec<f32> {
+    // fixed pseudo-random start: a constant vector can sit orthogonal to the answer
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut v: Vec<f32> = (0..dim)
+        .map(|_| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        })
+        .collect();
+    for _ in 0..PCA_ITERS {
+        if let Some(u) = not {
+            let p = dot(&v, u);
+            v.iter_mut().zip(u).for_each(|(x, y)| *x -= p * y);
+        }
+        unit(&mut v);
+        let mut next = vec![0f32; dim];
+        for r in rows {
+            let p = dot(r, &v);
+            next.iter_mut().zip(r).for_each(|(x, y)| *x += p * y);
+        }
+        v = next;
+    }
+    if let Some(u) = not {
+        let p = dot(&v, u);
+        v.iter_mut().zip(u).for_each(|(x, y)| *x -= p * y);
+    }
+    unit(&mut v);
+    v
+}
+
+/// Cluster label per row from Lloyd's k-means, seeded by farthest-point
+/// picks starting at row 0.
+pub fn kmeans(m: &Matrix, k: usize) -> Vec<usize> {
+    let n = m.rows();
+    let k = k.min(n);
+    if k == 0 {
+        return vec![0; n];
+    }
+    let mut centers: Vec<Vec<f32>> = vec![m.row(0).to_vec()];
+    let mut nearest: Vec<f32> = (0..n).map(|i| dist2(m.row(i), &centers[0])).collect();
+    while centers.len() < k {
+        let far = (0..n).max_by(|&a, &b| nearest[a].total_cmp(&nearest[b])).unwrap_or(0);
+        centers.push(m.row(far).to_vec());
+        let c = centers.last().expect("just pushed");
+        for (i, d) in nearest.iter_mut().enumerate() {
+            *d = d.min(dist2(m.row(i), c));
+        }
+    }
+    let mut labels = vec![usize::MAX; n];
+    for _ in 0..KMEANS_ITERS {
+        let mut moved = false;
+        for (i, l) in labels.iter_mut().enumerate() {
+            let best = (0..k).min_by(|&a, &b| dist2(m.row(i), &centers[a]).total_cmp(&dist2(m.row(i), &centers[b]))).unwrap_or(0);
+            moved |= *l != best;
+            *l = best;
+        }
+        if !moved {
+            break;
+        }
+        let mut sums = vec![vec![0f32; m.dim]; k];
+        let mut counts = vec![0usize; k];
+        for (i, &l) in labels.iter().enumerate() {
+            counts[l] += 1;
+            sums[l].iter_mut().zip(m.row(i)).for_each(|(s, x)| *s += x);
+        }
+        for ((c, s), &count) in centers.iter_mut().zip(sums).zip(&counts) {
+            // an emptied cluster keeps its old center
+            if count > 0 {
+                *c = s.into_iter().map(|x| x / count as f32).collect();
+            }
+        }
+    }
+    labels
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn dist2(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+fn unit(v: &mut [f32]) {
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
@@ -0,0 +1,52 @@
+//! Detached follow-up jobs, `build-hnsw --background` and `upgrade
+//! --background`. A marker file says a job is pending or running, so a
+//! second one is not started; the job removes it when done. A job waits for
+//! the command that started it to release the store.
+
+use anyhow::Result;
+use std::{env, fs, path::Path, process::{Command, Stdio}, thread, time::Duration};
+
+/// A marker older than this is left over from a crash and ignored.
+const MARKER_TTL_SECS: u64 = 3600;
+/// How long a job waits for another command to release the store.
+const WAIT_FOR_STORE_SECS: u64 = 120;
+
+/// Whether `marker` is present and fresh.
+pub fn pending(marker: &Path) -> bool {
+    fs::metadata(marker)
+        .and_then(|m| m.modified())
+        .is_ok_and(|t| t.elapsed().is_ok_and(|age| age.as_secs() < MARKER_TTL_SECS))
+}
+
+/// Write `marker` and start this binary with `args` detached, output to
+/// `log`. The marker is removed again if the job cannot start.
+pub fn spawn(marker: &Path, log: &Path, args: &[&str]) -> Result<()> {
+    let start = || -> Result<()> {
+        fs::write(marker, std::process::id().to_string())?;
+        let log = fs::File::create(log)?;
+        Command::new(env::current_exe()?)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()?;
+        Ok(())
+    };
+    start().inspect_err(|_| {
+        let _ = fs::remove_file(marker);
+    })
+}
+
+/// `open`, retried while another command holds the store.
+pub fn when_free<T>(open: impl Fn() -> Result<T>) -> Result<T> {
+    let mut waited = 0;
+    loop {
+        match open() {
+            Err(e) if waited < WAIT_FOR_STORE_SECS && format!("{e:#}").contains("already open") => {
+                thread::sleep(Duration::from_secs(1));
+                waited += 1;
+            }
+            r => return r,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Minimal hand-rolled argv parsing:
+//!   mentat <cmd> [positional..] [--flag] [--opt value | --opt=value]
+
+use anyhow::{Context, Result};
+use std::{collections::HashMap, str::FromStr};
+
+/// Options that consume the following argument as their value.
+const VALUE_OPTS: &[&str] = &["--k", "--n", "--seed", "--llm-cmd", "--diff", "--expand", "--offset", "--limit", "--file", "--profile", "--from", "--meta", "--model", "--root", "--format", "--out", "--rev", "--as-of", "--into", "--ref", "--min-similarity", "--method", "--clusters", "--history", "--save", "--queries", "--days", "--snippet", "--context", "--mode", "--budget-ms", "--baseline", "--candidate", "--by", "--files", "--kind"];
+
+pub struct Args {
+    pub cmd: Option<String>,
+    pos: Vec<String>,
+    opts: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(mut argv: I) -> Self {
+        argv.next(); // program name
+        let cmd = argv.next();
+        let mut pos = Vec::new();
+        let mut opts = HashMap::new();
+        while let Some(a) = argv.next() {
+            if let Some((k, v)) = a.split_once('=').filter(|_| a.starts_with("--")) {
+                opts.insert(k.to_string(), Some(v.to_string()));
+            } else if VALUE_OPTS.contains(&a.as_str()) {
+                opts.insert(a, argv.next());
+            } else if a.starts_with("--") {
+                opts.insert(a, None);
+            } else {
+                pos.push(a);
+            }
+        }
+        Self { cmd, pos, opts }
+    }
+
+    pub fn pos(&self, i: usize) -> Option<&str> {
+        self.pos.get(i).map(String::as_str)
+    }
+
+    /// Positional arguments from `i` on.
+    pub fn pos_from(&self, i: usize) -> &[String] {
+        self.pos.get(i..).unwrap_or(&[])
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.opts.contains_key(name)
+    }
+
+    pub fn opt(&self, name: &str) -> Option<&str> {
+        self.opts.get(name).and_then(|v| v.as_deref())
+    }
+
+    pub fn opt_or<T: FromStr>(&self, name: &str, default: T) -> Result<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match self.opt(name) {
+            Some(v) => v.parse().with_context(|| format!("invalid value for {name}: {v}")),
+            None => Ok(default),
+        }
+    }
+}
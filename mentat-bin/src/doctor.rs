@@ -0,0 +1,201 @@
+//! `mentat doctor`: checks the things support questions usually come down
+//! to (config, build features, model files, CUDA, the index and its lock,
+//! `[quota]` usage, disk space) and
+//! says how to fix each problem it finds.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{path::Path, process::Command};
+
+/// Warn when the index volume has less free space than this.
+const MIN_FREE_BYTES: u64 = 1 << 30;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+    Skip,
+}
+
+#[derive(Serialize, Debug)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+fn check(name: &'static str, status: Status, detail: impl Into<String>, fix: Option<&str>) -> Check {
+    Check { name, status, detail: detail.into(), fix: fix.map(str::to_string) }
+}
+
+/// Exit 2 when any check fails; warnings alone exit 0.
+pub fn run(index_dir: &str, json: bool) -> Result<i32> {
+    let mut checks = Vec::new();
+    let cfg = match mentat_config::Config::load() {
+        Ok(cfg) => {
+            let path = mentat_config::config_path();
+            let detail = if path.exists() { format!("{} loads", path.display()) } else { "no mentat.toml; using defaults".into() };
+            checks.push(check("config", Status::Ok, detail, None));
+            Some(cfg)
+        }
+        Err(e) => {
+            checks.push(check("config", Status::Fail, format!("{e:#}"), Some("correct mentat.toml (or $MENTAT_CONFIG)")));
+            None
+        }
+    };
+    checks.push(features());
+    let quota = cfg.as_ref().map(|c| c.quota).unwrap_or_default();
+    let embed = cfg.map(|c| c.embedder).unwrap_or_default();
+    checks.push(model(&embed.pins)?);
+    checks.push(cuda(&embed));
+    checks.extend(index(index_dir, &quota));
+    checks.push(disk(index_dir));
+    checks.push(check("daemon", Status::Skip, "this build has no daemon; every command opens the index directly", None));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for c in &checks {
+            let tag = match c.status {
+                Status::Ok => "ok",
+                Status::Warn => "warn",
+                Status::Fail => "FAIL",
+                Status::Skip => "skip",
+            };
+            println!("[{tag:>4}] {:<8} {}", c.name, c.detail);
+            if let Some(fix) = &c.fix {
+                println!("         fix: {fix}");
+            }
+        }
+    }
+    Ok(if checks.iter().any(|c| c.status == Status::Fail) { 2 } else { 0 })
+}
+
+fn features() -> Check {
+    let built = [("model", cfg!(feature = "model")), ("cuda", cfg!(feature = "cuda")), ("hashed", cfg!(feature = "hashed")), ("hnsw", cfg!(feature = "hnsw"))];
+    let on: Vec<&str> = built.iter().filter(|f| f.1).map(|f| f.0).collect();
+    let detail = format!("built with: {}", if on.is_empty() { "no optional features".into() } else { on.join(", ") });
+    if mentat_embedder::FALLBACK {
+        check("features", Status::Warn, format!("{detail}; embedding with the low-quality hashed fallback"), Some("rebuild with `--features model`"))
+    } else if !cfg!(feature = "model") {
+        check("features", Status::Warn, format!("{detail}; cannot embed, so indexing and search fail"), Some("rebuild with `--features model`"))
+    } else if !cfg!(feature = "hnsw") {
+        check("features", Status::Warn, format!("{detail}; every search is exact"), Some("rebuild with `--features hnsw`"))
+    } else {
+        check("features", Status::Ok, detail, None)
+    }
+}
+
+fn model(pins: &std::collections::BTreeMap<String, String>) -> Result<Check> {
+    if mentat_embedder::FALLBACK {
+        return Ok(check("model", Status::Skip, format!("{} needs no model files", mentat_embedder::MODEL), None));
+    }
+    let dir = mentat_embedder::model_dir();
+    let files = mentat_embedder::check_files(pins)?;
+    let missing: Vec<&str> = files.iter().filter(|f| f.blake3.is_none()).map(|f| f.file.as_str()).collect();
+    let mismatched: Vec<&str> = files.iter().filter(|f| f.blake3.is_some() && !f.ok()).map(|f| f.file.as_str()).collect();
+    Ok(if !missing.is_empty() {
+        check("model", Status::Fail, format!("{} missing from {}", missing.join(", "), dir.display()), Some("mentat models pull (or set MENTAT_MODEL_DIR)"))
+    } else if !mismatched.is_empty() {
+        check("model", Status::Fail, format!("{} differ from [embedder] pins", mismatched.join(", ")), Some("mentat models pull --force, or update the pins"))
+    } else if pins.is_empty() {
+        check("model", Status::Warn, format!("{} present in {}, not pinned", mentat_embedder::MODEL, dir.display()), Some("mentat models list prints [embedder.pins] to add"))
+    } else {
+        check("model", Status::Ok, format!("{} present and matching its pins", mentat_embedder::MODEL), None)
+    })
+}
+
+fn cuda(embed: &mentat_embedder::EmbedConfig) -> Check {
+    let wants_cuda = embed.devices.iter().any(|d| d.starts_with("cuda"));
+    let half = embed.precision != mentat_embedder::Precision::F32;
+    match (mentat_embedder::cuda_available(), wants_cuda) {
+        (true, _) => check("cuda", Status::Ok, "cuda:0 available", None),
+        (false, true) => check(
+            "cuda",
+            Status::Fail,
+            "[embedder] devices names CUDA, but cuda:0 cannot be opened",
+            Some("build with CUDA support and check the driver (nvidia-smi), or set devices = [\"cpu\"]"),
+        ),
+        (false, false) if half => check("cuda", Status::Warn, "no CUDA; the f16/bf16 precision setting falls back to f32 on CPU", None),
+        (false, false) => check("cuda", Status::Ok, "no CUDA; embedding on CPU", None),
+    }
+}
+
+fn index(dir: &str, quota: &mentat_indexer::quota::Quota) -> Vec<Check> {
+    if !Path::new(dir).join("kv.redb").exists() {
+        return vec![check("index", Status::Warn, format!("no index at {dir}"), Some("mentat index <path>"))];
+    }
+    let store = match mentat_store::Store::open_existing(dir) {
+        Ok(s) => s,
+        Err(e) if format!("{e:#}").contains("already open") => {
+            return vec![check(
+                "lock",
+                Status::Fail,
+                format!("{dir}/kv.redb is held by another process"),
+                Some("wait for the running mentat command (e.g. `mentat index`) to finish, or stop it"),
+            )];
+        }
+        Err(e) => return vec![check("index", Status::Fail, format!("{e:#}"), Some("mentat verify; rebuild with `mentat index` if it cannot open"))],
+    };
+    let mut out = vec![check("lock", Status::Ok, "index not locked by another process", None)];
+    let counts = store.files().and_then(|f| Ok((f.len(), store.chunks()?.len())));
+    let usage = counts.as_ref().ok().map(|&(files, chunks)| mentat_indexer::quota::Usage::of(files, chunks));
+    out.push(match (store.schema_version(), counts) {
+        (Err(e), _) | (_, Err(e)) => check("index", Status::Fail, format!("{e:#}"), Some("mentat verify")),
+        (Ok(Some(v)), _) if v > mentat_store::SCHEMA_VERSION => check(
+            "index",
+            Status::Fail,
+            format!("schema v{v} is newer than this build understands (v{})", mentat_store::SCHEMA_VERSION),
+            Some("upgrade mentat, or re-index into a fresh directory"),
+        ),
+        (Ok(v), Ok((files, chunks))) => {
+            let schema = v.map_or("created before schema versioning".to_string(), |v| format!("schema v{v}"));
+            check("index", Status::Ok, format!("{dir}: {schema}, {files} files, {chunks} chunks"), None)
+        }
+    });
+    if let Some(usage) = usage.filter(|_| !quota.is_off()) {
+        out.push(quota_check(quota, &usage));
+    }
+    out
+}
+
+fn quota_check(quota: &mentat_indexer::quota::Quota, usage: &mentat_indexer::quota::Usage) -> Check {
+    let fix = Some("raise [quota], or trim the collection (`mentat suggest-ignores`)");
+    if let Err(e) = quota.check(usage) {
+        return check("quota", Status::Fail, format!("{e:#}"), fix);
+    }
+    match quota.warnings(usage) {
+        w if w.is_empty() => check("quota", Status::Ok, format!("{} files, {} chunks within [quota]", usage.files, usage.chunks), None),
+        w => check("quota", Status::Warn, format!("near [quota] {}", w.join("; ")), fix),
+    }
+}
+
+/// Free space via `df`, which every unix has; no statvfs binding needed.
+fn disk(dir: &str) -> Check {
+    let target = if Path::new(dir).exists() { dir } else { "." };
+    let out = Command::new("df").args(["-Pk", target]).output();
+    let avail_kb = out.ok().filter(|o| o.status.success()).and_then(|o| {
+        let text = String::from_utf8_lossy(&o.stdout).into_owned();
+        text.lines().nth(1)?.split_whitespace().nth(3)?.parse::<u64>().ok()
+    });
+    let Some(kb) = avail_kb else {
+        return check("disk", Status::Skip, "could not run df", None);
+    };
+    let free = kb * 1024;
+    let index_size = std::fs::metadata(Path::new(dir).join("kv.redb")).map(|m| m.len()).unwrap_or(0);
+    let gib = |b: u64| b as f64 / (1u64 << 30) as f64;
+    if free < MIN_FREE_BYTES.max(index_size) {
+        check(
+            "disk",
+            Status::Warn,
+            format!("{:.1} GiB free on the index volume (index is {:.1} GiB)", gib(free), gib(index_size)),
+            Some("free space before re-indexing; redb grows the file before it compacts"),
+        )
+    } else {
+        check("disk", Status::Ok, format!("{:.1} GiB free", gib(free)), None)
+    }
+}
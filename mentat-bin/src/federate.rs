@@ -0,0 +1,87 @@
+//! `[search] federate`: other index directories `mentat search` queries
+//! along with `index/`, such as a team's docs index on a shared mount, so
+//! personal notes and the shared knowledge base are one query away.
+//!
+//! The query is embedded once and run against every index, and the hits
+//! are merged by distance. Distances only compare within one vector space,
+//! so an index holding other vectors is skipped with a warning, as is one
+//! that cannot be opened. Every hit is labeled with its index (`local` for
+//! this one). Nothing is written to the other indexes, not even hit counts.
+//! `--save`, `--rev`, `--as-of`, `--pseudonymize` and postprocess commands
+//! work on one index, so with any of them the search stays local.
+
+use anyhow::Result;
+use mentat_retriever::{query::Query, rag::Node, snippet::Verbosity, Hit, Retriever};
+use std::collections::BTreeMap;
+
+pub const LOCAL: &str = "local";
+
+pub struct Source {
+    pub label: String,
+    pub retr: Retriever,
+}
+
+/// The configured indexes that can be searched together with `local`.
+pub fn open(indexes: &BTreeMap<String, String>, local: &Retriever, cfg: &mentat_config::Search, budget: Option<std::time::Duration>) -> Vec<Source> {
+    let mut sources = Vec::new();
+    for (label, dir) in indexes {
+        let mut retr = match Retriever::open(dir) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("[search] skipping {label} ({dir}): {e:#}");
+                continue;
+            }
+        };
+        let (theirs, ours) = (retr.vectors(), local.vectors());
+        if !theirs.same_space(ours) {
+            eprintln!("[search] skipping {label} ({dir}): it holds {} {}-dim vectors, this index {} {}-dim ones", theirs.model, theirs.dim, ours.model, ours.dim);
+            continue;
+        }
+        retr.set_relevance(cfg.relevance);
+        if let Some(b) = budget {
+            retr.set_budget(b);
+        }
+        sources.push(Source { label: label.clone(), retr });
+    }
+    sources
+}
+
+/// The `k` nearest hits over `local` (already searched) and `sources`, each
+/// with the index of its source, None for local.
+pub fn search(local_hits: Vec<Hit>, sources: &[Source], q: &Query, qv: &[f32; mentat_embedder::D], k: usize, no_merge: bool) -> Result<Vec<(Option<usize>, Hit)>> {
+    let boosts = mentat_config::Config::load()?.ranking;
+    let fetch = if boosts.is_empty() { k } else { k * 3 };
+    let mut all: Vec<(Option<usize>, Hit)> = local_hits.into_iter().map(|h| (None, h)).collect();
+    for (i, s) in sources.iter().enumerate() {
+        let hits = if no_merge {
+            s.retr.rerank(s.retr.search_query_vec(q, qv, fetch)?, &boosts, q)?
+        } else {
+            mentat_retriever::merge_overlaps(s.retr.rerank(s.retr.search_query_vec(q, qv, fetch * 3)?, &boosts, q)?)
+        };
+        all.extend(hits.into_iter().take(k).map(|h| (Some(i), h)));
+    }
+    all.sort_by(|a, b| a.1.distance.total_cmp(&b.1.distance));
+    all.truncate(k);
+    Ok(all)
+}
+
+/// The retriever and label a hit came from.
+pub fn source<'a>(local: &'a Retriever, sources: &'a [Source], i: Option<usize>) -> (&'a Retriever, &'a str) {
+    match i {
+        Some(i) => (&sources[i].retr, &sources[i].label),
+        None => (local, LOCAL),
+    }
+}
+
+/// Nodes as `search --json` prints them, with an `index` metadata label.
+pub fn to_nodes(local: &Retriever, sources: &[Source], hits: &[(Option<usize>, Hit)], v: &Verbosity) -> Result<Vec<Node>> {
+    let mut nodes = Vec::with_capacity(hits.len());
+    for (i, h) in hits {
+        let (retr, label) = source(local, sources, *i);
+        for mut n in retr.to_nodes_with(std::slice::from_ref(h), v)? {
+            n.metadata.insert("index".into(), label.into());
+            nodes.push(n);
+        }
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,107 @@
+//! `mentat graph export`: the related-files graph as DOT, GraphML, or JSON
+//! in the `{nodes, links}` shape force-directed layouts (d3-force, sigma)
+//! read. Edges are undirected; a pair linked both ways keeps the higher
+//! similarity.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+#[derive(Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub links: Vec<Link>,
+}
+
+#[derive(Serialize)]
+pub struct Node {
+    /// the file path
+    pub id: String,
+    pub size: usize,
+    pub chunks: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Link {
+    pub source: String,
+    pub target: String,
+    pub similarity: f32,
+}
+
+pub fn load(store: &mentat_store::Store) -> Result<Graph> {
+    let files = store.files()?;
+    let paths: HashMap<[u8; 32], &str> = files.iter().map(|(h, f)| (*h, f.path.as_str())).collect();
+    let mut chunks: HashMap<[u8; 32], usize> = HashMap::new();
+    for (_, c) in store.chunks()? {
+        *chunks.entry(c.file_hash).or_default() += 1;
+    }
+    let mut nodes = Vec::with_capacity(files.len());
+    let mut edges: BTreeMap<(&str, &str), f32> = BTreeMap::new();
+    for (h, f) in &files {
+        nodes.push(Node {
+            id: f.path.clone(),
+            size: f.size,
+            chunks: chunks.get(h).copied().unwrap_or(0),
+            license: store.get_license(*h)?,
+        });
+        for (other, sim) in store.get_related(*h)? {
+            let Some(o) = paths.get(&other).copied() else { continue };
+            let key = if f.path.as_str() < o { (f.path.as_str(), o) } else { (o, f.path.as_str()) };
+            let e = edges.entry(key).or_insert(sim);
+            *e = e.max(sim);
+        }
+    }
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    let links = edges
+        .into_iter()
+        .map(|((a, b), similarity)| Link { source: a.to_string(), target: b.to_string(), similarity })
+        .collect();
+    Ok(Graph { nodes, links })
+}
+
+pub fn to_dot(g: &Graph) -> String {
+    let q = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut out = String::from("graph related {\n");
+    for n in &g.nodes {
+        let _ = writeln!(out, "  {} [chunks={}, size={}];", q(&n.id), n.chunks, n.size);
+    }
+    for l in &g.links {
+        let _ = writeln!(out, "  {} -- {} [weight={:.4}];", q(&l.source), q(&l.target), l.similarity);
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn to_graphml(g: &Graph) -> String {
+    let x = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"size\" for=\"node\" attr.name=\"size\" attr.type=\"long\"/>\n",
+        "  <key id=\"chunks\" for=\"node\" attr.name=\"chunks\" attr.type=\"int\"/>\n",
+        "  <key id=\"license\" for=\"node\" attr.name=\"license\" attr.type=\"string\"/>\n",
+        "  <key id=\"similarity\" for=\"edge\" attr.name=\"similarity\" attr.type=\"double\"/>\n",
+        "  <graph id=\"related\" edgedefault=\"undirected\">\n",
+    ));
+    for n in &g.nodes {
+        let _ = write!(out, "    <node id=\"{}\"><data key=\"size\">{}</data><data key=\"chunks\">{}</data>", x(&n.id), n.size, n.chunks);
+        if let Some(l) = &n.license {
+            let _ = write!(out, "<data key=\"license\">{}</data>", x(l));
+        }
+        out.push_str("</node>\n");
+    }
+    for l in &g.links {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"similarity\">{:.4}</data></edge>",
+            x(&l.source),
+            x(&l.target),
+            l.similarity
+        );
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
@@ -0,0 +1,92 @@
+//! Saved HNSW graph handling for the CLI. A graph that is missing, corrupt
+//! or older than the index is never rebuilt on the query path: the query
+//! runs exact, and `mentat build-hnsw --background` is started detached to
+//! replace the graph for later queries.
+//!
+//! Builds report progress on stderr (one line per 10% when not on a
+//! terminal, so the background log stays short) and stop at the next
+//! progress point after `mentat build-hnsw --cancel`.
+
+use anyhow::Result;
+use crate::background;
+use std::{fs, io::IsTerminal, path::Path, time::Instant};
+
+/// Basename the graph is saved under.
+pub const BASE: &str = "index/embeds";
+/// Present while a background rebuild is pending or running.
+const MARKER: &str = "index/embeds.rebuild";
+const LOG: &str = "index/embeds.rebuild.log";
+/// Written by `--cancel`; a running build stops when it sees it.
+const CANCEL: &str = "index/embeds.rebuild.cancel";
+
+/// Load the saved graph, or warn and schedule a rebuild; search stays exact.
+/// Builds without the `hnsw` feature skip this and always search exact.
+pub fn load_or_schedule(retr: &mut mentat_retriever::Retriever) {
+    if !mentat_retriever::HNSW {
+        return;
+    }
+    if let Err(e) = retr.load_hnsw(BASE) {
+        eprintln!("[hnsw] {e:#}; using exact search");
+        schedule_rebuild();
+    }
+}
+
+fn schedule_rebuild() {
+    if background::pending(Path::new(MARKER)) {
+        eprintln!("[hnsw] a rebuild is already scheduled (see {LOG})");
+        return;
+    }
+    match background::spawn(Path::new(MARKER), Path::new(LOG), &["build-hnsw", "--background"]) {
+        Ok(()) => eprintln!("[hnsw] rebuilding in the background (log: {LOG})"),
+        Err(e) => eprintln!("[hnsw] could not start a rebuild: {e:#}; run `mentat build-hnsw`"),
+    }
+}
+
+/// `mentat build-hnsw [--background]`. In the background the store may still
+/// be held by the search that scheduled the build, so opening is retried.
+pub fn build(background: bool) -> Result<()> {
+    if Path::new(CANCEL).exists() {
+        fs::remove_file(CANCEL)?;
+    }
+    let result = (|| -> Result<()> {
+        let mut retr = if background { background::when_free(mentat_retriever::Retriever::open_default)? } else { mentat_retriever::Retriever::open_default()? };
+        let (t, tty) = (Instant::now(), std::io::stderr().is_terminal());
+        let mut last_tenth = 0;
+        retr.build_hnsw_with(BASE, &mut |done, total| {
+            let tenth = done * 10 / total.max(1);
+            if tty || tenth > last_tenth {
+                let secs = t.elapsed().as_secs_f32();
+                let left = if done > 0 { format!(", ~{:.0}s left", secs * (total - done) as f32 / done as f32) } else { String::new() };
+                let line = format!("[hnsw] {done}/{total} points ({}%), {secs:.0}s{left}", done * 100 / total.max(1));
+                if tty {
+                    eprint!("\r{line}\x1b[K");
+                    if done == total {
+                        eprintln!();
+                    }
+                } else {
+                    eprintln!("{line}");
+                }
+                last_tenth = tenth;
+            }
+            !Path::new(CANCEL).exists()
+        })
+    })();
+    if Path::new(CANCEL).exists() {
+        fs::remove_file(CANCEL)?;
+    }
+    if background && Path::new(MARKER).exists() {
+        fs::remove_file(MARKER)?;
+    }
+    result
+}
+
+/// `mentat build-hnsw --cancel`: ask a running build to stop.
+pub fn cancel() -> Result<()> {
+    if !Path::new(MARKER).exists() {
+        println!("No background HNSW build is running; a foreground one stops with Ctrl-C");
+        return Ok(());
+    }
+    fs::write(CANCEL, std::process::id().to_string())?;
+    println!("Asked the HNSW build to stop (see {LOG})");
+    Ok(())
+}
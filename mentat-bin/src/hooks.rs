@@ -0,0 +1,38 @@
+//! `[hooks]` commands: run through `sh -c` with the event as JSON on stdin
+//! and its name in $MENTAT_EVENT. A failing hook is reported, never fatal.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub fn index_complete(hooks: &mentat_config::Hooks, rep: &mentat_indexer::report::IndexReport) {
+    fire(hooks.on_index.as_deref(), "index_complete", json!({ "event": "index_complete", "report": rep }));
+    if !rep.errors.is_empty() {
+        fire(hooks.on_error.as_deref(), "index_error", json!({ "event": "index_error", "errors": rep.errors }));
+    }
+}
+
+pub fn index_failed(hooks: &mentat_config::Hooks, err: &anyhow::Error) {
+    fire(hooks.on_error.as_deref(), "index_failed", json!({ "event": "index_failed", "error": format!("{err:#}") }));
+}
+
+fn fire(cmd: Option<&str>, event: &str, payload: Value) {
+    let Some(cmd) = cmd else { return };
+    if let Err(e) = run(cmd, event, &payload) {
+        eprintln!("[hooks] {event} hook failed: {e:#}");
+    }
+}
+
+fn run(cmd: &str, event: &str, payload: &Value) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .env("MENTAT_EVENT", event)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(serde_json::to_string(payload)?.as_bytes())?;
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "exited with {status}");
+    Ok(())
+}
@@ -1,125 +1,1678 @@
-use std::{env, fs, path::Path};
-use anyhow::Result;
+use std::{env, path::Path};
+use anyhow::{Context, Result};
 
+mod background;
+mod cli;
+mod doctor;
+mod federate;
+mod graph;
+mod hnsw;
+mod hooks;
+mod models;
+mod notes;
+mod postprocess;
+mod rebuild;
+mod selftest;
+mod upgrade;
+mod usage;
+
+/// Exit codes: 0 ok, 1 fatal error, 2 completed with per-file failures.
 fn main() {
-    if let Err(e) = real_main() {
-        eprintln!("Error: {e:?}");
+    match real_main() {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            std::process::exit(1);
+        }
     }
 }
 
-fn real_main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    match args.get(1).map(String::as_str) {
+fn real_main() -> Result<i32> {
+    let args = cli::Args::parse(env::args());
+    // pins guard every model load; a broken mentat.toml is reported by the commands that need it
+    if let Ok(cfg) = mentat_config::Config::load() {
+        mentat_embedder::set_pins(cfg.embedder.pins);
+    }
+    match args.cmd.as_deref() {
         Some("ingest") => {
-            let target = args.get(2).map(String::as_str).unwrap_or(".");
-            let chunks = mentat_ingest::ingest(target)?;
-            let _ = mentat_ingest::dump_json(&chunks);
-            println!("Ingested {} files", chunks.len());
+            return run_ingest(args.pos(0).unwrap_or("."), args.opt("--diff"));
         }
         Some("index") => {
-            let target = args.get(2).map(String::as_str).unwrap_or(".");
-            run_index(target)?;
+            let target = args.pos(0).unwrap_or(".");
+            let cfg = mentat_config::Config::load_profile(args.opt("--profile"))?;
+            if let Some(p) = &cfg.profile {
+                eprintln!("[index] using profile {p}");
+            }
+            let opts = index_options(&args, &cfg)?;
+            let dir = args.opt("--into").unwrap_or("index");
+            if args.flag("--estimate") {
+                return run_estimate(target, dir, &opts);
+            }
+            let mut full = args.flag("--rebuild");
+            if !full && !args.flag("--in-place") {
+                if let Some(why) = rebuild::reason(dir, &opts)? {
+                    eprintln!("[index] {why}: building a new copy while {dir} keeps serving (--in-place to update it directly)");
+                    full = true;
+                }
+            }
+            if full && args.flag("--background") {
+                return rebuild::spawn(dir);
+            }
+            if full {
+                return rebuild::run(target, dir, &opts, &cfg.hooks);
+            }
+            return run_index(target, dir, &opts, &cfg.hooks);
+        }
+        Some("search") if args.opt("--queries").is_some() => {
+            let file = args.opt("--queries").unwrap_or_default();
+            let format = args.opt("--format").unwrap_or("text");
+            let (k, no_merge, defaults) = (args.opt_or("--k", 5)?, args.flag("--no-merge"), !args.flag("--all"));
+            return run_search_batch(file, format, k, no_merge, defaults, args.flag("--pseudonymize"), &verbosity(&args)?);
         }
         Some("search") => {
-            let q = args.get(2).map(String::as_str).unwrap_or("");
-            let retr = mentat_retriever::Retriever::open_default()?;
-            let results = retr.search(q, 5)?;
+            let q = &standalone_query(args.pos(0).unwrap_or(""), args.opt("--history"), args.opt("--llm-cmd"))?;
+            let mut retr = mentat_retriever::Retriever::open_default()?;
+            retr.set_relevance(mentat_config::Config::load()?.search.relevance);
+            restrict_to_rev(&mut retr, args.opt("--rev"))?;
+            if let Some(date) = args.opt("--as-of") {
+                retr.set_as_of(mentat_retriever::query::parse_date(date)?);
+            }
+            let parsed = parse_query(retr.store(), q, !args.flag("--all"))?;
+            if let Some(b) = budget(&args)? {
+                retr.set_budget(b);
+            }
+            let cfg = mentat_config::Config::load()?;
+            if !cfg.search.federate.is_empty() && !args.flag("--local") {
+                let single = ["--save", "--rev", "--as-of"]
+                    .into_iter()
+                    .find(|o| args.opt(o).is_some())
+                    .or(args.flag("--pseudonymize").then_some("--pseudonymize"))
+                    .or((!cfg.search.postprocess.is_empty()).then_some("[search] postprocess"));
+                if let Some(option) = single {
+                    anyhow::bail!("{option} does not work across the [search] federate indexes; add --local to search this index only");
+                }
+                return run_search_federated(&retr, &parsed, q, &args);
+            }
+            if args.flag("--pseudonymize") {
+                retr.set_pseudonymize()?;
+            }
+            let t = std::time::Instant::now();
+            let results = search_merged(&retr, &parsed, None, args.opt_or("--k", 5)?, args.flag("--no-merge"))?;
+            usage::record("index", usage::Event::Search { at: usage::now(), ms: t.elapsed().as_millis() as u64, hits: results.len() });
+            let processed = postprocessed(&retr, q, &results, &verbosity(&args)?)?;
+            let results = match &processed {
+                Some(nodes) => postprocess::keep(&results, nodes),
+                None => results,
+            };
+            if let Some(file) = args.opt("--save") {
+                notes::save(file, "search", &args, q, &retr, &results, &verbosity(&args)?)?;
+                eprintln!("[search] appended to {file}");
+            }
+            if args.flag("--json") {
+                let nodes = match processed {
+                    Some(nodes) => nodes,
+                    None => retr.to_nodes_with(&results, &verbosity(&args)?)?,
+                };
+                println!("{}", serde_json::to_string_pretty(&nodes)?);
+                return Ok(0);
+            }
             println!("Top results for: \"{}\"", q);
-            for (id, sim) in results {
-                println!("{:6.3}  {}", sim, id);
+            print_hits(&retr, &results, processed.as_deref().unwrap_or_default(), &verbosity(&args)?)?;
+            if retr.truncated() {
+                println!("(search budget reached: results are best-effort)");
             }
         }
+        Some("upgrade") => {
+            let limit = args.opt("--limit").map(str::parse).transpose()?;
+            return upgrade::run(args.opt("--into").unwrap_or("index"), limit, args.flag("--background"));
+        }
+        Some("build-hnsw") if args.flag("--cancel") => hnsw::cancel()?,
         Some("build-hnsw") => {
-            let mut retr = mentat_retriever::Retriever::open_default()?;
-            retr.build_hnsw("index/embeds")?;
+            hnsw::build(args.flag("--background"))?;
         }
         Some("search-hnsw") => {
-            let q = args.get(2).map(String::as_str).unwrap_or("");
+            let q = &standalone_query(args.pos(0).unwrap_or(""), args.opt("--history"), args.opt("--llm-cmd"))?;
             let mut retr = mentat_retriever::Retriever::open_default()?;
-            retr.load_hnsw("index/embeds.hnsw")?;
-            let results = retr.search(q, 5)?;
+            retr.set_relevance(mentat_config::Config::load()?.search.relevance);
+            restrict_to_rev(&mut retr, args.opt("--rev"))?;
+            let parsed = parse_query(retr.store(), q, !args.flag("--all"))?;
+            if !parsed.has_filters() && args.opt("--rev").is_none() {
+                hnsw::load_or_schedule(&mut retr);
+            }
+            if let Some(b) = budget(&args)? {
+                retr.set_budget(b);
+            }
+            if args.flag("--pseudonymize") {
+                retr.set_pseudonymize()?;
+            }
+            let t = std::time::Instant::now();
+            let results = search_merged(&retr, &parsed, None, args.opt_or("--k", 5)?, args.flag("--no-merge"))?;
+            usage::record("index", usage::Event::Search { at: usage::now(), ms: t.elapsed().as_millis() as u64, hits: results.len() });
+            let processed = postprocessed(&retr, q, &results, &verbosity(&args)?)?;
+            let results = match &processed {
+                Some(nodes) => postprocess::keep(&results, nodes),
+                None => results,
+            };
+            if let Some(file) = args.opt("--save") {
+                notes::save(file, "search-hnsw", &args, q, &retr, &results, &verbosity(&args)?)?;
+                eprintln!("[search] appended to {file}");
+            }
+            if args.flag("--json") {
+                let nodes = match processed {
+                    Some(nodes) => nodes,
+                    None => retr.to_nodes_with(&results, &verbosity(&args)?)?,
+                };
+                println!("{}", serde_json::to_string_pretty(&nodes)?);
+                return Ok(0);
+            }
             println!("HNSW results for: \"{}\"", q);
-            for (i, d) in results {
-                println!("{:6.3}  id[{}]", d, i);
+            print_hits(&retr, &results, processed.as_deref().unwrap_or_default(), &verbosity(&args)?)?;
+            if retr.truncated() {
+                println!("(search budget reached: results are best-effort)");
             }
         }
+        Some("eval") => {
+            let qrels = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat eval <qrels.jsonl>"))?;
+            let k = args.opt_or("--k", 10)?;
+            match (args.opt("--baseline"), args.opt("--candidate")) {
+                (None, None) => run_eval(qrels, k, args.flag("--hnsw"), args.flag("--json"))?,
+                (base, cand) => run_eval_compare(qrels, base.unwrap_or("index"), cand.unwrap_or("index"), k, args.flag("--hnsw"), args.flag("--json"))?,
+            }
+        }
+        Some("alias") => {
+            return run_alias(args.pos(0), args.pos(1), args.pos(2));
+        }
+        Some("ls") => {
+            let (offset, limit) = (args.opt_or("--offset", 0)?, args.opt_or("--limit", 100)?);
+            return run_ls(args.pos(0).unwrap_or(""), args.opt("--file"), offset, limit, args.flag("--json"));
+        }
+        Some("chunk") => {
+            let id = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat chunk <chunk_id> [--expand N] [--json]"))?;
+            return run_chunk(id, args.opt_or("--expand", 0)?, args.flag("--json"));
+        }
+        Some("sample") => {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos() as u64;
+            let seed = args.opt_or("--seed", now)?;
+            return run_sample(args.opt_or("--n", 10)?, seed, args.opt_or("--snippet", 8)?, args.flag("--json"));
+        }
+        Some("slowest") => {
+            return run_slowest(args.opt_or("--n", 20)?, args.opt("--by").unwrap_or("total"), args.flag("--json"));
+        }
+        Some("import") => {
+            let src = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat import --from faiss <file> --model NAME"))?;
+            return run_import(args.opt("--from"), src, args.opt("--meta"), args.opt("--model"), args.opt("--root"), args.flag("--force"));
+        }
+        Some("export-vectors") => {
+            return run_export_vectors(args.opt("--format").unwrap_or("npy"), args.opt("--out"), args.flag("--pseudonymize"));
+        }
+        Some("cite") => {
+            let id = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat cite <chunk_id>"))?;
+            return run_cite(id);
+        }
+        Some("resolve") => {
+            let c = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat resolve <citation> [--json]"))?;
+            return run_resolve(c, args.flag("--json"));
+        }
+        Some("reveal") => {
+            let p = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat reveal <pseudonym>"))?;
+            return run_reveal(p);
+        }
+        Some("cite-check") => {
+            let answer = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat cite-check <answer.md> [--json]"))?;
+            return run_cite_check(answer, args.flag("--json"));
+        }
+        Some("grep") => {
+            let pattern = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat grep <regex> [query] [--ignore-case] [--limit N] [--all] [--json]"))?;
+            let q = args.pos(1).unwrap_or("");
+            return run_grep(pattern, q, args.flag("--ignore-case"), args.opt_or("--limit", 50)?, !args.flag("--all"), args.flag("--json"));
+        }
+        Some("secrets") => {
+            return run_secrets(args.flag("--no-semantic"), args.flag("--json"));
+        }
+        Some("provenance") => {
+            let file = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat provenance <file> [--ref DIR] [--min-similarity S] [--json]"))?;
+            let min = args.opt_or("--min-similarity", mentat_retriever::provenance::MIN_SIMILARITY)?;
+            return run_provenance(file, args.opt("--ref").unwrap_or("reference"), min, args.flag("--json"));
+        }
+        Some("graph") => match args.pos(0) {
+            Some("export") => run_graph_export(args.opt("--format").unwrap_or("json"), args.opt("--out"), args.flag("--pseudonymize"))?,
+            _ => anyhow::bail!("usage: mentat graph export [--format json|dot|graphml] [--out FILE]"),
+        },
+        Some("project") => {
+            let method = args.opt("--method").unwrap_or("pca");
+            let (clusters, format) = (args.opt_or("--clusters", 8)?, args.opt("--format").unwrap_or("csv"));
+            run_project(method, clusters, format, args.opt("--out"), args.flag("--pseudonymize"))?;
+        }
+        Some("reindex") => {
+            anyhow::ensure!(!args.pos_from(0).is_empty(), "usage: mentat reindex <file>... [--into DIR]");
+            let cfg = mentat_config::Config::load_profile(args.opt("--profile"))?;
+            let opts = index_options(&args, &cfg)?;
+            return run_reindex(args.opt("--into").unwrap_or("index"), args.pos_from(0), &opts);
+        }
+        Some("docs") => match (args.pos(0), args.pos(1)) {
+            (Some("add"), Some(file)) => {
+                let cfg = mentat_config::Config::load_profile(args.opt("--profile"))?;
+                return run_docs_add(file, &index_options(&args, &cfg)?);
+            }
+            (Some("rm"), Some(_)) => return run_docs_rm(args.pos_from(1)),
+            _ => anyhow::bail!("usage: mentat docs add <docs.jsonl> | mentat docs rm <id>..."),
+        },
+        Some("swap") => {
+            let (Some(alias), Some(target)) = (args.pos(0), args.pos(1)) else {
+                anyhow::bail!("usage: mentat swap <alias> <index-dir>");
+            };
+            run_swap(alias, target)?;
+        }
+        Some("related") => {
+            let path = args.pos(0).ok_or_else(|| anyhow::anyhow!("usage: mentat related <path> [--json]"))?;
+            run_related(path, args.flag("--json"))?;
+        }
+        Some("licenses") => {
+            run_licenses(args.flag("--json"))?;
+        }
+        Some("suggest-ignores") => {
+            run_suggest_ignores(args.opt("--root"), args.flag("--json"))?;
+        }
+        Some("usage") => {
+            usage::show(args.opt("--into").unwrap_or("index"), args.opt_or("--days", 30)?, args.flag("--json"))?;
+        }
+        Some("doctor") => {
+            return doctor::run(args.opt("--into").unwrap_or("index"), args.flag("--json"));
+        }
+        Some("selftest") => {
+            return selftest::run(args.flag("--json"));
+        }
+        Some("models") => {
+            let pins = mentat_config::Config::load()?.embedder.pins;
+            return match args.pos(0) {
+                Some("list") | None => models::list(&pins, args.flag("--json")),
+                Some("verify") => models::verify(&pins),
+                Some("pull") => models::pull(&pins, args.flag("--force")),
+                Some("distill") => models::distill(),
+                Some(other) => anyhow::bail!("unknown models action `{other}` (expected list, verify, pull or distill)"),
+            };
+        }
+        Some("config") => {
+            let root = Path::new(args.opt("--root").unwrap_or("."));
+            match (args.pos(0), args.pos(1)) {
+                (Some("export"), None) => run_config_export(root, args.opt("--out"))?,
+                (Some("import"), Some(file)) => run_config_import(root, file, args.flag("--force"))?,
+                _ => anyhow::bail!("usage: mentat config export [--out FILE] | import <bundle.toml> [--force] [--root DIR]"),
+            }
+        }
+        Some("verify") => {
+            return run_verify();
+        }
+        Some("salvage") => {
+            let src = args.pos(0).unwrap_or("index");
+            let dst = args.opt("--into").map(str::to_string).unwrap_or_else(|| format!("{src}.salvaged"));
+            return run_salvage(src, &dst, args.flag("--json"));
+        }
+        Some("report") => {
+            run_report(args.pos(0).unwrap_or("last"))?;
+        }
+        Some("eval-synth") => {
+            run_eval_synth(args.opt_or("--n", 50)?, args.opt_or("--seed", 1)?, args.opt("--llm-cmd"))?;
+        }
+        Some("gen-corpus") => {
+            let kind = args.opt("--kind").unwrap_or("mixed").parse()?;
+            run_gen_corpus(args.opt("--out").unwrap_or("corpus"), args.opt_or("--files", 1000)?, kind, args.opt_or("--seed", 1)?, args.flag("--force"), args.flag("--json"))?;
+        }
         _ => {
             println!("mentat veyrsson — condensed stub");
             println!("USAGE:");
-            println!("  mentat ingest <path>   # list files + hashes (json manifest)");
-            println!("  mentat index  <path>   # build ReDB index (files, chunks, embeds)");
-            println!("  mentat search <query>  # brute-force search");
-            println!("  mentat build-hnsw      # build HNSW index from embeddings");
-            println!("  mentat search-hnsw <query> # query via HNSW");
+            println!("  mentat ingest <path> [--diff prev.json] # write ingest_manifest.json; list added/changed/removed");
+            println!("  mentat index  <path> [--fail-fast] [--retune] [--profile NAME] [--into DIR] [--estimate] [--rebuild [--background]] [--in-place] [--mode full|static|hashed] [--tiered] # build ReDB index (files, chunks, embeds); --rebuild builds from scratch beside DIR and swaps it in, as a model/chunker change does unless --in-place; --mode switches the collection's embedding mode; --tiered embeds statically first and upgrades in the background");
+            println!("  mentat upgrade [--into DIR] [--limit N] # re-embed a tiered index's static vectors with the full model, most searched first");
+            println!("  mentat search <query> [--k N] [--json] [--no-merge] [--rev REV] [--as-of YYYY-MM-DD] [--history FILE [--llm-cmd CMD]] [--all] [--save NOTES.md] [--snippet N] [--context N] [--no-full-text] [--budget-ms N] [--pseudonymize] [--local] # brute-force search, across [search] federate indexes too unless --local; supports path: lang: after: before: license: owner: \"phrase\" -x OR");
+            println!("  mentat search --queries FILE [--format text|jsonl] [--k N] [--no-merge] [--all] [--snippet N] [--context N] [--no-full-text] [--pseudonymize] # one query per line, embedded in batches");
+            println!("  mentat build-hnsw      # build HNSW index from embeddings, with progress on stderr");
+            println!("  mentat build-hnsw --cancel # stop a background build at its next progress point");
+            println!("  mentat search-hnsw <query> [--k N] [--json] [--no-merge] [--rev REV] [--save NOTES.md] [--snippet N] [--context N] [--no-full-text] [--budget-ms N] [--pseudonymize] # query via HNSW");
+            println!("  mentat alias [ls | add <name> <query> | rm <name>] # saved queries, used as @name");
+            println!("  mentat ls [prefix] [--file PATH] [--offset N] [--limit N] [--json] # browse indexed files/chunks");
+            println!("  mentat chunk <chunk_id> [--expand N] [--json] # chunk text and N neighbors each side");
+            println!("  mentat sample [--n N] [--seed S] [--snippet N] [--json] # random chunks with their text and metadata");
+            println!("  mentat slowest [--n N] [--by total|chunk|embed|store] [--json] # files that cost the most to index, from their last run");
+            println!("  mentat import --from faiss <file> --model NAME [--meta spans.jsonl] [--root DIR] [--force] # store vectors computed elsewhere");
+            println!("  mentat export-vectors [--format npy|faiss] [--out FILE] [--pseudonymize] # vectors plus a .jsonl id/path sidecar");
+            println!("  mentat cite <chunk_id> # stable citation PATH:START-END#FINGERPRINT");
+            println!("  mentat resolve <citation> [--json] # where the cited text is now");
+            println!("  mentat reveal <pseudonym> # the path a --pseudonymize output named p-<hash>");
+            println!("  mentat cite-check <answer.md> [--json] # verify an answer's citations, paths and quoted code");
+            println!("  mentat grep <regex> [query] [--ignore-case] [--limit N] [--all] [--json] # regex over indexed files, scoped by the query's filters and ranked by its words");
+            println!("  mentat secrets [--no-semantic] [--json] # scan indexed files for leaked credentials");
+            println!("  mentat provenance <file> [--ref DIR] [--min-similarity S] [--json] # regions likely copied from a reference index");
+            println!("  mentat reindex <file>... [--into DIR] # re-index just these files now, e.g. from an editor's on-save hook");
+            println!("  mentat docs add <docs.jsonl> | rm <id>... # documents by id ({{\"id\": \"https://..\", \"text\": ..}} per line)");
+            println!("  mentat swap <alias> <index-dir> # atomically point an index alias (e.g. index) at another build");
+            println!("  mentat related <path> [--json] # files most similar to this one");
+            println!("  mentat graph export [--format json|dot|graphml] [--out FILE] [--pseudonymize] # related-files graph for visualization");
+            println!("  mentat project [--method pca] [--clusters N] [--format csv|json] [--out FILE] [--pseudonymize] # 2D coordinates per chunk for plotting");
+            println!("  mentat suggest-ignores [--root DIR] [--json] # .ingestignore entries for bulky low-value paths");
+            println!("  mentat licenses [--json] # indexed files grouped by detected license");
+            println!("  mentat models [list [--json] | verify | pull [--force] | distill] # model files, blake3 and [embedder] pins; distill writes the static-mode token table");
+            println!("  mentat config export [--out FILE] | import <bundle.toml> [--force] [--root DIR] # mentat.toml, .ingestignore and saved aliases as one file");
+            println!("  mentat verify          # check stored vectors and references");
+            println!("  mentat salvage [DIR] [--into OUT] [--json] # copy every readable row of a damaged index into a fresh one");
+            println!("  mentat usage [--days N] [--json] # local per-day queries, latency, cache hits and index size ([usage] enabled = true)");
+            println!("  mentat doctor [--into DIR] [--json] # diagnose config, model files, CUDA, index, lock and disk space");
+            println!("  mentat selftest [--json] # index a built-in corpus in a temp dir and check search finds each file; exit 2 on failure");
+            println!("  mentat report [last|list|<file>] # show index build reports");
+            println!("  mentat eval <qrels.jsonl> [--k N] [--hnsw] [--json] # MRR / recall@k");
+            println!("  mentat eval <qrels.jsonl> --baseline DIR [--candidate DIR] # per-query rank changes between two index copies");
+            println!("  mentat eval-synth [--n N] [--seed S] [--llm-cmd CMD] # generate qrels.jsonl on stdout");
+            println!("  mentat gen-corpus [--files N] [--kind code|prose|mixed] [--seed S] [--out DIR] [--force] [--json] # write a synthetic corpus, the same for the same seed, to benchmark and load-test indexing");
+        }
+    }
+    Ok(0)
+}
+
+fn run_ingest(target: &str, diff_against: Option<&str>) -> Result<i32> {
+    use mentat_ingest::manifest::{self, Manifest};
+    // load first: the previous manifest may be the one we're about to overwrite
+    let prev = diff_against.map(Manifest::load).transpose()?;
+    let m = manifest::build(target)?;
+    m.save(manifest::DEFAULT_PATH)?;
+    let skipped = m.files.iter().filter(|f| f.status == manifest::Status::Skipped).count();
+    println!("Ingested {} files ({} skipped, {} errors)", m.files.len(), skipped, m.errors());
+    if let Some(prev) = prev {
+        let d = manifest::diff(&prev, &m);
+        for p in &d.added {
+            println!("+ {p}");
+        }
+        for p in &d.changed {
+            println!("~ {p}");
+        }
+        for p in &d.removed {
+            println!("- {p}");
+        }
+        println!("{} added, {} changed, {} removed", d.added.len(), d.changed.len(), d.removed.len());
+    }
+    Ok(if m.errors() > 0 { 2 } else { 0 })
+}
+
+fn run_chunk(id: &str, expand: usize, json: bool) -> Result<i32> {
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let id = resolve_chunk_id(retr.store(), id)?;
+    let Some(c) = retr.chunk(id, expand)? else {
+        eprintln!("no chunk {}", hex::encode(id));
+        return Ok(1);
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&c)?);
+        return Ok(0);
+    }
+    for part in c.before.iter().chain(std::iter::once(&c)).chain(&c.after) {
+        let marker = if part.chunk_id == c.chunk_id { ">>" } else { "--" };
+        println!("{marker} {}:{}-{} ({})", part.path, part.start, part.end, part.chunk_id);
+        match &part.text {
+            Some(t) => println!("{t}"),
+            None => println!("<file changed since indexing; re-run mentat index>"),
+        }
+    }
+    Ok(0)
+}
+
+/// Random chunks with their text, to see what a collection actually holds.
+/// The seed is printed so a sample can be drawn again.
+fn run_sample(n: usize, seed: u64, lines: usize, json: bool) -> Result<i32> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let mut out = Vec::new();
+    for (id, c) in store.sample_chunks(n, seed)? {
+        let path = store.get_file(c.file_hash)?.map(|f| f.path).unwrap_or_default();
+        out.push(serde_json::json!({
+            "id": hex::encode(id),
+            "path": path,
+            "start": c.start,
+            "end": c.end,
+            "file_hash": hex::encode(c.file_hash),
+            "chunker": c.chunker,
+            "license": store.get_license(c.file_hash)?,
+            "generated": store.get_generated(c.file_hash)?,
+            "boilerplate": store.get_boilerplate(id)?,
+            "text": store.chunk_text(&c)?,
+        }));
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "seed": seed, "chunks": out }))?);
+        return Ok(0);
+    }
+    for c in &out {
+        println!("-- {}:{}-{} ({})", c["path"].as_str().unwrap_or_default(), c["start"], c["end"], &c["id"].as_str().unwrap_or_default()[..12]);
+        match c["text"].as_str() {
+            Some(t) => println!("{}", mentat_retriever::snippet::head(t, lines)),
+            None => println!("<file changed since indexing; re-run mentat index>"),
+        }
+    }
+    eprintln!("[sample] {} chunks, seed {seed} (--seed {seed} draws them again)", out.len());
+    Ok(0)
+}
+
+fn run_slowest(n: usize, by: &str, json: bool) -> Result<i32> {
+    let key: fn(&mentat_store::FileTiming) -> u64 = match by {
+        "total" => |t| t.total_us(),
+        "chunk" => |t| t.chunk_us,
+        "embed" => |t| t.embed_us,
+        "store" => |t| t.store_us,
+        other => anyhow::bail!("unknown --by {other} (total, chunk, embed, store)"),
+    };
+    let store = mentat_store::Store::open_existing("index")?;
+    let paths: std::collections::HashMap<[u8; 32], String> = store.files()?.into_iter().map(|(h, f)| (h, f.path)).collect();
+    let mut rows: Vec<mentat_indexer::report::FileCost> = store
+        .timings()?
+        .into_iter()
+        .filter_map(|(h, timing)| paths.get(&h).map(|path| mentat_indexer::report::FileCost { path: path.clone(), timing }))
+        .collect();
+    rows.sort_by_key(|c| std::cmp::Reverse(key(&c.timing)));
+    rows.truncate(n);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(0);
+    }
+    if rows.is_empty() {
+        eprintln!("[slowest] no timings recorded; run mentat index");
+        return Ok(0);
+    }
+    let ms = |us: u64| us as f64 / 1000.0;
+    println!("{:>10} {:>10} {:>10} {:>10} {:>8}  path", "total ms", "chunk ms", "embed ms", "store ms", "embedded");
+    for c in &rows {
+        let t = &c.timing;
+        println!("{:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>8}  {}", ms(t.total_us()), ms(t.chunk_us), ms(t.embed_us), ms(t.store_us), t.embedded, c.path);
+    }
+    Ok(0)
+}
+
+/// Expand `@aliases` (store entries win over mentat.toml), parse, and add
+/// the `[search] exclude` words unless `defaults` is off.
+fn parse_query(store: &mentat_store::Store, q: &str, defaults: bool) -> Result<mentat_retriever::query::Query> {
+    let cfg = mentat_config::Config::load()?;
+    let mut aliases = cfg.aliases;
+    aliases.extend(store.aliases()?);
+    let expanded = mentat_retriever::query::expand_aliases(q, &|name| aliases.get(name).cloned())?;
+    if expanded != q {
+        eprintln!("[search] expanded to: {expanded}");
+    }
+    let mut parsed = mentat_retriever::query::parse(&expanded)?;
+    if defaults {
+        for word in &cfg.search.exclude {
+            parsed.clauses.push(mentat_retriever::query::Clause {
+                field: mentat_retriever::query::Field::Word,
+                values: vec![word.clone()],
+                negated: true,
+            });
         }
     }
+    Ok(parsed)
+}
+
+fn run_alias(action: Option<&str>, name: Option<&str>, query: Option<&str>) -> Result<i32> {
+    let usage = "usage: mentat alias [ls | add <name> <query> | rm <name>]";
+    match (action.unwrap_or("ls"), name, query) {
+        ("ls", None, None) => {
+            let store = mentat_store::Store::open_existing("index")?;
+            let cfg = mentat_config::Config::load()?;
+            let saved = store.aliases()?;
+            for (n, q) in &cfg.aliases {
+                if !saved.iter().any(|(s, _)| s == n) {
+                    println!("@{n}\t{q}\t(mentat.toml)");
+                }
+            }
+            for (n, q) in &saved {
+                println!("@{n}\t{q}");
+            }
+        }
+        ("add", Some(n), Some(q)) => {
+            if n.is_empty() || n.contains(char::is_whitespace) || n.starts_with('@') {
+                anyhow::bail!("alias names are single words without a leading @");
+            }
+            // catch syntax errors now rather than at first use
+            mentat_retriever::query::parse(q)?;
+            mentat_store::Store::open("index")?.put_alias(n, q)?;
+            println!("saved @{n}");
+        }
+        ("rm", Some(n), None) => {
+            if !mentat_store::Store::open_existing("index")?.remove_alias(n)? {
+                eprintln!("no saved alias @{n}");
+                return Ok(1);
+            }
+            println!("removed @{n}");
+        }
+        _ => anyhow::bail!(usage),
+    }
+    Ok(0)
+}
+
+fn run_ls(prefix: &str, file: Option<&str>, offset: usize, limit: usize, json: bool) -> Result<i32> {
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let next = match file {
+        Some(path) => {
+            let Some(page) = retr.list_chunks(path, offset, limit)? else {
+                eprintln!("{path} is not in the index");
+                return Ok(1);
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&page)?);
+                return Ok(0);
+            }
+            for c in &page.items {
+                let mark = if c.embedded { "" } else { "  (no embedding)" };
+                println!("{}  {}-{}{mark}", &c.chunk_id[..12], c.start, c.end);
+            }
+            (page.next, page.total)
+        }
+        None => {
+            let page = retr.list_files(prefix, offset, limit)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&page)?);
+                return Ok(0);
+            }
+            for f in &page.items {
+                println!("{:>9}  {:>4} chunks  {}", f.size, f.chunks, f.path);
+            }
+            (page.next, page.total)
+        }
+    };
+    if let (Some(n), total) = next {
+        println!("... {} more (--offset {n})", total - n);
+    }
+    Ok(0)
+}
+
+/// Full 64-hex id, or a unique prefix of one (as printed by search).
+fn resolve_chunk_id(store: &mentat_store::Store, id: &str) -> Result<[u8; 32]> {
+    if id.len() == 64 {
+        return mentat_indexer::hex_to32(id);
+    }
+    let id = id.to_ascii_lowercase();
+    let matches: Vec<[u8; 32]> =
+        store.chunks()?.into_iter().map(|(k, _)| k).filter(|k| hex::encode(k).starts_with(&id)).collect();
+    match matches.as_slice() {
+        [one] => Ok(*one),
+        [] => anyhow::bail!("no chunk id starts with {id}"),
+        _ => anyhow::bail!("chunk id prefix {id} is ambiguous ({} matches)", matches.len()),
+    }
+}
+
+fn run_cite(id: &str) -> Result<i32> {
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let id = resolve_chunk_id(retr.store(), id)?;
+    match retr.cite(id)? {
+        Some(c) => println!("{c}"),
+        None => {
+            eprintln!("no citable text for chunk {} (missing, or file changed since indexing)", hex::encode(id));
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn run_resolve(citation: &str, json: bool) -> Result<i32> {
+    let c: mentat_retriever::cite::Citation = citation.parse()?;
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let Some(r) = retr.resolve_citation(&c)? else {
+        eprintln!("no indexed text matches {c}");
+        return Ok(1);
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&r)?);
+    } else {
+        let how = if r.in_place && r.distance == 0 { "unchanged".to_string() } else { format!("{} bits off", r.distance) };
+        println!("{}:{}-{}  {}  ({how})", r.path, r.start, r.end, &r.chunk_id[..12]);
+    }
+    Ok(0)
+}
+
+/// Exit 2 if any claim in the answer is not backed by the index.
+fn run_cite_check(answer: &str, json: bool) -> Result<i32> {
+    let text = std::fs::read_to_string(answer)?;
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let checks = mentat_retriever::cite::extract(&text)
+        .iter()
+        .map(|c| retr.check_claim(c))
+        .collect::<Result<Vec<_>>>()?;
+    let missing = checks.iter().filter(|c| !c.found).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        use mentat_retriever::cite::Claim;
+        for c in &checks {
+            let what = match &c.claim {
+                Claim::Citation { citation } => citation.clone(),
+                Claim::Path { path, line: Some(n) } => format!("{path}:{n}"),
+                Claim::Path { path, line: None } => path.clone(),
+                Claim::Snippet { text } => format!("\"{}\"", text.lines().next().unwrap_or("").chars().take(60).collect::<String>()),
+            };
+            let mark = if c.found { "ok " } else { "!! " };
+            println!("{mark} {:.2}  {what}  {}", c.score, c.location.as_deref().unwrap_or("-"));
+        }
+        println!("{} claims, {missing} not found in the index", checks.len());
+    }
+    Ok(if missing == 0 { 0 } else { 2 })
+}
+
+/// Exit 2 on any rule finding; semantic leads alone don't fail.
+fn run_grep(pattern: &str, q: &str, ignore_case: bool, limit: usize, defaults: bool, json: bool) -> Result<i32> {
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let parsed = parse_query(retr.store(), q, defaults)?;
+    let rep = mentat_retriever::grep::grep(&retr, pattern, ignore_case, &parsed, limit)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rep)?);
+        return Ok(0);
+    }
+    for m in &rep.matches {
+        match m.distance {
+            Some(d) => println!("{d:6.3}  {}:{}: {}", m.path, m.line, m.text),
+            None => println!("{}:{}: {}", m.path, m.line, m.text),
+        }
+    }
+    if !rep.stale.is_empty() {
+        eprintln!("[grep] {} file(s) changed since indexing were not scanned; re-run mentat index", rep.stale.len());
+    }
+    eprintln!("[grep] {} of {} matches in {} files scanned", rep.matches.len(), rep.total, rep.files_scanned);
+    Ok(0)
+}
+
+fn run_secrets(no_semantic: bool, json: bool) -> Result<i32> {
+    let retr = mentat_retriever::Retriever::open_default()?;
+    let rep = mentat_retriever::secrets::scan(&retr, !no_semantic)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rep)?);
+    } else {
+        for f in &rep.findings {
+            println!("{}:{}  {}  {}", f.path, f.line, f.rule, f.excerpt);
+        }
+        for f in &rep.leads {
+            println!("review {}:{}  ({}, {:.3})  {}", f.path, f.line, f.rule, f.distance.unwrap_or(0.0), f.excerpt);
+        }
+        if !rep.stale.is_empty() {
+            println!("{} file(s) changed since indexing were not scanned; re-run mentat index", rep.stale.len());
+        }
+        println!("{} findings, {} leads to review", rep.findings.len(), rep.leads.len());
+    }
+    Ok(if rep.findings.is_empty() { 0 } else { 2 })
+}
+
+fn run_provenance(file: &str, ref_dir: &str, min_similarity: f32, json: bool) -> Result<i32> {
+    let reference = mentat_retriever::Retriever::open(ref_dir)
+        .map_err(|e| e.context(format!("no reference index at {ref_dir}; build one with mentat index <dir> --into {ref_dir}")))?;
+    let data = std::fs::read(file)?;
+    let rep = mentat_retriever::provenance::check(&reference, file, &data, min_similarity)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rep)?);
+    } else {
+        for r in &rep.regions {
+            println!(
+                "{file}:{}-{}  ~ {}:{}-{}  similarity {:.3}, verbatim {:.2}",
+                r.first_line, r.last_line, r.ref_path, r.ref_start, r.ref_end, r.similarity, r.verbatim
+            );
+        }
+        println!("{} region(s) across {} span(s) match {ref_dir}", rep.regions.len(), rep.spans);
+    }
+    Ok(if rep.regions.is_empty() { 0 } else { 2 })
+}
+
+/// Blue/green reindex: build with `mentat index <path> --into index-v2`,
+/// then `mentat swap index index-v2`. The alias is a symlink replaced by
+/// rename, so a command opening it sees either the old build or the new one.
+#[cfg(unix)]
+fn run_swap(alias: &str, target: &str) -> Result<()> {
+    let alias_path = Path::new(alias);
+    if !Path::new(target).join("kv.redb").is_file() {
+        anyhow::bail!("{target} has no kv.redb; build it first with mentat index <path> --into {target}");
+    }
+    let previous = match std::fs::symlink_metadata(alias_path) {
+        Ok(m) if m.file_type().is_symlink() => Some(std::fs::read_link(alias_path)?),
+        Ok(_) => anyhow::bail!("{alias} is a real directory; move it aside (e.g. to {alias}-v1) and swap to that first"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let dest = std::fs::canonicalize(target)?;
+    let tmp = alias_path.with_file_name(format!(".{}.swap", alias_path.file_name().and_then(|n| n.to_str()).unwrap_or(alias)));
+    let _ = std::fs::remove_file(&tmp);
+    std::os::unix::fs::symlink(&dest, &tmp)?;
+    std::fs::rename(&tmp, alias_path)?;
+    match previous {
+        Some(p) => println!("{alias} -> {} (was {})", dest.display(), p.display()),
+        None => println!("{alias} -> {}", dest.display()),
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn run_swap(_alias: &str, _target: &str) -> Result<()> {
+    anyhow::bail!("mentat swap needs symlinks and is only supported on unix")
+}
+
+fn run_related(path: &str, json: bool) -> Result<()> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let files = store.files()?;
+    let (fhash, _) = files
+        .iter()
+        .find(|(_, f)| f.path == path)
+        .ok_or_else(|| anyhow::anyhow!("{path} is not indexed"))?;
+    let related: Vec<(String, f32)> = store
+        .get_related(*fhash)?
+        .into_iter()
+        .filter_map(|(h, s)| files.iter().find(|(fh, _)| *fh == h).map(|(_, f)| (f.path.clone(), s)))
+        .collect();
+    if json {
+        let rows: Vec<_> = related.iter().map(|(p, s)| serde_json::json!({ "path": p, "similarity": s })).collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    if related.is_empty() {
+        println!("No related files above the similarity threshold.");
+    }
+    for (p, s) in &related {
+        println!("{s:.3}  {p}");
+    }
+    Ok(())
+}
+
+fn run_graph_export(format: &str, out: Option<&str>, pseudonymize: bool) -> Result<()> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let mut g = graph::load(&store)?;
+    if pseudonymize {
+        let p = mentat_retriever::pseudonym::Pseudonyms::open(&store)?;
+        for n in &mut g.nodes {
+            n.id = p.name(&store, &n.id)?;
+        }
+        for l in &mut g.links {
+            l.source = p.name(&store, &l.source)?;
+            l.target = p.name(&store, &l.target)?;
+        }
+    }
+    let text = match format {
+        "json" => serde_json::to_string_pretty(&g)?,
+        "dot" => graph::to_dot(&g),
+        "graphml" => graph::to_graphml(&g),
+        other => anyhow::bail!("unknown --format {other} (expected json, dot or graphml)"),
+    };
+    match out {
+        Some(path) => {
+            std::fs::write(path, text)?;
+            eprintln!("Wrote {} files and {} links to {path}", g.nodes.len(), g.links.len());
+        }
+        None => println!("{text}"),
+    }
     Ok(())
 }
 
-fn run_index(path: &str) -> Result<()> {
-    // 1) ingest
-    eprintln!("[index] Starting ingest...");
-    let files = mentat_ingest::ingest(path)?;
-    eprintln!("[index] Found {} files", files.len());
-    // 2) open store
+fn run_project(method: &str, clusters: usize, format: &str, out: Option<&str>, pseudonymize: bool) -> Result<()> {
+    match method {
+        "pca" => {}
+        "umap" => anyhow::bail!("umap projection is not supported yet; use --method pca"),
+        other => anyhow::bail!("unknown --method {other} (expected pca)"),
+    }
+    let store = mentat_store::Store::open_existing("index")?;
+    let files = export_paths(&store, pseudonymize)?;
+    let mut m = mentat_vecio::Matrix { dim: mentat_embedder::D, data: Vec::new() };
+    let mut rows = Vec::new();
+    for (id, v) in store.embeds()? {
+        if mentat_embedder::invalid_reason(&v).is_some() {
+            continue;
+        }
+        let Some(c) = store.get_chunk(id)? else { continue };
+        let Some(path) = files.get(&c.file_hash) else { continue };
+        m.data.extend_from_slice(&v);
+        rows.push((hex::encode(id), path.clone(), c.start, c.end));
+    }
+    let xy = mentat_vecio::project::pca2(&m);
+    let labels = mentat_vecio::project::kmeans(&m, clusters);
+    let text = match format {
+        "csv" => {
+            let mut s = String::from("chunk_id,path,start,end,x,y,cluster\n");
+            for (((id, path, start, end), [x, y]), c) in rows.iter().zip(&xy).zip(&labels) {
+                s.push_str(&format!("{id},\"{}\",{start},{end},{x},{y},{c}\n", path.replace('"', "\"\"")));
+            }
+            s
+        }
+        "json" => {
+            let items: Vec<_> = rows
+                .iter()
+                .zip(&xy)
+                .zip(&labels)
+                .map(|(((id, path, start, end), [x, y]), c)| {
+                    serde_json::json!({ "chunk_id": id, "path": path, "start": start, "end": end, "x": x, "y": y, "cluster": c })
+                })
+                .collect();
+            serde_json::to_string_pretty(&items)?
+        }
+        other => anyhow::bail!("unknown --format {other} (expected csv or json)"),
+    };
+    match out {
+        Some(path) => {
+            std::fs::write(path, text)?;
+            eprintln!("Wrote {} points in {} clusters to {path}", rows.len(), clusters.min(rows.len()));
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+fn run_licenses(json: bool) -> Result<()> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let mut by_license: std::collections::BTreeMap<String, Vec<String>> = std::collections::BTreeMap::new();
+    for (h, f) in store.files()? {
+        let license = store.get_license(h)?.unwrap_or_else(|| "none".into());
+        by_license.entry(license).or_default().push(f.path);
+    }
+    for paths in by_license.values_mut() {
+        paths.sort();
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&by_license)?);
+        return Ok(());
+    }
+    for (license, paths) in &by_license {
+        println!("{license} ({})", paths.len());
+        for p in paths {
+            println!("  {p}");
+        }
+    }
+    Ok(())
+}
+
+/// Print the suggestions as a `.ingestignore` fragment: a comment with the
+/// projected savings above each pattern.
+fn run_suggest_ignores(root: Option<&str>, json: bool) -> Result<()> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let root = match root {
+        Some(r) => r.to_string(),
+        None => store.get_meta(mentat_store::META_ROOT)?.map(|r| String::from_utf8_lossy(&r).into_owned()).unwrap_or_else(|| ".".into()),
+    };
+    let ignored: Vec<String> = std::fs::read_to_string(Path::new(&root).join(".ingestignore"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    let (suggestions, total) = mentat_indexer::suggest::suggest(&store, &ignored)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&suggestions)?);
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        eprintln!("[suggest-ignores] nothing stands out in {total} chunks");
+        return Ok(());
+    }
+    for s in &suggestions {
+        let why: Vec<String> = s.reasons.iter().filter(|(_, n)| **n > 0).map(|(r, n)| format!("{n} {r}")).collect();
+        let share = s.chunks as f64 * 100.0 / total.max(1) as f64;
+        let mib = s.bytes as f64 / (1u64 << 20) as f64;
+        println!("# saves {} chunks ({share:.1}%), {} files, {mib:.1} MiB; low-value: {}", s.chunks, s.files, why.join(", "));
+        println!("{}", s.pattern);
+    }
+    Ok(())
+}
+
+fn run_config_export(root: &Path, out: Option<&str>) -> Result<()> {
+    let read = |p: &Path| -> Result<Option<String>> {
+        if !p.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(p).with_context(|| format!("reading {}", p.display()))?))
+    };
+    let mut bundle = mentat_config::bundle::Bundle {
+        version: mentat_config::bundle::VERSION,
+        config: read(&mentat_config::config_path())?,
+        ingestignore: read(&root.join(".ingestignore"))?,
+        ..Default::default()
+    };
+    if Path::new("index/kv.redb").exists() {
+        bundle.aliases = mentat_store::Store::open_existing("index")?.aliases()?.into_iter().collect();
+    }
+    let text = bundle.to_toml()?;
+    match out {
+        Some(path) => {
+            std::fs::write(path, text)?;
+            eprintln!("Wrote {path}");
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+/// Files that already exist with other contents are only replaced with `force`.
+fn run_config_import(root: &Path, file: &str, force: bool) -> Result<()> {
+    let text = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+    let bundle = mentat_config::bundle::Bundle::parse(&text).with_context(|| format!("in {file}"))?;
+    let writes: Vec<(std::path::PathBuf, &String)> = [
+        (mentat_config::config_path(), bundle.config.as_ref()),
+        (root.join(".ingestignore"), bundle.ingestignore.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(p, c)| c.map(|c| (p, c)))
+    .collect();
+    let clobbered: Vec<String> = writes
+        .iter()
+        .filter(|(p, c)| std::fs::read_to_string(p).is_ok_and(|old| old != **c))
+        .map(|(p, _)| p.display().to_string())
+        .collect();
+    if !clobbered.is_empty() && !force {
+        anyhow::bail!("would overwrite {} (pass --force to replace)", clobbered.join(", "));
+    }
+    for (p, c) in &writes {
+        std::fs::write(p, c)?;
+        println!("wrote {}", p.display());
+    }
+    if !bundle.aliases.is_empty() {
+        let store = mentat_store::Store::open("index")?;
+        for (n, q) in &bundle.aliases {
+            mentat_retriever::query::parse(q).with_context(|| format!("alias @{n}"))?;
+            store.put_alias(n, q)?;
+        }
+        println!("saved {} alias(es)", bundle.aliases.len());
+    }
+    Ok(())
+}
+
+/// `--rev`: only file versions whose content matches that git revision.
+fn restrict_to_rev(retr: &mut mentat_retriever::Retriever, rev: Option<&str>) -> Result<()> {
+    let Some(rev) = rev else { return Ok(()) };
+    let root = retr
+        .store()
+        .get_meta(mentat_store::META_ROOT)?
+        .map(|r| String::from_utf8_lossy(&r).into_owned())
+        .ok_or_else(|| anyhow::anyhow!("index has no recorded root"))?;
+    let files = mentat_retriever::rev::files_at(retr.store(), Path::new(&root), rev)?;
+    eprintln!("[search] {} indexed files match {rev}", files.len());
+    retr.restrict_files(files);
+    Ok(())
+}
+
+/// Top `k` after `[ranking]` boosts and folding overlapping spans; over-fetches
+/// so merging doesn't shrink the list. `qv` is the query's embedding when the
+/// caller already has it.
+fn search_merged(
+    retr: &mentat_retriever::Retriever,
+    q: &mentat_retriever::query::Query,
+    qv: Option<&[f32; mentat_embedder::D]>,
+    k: usize,
+    no_merge: bool,
+) -> Result<Vec<mentat_retriever::Hit>> {
+    let boosts = mentat_config::Config::load()?.ranking;
+    // boosts can promote hits from below the cut, so fetch extra for them too
+    let fetch = if boosts.is_empty() { k } else { k * 3 };
+    let search = |n| match qv {
+        Some(v) => retr.search_query_vec(q, v, n),
+        None => retr.search_query(q, n),
+    };
+    if no_merge {
+        let mut hits = retr.rerank(search(fetch)?, &boosts, q)?;
+        hits.truncate(k);
+        note_hits(retr, &hits);
+        return Ok(hits);
+    }
+    let mut hits = mentat_retriever::merge_overlaps(retr.rerank(search(fetch * 3)?, &boosts, q)?);
+    hits.truncate(k);
+    note_hits(retr, &hits);
+    Ok(hits)
+}
+
+/// Move chunks that searches return up the tiered upgrade queue. Best
+/// effort: a failed write never fails the search.
+fn note_hits(retr: &mentat_retriever::Retriever, hits: &[mentat_retriever::Hit]) {
+    let ids: Vec<[u8; 32]> = hits
+        .iter()
+        .flat_map(|h| std::iter::once(&h.chunk_id).chain(&h.merged))
+        .filter_map(|id| hex::decode(id).ok()?.try_into().ok())
+        .collect();
+    if let Err(e) = retr.store().note_hits(&ids) {
+        eprintln!("[upgrade] could not record hits: {e:#}");
+    }
+}
+
+/// `mentat search` over this index and the `[search] federate` ones.
+fn run_search_federated(retr: &mentat_retriever::Retriever, q: &mentat_retriever::query::Query, text: &str, args: &cli::Args) -> Result<i32> {
+    let cfg = mentat_config::Config::load()?;
+    let sources = federate::open(&cfg.search.federate, retr, &cfg.search, budget(args)?);
+    let (k, no_merge, v) = (args.opt_or("--k", 5)?, args.flag("--no-merge"), verbosity(args)?);
+    let semantic = q.semantic_text();
+    anyhow::ensure!(!semantic.trim().is_empty(), "query has no search words or phrases");
+    let t = std::time::Instant::now();
+    let qv = retr.embed_query(&semantic)?;
+    let local = search_merged(retr, q, Some(&qv), k, no_merge)?;
+    let hits = federate::search(local, &sources, q, &qv, k, no_merge)?;
+    usage::record("index", usage::Event::Search { at: usage::now(), ms: t.elapsed().as_millis() as u64, hits: hits.len() });
+    if args.flag("--json") {
+        println!("{}", serde_json::to_string_pretty(&federate::to_nodes(retr, &sources, &hits, &v)?)?);
+        return Ok(0);
+    }
+    println!("Top results for: \"{}\"", text);
+    for (i, h) in &hits {
+        let (r, label) = federate::source(retr, &sources, *i);
+        print_hits(r, std::slice::from_ref(h), &[], &v)?;
+        println!("        index: {label}");
+    }
+    if retr.truncated() || sources.iter().any(|s| s.retr.truncated()) {
+        println!("(search budget reached: results are best-effort)");
+    }
+    Ok(0)
+}
+
+/// `mentat search --queries FILE`: one query per line (blank lines and `#`
+/// comments skipped), embedded in batches of `QUERY_BATCH` by one model
+/// load. Results stream out as each batch finishes; a query that fails is
+/// reported in place and the run exits 2.
+fn run_search_batch(
+    file: &str,
+    format: &str,
+    k: usize,
+    no_merge: bool,
+    defaults: bool,
+    pseudonymize: bool,
+    v: &mentat_retriever::snippet::Verbosity,
+) -> Result<i32> {
+    const QUERY_BATCH: usize = 32;
+    anyhow::ensure!(matches!(format, "text" | "jsonl"), "unknown --format {format} (expected text or jsonl)");
+    let text = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).collect();
+    let mut retr = mentat_retriever::Retriever::open_default()?;
+    retr.set_relevance(mentat_config::Config::load()?.search.relevance);
+    if pseudonymize {
+        retr.set_pseudonymize()?;
+    }
+    let mut failed = 0;
+    for batch in lines.chunks(QUERY_BATCH) {
+        let parsed: Vec<Result<mentat_retriever::query::Query>> = batch
+            .iter()
+            .map(|l| {
+                let q = parse_query(retr.store(), l, defaults)?;
+                anyhow::ensure!(!q.semantic_text().trim().is_empty(), "query has no search words or phrases");
+                Ok(q)
+            })
+            .collect();
+        let texts: Vec<String> = parsed.iter().flatten().map(|q| q.semantic_text()).collect();
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let vecs = if refs.is_empty() { Vec::new() } else { retr.embed_queries(&refs)? };
+        let mut vecs = vecs.into_iter();
+        for (line, q) in batch.iter().zip(parsed) {
+            let t = std::time::Instant::now();
+            let hits = q.and_then(|q| {
+                let v = vecs.next().expect("one embedding per parsed query");
+                search_merged(&retr, &q, Some(&v), k, no_merge)
+            });
+            if let Ok(hits) = &hits {
+                usage::record("index", usage::Event::Search { at: usage::now(), ms: t.elapsed().as_millis() as u64, hits: hits.len() });
+            }
+            let hits = match hits {
+                Ok(hits) => hits,
+                Err(e) => {
+                    failed += 1;
+                    match format {
+                        "jsonl" => println!("{}", serde_json::json!({ "query": line, "error": format!("{e:#}") })),
+                        _ => println!("Error for: \"{line}\": {e:#}"),
+                    }
+                    continue;
+                }
+            };
+            match format {
+                "jsonl" => println!("{}", serde_json::json!({ "query": line, "hits": retr.to_nodes_with(&hits, v)? })),
+                _ => {
+                    println!("Top results for: \"{line}\"");
+                    print_hits(&retr, &hits, &[], v)?;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        eprintln!("[search] {failed} of {} queries failed", lines.len());
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+/// `--budget-ms N`, else `[search] budget_ms`; None when zero.
+fn budget(args: &cli::Args) -> Result<Option<std::time::Duration>> {
+    let ms = args.opt_or("--budget-ms", mentat_config::Config::load()?.search.budget_ms)?;
+    Ok((ms > 0).then_some(std::time::Duration::from_millis(ms)))
+}
+
+/// mentat.toml's indexing settings with `--fail-fast`, `--retune`,
+/// `--tiered` and `--mode` applied on top; for `index`, `reindex` and
+/// `docs add`.
+fn index_options(args: &cli::Args, cfg: &mentat_config::Config) -> Result<mentat_indexer::IndexOptions> {
+    let mut embed = cfg.embedder.clone();
+    embed.tiered |= args.flag("--tiered");
+    if let Some(mode) = args.opt("--mode") {
+        embed.mode = Some(match mode {
+            "full" => mentat_embedder::Mode::Full,
+            "static" => mentat_embedder::Mode::Static,
+            "hashed" => mentat_embedder::Mode::Hashed,
+            other => anyhow::bail!("--mode must be full, static or hashed, not `{other}`"),
+        });
+    }
+    Ok(mentat_indexer::IndexOptions {
+        fail_fast: args.flag("--fail-fast"),
+        normalize: cfg.text.clone(),
+        chunker: cfg.chunker.clone(),
+        caps: cfg.caps.clone(),
+        embed,
+        retune: args.flag("--retune"),
+        keep_history_days: cfg.history.keep_days,
+        generated: cfg.generated.clone(),
+        quota: cfg.quota,
+        owners: cfg.owners,
+    })
+}
+
+/// `[search]` snippet settings with `--snippet N`, `--context N` and
+/// `--no-full-text` applied on top.
+fn verbosity(args: &cli::Args) -> Result<mentat_retriever::snippet::Verbosity> {
+    let mut v = mentat_config::Config::load()?.search.verbosity();
+    v.lines = args.opt_or("--snippet", v.lines)?;
+    v.context = args.opt_or("--context", v.context)?;
+    if args.flag("--no-full-text") {
+        v.full_text = false;
+    }
+    Ok(v)
+}
+
+/// Result nodes after `[search] postprocess`; None when no command is set.
+fn postprocessed(
+    retr: &mentat_retriever::Retriever,
+    q: &str,
+    hits: &[mentat_retriever::Hit],
+    v: &mentat_retriever::snippet::Verbosity,
+) -> Result<Option<Vec<mentat_retriever::rag::Node>>> {
+    let cfg = mentat_config::Config::load()?.search;
+    if cfg.postprocess.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(postprocess::apply(&cfg, q, retr.to_nodes_with(hits, v)?)))
+}
+
+/// One line per hit, then its first `v.lines` lines of text between
+/// `v.context` lines of the file on each side, indented.
+/// `processed` are the hits' nodes after `[search] postprocess`, for their
+/// annotations; empty when none ran.
+fn print_hits(
+    retr: &mentat_retriever::Retriever,
+    hits: &[mentat_retriever::Hit],
+    processed: &[mentat_retriever::rag::Node],
+    v: &mentat_retriever::snippet::Verbosity,
+) -> Result<()> {
+    let show = v.lines > 0 || v.context > 0;
+    let nodes = if show { retr.to_nodes_with(hits, &mentat_retriever::snippet::Verbosity { full_text: false, ..*v })? } else { Vec::new() };
+    for (i, h) in hits.iter().enumerate() {
+        let merged = if h.merged.is_empty() { String::new() } else { format!("  (+{} overlapping)", h.merged.len()) };
+        println!("{:6.3}  {:4.2}  {}:{}-{}  {}{merged}", h.distance, retr.relevance(h.distance), retr.shown_path(&h.path)?, h.start, h.end, &h.chunk_id[..12]);
+        if let Some(o) = retr.hit_owners(h)? {
+            let who: Vec<&str> = o.codeowners.iter().chain(o.authors.first()).map(String::as_str).collect();
+            println!("        owners: {}", who.join(", "));
+        }
+        for note in postprocess::annotations(processed, &h.chunk_id) {
+            println!("        -> {note}");
+        }
+        let Some(n) = nodes.get(i) else { continue };
+        let context = |key: &str| n.metadata.get(key).and_then(|c| c.as_str()).filter(|c| !c.is_empty()).map(str::to_string);
+        let parts = [context("context_before"), (v.lines > 0).then(|| n.text.clone()), context("context_after")];
+        for (part, mark) in parts.iter().zip(["  ", "| ", "  "]) {
+            for line in part.iter().flat_map(|p| p.lines()) {
+                println!("        {mark}{line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_eval(qrels: &str, k: usize, hnsw: bool, json: bool) -> Result<()> {
+    let qrels = mentat_eval::load_qrels(qrels)?;
+    let mut retr = mentat_retriever::Retriever::open_default()?;
+    if hnsw {
+        hnsw::load_or_schedule(&mut retr);
+    }
+    let report = eval_with(&retr, &qrels, k)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    for r in &report.results {
+        let rank = r.rank.map_or("-".to_string(), |n| n.to_string());
+        println!("{:>4}  {}", rank, r.query);
+    }
+    println!("queries: {}", report.queries);
+    println!("MRR:     {:.4}", report.mrr);
+    for (k, rec) in report.ks.iter().zip(&report.recall) {
+        println!("R@{:<5} {:.4}", k, rec);
+    }
+    Ok(())
+}
+
+fn eval_with(retr: &mentat_retriever::Retriever, qrels: &[mentat_eval::Qrel], k: usize) -> Result<mentat_eval::Report> {
+    let ks: Vec<usize> = [1, 5, 10].into_iter().filter(|&c| c < k).chain([k]).collect();
+    // over-fetch chunks so k distinct files are usually available
+    let fetch = k * 4;
+    mentat_eval::evaluate(qrels, &ks, |q| {
+        let hits = if retr.has_hnsw() { retr.search(q, fetch)? } else { retr.search_exact(q, fetch)? };
+        Ok(hits.into_iter().map(|h| h.path).collect())
+    })
+}
+
+/// The qrels against two index directories (copies of `index/`, or
+/// `index` itself). With `--hnsw`, each uses its own graph if it has one;
+/// nothing is scheduled for a copy.
+fn run_eval_compare(qrels: &str, baseline: &str, candidate: &str, k: usize, hnsw: bool, json: bool) -> Result<()> {
+    let qrels = mentat_eval::load_qrels(qrels)?;
+    let report = |dir: &str| -> Result<mentat_eval::Report> {
+        let mut retr = mentat_retriever::Retriever::open(dir).with_context(|| format!("opening {dir}"))?;
+        if hnsw && mentat_retriever::HNSW {
+            if let Err(e) = retr.load_hnsw(&format!("{}/embeds", dir.trim_end_matches('/'))) {
+                eprintln!("[eval] {dir}: {e:#}; using exact search");
+            }
+        }
+        eval_with(&retr, &qrels, k)
+    };
+    let cmp = mentat_eval::compare::compare(&report(baseline)?, &report(candidate)?);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&cmp)?);
+        return Ok(());
+    }
+    let rank = |r: Option<usize>| r.map_or("-".to_string(), |n| n.to_string());
+    for c in &cmp.changes {
+        println!("{:>4} -> {:<4} {:+.3}  {}", rank(c.baseline), rank(c.candidate), c.delta(), c.query);
+    }
+    println!("queries:   {} ({} improved, {} regressed, {} unchanged)", cmp.queries, cmp.improved, cmp.regressed, cmp.queries - cmp.changes.len());
+    println!("MRR delta: {:+.4}", cmp.mrr_delta);
+    for (k, d) in cmp.ks.iter().zip(&cmp.recall_delta) {
+        println!("R@{:<8} {:+.4}", k, d);
+    }
+    Ok(())
+}
+
+/// Sample chunks and emit one synthetic qrel per chunk. With --llm-cmd, the
+/// chunk text is piped to the command and its first output line is the query.
+fn run_eval_synth(n: usize, seed: u64, llm_cmd: Option<&str>) -> Result<()> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let chunks = store.sample_chunks(n * 2, seed)?;
+    let mut emitted = 0;
+    for (_, chunk) in chunks {
+        if emitted == n {
+            break;
+        }
+        let Some(text) = store.chunk_text(&chunk)? else { continue };
+        let query = match llm_cmd {
+            Some(cmd) => ask_command(cmd, &text)?,
+            None => mentat_eval::synth::question_for(&text),
+        };
+        let (Some(query), Some(file)) = (query, store.get_file(chunk.file_hash)?) else { continue };
+        let qrel = mentat_eval::Qrel { query, expected: vec![file.path] };
+        println!("{}", serde_json::to_string(&qrel)?);
+        emitted += 1;
+    }
+    eprintln!("[eval-synth] emitted {emitted} qrels");
+    Ok(())
+}
+
+fn run_gen_corpus(out: &str, files: usize, kind: mentat_eval::corpus::Kind, seed: u64, force: bool, json: bool) -> Result<()> {
+    let dir = std::path::Path::new(out);
+    if !force && dir.read_dir().is_ok_and(|mut d| d.next().is_some()) {
+        anyhow::bail!("{out} is not empty; pass --force to write into it anyway");
+    }
+    let sum = mentat_eval::corpus::generate(dir, files, kind, seed)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sum)?);
+    } else {
+        println!("wrote {} files ({} bytes) to {out}", sum.files, sum.bytes);
+    }
+    Ok(())
+}
+
+/// With `--history` (earlier turns, one per line), rewrite a follow-up into a
+/// standalone query: by `--llm-cmd` if given, else by rule.
+fn standalone_query(q: &str, history: Option<&str>, llm_cmd: Option<&str>) -> Result<String> {
+    let Some(file) = history else { return Ok(q.to_string()) };
+    let turns: Vec<String> = std::fs::read_to_string(file)?.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect();
+    if turns.is_empty() {
+        return Ok(q.to_string());
+    }
+    let rewritten = match llm_cmd {
+        Some(cmd) => {
+            let prompt = format!(
+                "Rewrite the follow-up as a standalone search query. Reply with the query only.\n\nConversation:\n{}\n\nFollow-up: {q}\n",
+                turns.join("\n")
+            );
+            ask_command(cmd, &prompt)?
+        }
+        None => mentat_retriever::rewrite::rewrite(q, &turns),
+    };
+    match rewritten {
+        Some(r) => {
+            eprintln!("[search] rewritten as: {r}");
+            Ok(r)
+        }
+        None => Ok(q.to_string()),
+    }
+}
+
+fn ask_command(cmd: &str, input: &str) -> Result<Option<String>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    let out = child.wait_with_output()?;
+    anyhow::ensure!(out.status.success(), "--llm-cmd exited with {}", out.status);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    Ok(stdout.lines().map(str::trim).find(|l| !l.is_empty()).map(str::to_string))
+}
+
+fn run_index(path: &str, dir: &str, opts: &mentat_indexer::IndexOptions, on: &mentat_config::Hooks) -> Result<i32> {
     eprintln!("[index] Opening store...");
+    let run = mentat_store::Store::open(dir).and_then(|store| mentat_indexer::run_index(path, &store, opts));
+    let rep = match run {
+        Ok(rep) => rep,
+        Err(e) => {
+            hooks::index_failed(on, &e);
+            return Err(e);
+        }
+    };
+    let saved = mentat_indexer::report::save(Path::new(dir), &rep)?;
+    hooks::index_complete(on, &rep);
+    if usage::enabled() {
+        let chunks = mentat_store::Store::open_existing(dir)?.chunks()?.len();
+        let cached = rep.embeddings_cached + rep.embeddings_reused;
+        usage::record(dir, usage::Event::Index { at: usage::now(), ms: rep.timing.total_ms, chunks, computed: rep.embeddings_computed, cached });
+    }
+    println!(
+        "Indexed {} files ({} unchanged, {} skipped, {} deleted): {} embeddings computed, {} cached, {} reused",
+        rep.files_indexed,
+        rep.files_unchanged,
+        rep.files_skipped.len(),
+        rep.files_deleted.len(),
+        rep.embeddings_computed,
+        rep.embeddings_cached,
+        rep.embeddings_reused,
+    );
+    if let Some(t) = &rep.embed {
+        println!("Embedded in batches of {} (max_len {}) on {}", t.batch_size, t.max_len, t.device);
+    }
+    if rep.truncated.texts > 0 {
+        let t = rep.truncated;
+        println!("{} chunks cut to fit max_len at a sentence or word boundary ({} tokens, {} bytes left out)", t.texts, t.tokens_dropped, t.bytes_dropped);
+    }
+    if rep.chunks_capped > 0 {
+        println!("{} chunks left out by [caps]", rep.chunks_capped);
+    }
+    for w in &rep.quota_warnings {
+        println!("warning: near [quota] {w}");
+    }
+    println!("Index built at ./{dir}/kv.redb (report: {})", saved.display());
+    if rep.upgrades_queued > 0 {
+        println!("{} chunks have static vectors until upgraded to the full model", rep.upgrades_queued);
+        upgrade::schedule(dir);
+    }
+    if !rep.errors.is_empty() {
+        println!("{} file(s) failed; see the report for details", rep.errors.len());
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+/// Dry run: what `mentat index` would embed, and roughly how long it takes.
+fn run_estimate(target: &str, dir: &str, opts: &mentat_indexer::IndexOptions) -> Result<i32> {
+    let store = if Path::new(dir).join("kv.redb").exists() { Some(mentat_store::Store::open_existing(dir)?) } else { None };
+    let e = mentat_indexer::estimate::estimate(target, store.as_ref(), opts)?;
+    println!("{} files ({} unreadable, {} generated), {} chunks ({} capped)", e.files, e.unreadable, e.generated, e.chunks, e.capped);
+    println!("  {} already embedded, {} reusable from other versions, {} to embed", e.cached, e.reusable, e.to_embed);
+    println!("  ~{} tokens at max_len {}", e.tokens, e.max_len);
+    match e.seconds {
+        Some(s) => println!("  ~{s:.0}s of embedding at the stored throughput"),
+        None => println!("  no stored throughput on this machine; time unknown until the first run"),
+    }
+    println!("  local model {}: no per-token cost", mentat_embedder::MODEL);
+    Ok(0)
+}
+
+fn run_reindex(dir: &str, paths: &[String], opts: &mentat_indexer::IndexOptions) -> Result<i32> {
+    let store = mentat_store::Store::open_existing(dir)?;
+    let rep = mentat_indexer::saved::index_saved(&store, paths, opts)?;
+    eprintln!(
+        "[reindex] {} indexed, {} unchanged, {} removed in {} ms: {} embeddings computed, {} reused",
+        rep.files_indexed,
+        rep.files_unchanged,
+        rep.files_deleted.len(),
+        rep.timing.total_ms,
+        rep.embeddings_computed,
+        rep.embeddings_reused,
+    );
+    for s in &rep.files_skipped {
+        eprintln!("[reindex] skipped {}: {}", s.path, s.reason);
+    }
+    for w in &rep.quota_warnings {
+        println!("warning: near [quota] {w}");
+    }
+    if !rep.errors.is_empty() {
+        println!("{} file(s) failed", rep.errors.len());
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+fn run_docs_add(file: &str, opts: &mentat_indexer::IndexOptions) -> Result<i32> {
+    let mut docs = Vec::new();
+    for (i, line) in std::fs::read_to_string(file)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let doc: mentat_indexer::docs::Document =
+            serde_json::from_str(line).map_err(|e| anyhow::anyhow!("{file}:{}: {e}", i + 1))?;
+        docs.push(doc);
+    }
     let store = mentat_store::Store::open_default()?;
-    // 3) for each file, chunk + embed
-    eprintln!("[index] Processing files...");
-    let root = Path::new(path);
-    for (idx, f) in files.iter().enumerate() {
-        eprintln!("[index] File {}/{}: {}", idx+1, files.len(), f.path);
-        // write file meta
-        let fhash = hex_to32(&f.hash)?;
-        store.put_file(fhash, &mentat_store::FileMeta { path: relativize(&f.path, root), size: f.size })?;
-        // chunk
-        let spans = mentat_chunker::chunk_file(&f.path)?;
-        if spans.is_empty() { continue; }
-        let data = fs::read(&f.path)?;
-        for s in spans {
-            // chunk id = blake3(file_hash || start || end)
-            let mut id_src = Vec::with_capacity(32 + 16);
-            id_src.extend_from_slice(&fhash);
-            id_src.extend_from_slice(&s.start.to_le_bytes());
-            id_src.extend_from_slice(&s.end.to_le_bytes());
-            let chunk_id = mentat_store::blake32(&id_src);
-
-            // embed from raw slice
-            let slice = &data[s.start..s.end];
-            let text = String::from_utf8_lossy(slice);
-            let emb = mentat_embedder::embed_text(&text)?;
-            store.put_chunk(chunk_id, &mentat_store::ChunkMeta {
-                file_hash: fhash,
-                start: s.start,
-                end: s.end,
-                span_hash: hex_to32(&s.hash)?,
-            })?;
-            store.put_embed(chunk_id, &emb)?;
-        }
-    }
-    println!("Index built at ./index/kv.redb");
-    Ok(())
+    let rep = mentat_indexer::docs::index_documents(&store, &docs, opts)?;
+    println!(
+        "Indexed {} documents ({} unchanged, {} skipped): {} embeddings computed, {} cached, {} reused",
+        rep.files_indexed,
+        rep.files_unchanged,
+        rep.files_skipped.len(),
+        rep.embeddings_computed,
+        rep.embeddings_cached,
+        rep.embeddings_reused,
+    );
+    for w in &rep.quota_warnings {
+        println!("warning: near [quota] {w}");
+    }
+    if !rep.errors.is_empty() {
+        println!("{} document(s) failed", rep.errors.len());
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+fn run_docs_rm(ids: &[String]) -> Result<i32> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let missing = mentat_indexer::docs::remove_documents(&store, ids)?;
+    for id in &missing {
+        eprintln!("not an indexed document: {id}");
+    }
+    println!("Removed {} document(s)", ids.len() - missing.len());
+    Ok(if missing.is_empty() { 0 } else { 2 })
+}
+
+/// Vectors from another store plus a JSONL sidecar of the spans they embed
+/// (default: the vector file with a .jsonl extension).
+fn run_import(
+    from: Option<&str>,
+    src: &str,
+    meta: Option<&str>,
+    model: Option<&str>,
+    root: Option<&str>,
+    force: bool,
+) -> Result<i32> {
+    let vectors = match from {
+        Some("faiss") => mentat_vecio::faiss::read(Path::new(src))?,
+        Some(other @ ("chroma" | "qdrant")) => anyhow::bail!("importing from {other} is not supported yet; export to a FAISS flat index first"),
+        _ => anyhow::bail!("--from must be faiss"),
+    };
+    let model = model.ok_or_else(|| anyhow::anyhow!("--model is required: name the model the vectors come from"))?;
+    if model != mentat_embedder::MODEL && !force {
+        anyhow::bail!("vectors from {model} are not comparable with {} query embeddings (--force to import anyway)", mentat_embedder::MODEL);
+    }
+    let meta = meta.map(std::path::PathBuf::from).unwrap_or_else(|| Path::new(src).with_extension("jsonl"));
+    let spans = mentat_vecio::read_sidecar(&meta)?;
+    let store = mentat_store::Store::open_default()?;
+    let rep = mentat_indexer::import::import(&store, Path::new(root.unwrap_or(".")), &spans, &vectors)?;
+    println!(
+        "Imported {} of {} vectors into {} files ({} rejected, {} files missing)",
+        rep.imported,
+        rep.vectors,
+        rep.files,
+        rep.rejected,
+        rep.missing.len()
+    );
+    Ok(if rep.imported == rep.vectors { 0 } else { 2 })
+}
+
+/// All stored vectors in key order, and the span of each row in a JSONL
+/// sidecar next to `out` (which `mentat import` reads back).
+fn run_export_vectors(format: &str, out: Option<&str>, pseudonymize: bool) -> Result<i32> {
+    let write = match format {
+        "npy" => mentat_vecio::npy::write,
+        "faiss" => mentat_vecio::faiss::write,
+        "parquet" => anyhow::bail!("parquet export is not supported yet; use npy"),
+        other => anyhow::bail!("unknown --format {other} (expected npy or faiss)"),
+    };
+    let out = Path::new(out.unwrap_or(if format == "faiss" { "vectors.faiss" } else { "vectors.npy" }));
+    let store = mentat_store::Store::open_existing("index")?;
+    let files = export_paths(&store, pseudonymize)?;
+    let mut m = mentat_vecio::Matrix { dim: mentat_embedder::D, data: Vec::new() };
+    let mut spans = Vec::new();
+    for (id, v) in store.embeds()? {
+        let Some(c) = store.get_chunk(id)? else { continue };
+        let Some(path) = files.get(&c.file_hash) else { continue };
+        m.data.extend_from_slice(&v);
+        spans.push(mentat_vecio::SpanRef { path: path.clone(), start: c.start, end: c.end, chunk_id: Some(hex::encode(id)) });
+    }
+    let sidecar = out.with_extension("jsonl");
+    write(out, &m)?;
+    mentat_vecio::write_sidecar(&sidecar, &spans)?;
+    println!("Wrote {} vectors of dimension {} to {} (spans: {})", spans.len(), m.dim, out.display(), sidecar.display());
+    Ok(0)
+}
+
+/// File paths by content hash, or their pseudonyms with `--pseudonymize`.
+fn export_paths(store: &mentat_store::Store, pseudonymize: bool) -> Result<std::collections::HashMap<[u8; 32], String>> {
+    let p = pseudonymize.then(|| mentat_retriever::pseudonym::Pseudonyms::open(store)).transpose()?;
+    store
+        .files()?
+        .into_iter()
+        .map(|(h, m)| match &p {
+            Some(p) => Ok((h, p.name(store, &m.path)?)),
+            None => Ok((h, m.path)),
+        })
+        .collect()
+}
+
+/// `mentat reveal <pseudonym>`: the path a `--pseudonymize` output stood in for.
+fn run_reveal(pseudonym: &str) -> Result<i32> {
+    let store = mentat_store::Store::open_existing("index")?;
+    match store.get_pseudonym(pseudonym)? {
+        Some(path) => {
+            println!("{path}");
+            Ok(0)
+        }
+        None => {
+            eprintln!("no path was given the pseudonym {pseudonym} in this index");
+            Ok(1)
+        }
+    }
+}
+
+/// Flag NaN/zero-norm vectors and dangling rows already in the store.
+fn run_verify() -> Result<i32> {
+    let store = mentat_store::Store::open_existing("index")?;
+    let mut bad = 0;
+    for (id, v) in store.embeds()? {
+        let Some(reason) = mentat_embedder::invalid_reason(&v) else { continue };
+        bad += 1;
+        let loc = match store.get_chunk(id)? {
+            Some(c) => {
+                let path = store.get_file(c.file_hash)?.map(|f| f.path).unwrap_or_default();
+                format!("{}:{}-{}", path, c.start, c.end)
+            }
+            None => "<no chunk row>".to_string(),
+        };
+        println!("invalid vector {}  {}  ({})", hex::encode(id), loc, reason);
+    }
+    let integ = store.integrity()?;
+    for id in &integ.dangling_chunks {
+        println!("dangling chunk {} (file row missing)", hex::encode(id));
+    }
+    for id in &integ.dangling_embeds {
+        println!("dangling embed {} (chunk row missing)", hex::encode(id));
+    }
+    // not damage: spans cut under other settings until the next `mentat index` re-cuts them
+    let current = mentat_indexer::stored_chunker(&store)?.map(|c| c.id());
+    let other: usize = integ.chunkers.iter().filter(|(id, _)| Some(**id) != current).map(|(_, n)| n).sum();
+    if other > 0 {
+        println!("{other} chunk(s) cut under other chunker settings or an older chunker version; `mentat index` re-cuts files (re-add documents with `mentat docs add`)");
+    }
+    println!(
+        "{} files, {} chunks, {} embeds: {} invalid vectors, {} dangling chunks, {} dangling embeds",
+        integ.files, integ.chunks, integ.embeds, bad, integ.dangling_chunks.len(), integ.dangling_embeds.len()
+    );
+    Ok(if bad == 0 && integ.is_clean() { 0 } else { 2 })
 }
 
-fn hex_to32(h: &str) -> Result<[u8;32]> {
-    let bytes = hex::decode(h)?;
-    let arr: [u8;32] = bytes.as_slice().try_into().map_err(|_| anyhow::anyhow!("bad len"))?;
-    Ok(arr)
+/// Exit 2 when rows were lost; the salvaged index is written either way.
+fn run_salvage(src: &str, dst: &str, json: bool) -> Result<i32> {
+    let rep = mentat_store::salvage::salvage(Path::new(src), Path::new(dst))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rep)?);
+    } else {
+        for t in &rep.tables {
+            let note = match (&t.stopped, t.missing) {
+                (Some(e), _) => format!("  scan stopped: {e}"),
+                (None, true) => "  (not in source)".into(),
+                (None, false) => String::new(),
+            };
+            println!("{:<18} {:>8} copied {:>6} invalid{note}", t.table, t.copied, t.invalid);
+        }
+        println!("dropped {} chunks without a file, {} embeddings without a chunk", rep.dangling_chunks, rep.dangling_embeds);
+        println!("salvaged index at {dst}/kv.redb; check it with `mentat verify`, then move it into place (or `mentat swap`)");
+    }
+    Ok(if rep.is_lossless() { 0 } else { 2 })
 }
 
-fn relativize(p: &str, root: &Path) -> String {
-    let pp = Path::new(p);
-    match pp.strip_prefix(root) {
-        Ok(r) => r.display().to_string(),
-        Err(_) => p.to_string(),
+fn run_report(which: &str) -> Result<()> {
+    let dir = Path::new("index");
+    match which {
+        "list" => {
+            for p in mentat_indexer::report::list(dir)? {
+                println!("{}", p.display());
+            }
+        }
+        "last" => match mentat_indexer::report::load_last(dir)? {
+            Some(rep) => println!("{}", serde_json::to_string_pretty(&rep)?),
+            None => println!("no index reports yet"),
+        },
+        file => {
+            let rep = mentat_indexer::report::load(Path::new(file))?;
+            println!("{}", serde_json::to_string_pretty(&rep)?);
+        }
     }
+    Ok(())
 }
 
-// temp explicit uses
-use anyhow;
-use hex;
-use mentat_ingest;
-use mentat_chunker;
-use mentat_store;
-use mentat_embedder;
-use mentat_retriever;
+/// Unix seconds as `YYYY-MM-DD HH:MM` UTC (civil from days, Howard Hinnant).
+fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    let t = secs % 86400;
+    format!("{y:04}-{m:02}-{d:02} {:02}:{:02}", t / 3600, t % 3600 / 60)
+}
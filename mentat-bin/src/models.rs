@@ -0,0 +1,100 @@
+//! `mentat models list | verify | pull | distill`: the model cache in
+//! `mentat_embedder::model_dir()`, checked against `[embedder] pins`.
+//! `pull` downloads with curl, so it needs no HTTP stack of its own.
+
+use anyhow::Result;
+use mentat_embedder::FileCheck;
+use std::{collections::BTreeMap, process::Command};
+
+pub fn list(pins: &BTreeMap<String, String>, json: bool) -> Result<i32> {
+    let checks = mentat_embedder::check_files(pins)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+        return Ok(0);
+    }
+    println!("{} in {}", mentat_embedder::MODEL, mentat_embedder::model_dir().display());
+    for c in &checks {
+        let size = c.size.map_or("-".to_string(), |s| s.to_string());
+        println!("  {:<20} {:>10}  {}  {}", c.file, size, c.blake3.as_deref().unwrap_or("-"), status(c));
+    }
+    if checks.iter().any(|c| c.pin.is_none() && c.blake3.is_some()) {
+        println!("\nTo pin the files as they are now, add to mentat.toml:\n\n[embedder.pins]");
+        for c in checks.iter().filter(|c| c.pin.is_none()) {
+            if let Some(h) = &c.blake3 {
+                println!("\"{}\" = \"{h}\"", c.file);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Exit 2 when a file is missing or differs from its pin.
+pub fn verify(pins: &BTreeMap<String, String>) -> Result<i32> {
+    let checks = mentat_embedder::check_files(pins)?;
+    for c in &checks {
+        println!("{:<20} {}", c.file, status(c));
+    }
+    let bad = checks.iter().filter(|c| !c.ok()).count();
+    if bad > 0 {
+        eprintln!("{bad} model file(s) missing or not matching their pin; `mentat models pull --force` fetches them again");
+        return Ok(2);
+    }
+    if pins.is_empty() {
+        eprintln!("no [embedder] pins configured; `mentat models list` prints the lines to add");
+    }
+    Ok(0)
+}
+
+/// Fetch missing files (every file with `force`) from `MODEL_URL`. A download
+/// that doesn't match its pin is discarded and the old file kept.
+pub fn pull(pins: &BTreeMap<String, String>, force: bool) -> Result<i32> {
+    let dir = mentat_embedder::model_dir();
+    std::fs::create_dir_all(&dir)?;
+    let mut failed = 0;
+    for c in mentat_embedder::check_files(pins)? {
+        if !mentat_embedder::MODEL_FILES.contains(&c.file.as_str()) || (c.ok() && !force) {
+            continue;
+        }
+        let url = format!("{}/{}", mentat_embedder::MODEL_URL, c.file);
+        let part = dir.join(format!("{}.part", c.file));
+        eprintln!("[models] fetching {url}");
+        let status = Command::new("curl").args(["-fL", "--retry", "3", "-o"]).arg(&part).arg(&url).status()?;
+        if !status.success() {
+            eprintln!("[models] {}: curl exited with {status}", c.file);
+            let _ = std::fs::remove_file(&part);
+            failed += 1;
+            continue;
+        }
+        let mut h = blake3::Hasher::new();
+        std::io::copy(&mut std::fs::File::open(&part)?, &mut h)?;
+        let got = h.finalize().to_hex().to_string();
+        if c.pin.as_ref().is_some_and(|p| *p != got) {
+            eprintln!("[models] {}: download has blake3 {got}, pin expects {}; kept the old file", c.file, c.pin.as_deref().unwrap_or(""));
+            std::fs::remove_file(&part)?;
+            failed += 1;
+            continue;
+        }
+        std::fs::rename(&part, dir.join(&c.file))?;
+        println!("{}  {got}", c.file);
+    }
+    Ok(if failed > 0 { 2 } else { 0 })
+}
+
+/// Run every vocabulary token through the model once and save the table
+/// static mode averages. Takes minutes on CPU; needed once per model.
+pub fn distill() -> Result<i32> {
+    let rows = mentat_embedder::distill()?;
+    let path = mentat_embedder::model_dir().join(mentat_embedder::STATIC_FILE);
+    println!("{rows} token vectors written to {}", path.display());
+    println!("index a collection in static mode with `mentat index <path> --mode static` (or [embedder] mode = \"static\")");
+    Ok(0)
+}
+
+fn status(c: &FileCheck) -> &'static str {
+    match (&c.blake3, &c.pin) {
+        (None, _) => "MISSING",
+        (Some(_), None) => "unpinned",
+        (Some(h), Some(p)) if h == p => "ok",
+        (Some(_), Some(_)) => "MISMATCH",
+    }
+}
@@ -0,0 +1,72 @@
+//! `mentat search --save FILE`: the search as a markdown section appended to
+//! FILE, so one file can grow into a research log. Each hit carries its
+//! citation, which `mentat resolve` turns back into text after edits.
+
+use crate::cli::Args;
+use anyhow::Result;
+use std::{fs, io::Write, path::Path};
+
+/// Lines of chunk text shown per hit when `[search] snippet_lines` is 0.
+const SNIPPET_LINES: usize = 12;
+
+/// Append the search to `path`, creating the file and its directory if needed.
+pub fn save(
+    path: &str,
+    cmd: &str,
+    args: &Args,
+    query: &str,
+    retr: &mentat_retriever::Retriever,
+    hits: &[mentat_retriever::Hit],
+    v: &mentat_retriever::snippet::Verbosity,
+) -> Result<()> {
+    let nodes = retr.to_nodes(hits)?;
+    let shown = if v.lines > 0 { v.lines } else { SNIPPET_LINES };
+    let mut md = format!("## {query}\n\n");
+    md.push_str(&format!("- searched: {} UTC\n", crate::utc_timestamp(mentat_indexer::report::now_millis() / 1000)));
+    md.push_str(&format!("- command: `mentat {cmd}`\n"));
+    let typed = args.pos(0).unwrap_or("");
+    if typed != query {
+        md.push_str(&format!("- asked: {typed}\n"));
+    }
+    for opt in ["--k", "--rev", "--as-of", "--history"] {
+        if let Some(v) = args.opt(opt) {
+            md.push_str(&format!("- `{opt} {v}`\n"));
+        }
+    }
+    for flag in ["--no-merge", "--all"] {
+        if args.flag(flag) {
+            md.push_str(&format!("- `{flag}`\n"));
+        }
+    }
+    if nodes.is_empty() {
+        md.push_str("\nNo results.\n");
+    }
+    for (i, n) in nodes.iter().enumerate() {
+        let cite = n.metadata.get("citation").and_then(|c| c.as_str()).unwrap_or(&n.id);
+        let relevance = n.metadata.get("relevance").and_then(|r| r.as_f64()).unwrap_or(n.score as f64);
+        md.push_str(&format!("\n### {}. `{cite}` ({:.3}, relevance {relevance:.2})\n", i + 1, n.score));
+        if n.text.is_empty() {
+            md.push_str("\n_file changed since indexing_\n");
+            continue;
+        }
+        let lang = Path::new(n.metadata.get("path").and_then(|p| p.as_str()).unwrap_or(""))
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let snippet = mentat_retriever::snippet::head(&n.text, shown);
+        // a fence longer than any backtick run inside the snippet
+        let fence = "`".repeat(longest_backtick_run(&snippet).max(2) + 1);
+        md.push_str(&format!("\n{fence}{lang}\n{snippet}\n{fence}\n"));
+    }
+    md.push('\n');
+    if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    f.write_all(md.as_bytes())?;
+    Ok(())
+}
+
+fn longest_backtick_run(s: &str) -> usize {
+    s.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
@@ -0,0 +1,78 @@
+//! `[search] postprocess` commands, run through `sh -c` over each search's
+//! results: the query and its nodes arrive as `{"query", "nodes"}` JSON on
+//! stdin (nodes as `search --json` prints them), and the command prints
+//! the nodes to keep, in order, as a JSON array. It may drop or reorder
+//! hits, rewrite `text`, or add metadata; strings under an `annotations`
+//! metadata list are printed below the hit in text output.
+//!
+//! Commands run in order, each within `postprocess_timeout_ms`. One that
+//! fails, times out or prints something unreadable is skipped with a
+//! warning, and the nodes pass on as they were: a broken processor never
+//! loses a search.
+
+use anyhow::{Context, Result};
+use mentat_retriever::rag::Node;
+use serde_json::json;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// `nodes` through every configured command.
+pub fn apply(cfg: &mentat_config::Search, query: &str, mut nodes: Vec<Node>) -> Vec<Node> {
+    let timeout = Duration::from_millis(cfg.postprocess_timeout_ms);
+    for cmd in &cfg.postprocess {
+        match run(cmd, query, &nodes, timeout) {
+            Ok(out) => nodes = out,
+            Err(e) => eprintln!("[postprocess] skipping `{cmd}`: {e:#}"),
+        }
+    }
+    nodes
+}
+
+/// The hits `nodes` kept, in their order.
+pub fn keep(hits: &[mentat_retriever::Hit], nodes: &[Node]) -> Vec<mentat_retriever::Hit> {
+    nodes.iter().filter_map(|n| hits.iter().find(|h| h.chunk_id == n.id).cloned()).collect()
+}
+
+/// The `annotations` a processor attached to the node for `chunk_id`.
+pub fn annotations<'a>(nodes: &'a [Node], chunk_id: &str) -> Vec<&'a str> {
+    let node = nodes.iter().find(|n| n.id == chunk_id);
+    let list = node.and_then(|n| n.metadata.get("annotations")).and_then(|a| a.as_array());
+    list.into_iter().flatten().filter_map(|a| a.as_str()).collect()
+}
+
+fn run(cmd: &str, query: &str, nodes: &[Node], timeout: Duration) -> Result<Vec<Node>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // feed and drain on threads, so a command that writes before it has
+    // read everything cannot deadlock against us
+    let payload = serde_json::to_vec(&json!({ "query": query, "nodes": nodes }))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let writer = std::thread::spawn(move || stdin.write_all(&payload));
+    let mut stdout = child.stdout.take().expect("piped stdout");
+    let reader = std::thread::spawn(move || {
+        let mut out = Vec::new();
+        stdout.read_to_end(&mut out).map(|_| out)
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("timed out after {} ms", timeout.as_millis());
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    // a command may exit without reading its input
+    let _ = writer.join();
+    let out = reader.join().map_err(|_| anyhow::anyhow!("stdout reader panicked"))??;
+    anyhow::ensure!(status.success(), "exited with {status}");
+    serde_json::from_slice(&out).context("expected a JSON array of nodes on stdout")
+}
@@ -0,0 +1,118 @@
+//! `mentat index <path> --rebuild`: a full rebuild written into `<dir>.tmp`
+//! and moved over `<dir>` only once it has finished. A rebuild that fails
+//! leaves the old index serving and the partial one is removed.
+//!
+//! Saved aliases, the usage log and earlier build reports are carried into
+//! the new build; derived state (history, HNSW graph, related files) is
+//! recomputed or rebuilt on first use. Commands that already have the old
+//! store open keep reading it until they exit.
+//!
+//! A plain `mentat index` takes this path by itself when the run would
+//! re-embed or re-cut everything anyway (see
+//! `mentat_indexer::full_rebuild_reason`), so a model or chunker change
+//! never leaves the live index half converted; `--in-place` opts out.
+//! `--background` detaches the rebuild and logs to `<dir>.rebuild.log`.
+
+use anyhow::{Context, Result};
+use std::{env, fs, path::Path, process::{Command, Stdio}};
+
+pub fn run(target: &str, dir: &str, opts: &mentat_indexer::IndexOptions, on: &mentat_config::Hooks) -> Result<i32> {
+    let tmp = format!("{dir}.tmp");
+    if Path::new(&tmp).exists() {
+        let held = Path::new(&tmp).join("kv.redb").exists()
+            && mentat_store::Store::open_existing(&tmp).is_err_and(|e| format!("{e:#}").contains("already open"));
+        if held {
+            anyhow::bail!("a rebuild into {tmp} is already running (see {dir}.rebuild.log)");
+        }
+        eprintln!("[index] removing {tmp} left by an earlier rebuild");
+        fs::remove_dir_all(&tmp)?;
+    }
+    carry_over(dir, &tmp).with_context(|| format!("copying aliases and logs from {dir}"))?;
+    let code = match crate::run_index(target, &tmp, opts, on) {
+        Ok(code) => code,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&tmp);
+            return Err(e.context(format!("rebuild failed; {dir} is unchanged")));
+        }
+    };
+    replace(&tmp, dir)?;
+    println!("{dir} replaced by the rebuild");
+    Ok(code)
+}
+
+/// Why `mentat index` into `dir` should rebuild rather than update in
+/// place; None for a new or compatible index.
+pub fn reason(dir: &str, opts: &mentat_indexer::IndexOptions) -> Result<Option<String>> {
+    if !Path::new(dir).join("kv.redb").exists() {
+        return Ok(None);
+    }
+    mentat_indexer::full_rebuild_reason(&mentat_store::Store::open_existing(dir)?, opts)
+}
+
+/// Run this same command again as a detached `--rebuild`.
+pub fn spawn(dir: &str) -> Result<i32> {
+    let log_path = format!("{dir}.rebuild.log");
+    let log = fs::File::create(&log_path)?;
+    let mut args: Vec<String> = env::args().skip(1).filter(|a| a != "--background").collect();
+    if !args.iter().any(|a| a == "--rebuild") {
+        args.push("--rebuild".into());
+    }
+    let child = Command::new(env::current_exe()?).args(&args).stdin(Stdio::null()).stdout(log.try_clone()?).stderr(log).spawn()?;
+    println!("Rebuilding {dir} in the background (pid {}); it keeps serving until the swap. Log: {log_path}", child.id());
+    Ok(0)
+}
+
+fn carry_over(dir: &str, tmp: &str) -> Result<()> {
+    fs::create_dir_all(tmp)?;
+    let old = Path::new(dir);
+    if old.join("kv.redb").exists() {
+        let aliases = mentat_store::Store::open_existing(dir)?.aliases()?;
+        let store = mentat_store::Store::open(tmp)?;
+        for (name, query) in &aliases {
+            store.put_alias(name, query)?;
+        }
+    }
+    if old.join("usage.jsonl").is_file() {
+        fs::copy(old.join("usage.jsonl"), Path::new(tmp).join("usage.jsonl"))?;
+    }
+    let reports = mentat_indexer::report::reports_dir(old);
+    if reports.is_dir() {
+        let to = mentat_indexer::report::reports_dir(Path::new(tmp));
+        fs::create_dir_all(&to)?;
+        for entry in fs::read_dir(&reports)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), to.join(entry.file_name()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A `mentat swap` alias is repointed at the build, renamed to
+/// `<dir>-<unix secs>`; the directory it pointed at is left in place. A real
+/// directory is renamed aside and then deleted, so between the two renames
+/// a command opening `<dir>` finds no index rather than a partial one.
+fn replace(tmp: &str, dir: &str) -> Result<()> {
+    let is_link = fs::symlink_metadata(dir).is_ok_and(|m| m.file_type().is_symlink());
+    if is_link {
+        let build = format!("{dir}-{}", mentat_indexer::report::now_millis() / 1000);
+        fs::rename(tmp, &build)?;
+        return crate::run_swap(dir, &build);
+    }
+    if !Path::new(dir).exists() {
+        fs::rename(tmp, dir)?;
+        return Ok(());
+    }
+    let old = format!("{dir}.old");
+    if Path::new(&old).exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    fs::rename(dir, &old)?;
+    if let Err(e) = fs::rename(tmp, dir) {
+        fs::rename(&old, dir)?;
+        return Err(anyhow::Error::from(e).context(format!("could not move {tmp} into place; {dir} restored")));
+    }
+    fs::remove_dir_all(&old).with_context(|| format!("the rebuild is live; remove {old} by hand"))?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! `mentat selftest`: index a small built-in corpus in a temporary
+//! directory, check that each probe query finds its file by exact and by
+//! HNSW search, then delete everything. Uses the deployment's model and
+//! `[embedder]` settings, so a pass means this install can index and serve.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{env, fs, path::Path, time::Instant};
+
+/// (path, contents, a query that should rank this file first)
+const CORPUS: &[(&str, &str, &str)] = &[
+    (
+        "billing/invoice.py",
+        "def invoice_total(lines, tax_rate):\n    \"\"\"Sum the line items of an invoice and add sales tax.\"\"\"\n    subtotal = sum(l.price * l.quantity for l in lines)\n    return subtotal + subtotal * tax_rate\n",
+        "add sales tax to the invoice total",
+    ),
+    (
+        "garden/tomatoes.md",
+        "# Growing tomatoes\n\nWater tomato plants deeply at the roots twice a week in summer,\nand mulch the beds so the soil stays moist between waterings.\n",
+        "how often to water tomato plants",
+    ),
+    (
+        "net/retry.rs",
+        "/// Retry a failed HTTP request with exponential backoff and jitter.\npub fn retry<T>(mut call: impl FnMut() -> Result<T>, attempts: u32) -> Result<T> {\n    let mut delay = 100;\n    loop {\n        match call() {\n            Err(_) if attempts > 1 => { sleep_ms(delay + jitter()); delay *= 2; }\n            r => return r,\n        }\n    }\n}\n",
+        "exponential backoff when a request fails",
+    ),
+    (
+        "astro/orbits.txt",
+        "Kepler's laws: planets move in ellipses with the sun at one focus, sweep equal\nareas in equal times, and the square of the orbital period grows with the cube\nof the orbit's size.\n",
+        "laws of planetary motion around the sun",
+    ),
+];
+
+#[derive(Serialize, Debug)]
+struct Step {
+    name: String,
+    ok: bool,
+    ms: u64,
+    detail: String,
+}
+
+/// Exit 2 when any step fails; the temporary directory is removed either way.
+pub fn run(json: bool) -> Result<i32> {
+    let dir = env::temp_dir().join(format!("mentat-selftest-{}", std::process::id()));
+    let mut steps = Vec::new();
+    let result = steps_in(&dir, &mut steps);
+    let _ = fs::remove_dir_all(&dir);
+    if let Err(e) = result {
+        steps.push(Step { name: "setup".into(), ok: false, ms: 0, detail: format!("{e:#}") });
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&steps)?);
+    } else {
+        for s in &steps {
+            println!("[{}] {:<14} {:>6}ms  {}", if s.ok { " ok " } else { "FAIL" }, s.name, s.ms, s.detail);
+        }
+        if !mentat_retriever::HNSW {
+            println!("hnsw: skipped; built without the `hnsw` feature");
+        }
+        println!("daemon: skipped; this build has no daemon");
+    }
+    Ok(if steps.iter().all(|s| s.ok) { 0 } else { 2 })
+}
+
+/// A failing check is recorded and later checks still run; an error here
+/// means the test could not go on at all.
+fn steps_in(dir: &Path, steps: &mut Vec<Step>) -> Result<()> {
+    let corpus = dir.join("corpus");
+    for (path, text, _) in CORPUS {
+        let p = corpus.join(path);
+        fs::create_dir_all(p.parent().expect("corpus paths have a directory"))?;
+        fs::write(p, text)?;
+    }
+
+    let cfg = mentat_config::Config::load()?;
+    let opts = mentat_indexer::IndexOptions { embed: cfg.embedder, ..Default::default() };
+    let t = Instant::now();
+    let indexed = mentat_store::Store::open(dir.join("index"))
+        .and_then(|store| mentat_indexer::run_index(&corpus.to_string_lossy(), &store, &opts));
+    let ms = t.elapsed().as_millis() as u64;
+    let rep = match indexed {
+        Ok(rep) => rep,
+        Err(e) => {
+            steps.push(Step { name: "index".into(), ok: false, ms, detail: format!("{e:#}") });
+            return Ok(());
+        }
+    };
+    let ok = rep.files_indexed == CORPUS.len() && rep.errors.is_empty();
+    let detail = format!("{} of {} files, {} embeddings, {} errors", rep.files_indexed, CORPUS.len(), rep.embeddings_computed, rep.errors.len());
+    steps.push(Step { name: "index".into(), ok, ms, detail });
+
+    let mut retr = mentat_retriever::Retriever::open(dir.join("index"))?;
+    for (path, _, query) in CORPUS {
+        let t = Instant::now();
+        let hit = retr.search_exact(query, 1);
+        steps.push(probe("search", path, query, hit, t));
+    }
+    if !mentat_retriever::HNSW {
+        return Ok(());
+    }
+    let t = Instant::now();
+    if let Err(e) = retr.build_hnsw_in_memory() {
+        steps.push(Step { name: "hnsw build".into(), ok: false, ms: t.elapsed().as_millis() as u64, detail: format!("{e:#}") });
+        return Ok(());
+    }
+    steps.push(Step { name: "hnsw build".into(), ok: true, ms: t.elapsed().as_millis() as u64, detail: format!("{} vectors", rep.chunks_created) });
+    for (path, _, query) in CORPUS {
+        let t = Instant::now();
+        let hit = retr.search(query, 1);
+        steps.push(probe("search-hnsw", path, query, hit, t));
+    }
+    Ok(())
+}
+
+fn probe(name: &str, expected: &str, query: &str, hits: Result<Vec<mentat_retriever::Hit>>, t: Instant) -> Step {
+    let ms = t.elapsed().as_millis() as u64;
+    let (ok, detail) = match hits {
+        Ok(hits) => match hits.first() {
+            Some(h) if h.path == expected => (true, format!("\"{query}\" -> {}", h.path)),
+            Some(h) => (false, format!("\"{query}\" -> {}, expected {expected}", h.path)),
+            None => (false, format!("\"{query}\" -> no hits, expected {expected}")),
+        },
+        Err(e) => (false, format!("\"{query}\": {e:#}")),
+    };
+    Step { name: name.into(), ok, ms, detail }
+}
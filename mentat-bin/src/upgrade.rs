@@ -0,0 +1,98 @@
+//! `mentat upgrade [--into DIR] [--limit N] [--background]`: replace the
+//! static vectors a tiered `mentat index` run stored with the full model's,
+//! most searched chunks first (searches count hits on queued chunks).
+//!
+//! The store is opened only to take a batch and to write it back; the model
+//! runs with the store closed, so searches keep working during an upgrade.
+//! A tiered index run starts this in the background by itself.
+
+use anyhow::Result;
+use crate::background;
+use std::{fs, path::Path, time::Instant};
+
+/// Chunks embedded per store round trip.
+const BATCH: usize = 32;
+
+fn marker(dir: &str) -> std::path::PathBuf {
+    Path::new(dir).join("upgrade.pending")
+}
+
+/// Start `mentat upgrade --background` detached, unless one is running.
+pub fn schedule(dir: &str) {
+    if background::pending(&marker(dir)) {
+        return;
+    }
+    let log = Path::new(dir).join("upgrade.log");
+    match background::spawn(&marker(dir), &log, &["upgrade", "--into", dir, "--background"]) {
+        Ok(()) => println!("Upgrading to full-model vectors in the background (log: {})", log.display()),
+        Err(e) => eprintln!("[upgrade] could not start: {e:#}; run `mentat upgrade`"),
+    }
+}
+
+pub fn run(dir: &str, limit: Option<usize>, background: bool) -> Result<i32> {
+    let result = upgrade(dir, limit.unwrap_or(usize::MAX));
+    if background {
+        let _ = fs::remove_file(marker(dir));
+    }
+    let (done, left) = result?;
+    println!("{done} chunk(s) re-embedded with {}; {left} still queued", mentat_embedder::MODEL);
+    Ok(0)
+}
+
+/// Returns (taken off the queue, still queued).
+fn upgrade(dir: &str, limit: usize) -> Result<(usize, usize)> {
+    let cfg = mentat_config::Config::load()?;
+    mentat_embedder::set_precision(cfg.embedder.precision);
+    mentat_embedder::set_pins(cfg.embedder.pins);
+    let max_len = if cfg.embedder.max_len > 0 { cfg.embedder.max_len } else { mentat_embedder::MAX_LEN };
+    let mut done = 0;
+    let t = Instant::now();
+    loop {
+        let store = open_when_free(dir)?;
+        let queue = store.upgrade_queue()?;
+        if queue.is_empty() || done >= limit {
+            if done > 0 {
+                // upgraded files' means moved
+                mentat_indexer::related::update(&store)?;
+            }
+            return Ok((done, queue.len()));
+        }
+        let norm = mentat_indexer::stored_normalize(&store)?.unwrap_or_default();
+        let (mut ids, mut texts, mut dropped) = (Vec::new(), Vec::new(), Vec::new());
+        for (id, _) in queue.iter().take(BATCH.min(limit - done)) {
+            // a chunk that is gone, or whose file changed, has nothing to upgrade
+            match store.get_chunk(*id)?.map(|c| store.chunk_text(&c)).transpose()?.flatten() {
+                Some(text) => {
+                    ids.push(*id);
+                    texts.push(norm.apply(&text));
+                }
+                None => dropped.push(*id),
+            }
+        }
+        drop(store);
+
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let vectors = mentat_embedder::embed_batch(&refs, max_len)?;
+        let taken = ids.len() + dropped.len();
+        let mut upgraded = Vec::new();
+        for (id, v) in ids.into_iter().zip(vectors) {
+            // an unusable vector keeps the static one rather than retrying forever
+            match mentat_embedder::invalid_reason(&v) {
+                None => upgraded.push((id, v)),
+                Some(_) => dropped.push(id),
+            }
+        }
+        open_when_free(dir)?.finish_upgrades(&upgraded, &dropped)?;
+        done += taken;
+        eprintln!("[upgrade] {done} done, {} left ({:.0}s)", queue.len() - taken, t.elapsed().as_secs_f32());
+        // the saved graph holds the old vectors; a later search rebuilds it
+        let hdr = Path::new(dir).join("embeds.hdr");
+        if hdr.exists() {
+            fs::remove_file(hdr)?;
+        }
+    }
+}
+
+fn open_when_free(dir: &str) -> Result<mentat_store::Store> {
+    background::when_free(|| mentat_store::Store::open_existing(dir))
+}
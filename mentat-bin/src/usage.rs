@@ -0,0 +1,149 @@
+//! Local usage analytics, on only with `[usage] enabled = true`. Searches
+//! and index runs append one JSON line each to `index/usage.jsonl`; nothing
+//! leaves the machine. `mentat usage` summarizes the log per UTC day:
+//! queries, search latency, embedding cache hit rate and index size.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+const LOG: &str = "usage.jsonl";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Search {
+        at: u64,
+        ms: u64,
+        hits: usize,
+    },
+    Index {
+        at: u64,
+        ms: u64,
+        /// chunks in the index after the run
+        chunks: usize,
+        computed: usize,
+        /// cached plus reused: embeddings that did not go to the model
+        cached: usize,
+    },
+}
+
+pub fn enabled() -> bool {
+    mentat_config::Config::load().is_ok_and(|c| c.usage.enabled)
+}
+
+/// Append `event` if `[usage]` is enabled. Failing to log never fails the command.
+pub fn record(index_dir: &str, event: Event) {
+    if !enabled() {
+        return;
+    }
+    let write = || -> Result<()> {
+        let mut f = fs::OpenOptions::new().create(true).append(true).open(Path::new(index_dir).join(LOG))?;
+        writeln!(f, "{}", serde_json::to_string(&event)?)?;
+        Ok(())
+    };
+    if let Err(e) = write() {
+        eprintln!("[usage] not recorded: {e:#}");
+    }
+}
+
+pub fn now() -> u64 {
+    mentat_indexer::report::now_millis() / 1000
+}
+
+#[derive(Serialize, Default, Debug)]
+struct Day {
+    queries: usize,
+    median_ms: Option<u64>,
+    p95_ms: Option<u64>,
+    index_runs: usize,
+    /// share of chunks whose embedding was cached or reused
+    cache_hit_rate: Option<f32>,
+    /// index size after the day's last run
+    chunks: Option<usize>,
+}
+
+/// The last `days` days that have any events, oldest first.
+pub fn show(index_dir: &str, days: usize, json: bool) -> Result<()> {
+    let path = Path::new(index_dir).join(LOG);
+    if !path.exists() {
+        println!("no usage recorded; set `[usage] enabled = true` in mentat.toml to start");
+        return Ok(());
+    }
+    let mut latencies: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    let mut by_day: BTreeMap<String, Day> = BTreeMap::new();
+    let mut embeds: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for line in fs::read_to_string(&path)?.lines() {
+        // lines from newer builds, or cut short by a crash, are skipped
+        let Ok(ev) = serde_json::from_str::<Event>(line) else { continue };
+        match ev {
+            Event::Search { at, ms, .. } => {
+                let day = crate::utc_timestamp(at)[..10].to_string();
+                by_day.entry(day.clone()).or_default().queries += 1;
+                latencies.entry(day).or_default().push(ms);
+            }
+            Event::Index { at, chunks, computed, cached, .. } => {
+                let day = crate::utc_timestamp(at)[..10].to_string();
+                let d = by_day.entry(day.clone()).or_default();
+                d.index_runs += 1;
+                d.chunks = Some(chunks);
+                let e = embeds.entry(day).or_default();
+                e.0 += cached;
+                e.1 += cached + computed;
+            }
+        }
+    }
+    for (day, mut ms) in latencies {
+        ms.sort_unstable();
+        let d = by_day.get_mut(&day).expect("every latency has a day");
+        d.median_ms = Some(ms[ms.len() / 2]);
+        d.p95_ms = Some(ms[(ms.len() * 95 / 100).min(ms.len() - 1)]);
+    }
+    for (day, (hit, total)) in embeds {
+        if total > 0 {
+            by_day.get_mut(&day).expect("every index run has a day").cache_hit_rate = Some(hit as f32 / total as f32);
+        }
+    }
+    let skip = by_day.len().saturating_sub(days);
+    let rows: Vec<(String, Day)> = by_day.into_iter().skip(skip).collect();
+    if json {
+        let map: BTreeMap<&str, &Day> = rows.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        println!("{}", serde_json::to_string_pretty(&map)?);
+        return Ok(());
+    }
+    let series = |f: &dyn Fn(&Day) -> Option<f64>| sparkline(&rows.iter().map(|(_, d)| f(d)).collect::<Vec<_>>());
+    println!("queries/day  {}", series(&|d| Some(d.queries as f64)));
+    println!("median ms    {}", series(&|d| d.median_ms.map(|v| v as f64)));
+    println!("cache hits   {}", series(&|d| d.cache_hit_rate.map(f64::from)));
+    println!("chunks       {}", series(&|d| d.chunks.map(|v| v as f64)));
+    println!();
+    println!("{:<10} {:>7} {:>9} {:>7} {:>5} {:>10} {:>9}", "day", "queries", "median_ms", "p95_ms", "runs", "cache_hits", "chunks");
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "-".into());
+    for (day, d) in &rows {
+        println!(
+            "{day:<10} {:>7} {:>9} {:>7} {:>5} {:>10} {:>9}",
+            d.queries,
+            opt(d.median_ms.map(|v| v.to_string())),
+            opt(d.p95_ms.map(|v| v.to_string())),
+            d.index_runs,
+            opt(d.cache_hit_rate.map(|r| format!("{:.0}%", r * 100.0))),
+            opt(d.chunks.map(|v| v.to_string())),
+        );
+    }
+    Ok(())
+}
+
+/// One block per value scaled between the series' min and max; gaps are blank.
+fn sparkline(values: &[Option<f64>]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let present: Vec<f64> = values.iter().flatten().copied().collect();
+    let (lo, hi) = present.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    values
+        .iter()
+        .map(|v| match v {
+            None => ' ',
+            Some(_) if hi <= lo => BARS[3],
+            Some(v) => BARS[(((v - lo) / (hi - lo)) * 7.0).round() as usize],
+        })
+        .collect()
+}